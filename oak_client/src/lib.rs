@@ -18,6 +18,7 @@ pub mod proto {
     pub mod oak {
         pub mod session {
             pub mod v1 {
+                #![allow(clippy::large_enum_variant)]
                 #![allow(clippy::return_self_not_must_use)]
                 tonic::include_proto!("oak.session.v1");
             }
@@ -28,13 +29,55 @@ pub mod proto {
 pub mod transport;
 pub mod verifier;
 
-use crate::transport::AsyncEvidenceProvider;
+#[cfg(test)]
+mod tests;
+
+use crate::{
+    proto::oak::session::v1::AttestationEvidence,
+    transport::AsyncEvidenceProvider,
+    verifier::{AttestationEvidenceVerifier, InsecureAttestationEvidenceVerifier},
+};
 use anyhow::{anyhow, Context};
 use oak_crypto::{encryptor::ClientEncryptor, proto::oak::crypto::v1::EncryptedResponse};
 use prost::Message;
-use std::vec::Vec;
+use rand::RngCore;
+use std::{
+    time::{Duration, Instant},
+    vec::Vec,
+};
 
 const EMPTY_ASSOCIATED_DATA: &[u8] = b"";
+const NONCE_SIZE: usize = 32;
+
+/// Ticket that allows a client to resume an attested session with the same server without fetching
+/// and verifying the attestation evidence again, e.g. after reconnecting following a disconnect.
+///
+/// Resuming a session only skips the attestation step: every request is still encrypted with a
+/// freshly generated ephemeral key, so requests sent in different sessions don't share session
+/// keys. However, the following caveats apply:
+/// - The ticket extends the trust decision that was made when the evidence was verified until the
+///   ticket expires. Revocations that happen in the meantime (e.g. of the TEE certificates or of
+///   the endorsed binary) are not taken into account, so the lifetime should be kept short.
+/// - All requests, including those sent in resumed sessions, are encrypted to the same long-term
+///   server encryption key. Compromising that key exposes every request sent during the lifetime of
+///   the ticket, so resumption does not provide forward secrecy with respect to the server key.
+/// - The ticket must be stored with the same care as the verification result itself, since anyone
+///   who can replace it can make the client trust an unattested key.
+///
+/// If the server restarts, it generates a new encryption key, and requests sent in a resumed
+/// session fail to be decrypted. In that case the client has to create a new session.
+#[derive(Clone, Debug)]
+pub struct ResumptionTicket {
+    server_encryption_public_key: Vec<u8>,
+    expiration_time: Instant,
+}
+
+impl ResumptionTicket {
+    /// Returns whether the ticket can still be used for resuming a session.
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expiration_time
+    }
+}
 
 /// Client for connecting to Oak.
 /// Represents a Relying Party from the RATS Architecture:
@@ -45,24 +88,73 @@ where
 {
     transport: T,
     server_encryption_public_key: Vec<u8>,
+    // The verified attestation evidence of the server. Not available in resumed sessions.
+    evidence: Option<AttestationEvidence>,
 }
 
 impl<T> OakClient<T>
 where
     T: micro_rpc::AsyncTransport<Error = anyhow::Error> + AsyncEvidenceProvider,
 {
-    pub async fn create(mut transport: T) -> anyhow::Result<Self> {
+    pub async fn create(transport: T) -> anyhow::Result<Self> {
         // TODO(#3641): Implement client-side attestation verification.
+        Self::create_with_verifier(transport, &InsecureAttestationEvidenceVerifier).await
+    }
+
+    /// Creates a client that only sends requests to the server if `verifier` accepts the
+    /// attestation evidence that the server returned.
+    pub async fn create_with_verifier(
+        mut transport: T,
+        verifier: &(dyn AttestationEvidenceVerifier + Sync),
+    ) -> anyhow::Result<Self> {
+        // A fresh nonce allows the verifier to check that the evidence was generated for this
+        // session, rather than replayed.
+        let mut nonce = vec![0; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
         let evidence = transport
-            .get_evidence()
+            .get_evidence(&nonce)
             .await
             .context("couldn't get attestation evidence")?;
+        verifier
+            .verify(&evidence, &nonce)
+            .context("couldn't verify attestation evidence")?;
         Ok(Self {
             transport,
             server_encryption_public_key: evidence.encryption_public_key.to_vec(),
+            evidence: Some(evidence),
         })
     }
 
+    /// Resumes a previously attested session using `ticket`, without requesting attestation
+    /// evidence from the server. Returns an error if the ticket has expired.
+    pub fn resume(transport: T, ticket: &ResumptionTicket) -> anyhow::Result<Self> {
+        if ticket.is_expired() {
+            return Err(anyhow!("resumption ticket has expired"));
+        }
+        Ok(Self {
+            transport,
+            server_encryption_public_key: ticket.server_encryption_public_key.clone(),
+            evidence: None,
+        })
+    }
+
+    /// Returns the attestation evidence that was verified when the session was created. Returns
+    /// `None` for resumed sessions, which skip the attestation step.
+    pub fn attestation_evidence(&self) -> Option<&AttestationEvidence> {
+        self.evidence.as_ref()
+    }
+
+    /// Creates a ticket for resuming the current session, which is valid for `lifetime`.
+    ///
+    /// See [`ResumptionTicket`] for the security implications of choosing a long lifetime.
+    pub fn resumption_ticket(&self, lifetime: Duration) -> ResumptionTicket {
+        ResumptionTicket {
+            server_encryption_public_key: self.server_encryption_public_key.clone(),
+            expiration_time: Instant::now() + lifetime,
+        }
+    }
+
     pub async fn invoke(&mut self, request_body: &[u8]) -> anyhow::Result<Vec<u8>> {
         // Encrypt request.
         let mut client_encryptor = ClientEncryptor::create(&self.server_encryption_public_key)
@@ -0,0 +1,118 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::{proto::oak::session::v1::AttestationEvidence, transport::AsyncEvidenceProvider, *};
+use std::{sync::Mutex, time::Duration};
+
+const TEST_ENCRYPTION_PUBLIC_KEY: &[u8] = b"test public key";
+const TEST_TICKET_LIFETIME: Duration = Duration::from_secs(60);
+
+/// Transport that returns unattested evidence and counts how many times it was requested.
+#[derive(Default)]
+struct TestTransport {
+    evidence_requests: usize,
+}
+
+#[async_trait::async_trait]
+impl micro_rpc::AsyncTransport for TestTransport {
+    type Error = anyhow::Error;
+    async fn invoke(&mut self, _request_bytes: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        Err(anyhow!("not implemented"))
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncEvidenceProvider for TestTransport {
+    async fn get_evidence(&mut self, nonce: &[u8]) -> anyhow::Result<AttestationEvidence> {
+        self.evidence_requests += 1;
+        Ok(AttestationEvidence {
+            encryption_public_key: TEST_ENCRYPTION_PUBLIC_KEY.to_vec(),
+            nonce: nonce.to_vec(),
+            ..Default::default()
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_attestation_evidence() {
+    let client = OakClient::create(TestTransport::default())
+        .await
+        .expect("couldn't create client");
+
+    let evidence = client
+        .attestation_evidence()
+        .expect("no attestation evidence");
+    assert_eq!(evidence.encryption_public_key, TEST_ENCRYPTION_PUBLIC_KEY);
+    assert_eq!(evidence.nonce.len(), NONCE_SIZE);
+}
+
+/// Verifier that accepts any evidence and records the nonce that it was verified against.
+#[derive(Default)]
+struct RecordingVerifier {
+    nonce: Mutex<Option<Vec<u8>>>,
+}
+
+impl AttestationEvidenceVerifier for RecordingVerifier {
+    fn verify(&self, _evidence: &AttestationEvidence, nonce: &[u8]) -> anyhow::Result<()> {
+        *self.nonce.lock().unwrap() = Some(nonce.to_vec());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_verifier_checks_requested_nonce() {
+    let verifier = RecordingVerifier::default();
+    let client = OakClient::create_with_verifier(TestTransport::default(), &verifier)
+        .await
+        .expect("couldn't create client");
+
+    let evidence = client
+        .attestation_evidence()
+        .expect("no attestation evidence");
+    assert_eq!(
+        verifier.nonce.lock().unwrap().as_ref(),
+        Some(&evidence.nonce)
+    );
+}
+
+#[tokio::test]
+async fn test_resume_session() {
+    let client = OakClient::create(TestTransport::default())
+        .await
+        .expect("couldn't create client");
+    let ticket = client.resumption_ticket(TEST_TICKET_LIFETIME);
+
+    let resumed_client =
+        OakClient::resume(TestTransport::default(), &ticket).expect("couldn't resume session");
+
+    assert_eq!(resumed_client.transport.evidence_requests, 0);
+    assert!(resumed_client.attestation_evidence().is_none());
+    assert_eq!(
+        resumed_client.server_encryption_public_key,
+        TEST_ENCRYPTION_PUBLIC_KEY
+    );
+}
+
+#[tokio::test]
+async fn test_resume_session_with_expired_ticket() {
+    let client = OakClient::create(TestTransport::default())
+        .await
+        .expect("couldn't create client");
+    let ticket = client.resumption_ticket(Duration::ZERO);
+
+    assert!(ticket.is_expired());
+    assert!(OakClient::resume(TestTransport::default(), &ticket).is_err());
+}
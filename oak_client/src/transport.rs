@@ -64,18 +64,22 @@ impl micro_rpc::AsyncTransport for GrpcStreamingTransport {
 
 #[async_trait::async_trait]
 pub trait AsyncEvidenceProvider {
-    async fn get_evidence(&mut self) -> anyhow::Result<AttestationEvidence>;
+    /// Requests attestation evidence from the server. If `nonce` is not empty, the server is
+    /// expected to generate fresh evidence that is bound to it.
+    async fn get_evidence(&mut self, nonce: &[u8]) -> anyhow::Result<AttestationEvidence>;
 }
 
 #[async_trait::async_trait]
 impl AsyncEvidenceProvider for GrpcStreamingTransport {
-    async fn get_evidence(&mut self) -> anyhow::Result<AttestationEvidence> {
+    async fn get_evidence(&mut self, nonce: &[u8]) -> anyhow::Result<AttestationEvidence> {
         let mut response_stream = self
             .rpc_client
             .stream(futures_util::stream::iter(vec![RequestWrapper {
                 // TODO(#3641): Rename the corresponding message to `GetEvidence`.
                 request: Some(request_wrapper::Request::GetPublicKeyRequest(
-                    GetPublicKeyRequest {},
+                    GetPublicKeyRequest {
+                        nonce: nonce.to_vec(),
+                    },
                 )),
             }]))
             .await
@@ -14,6 +14,8 @@
 // limitations under the License.
 //
 
+use crate::proto::oak::session::v1::AttestationEvidence;
+
 pub trait EvidenceProvider {
     fn get_evidence(&mut self) -> anyhow::Result<Evidence>;
 }
@@ -50,3 +52,25 @@ impl Verifier for AmdSevSnpVerifier {
         Ok(())
     }
 }
+
+/// Verifier that appraises the attestation evidence returned by the server before the client
+/// sends any requests to it.
+pub trait AttestationEvidenceVerifier {
+    /// Verifies `evidence`, which the server returned for the client-supplied `nonce`.
+    ///
+    /// The `nonce` field of the evidence is not covered by the attestation on its own, so
+    /// implementations that appraise the attestation report have to check that its report data
+    /// binds `nonce` in order to reject replayed evidence.
+    fn verify(&self, evidence: &AttestationEvidence, nonce: &[u8]) -> anyhow::Result<()>;
+}
+
+/// An instance of [`AttestationEvidenceVerifier`] that accepts any evidence.
+///
+/// Should only be used for testing, or for servers that don't run in a TEE.
+pub struct InsecureAttestationEvidenceVerifier;
+
+impl AttestationEvidenceVerifier for InsecureAttestationEvidenceVerifier {
+    fn verify(&self, _evidence: &AttestationEvidence, _nonce: &[u8]) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -15,13 +15,22 @@
 mod server;
 
 use clap::Parser;
+use oak_containers_transport::Address;
 
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(long, required = true)]
-    vsock_cid: u32,
-    #[arg(long, required = true)]
-    vsock_port: u32,
+    #[arg(long, required_unless_present_any = ["tcp_address", "unix_socket"])]
+    vsock_cid: Option<u32>,
+    #[arg(long, required_unless_present_any = ["tcp_address", "unix_socket"])]
+    vsock_port: Option<u32>,
+    /// Serve the guest over TCP at this address instead of vsock, so that stage1 and the
+    /// orchestrator can run as ordinary processes during local development.
+    #[arg(long, conflicts_with_all = ["vsock_cid", "vsock_port", "unix_socket"])]
+    tcp_address: Option<std::net::SocketAddr>,
+    /// Serve the guest over a Unix domain socket at this path instead of vsock, for the same
+    /// purpose as `--tcp-address`.
+    #[arg(long, conflicts_with_all = ["vsock_cid", "vsock_port"])]
+    unix_socket: Option<std::path::PathBuf>,
     #[arg(long, required = true, value_parser = path_exists,)]
     system_image: std::path::PathBuf,
     #[arg(long, required = true, value_parser = path_exists,)]
@@ -44,11 +53,14 @@ fn path_exists(s: &str) -> Result<std::path::PathBuf, String> {
 async fn main() -> Result<(), anyhow::Error> {
     let args = Args::parse();
 
-    server::new(
-        args.vsock_cid,
-        args.vsock_port,
-        args.system_image,
-        args.container_bundle,
-    )
-    .await
+    let listen_address = match (args.tcp_address, args.unix_socket) {
+        (Some(address), _) => Address::Tcp(address),
+        (None, Some(path)) => Address::Unix(path),
+        (None, None) => Address::Vsock {
+            cid: args.vsock_cid.expect("vsock CID is required by clap"),
+            port: args.vsock_port.expect("vsock port is required by clap"),
+        },
+    };
+
+    server::new(listen_address, args.system_image, args.container_bundle).await
 }
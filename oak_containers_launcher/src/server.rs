@@ -25,11 +25,10 @@ use self::proto::oak::containers::{
     launcher_server::{Launcher, LauncherServer},
     GetImageResponse,
 };
-use anyhow::anyhow;
 use futures::Stream;
+use oak_containers_transport::Address;
 use std::pin::Pin;
 use tokio::io::{AsyncReadExt, BufReader};
-use tokio_vsock::VsockListener;
 use tonic::{transport::Server, Request, Response, Status};
 
 // Most gRPC implementations limit message sizes to 4MiB. Let's stay
@@ -111,8 +110,7 @@ impl Launcher for LauncherServerImplementation {
 }
 
 pub async fn new(
-    vsock_cid: u32,
-    vsock_port: u32,
+    listen_address: Address,
     system_image: std::path::PathBuf,
     container_bundle: std::path::PathBuf,
 ) -> Result<(), anyhow::Error> {
@@ -120,11 +118,6 @@ pub async fn new(
         system_image,
         container_bundle,
     };
-    let vsock_listener = VsockListener::bind(vsock_cid, vsock_port)?.incoming();
-
-    Server::builder()
-        .add_service(LauncherServer::new(server_impl))
-        .serve_with_incoming(vsock_listener)
-        .await
-        .map_err(|error| anyhow!("server error: {:?}", error))
+    let router = Server::builder().add_service(LauncherServer::new(server_impl));
+    listen_address.serve(router).await
 }
@@ -21,42 +21,38 @@ mod proto {
     }
 }
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
+use oak_containers_transport::{Address, Liveness};
 use proto::oak::containers::launcher_client::LauncherClient as GrpcLauncherClient;
-use tokio_vsock::VsockStream;
-use tonic::transport::{Endpoint, Uri};
-use tower::service_fn;
-
-// Virtio VSOCK does not use URIs, hence this URI will never be used.
-// It is defined purely since in order to create a channel, since a URI has to
-// be supplied to create an `Endpoint`.
-static IGNORED_ENDPOINT_URI: &str = "file://[::]:0";
 
 /// Utility struct used to interface with the launcher
 pub struct LauncherClient {
     inner: GrpcLauncherClient<tonic::transport::channel::Channel>,
+    liveness: Liveness,
 }
 
 impl LauncherClient {
-    pub async fn create(
-        launcher_vsock_cid: u32,
-        launcher_vsock_port: u32,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let inner: GrpcLauncherClient<tonic::transport::channel::Channel> = {
-            let channel = Endpoint::try_from(IGNORED_ENDPOINT_URI)
-                .context("couldn't form endpoint")?
-                .connect_with_connector(service_fn(move |_: Uri| {
-                    VsockStream::connect(launcher_vsock_cid, launcher_vsock_port)
-                }))
-                .await
-                .context("couldn't connect to VSOCK socket")?;
-
-            GrpcLauncherClient::new(channel)
-        };
-        Ok(Self { inner })
+    pub async fn create(address: Address) -> Result<Self, Box<dyn std::error::Error>> {
+        let (channel, liveness) = address
+            .connect_with_liveness()
+            .await
+            .context("couldn't connect to launcher")?;
+        Ok(Self {
+            inner: GrpcLauncherClient::new(channel),
+            liveness,
+        })
     }
 
+    /// Downloads the container bundle, failing as soon as the connection to the launcher is lost.
     pub async fn get_container_bundle(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut liveness = self.liveness.clone();
+        tokio::select! {
+            container_buf = self.load_container_bundle() => container_buf,
+            () = liveness.lost() => Err(anyhow!("lost connection to launcher").into()),
+        }
+    }
+
+    async fn load_container_bundle(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         let mut stream = self
             .inner
             .get_container_bundle(())
@@ -17,23 +17,53 @@ mod client;
 use anyhow::anyhow;
 use clap::Parser;
 use client::LauncherClient;
+use oak_containers_transport::Address;
 
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(long, required = true)]
-    launcher_vsock_cid: u32,
-    #[arg(long, required = true)]
-    launcher_vsock_port: u32,
+    #[arg(
+        long,
+        required_unless_present_any = ["launcher_tcp_address", "launcher_unix_socket"],
+    )]
+    launcher_vsock_cid: Option<u32>,
+    #[arg(
+        long,
+        required_unless_present_any = ["launcher_tcp_address", "launcher_unix_socket"],
+    )]
+    launcher_vsock_port: Option<u32>,
+    /// Connect to the launcher over TCP at this address instead of vsock, so that the
+    /// orchestrator can run as an ordinary process during local development.
+    #[arg(
+        long,
+        conflicts_with_all = ["launcher_vsock_cid", "launcher_vsock_port", "launcher_unix_socket"],
+    )]
+    launcher_tcp_address: Option<std::net::SocketAddr>,
+    /// Connect to the launcher over a Unix domain socket at this path instead of vsock, for the
+    /// same purpose as `--launcher-tcp-address`.
+    #[arg(long, conflicts_with_all = ["launcher_vsock_cid", "launcher_vsock_port"])]
+    launcher_unix_socket: Option<std::path::PathBuf>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
-    let mut launcher_client =
-        LauncherClient::create(args.launcher_vsock_cid, args.launcher_vsock_port)
-            .await
-            .map_err(|error| anyhow!("couldn't create client: {:?}", error))?;
+    let launcher_address = match (args.launcher_tcp_address, args.launcher_unix_socket) {
+        (Some(address), _) => Address::Tcp(address),
+        (None, Some(path)) => Address::Unix(path),
+        (None, None) => Address::Vsock {
+            cid: args
+                .launcher_vsock_cid
+                .expect("launcher vsock CID is required by clap"),
+            port: args
+                .launcher_vsock_port
+                .expect("launcher vsock port is required by clap"),
+        },
+    };
+
+    let mut launcher_client = LauncherClient::create(launcher_address)
+        .await
+        .map_err(|error| anyhow!("couldn't create client: {:?}", error))?;
 
     let _container_bundle = launcher_client
         .get_container_bundle()
@@ -22,35 +22,38 @@ mod proto {
     }
 }
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use oak_containers_transport::{Address, Liveness};
 use proto::oak::containers::launcher_client::LauncherClient as GrpcLauncherClient;
-use tokio_vsock::VsockStream;
-use tonic::transport::{Channel, Endpoint, Uri};
-use tower::service_fn;
-
-static IGNORED_ENDPOINT_URI: &str = "file://[::]:0";
+use tonic::transport::Channel;
 
 pub struct LauncherClient {
     inner: GrpcLauncherClient<Channel>,
+    liveness: Liveness,
 }
 
 impl LauncherClient {
-    pub async fn new(launcher_vsock_cid: u32, launcher_vsock_port: u32) -> Result<Self> {
-        let inner: GrpcLauncherClient<Channel> = {
-            let channel = Endpoint::try_from(IGNORED_ENDPOINT_URI)
-                .context("couldn't form endpoint")?
-                .connect_with_connector(service_fn(move |_: Uri| {
-                    VsockStream::connect(launcher_vsock_cid, launcher_vsock_port)
-                }))
-                .await
-                .context("couldn't connect to VSOCK socket")?;
-
-            GrpcLauncherClient::new(channel)
-        };
-        Ok(Self { inner })
+    pub async fn new(address: Address) -> Result<Self> {
+        let (channel, liveness) = address
+            .connect_with_liveness()
+            .await
+            .context("couldn't connect to launcher")?;
+        Ok(Self {
+            inner: GrpcLauncherClient::new(channel),
+            liveness,
+        })
     }
 
+    /// Downloads the system image, failing as soon as the connection to the launcher is lost.
     pub async fn get_oak_system_image(&mut self) -> Result<Vec<u8>> {
+        let mut liveness = self.liveness.clone();
+        tokio::select! {
+            image_buf = self.load_oak_system_image() => image_buf,
+            () = liveness.lost() => Err(anyhow!("lost connection to launcher")),
+        }
+    }
+
+    async fn load_oak_system_image(&mut self) -> Result<Vec<u8>> {
         let mut stream = self
             .inner
             .get_oak_system_image(())
@@ -22,6 +22,7 @@ mod image;
 use clap::Parser;
 use client::LauncherClient;
 use image::Image;
+use oak_containers_transport::Address;
 use std::error::Error;
 
 #[derive(Parser, Debug)]
@@ -41,7 +42,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
     let image = Image::new(String::from(image::RAMFS_TMP_DIR))?;
-    let mut client = LauncherClient::new(args.launcher_vsock_cid, args.launcher_vsock_port).await?;
+    let mut client = LauncherClient::new(Address::Vsock {
+        cid: args.launcher_vsock_cid,
+        port: args.launcher_vsock_port,
+    })
+    .await?;
     let buf = client.get_oak_system_image().await?;
     image.unpack(&buf)?;
 
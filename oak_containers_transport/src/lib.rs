@@ -0,0 +1,225 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Transports over which the parts of Oak Containers talk gRPC to each other.
+//!
+//! In the VM, stage1 and the orchestrator reach the launcher over virtio vsock. For local
+//! development, the same peers can run as ordinary processes and use TCP or a Unix domain socket
+//! instead. Clients only wrap the returned [`Channel`] in their generated gRPC client.
+//!
+//! A vsock connection can silently go half-dead when the VM is paused or migrated, so channels
+//! send HTTP/2 keepalive pings and drop their connection once the server stops answering them.
+//! Clients learn about this through the [`Liveness`] of their channel, rather than only once an
+//! RPC times out.
+
+use anyhow::{anyhow, Context};
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpStream, UnixListener, UnixStream},
+    sync::watch,
+};
+use tokio_vsock::{VsockListener, VsockStream};
+use tonic::transport::{server::Router, Channel, Endpoint, Uri};
+use tower::service_fn;
+
+#[cfg(test)]
+mod tests;
+
+// Connections are established by the connectors below rather than from a URI,
+// hence this URI will never be used. It is defined purely since in order to
+// create a channel, since a URI has to be supplied to create an `Endpoint`.
+static IGNORED_ENDPOINT_URI: &str = "file://[::]:0";
+
+/// Interval at which channels send keepalive pings to the server, also while no RPC is in flight.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time after which a connection whose server didn't answer a keepalive ping is dropped.
+const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The address of a gRPC server, with the transport over which it is reached.
+#[derive(Clone, Debug)]
+pub enum Address {
+    /// Virtio vsock, which is how the guest in the VM reaches the host.
+    Vsock { cid: u32, port: u32 },
+    /// Plain TCP, for running the peers as ordinary processes during local development.
+    Tcp(SocketAddr),
+    /// A Unix domain socket, for the same purpose as TCP.
+    Unix(PathBuf),
+}
+
+/// Whether a channel is connected to its server.
+///
+/// A channel loses its connection when the server stops answering keepalive pings, and only
+/// reconnects on its next RPC, so callers that wait for the loss can reconnect right away.
+#[derive(Clone, Debug)]
+pub struct Liveness {
+    connections: watch::Receiver<Connections>,
+}
+
+// The connections of a channel.
+#[derive(Clone, Copy, Debug, Default)]
+struct Connections {
+    // Number of connections that are currently open.
+    open: usize,
+    // Number of connections that were closed since the channel was created.
+    lost: u64,
+}
+
+impl Liveness {
+    pub fn is_alive(&self) -> bool {
+        self.connections.borrow().open > 0
+    }
+
+    /// Waits until the channel loses a connection after this was called. A connection that was
+    /// lost before doesn't count, since the channel reconnects on its next RPC.
+    pub fn lost(&mut self) -> impl Future<Output = ()> + '_ {
+        let lost_before = self.connections.borrow_and_update().lost;
+        async move {
+            while self.connections.borrow_and_update().lost == lost_before {
+                // An error means that the channel was dropped, along with its connection.
+                if self.connections.changed().await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Address {
+    /// Connects a gRPC channel to the server at this address.
+    pub async fn connect(&self) -> anyhow::Result<Channel> {
+        let (channel, _) = self.connect_with_liveness().await?;
+        Ok(channel)
+    }
+
+    /// Connects a gRPC channel to the server at this address, and returns it together with its
+    /// [`Liveness`].
+    pub async fn connect_with_liveness(&self) -> anyhow::Result<(Channel, Liveness)> {
+        let endpoint = Endpoint::try_from(IGNORED_ENDPOINT_URI)
+            .context("couldn't form endpoint")?
+            .http2_keep_alive_interval(KEEPALIVE_INTERVAL)
+            .keep_alive_timeout(KEEPALIVE_TIMEOUT)
+            .keep_alive_while_idle(true);
+        let (sender, connections) = watch::channel(Connections::default());
+        let sender = Arc::new(sender);
+        let channel = match self.clone() {
+            Address::Vsock { cid, port } => endpoint
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    MonitoredStream::connect(VsockStream::connect(cid, port), sender.clone())
+                }))
+                .await
+                .context("couldn't connect to VSOCK socket")?,
+            Address::Tcp(address) => endpoint
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    MonitoredStream::connect(TcpStream::connect(address), sender.clone())
+                }))
+                .await
+                .context("couldn't connect to TCP socket")?,
+            Address::Unix(path) => endpoint
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    MonitoredStream::connect(UnixStream::connect(path.clone()), sender.clone())
+                }))
+                .await
+                .context("couldn't connect to Unix domain socket")?,
+        };
+        Ok((channel, Liveness { connections }))
+    }
+
+    /// Serves the gRPC services of `router` at this address until the server fails.
+    pub async fn serve(&self, router: Router) -> anyhow::Result<()> {
+        let result = match self {
+            Address::Vsock { cid, port } => {
+                let vsock_listener = VsockListener::bind(*cid, *port)?.incoming();
+                router.serve_with_incoming(vsock_listener).await
+            }
+            Address::Tcp(address) => router.serve(*address).await,
+            Address::Unix(path) => {
+                let unix_listener = UnixListener::bind(path)?;
+                let incoming = async_stream::stream! {
+                    loop {
+                        yield unix_listener.accept().await.map(|(stream, _)| stream);
+                    }
+                };
+                router.serve_with_incoming(incoming).await
+            }
+        };
+        result.map_err(|error| anyhow!("server error: {:?}", error))
+    }
+}
+
+// A connection of a channel, which is counted as open until the channel drops it.
+struct MonitoredStream<S> {
+    inner: S,
+    connections: Arc<watch::Sender<Connections>>,
+}
+
+impl<S> MonitoredStream<S> {
+    async fn connect(
+        stream: impl Future<Output = io::Result<S>>,
+        connections: Arc<watch::Sender<Connections>>,
+    ) -> io::Result<Self> {
+        let inner = stream.await?;
+        connections.send_modify(|connections| connections.open += 1);
+        Ok(Self { inner, connections })
+    }
+}
+
+impl<S> Drop for MonitoredStream<S> {
+    fn drop(&mut self) {
+        self.connections.send_modify(|connections| {
+            connections.open -= 1;
+            connections.lost += 1;
+        });
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for MonitoredStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for MonitoredStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
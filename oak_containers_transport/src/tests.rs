@@ -0,0 +1,100 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use hyper::{server::conn::Http, service::service_fn as hyper_service_fn, Body, Response};
+use std::convert::Infallible;
+use tokio::{net::TcpListener, task::JoinHandle};
+use tower::Service;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Serves HTTP/2 on the first connection that `listener` accepts, answering every request with an
+// empty response. Aborting the returned task closes the connection.
+fn serve_one_connection(listener: TcpListener) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.expect("couldn't accept connection");
+        let service =
+            hyper_service_fn(|_| async { Ok::<_, Infallible>(Response::new(Body::empty())) });
+        let _ = Http::new()
+            .http2_only(true)
+            .serve_connection(stream, service)
+            .await;
+    })
+}
+
+async fn call(channel: &mut Channel) -> anyhow::Result<()> {
+    std::future::poll_fn(|cx| channel.poll_ready(cx)).await?;
+    let request = tonic::codegen::http::Request::builder()
+        .uri("http://[::]:0/test")
+        .body(tonic::body::empty_body())?;
+    channel.call(request).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_liveness_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("couldn't bind listener");
+    let address = listener.local_addr().expect("couldn't get local address");
+    let server = serve_one_connection(listener);
+
+    let (mut channel, mut liveness) = Address::Tcp(address)
+        .connect_with_liveness()
+        .await
+        .expect("couldn't connect");
+    call(&mut channel).await.expect("couldn't call server");
+    assert!(liveness.is_alive());
+
+    // Killing the server closes the connection.
+    let lost = liveness.lost();
+    server.abort();
+    tokio::time::timeout(TIMEOUT, lost)
+        .await
+        .expect("loss of the connection wasn't reported");
+    assert!(!liveness.is_alive());
+
+    // The channel reconnects on a later RPC, once the server is back. Like the clients of the
+    // launcher, the RPC fails if the connection is lost meanwhile, which the earlier loss must not
+    // count as.
+    let listener = TcpListener::bind(address)
+        .await
+        .expect("couldn't bind listener");
+    let _server = serve_one_connection(listener);
+    let mut reconnected = false;
+    for _ in 0..10 {
+        let mut liveness = liveness.clone();
+        let result = tokio::select! {
+            result = call(&mut channel) => result,
+            () = liveness.lost() => Err(anyhow!("lost connection")),
+        };
+        if result.is_ok() {
+            reconnected = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(reconnected, "channel didn't reconnect");
+    assert!(liveness.is_alive());
+
+    // The earlier loss doesn't count once the channel reconnected.
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), liveness.lost())
+            .await
+            .is_err()
+    );
+}
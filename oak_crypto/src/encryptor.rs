@@ -28,9 +28,10 @@ use crate::{
 use alloc::{sync::Arc, vec::Vec};
 use anyhow::{anyhow, Context};
 
-// TODO(#3642): Remove this key and use a real encapsulated key generated by the client once Java
-// encryption is implemented.
-pub(crate) const TEST_ENCAPSULATED_PUBLIC_KEY: [u8; 65] = [
+// Encapsulated public key that is sent by clients that don't encrypt their messages yet, which
+// marks their sessions as plaintext sessions.
+// TODO(#3642): Remove this key once Java encryption is implemented.
+pub const TEST_ENCAPSULATED_PUBLIC_KEY: [u8; 65] = [
     4, 61, 141, 127, 160, 162, 184, 158, 72, 237, 105, 64, 182, 118, 163, 183, 174, 1, 81, 66, 139,
     37, 218, 208, 17, 139, 159, 158, 68, 123, 124, 96, 114, 150, 38, 251, 112, 28, 121, 132, 45,
     250, 118, 208, 142, 153, 124, 192, 139, 178, 239, 188, 177, 219, 52, 178, 123, 117, 254, 171,
@@ -161,17 +162,32 @@ pub struct RecipientContext {
     pub(crate) recipient_response_context: RecipientResponseContext,
 }
 
+/// How the messages of a crypto session between a client and the server are protected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionMode {
+    /// Messages are encrypted end to end with the keys of the HPKE session, so that they can only
+    /// be read by the client and the server, and not by intermediaries such as proxies that
+    /// terminate TLS.
+    Encrypted,
+    /// Messages are not encrypted. Used by clients that don't implement HPKE yet, which send a
+    /// fixed test key instead of an encapsulated public key.
+    // TODO(#3642): Remove once Java encryption is implemented.
+    Plaintext,
+}
+
 /// Encryptor object for decrypting client requests that are received by the server and encrypting
 /// server responses that will be sent back to the client. Each Encryptor object corresponds to a
 /// single crypto session between the client and the server.
-/// Encryptor state is initialized after receiving an initial request messagecontaining client's
-/// encapsulated public key.
+/// Encryptor state is initialized after receiving an initial request message containing client's
+/// encapsulated public key, which also determines the [`SessionMode`].
 ///
 /// Sequence numbers for requests and responses are incremented separately, meaning that there could
 /// be multiple responses per request and multiple requests per response.
 pub struct ServerEncryptor {
     crypto_context_generator: Arc<dyn CryptoContextGenerator>,
     context: Option<RecipientContext>,
+    mode: Option<SessionMode>,
+    allow_plaintext_sessions: bool,
 }
 
 impl ServerEncryptor {
@@ -179,58 +195,73 @@ impl ServerEncryptor {
         Self {
             crypto_context_generator,
             context: None,
+            mode: None,
+            allow_plaintext_sessions: true,
         }
     }
 
+    /// Rejects sessions in [`SessionMode::Plaintext`], so that only encrypted requests are
+    /// accepted.
+    pub fn reject_plaintext_sessions(mut self) -> Self {
+        self.allow_plaintext_sessions = false;
+        self
+    }
+
+    /// Returns the mode of the session, or `None` if no request was received yet.
+    pub fn mode(&self) -> Option<SessionMode> {
+        self.mode
+    }
+
     /// Decrypts a [`EncryptedRequest`] proto message using AEAD.
     /// Returns a response message plaintext and associated data.
     /// <https://datatracker.ietf.org/doc/html/rfc5116>
     pub fn decrypt(
         &mut self,
         encrypted_request: &EncryptedRequest,
-    ) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
-        match &mut self.context {
-            Some(context) => Self::decrypt_with_context(
-                encrypted_request,
-                &mut context.recipient_request_context,
-            ),
-            None => {
-                let crypto_context = self
-                    .crypto_context_generator
-                    .generate_context(&TEST_ENCAPSULATED_PUBLIC_KEY)
-                    .context("couldn't generate recipient crypto context")?;
-                let (mut recipient_request_context, recipient_response_context) = (
-                    crypto_context.recipient_request_context,
-                    crypto_context.recipient_response_context,
-                );
-                let (plaintext, associated_data) =
-                    Self::decrypt_with_context(encrypted_request, &mut recipient_request_context)?;
-                self.context = Some(RecipientContext {
-                    recipient_request_context,
-                    recipient_response_context,
-                });
-                Ok((plaintext, associated_data))
-            }
-        }
-    }
-
-    fn decrypt_with_context(
-        encrypted_request: &EncryptedRequest,
-        context: &mut RecipientRequestContext,
     ) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
         let encrypted_message = encrypted_request
             .encrypted_message
             .as_ref()
             .context("request doesn't contain encrypted message")?;
-        let plaintext = context
-            .open(
-                &encrypted_message.ciphertext,
-                &encrypted_message.associated_data,
-            )
-            .context("couldn't decrypt request")?;
+        if self.mode.is_none() {
+            self.initialize(encrypted_request)?;
+        }
+        let plaintext = match &mut self.context {
+            Some(context) => context
+                .recipient_request_context
+                .open(
+                    &encrypted_message.ciphertext,
+                    &encrypted_message.associated_data,
+                )
+                .context("couldn't decrypt request")?,
+            // Requests of plaintext sessions are not encrypted.
+            None => encrypted_message.ciphertext.to_vec(),
+        };
         Ok((plaintext, encrypted_message.associated_data.to_vec()))
     }
 
+    // Sets up the session from the encapsulated public key in the initial request message.
+    fn initialize(&mut self, encrypted_request: &EncryptedRequest) -> anyhow::Result<()> {
+        let serialized_encapsulated_public_key = encrypted_request
+            .serialized_encapsulated_public_key
+            .as_ref()
+            .context("initial request message doesn't contain encapsulated public key")?;
+        if serialized_encapsulated_public_key[..] == TEST_ENCAPSULATED_PUBLIC_KEY[..] {
+            if !self.allow_plaintext_sessions {
+                return Err(anyhow!("plaintext sessions are not allowed"));
+            }
+            self.mode = Some(SessionMode::Plaintext);
+        } else {
+            let crypto_context = self
+                .crypto_context_generator
+                .generate_context(serialized_encapsulated_public_key)
+                .context("couldn't generate recipient crypto context")?;
+            self.context = Some(crypto_context);
+            self.mode = Some(SessionMode::Encrypted);
+        }
+        Ok(())
+    }
+
     /// Encrypts `plaintext` and authenticates `associated_data` using AEAD.
     /// Returns a [`EncryptedResponse`] proto message.
     /// <https://datatracker.ietf.org/doc/html/rfc5116>
@@ -239,23 +270,24 @@ impl ServerEncryptor {
         plaintext: &[u8],
         associated_data: &[u8],
     ) -> anyhow::Result<EncryptedResponse> {
-        match &mut self.context {
-            Some(context) => {
-                let ciphertext = context
-                    .recipient_response_context
-                    .seal(plaintext, associated_data)
-                    .context("couldn't encrypt response")?;
-                let response = EncryptedResponse {
-                    encrypted_message: Some(AeadEncryptedMessage {
-                        ciphertext,
-                        associated_data: associated_data.to_vec(),
-                    }),
-                };
-                Ok(response)
+        let ciphertext = match (&mut self.context, self.mode) {
+            (Some(context), _) => context
+                .recipient_response_context
+                .seal(plaintext, associated_data)
+                .context("couldn't encrypt response")?,
+            // Responses of plaintext sessions are not encrypted.
+            (None, Some(SessionMode::Plaintext)) => plaintext.to_vec(),
+            (None, _) => {
+                return Err(anyhow!(
+                    "couldn't encrypt response because crypto context is not initialized"
+                ))
             }
-            None => Err(anyhow!(
-                "couldn't encrypt response because crypto context is not initialized"
-            )),
-        }
+        };
+        Ok(EncryptedResponse {
+            encrypted_message: Some(AeadEncryptedMessage {
+                ciphertext,
+                associated_data: associated_data.to_vec(),
+            }),
+        })
     }
 }
@@ -93,10 +93,10 @@ pub(crate) fn setup_base_sender(
     Ok((
         encapped_key.to_bytes().to_vec(),
         SenderRequestContext {
-            _inner: sender_context,
+            inner: sender_context,
         },
         SenderResponseContext {
-            _response_key: response_key,
+            response_key,
             response_base_nonce,
             sequence_number: 0,
         },
@@ -137,10 +137,10 @@ pub(crate) fn setup_base_recipient(
 
     Ok((
         RecipientRequestContext {
-            _inner: recipient_context,
+            inner: recipient_context,
         },
         RecipientResponseContext {
-            _response_key: response_key,
+            response_key,
             response_base_nonce,
             sequence_number: 0,
         },
@@ -148,7 +148,7 @@ pub(crate) fn setup_base_recipient(
 }
 
 pub(crate) struct SenderRequestContext {
-    _inner: AeadCtxS<Aead, Kdf, Kem>,
+    inner: AeadCtxS<Aead, Kdf, Kem>,
 }
 
 impl SenderRequestContext {
@@ -157,18 +157,16 @@ impl SenderRequestContext {
     pub(crate) fn seal(
         &mut self,
         plaintext: &[u8],
-        _associated_data: &[u8],
+        associated_data: &[u8],
     ) -> anyhow::Result<Vec<u8>> {
-        // TODO(#3642): Uncomment message encryption/decryption once Java encryption is implemented.
-        Ok(plaintext.to_vec())
-        // self.inner
-        //     .seal(plaintext, associated_data)
-        //     .map_err(|error| anyhow!("couldn't encrypt message: {}", error))
+        self.inner
+            .seal(plaintext, associated_data)
+            .map_err(|error| anyhow!("couldn't encrypt message: {}", error))
     }
 }
 
 pub(crate) struct RecipientRequestContext {
-    _inner: AeadCtxR<Aead, Kdf, Kem>,
+    inner: AeadCtxR<Aead, Kdf, Kem>,
 }
 
 impl RecipientRequestContext {
@@ -177,18 +175,16 @@ impl RecipientRequestContext {
     pub(crate) fn open(
         &mut self,
         ciphertext: &[u8],
-        _associated_data: &[u8],
+        associated_data: &[u8],
     ) -> anyhow::Result<Vec<u8>> {
-        // TODO(#3642): Uncomment message encryption/decryption once Java encryption is implemented.
-        Ok(ciphertext.to_vec())
-        // self.inner
-        //     .open(ciphertext, associated_data)
-        //     .map_err(|error| anyhow!("couldn't decrypt message: {}", error))
+        self.inner
+            .open(ciphertext, associated_data)
+            .map_err(|error| anyhow!("couldn't decrypt message: {}", error))
     }
 }
 
 pub(crate) struct SenderResponseContext {
-    _response_key: AeadKey,
+    response_key: AeadKey,
     response_base_nonce: AeadNonce,
     /// Sequence number that is XORed with the base nonce values to get AEAD nonces.
     /// Is represented as [`u128`] because the [`AEAD_NONCE_SIZE_BYTES`] is 12 bytes.
@@ -202,23 +198,20 @@ impl SenderResponseContext {
     pub(crate) fn open(
         &mut self,
         ciphertext: &[u8],
-        _associated_data: &[u8],
+        associated_data: &[u8],
     ) -> anyhow::Result<Vec<u8>> {
-        let _nonce = compute_nonce(self.sequence_number, &self.response_base_nonce)
+        let nonce = compute_nonce(self.sequence_number, &self.response_base_nonce)
             .context("couldn't compute nonce")?;
-        // TODO(#3642): Uncomment message encryption/decryption once Java encryption is implemented.
-        // let plaintext =
-        //     crate::aead::decrypt(&self.response_key, &nonce, ciphertext, associated_data)
-        //         .context("couldn't decrypt response message")?;
+        let plaintext = aead::decrypt(&self.response_key, &nonce, ciphertext, associated_data)
+            .context("couldn't decrypt response message")?;
         increment_sequence_number(&mut self.sequence_number)
             .context("couldn't increment sequence number")?;
-        Ok(ciphertext.to_vec())
-        // Ok(plaintext)
+        Ok(plaintext)
     }
 }
 
 pub(crate) struct RecipientResponseContext {
-    _response_key: AeadKey,
+    response_key: AeadKey,
     response_base_nonce: AeadNonce,
     /// Sequence number that is XORed with the base nonce values to get AEAD nonces.
     /// Is represented as [`u128`] because the [`AEAD_NONCE_SIZE_BYTES`] is 12 bytes.
@@ -232,18 +225,15 @@ impl RecipientResponseContext {
     pub(crate) fn seal(
         &mut self,
         plaintext: &[u8],
-        _associated_data: &[u8],
+        associated_data: &[u8],
     ) -> anyhow::Result<Vec<u8>> {
-        let _nonce = compute_nonce(self.sequence_number, &self.response_base_nonce)
+        let nonce = compute_nonce(self.sequence_number, &self.response_base_nonce)
             .context("couldn't compute nonce")?;
-        // TODO(#3642): Uncomment message encryption/decryption once Java encryption is implemented.
-        // let ciphertext =
-        //     crate::aead::encrypt(&self.response_key, &nonce, plaintext, associated_data)
-        //         .context("couldn't encrypt response message")?;
+        let ciphertext = aead::encrypt(&self.response_key, &nonce, plaintext, associated_data)
+            .context("couldn't encrypt response message")?;
         increment_sequence_number(&mut self.sequence_number)
             .context("couldn't increment sequence number")?;
-        Ok(plaintext.to_vec())
-        // Ok(ciphertext)
+        Ok(ciphertext)
     }
 }
 
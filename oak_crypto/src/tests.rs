@@ -15,11 +15,15 @@
 //
 
 use crate::{
-    encryptor::{ClientEncryptor, EncryptionKeyProvider, ServerEncryptor},
+    encryptor::{
+        ClientEncryptor, EncryptionKeyProvider, ServerEncryptor, SessionMode,
+        TEST_ENCAPSULATED_PUBLIC_KEY,
+    },
     hpke::{
         aead::{AEAD_ALGORITHM_KEY_SIZE_BYTES, AEAD_NONCE_SIZE_BYTES},
         setup_base_recipient, setup_base_sender, KeyPair,
     },
+    proto::oak::crypto::v1::{AeadEncryptedMessage, EncryptedRequest},
     util::i2osp,
 };
 use alloc::sync::Arc;
@@ -44,7 +48,7 @@ const TEST_SESSION_SIZE: usize = 8;
 
 #[test]
 fn test_aead() {
-    let encrypted_message = crate::hpke::aead::_encrypt(
+    let encrypted_message = crate::hpke::aead::encrypt(
         &TEST_AEAD_KEY,
         &TEST_NONCE,
         TEST_REQUEST_MESSAGE,
@@ -53,7 +57,7 @@ fn test_aead() {
     .expect("couldn't encrypt test message");
     // Check that the message was encrypted.
     assert_ne!(TEST_REQUEST_MESSAGE, encrypted_message);
-    let decrypted_message = crate::hpke::aead::_decrypt(
+    let decrypted_message = crate::hpke::aead::decrypt(
         &TEST_AEAD_KEY,
         &TEST_NONCE,
         &encrypted_message,
@@ -90,8 +94,7 @@ fn test_hpke() {
             .seal(&test_request_message, &test_request_associated_data)
             .expect("sender context couldn't seal request");
         // Check that the message was encrypted.
-        // TODO(#3642): Uncomment message encryption/decryption once Java encryption is implemented.
-        // assert_ne!(test_request_message, encrypted_request);
+        assert_ne!(test_request_message, encrypted_request);
         let decrypted_request = recipient_context
             .open(&encrypted_request, &test_request_associated_data)
             .expect("recipient context couldn't open request");
@@ -101,8 +104,7 @@ fn test_hpke() {
             .seal(&test_response_message, &test_response_associated_data)
             .expect("recipient context couldn't seal response");
         // Check that the message was encrypted.
-        // TODO(#3642): Uncomment message encryption/decryption once Java encryption is implemented.
-        // assert_ne!(test_response_message, encrypted_response);
+        assert_ne!(test_response_message, encrypted_response);
         let decrypted_response = sender_response_context
             .open(&encrypted_response, &test_response_associated_data)
             .expect("sender couldn't open response");
@@ -129,8 +131,14 @@ fn test_encryptor() {
             .encrypt(&test_request_message, &test_request_associated_data)
             .expect("client couldn't encrypt request");
         // Check that the message was encrypted.
-        // TODO(#3642): Uncomment message encryption/decryption once Java encryption is implemented.
-        // assert_ne!(test_request_message, encrypted_request);
+        assert_ne!(
+            test_request_message,
+            encrypted_request
+                .encrypted_message
+                .as_ref()
+                .unwrap()
+                .ciphertext
+        );
         let (decrypted_request, request_associated_data) = server_encryptor
             .decrypt(&encrypted_request)
             .expect("server couldn't decrypt request");
@@ -141,14 +149,79 @@ fn test_encryptor() {
             .encrypt(&test_response_message, &test_response_associated_data)
             .expect("server couldn't encrypt response");
         // Check that the message was encrypted.
-        // TODO(#3642): Uncomment message encryption/decryption once Java encryption is implemented.
-        // assert_ne!(test_response_message, encrypted_response);
+        assert_ne!(
+            test_response_message,
+            encrypted_response
+                .encrypted_message
+                .as_ref()
+                .unwrap()
+                .ciphertext
+        );
         let (decrypted_response, response_associated_data) = client_encryptor
             .decrypt(&encrypted_response)
             .expect("client couldn't decrypt response");
         assert_eq!(test_response_message, decrypted_response);
         assert_eq!(test_response_associated_data, response_associated_data);
     }
+    assert_eq!(server_encryptor.mode(), Some(SessionMode::Encrypted));
+}
+
+#[test]
+fn test_encryptor_with_tampered_request() {
+    let key_provider = Arc::new(EncryptionKeyProvider::new());
+    let mut client_encryptor = ClientEncryptor::create(&key_provider.get_serialized_public_key())
+        .expect("couldn't create client encryptor");
+    let mut server_encryptor = ServerEncryptor::new(key_provider);
+
+    let mut encrypted_request = client_encryptor
+        .encrypt(TEST_REQUEST_MESSAGE, TEST_REQUEST_ASSOCIATED_DATA)
+        .expect("client couldn't encrypt request");
+    encrypted_request
+        .encrypted_message
+        .as_mut()
+        .unwrap()
+        .ciphertext[0] ^= 1;
+
+    assert!(server_encryptor.decrypt(&encrypted_request).is_err());
+}
+
+fn test_plaintext_request() -> EncryptedRequest {
+    EncryptedRequest {
+        encrypted_message: Some(AeadEncryptedMessage {
+            ciphertext: TEST_REQUEST_MESSAGE.to_vec(),
+            associated_data: TEST_REQUEST_ASSOCIATED_DATA.to_vec(),
+        }),
+        serialized_encapsulated_public_key: Some(TEST_ENCAPSULATED_PUBLIC_KEY.to_vec()),
+    }
+}
+
+#[test]
+fn test_encryptor_plaintext_session() {
+    let key_provider = Arc::new(EncryptionKeyProvider::new());
+    let mut server_encryptor = ServerEncryptor::new(key_provider);
+
+    let (request, request_associated_data) = server_encryptor
+        .decrypt(&test_plaintext_request())
+        .expect("server couldn't decrypt request");
+    assert_eq!(request, TEST_REQUEST_MESSAGE);
+    assert_eq!(request_associated_data, TEST_REQUEST_ASSOCIATED_DATA);
+    assert_eq!(server_encryptor.mode(), Some(SessionMode::Plaintext));
+
+    let response = server_encryptor
+        .encrypt(TEST_RESPONSE_MESSAGE, TEST_RESPONSE_ASSOCIATED_DATA)
+        .expect("server couldn't encrypt response");
+    assert_eq!(
+        response.encrypted_message.unwrap().ciphertext,
+        TEST_RESPONSE_MESSAGE
+    );
+}
+
+#[test]
+fn test_encryptor_rejects_plaintext_session() {
+    let key_provider = Arc::new(EncryptionKeyProvider::new());
+    let mut server_encryptor = ServerEncryptor::new(key_provider).reject_plaintext_sessions();
+
+    assert!(server_encryptor.decrypt(&test_plaintext_request()).is_err());
 }
 
 #[test]
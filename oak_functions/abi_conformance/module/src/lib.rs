@@ -0,0 +1,79 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Wasm module of the Oak Functions ABI conformance suite.
+//!
+//! The request is the name of the test case to run. Keep in sync with the test cases in
+//! `oak_functions/abi_conformance/src/lib.rs`.
+
+use oak_functions_abi::{proto::OakStatus, ExtensionHandle};
+
+#[oak_functions_sdk::main]
+fn handle(test_case: String) -> Vec<u8> {
+    match test_case.as_str() {
+        "ReadRequest" => test_case.into_bytes(),
+        "ReadRequestTwice" => {
+            oak_functions_sdk::read_request().expect("couldn't read request body a second time")
+        }
+        "EmptyResponse" => Vec::new(),
+        "WriteResponseTwice" => {
+            // The response written last replaces the one written first.
+            oak_functions_sdk::write_response(b"First").expect("couldn't write response body");
+            b"Second".to_vec()
+        }
+        "LargeResponse" => vec![42u8; 1 << 20],
+        "LookupPresent" => oak_functions_sdk::storage_get_item(b"Key")
+            .expect("couldn't look up entry")
+            .expect("entry not found"),
+        "LookupAbsent" => match oak_functions_sdk::storage_get_item(b"AbsentKey") {
+            Ok(None) => b"None".to_vec(),
+            result => format!("{:?}", result).into_bytes(),
+        },
+        "WriteLog" => {
+            oak_functions_sdk::write_log_message("First").expect("couldn't write log message");
+            oak_functions_sdk::write_log_message("Second").expect("couldn't write log message");
+            Vec::new()
+        }
+        "WriteLogInvalidUtf8" => status_name(invoke(ExtensionHandle::LoggingHandle, &[0xff, 0xfe])),
+        // Conforming hosts don't provide the testing extension.
+        "InvalidHandle" => status_name(oak_functions_sdk::testing(&[]).map(|_| ())),
+        "Trap" => panic!("trap requested"),
+        _ => panic!("unknown test case"),
+    }
+}
+
+/// Invokes an extension directly through the ABI, so that arbitrary bytes can be sent.
+fn invoke(handle: ExtensionHandle, request: &[u8]) -> Result<(), OakStatus> {
+    let mut response_ptr: *mut u8 = std::ptr::null_mut();
+    let mut response_len: usize = 0;
+    let status = unsafe {
+        oak_functions_abi::invoke(
+            handle,
+            request.as_ptr(),
+            request.len(),
+            &mut response_ptr,
+            &mut response_len,
+        )
+    };
+    oak_functions_sdk::result_from_status(status as i32, ())
+}
+
+fn status_name(result: Result<(), OakStatus>) -> Vec<u8> {
+    match result {
+        Ok(()) => b"Ok".to_vec(),
+        Err(status) => format!("{:?}", status).into_bytes(),
+    }
+}
@@ -0,0 +1,140 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! A conformance suite for implementations of the Oak Functions ABI on the host side.
+//!
+//! The suite consists of a Wasm module that exercises the ABI, and of test cases that describe the
+//! response and log messages that a conforming host produces for each request. Any host can be
+//! checked by implementing [`Host`] for it and passing it to [`run`].
+
+use anyhow::Context;
+use std::collections::HashMap;
+
+/// The observable result of invoking a Wasm module.
+#[derive(Debug, Default, PartialEq)]
+pub struct Invocation {
+    /// The response body written by the module, or an empty body if the module didn't write one.
+    pub response: Vec<u8>,
+    /// The messages logged by the module through the logging extension, in order.
+    pub logs: Vec<String>,
+}
+
+/// A host implementation of the Oak Functions ABI.
+///
+/// Hosts must provide the lookup and logging extensions, and must not provide any other extension.
+pub trait Host {
+    /// Runs a single invocation of the given Wasm module with the given lookup data.
+    ///
+    /// Traps in the Wasm module must not be reported as an error, but as an empty response.
+    fn invoke(
+        &mut self,
+        wasm_module_bytes: &[u8],
+        lookup_data: HashMap<Vec<u8>, Vec<u8>>,
+        request: &[u8],
+    ) -> anyhow::Result<Invocation>;
+}
+
+/// A single test case, which sends its name as the request to the conformance module.
+pub struct TestCase {
+    pub name: &'static str,
+    pub lookup_data: HashMap<Vec<u8>, Vec<u8>>,
+    pub expected: Invocation,
+}
+
+/// A test case for which the host didn't produce the expected result.
+#[derive(Debug)]
+pub struct Failure {
+    pub test_case: &'static str,
+    pub reason: String,
+}
+
+/// Returns all test cases of the suite.
+pub fn test_cases() -> Vec<TestCase> {
+    vec![
+        test_case("ReadRequest", b"ReadRequest", &[]),
+        test_case("ReadRequestTwice", b"ReadRequestTwice", &[]),
+        test_case("EmptyResponse", b"", &[]),
+        test_case("WriteResponseTwice", b"Second", &[]),
+        test_case("LargeResponse", &vec![42u8; 1 << 20], &[]),
+        TestCase {
+            lookup_data: HashMap::from([(b"Key".to_vec(), b"Value".to_vec())]),
+            ..test_case("LookupPresent", b"Value", &[])
+        },
+        TestCase {
+            lookup_data: HashMap::from([(b"Key".to_vec(), b"Value".to_vec())]),
+            ..test_case("LookupAbsent", b"None", &[])
+        },
+        test_case("WriteLog", b"", &["First", "Second"]),
+        test_case("WriteLogInvalidUtf8", b"ErrInvalidArgs", &[]),
+        test_case("InvalidHandle", b"ErrInvalidHandle", &[]),
+        test_case("Trap", b"", &[]),
+    ]
+}
+
+fn test_case(name: &'static str, response: &[u8], logs: &[&str]) -> TestCase {
+    TestCase {
+        name,
+        lookup_data: HashMap::new(),
+        expected: Invocation {
+            response: response.to_vec(),
+            logs: logs.iter().map(|log| log.to_string()).collect(),
+        },
+    }
+}
+
+/// Compiles the Wasm module of the conformance suite.
+pub fn compile_module() -> anyhow::Result<Vec<u8>> {
+    let manifest_path = format!("{}/module/Cargo.toml", env!("CARGO_MANIFEST_DIR"));
+    oak_functions_test_utils::compile_rust_wasm(&manifest_path, false)
+        .context("couldn't compile conformance module")
+}
+
+/// Runs all test cases against the host, and returns the test cases that failed.
+pub fn run<H: Host>(host: &mut H, wasm_module_bytes: &[u8]) -> Vec<Failure> {
+    test_cases()
+        .into_iter()
+        .filter_map(|test_case| {
+            let reason = match host.invoke(
+                wasm_module_bytes,
+                test_case.lookup_data,
+                test_case.name.as_bytes(),
+            ) {
+                Ok(invocation) if invocation == test_case.expected => return None,
+                Ok(invocation) => describe_mismatch(&test_case.expected, &invocation),
+                Err(err) => format!("invocation failed: {:?}", err),
+            };
+            Some(Failure {
+                test_case: test_case.name,
+                reason,
+            })
+        })
+        .collect()
+}
+
+fn describe_mismatch(expected: &Invocation, actual: &Invocation) -> String {
+    if expected.response != actual.response {
+        // Responses can be large, so only the lengths and a short prefix are shown.
+        format!(
+            "expected response of {} bytes starting with {:?}, got {} bytes starting with {:?}",
+            expected.response.len(),
+            String::from_utf8_lossy(&expected.response[..expected.response.len().min(32)]),
+            actual.response.len(),
+            String::from_utf8_lossy(&actual.response[..actual.response.len().min(32)]),
+        )
+    } else {
+        format!("expected logs {:?}, got {:?}", expected.logs, actual.logs)
+    }
+}
@@ -0,0 +1,166 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Checks the hosts of the Oak Functions service, with the extensions that the service creates for
+//! them.
+
+use oak_functions_abi::{Request, Response};
+use oak_functions_abi_conformance::{Host, Invocation};
+use oak_functions_lookup::LookupDataManager;
+use oak_functions_service::{wasm, StandaloneLogger};
+use oak_functions_wasm::WasmLimits;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{atomic::AtomicU64, Arc, Once},
+};
+
+thread_local! {
+    // Messages logged by the Wasm module on this thread.
+    static LOGS: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+/// Records the messages that the Wasm module logs through the workload logging extension, which
+/// the logger of the service forwards to the `log` crate. Invocations run on the thread of the
+/// test, so the messages are recorded per thread, and the hosts can be checked in parallel.
+struct RecordingLog;
+
+impl log::Log for RecordingLog {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let message = record.args().to_string();
+        if let Some(message) = message.strip_prefix("[Wasm] ") {
+            LOGS.with(|logs| logs.borrow_mut().push(message.to_string()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static RECORDING_LOG: RecordingLog = RecordingLog;
+
+// Invokes the handler that `create_handler` creates with the logger and lookup data of the
+// service, as the service would, and records the messages that the module logs meanwhile.
+fn invoke<F>(
+    lookup_data: HashMap<Vec<u8>, Vec<u8>>,
+    request: &[u8],
+    create_handler: F,
+) -> anyhow::Result<Invocation>
+where
+    F: FnOnce(
+        StandaloneLogger,
+        Arc<LookupDataManager<StandaloneLogger>>,
+    ) -> anyhow::Result<Box<dyn Fn(Request) -> anyhow::Result<Response>>>,
+{
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        log::set_logger(&RECORDING_LOG).expect("couldn't set logger");
+        log::set_max_level(log::LevelFilter::Debug);
+    });
+
+    let logger = StandaloneLogger::default();
+    // The messages of the module are only logged if sensitive logging is enabled.
+    logger.set_sensitive_logging(true);
+    let lookup_data_manager = Arc::new(LookupDataManager::for_test(
+        lookup_data.into_iter().collect(),
+        logger.clone(),
+    ));
+    let handle_invoke = create_handler(logger, lookup_data_manager)?;
+    LOGS.with(|logs| logs.borrow_mut().clear());
+    let response = handle_invoke(Request {
+        body: request.to_vec(),
+    })?;
+    Ok(Invocation {
+        response: response.body()?.to_vec(),
+        logs: LOGS.with(|logs| logs.take()),
+    })
+}
+
+/// The Wasmi host, which the service uses by default.
+struct WasmiHost;
+
+impl Host for WasmiHost {
+    fn invoke(
+        &mut self,
+        wasm_module_bytes: &[u8],
+        lookup_data: HashMap<Vec<u8>, Vec<u8>>,
+        request: &[u8],
+    ) -> anyhow::Result<Invocation> {
+        invoke(lookup_data, request, |logger, lookup_data_manager| {
+            let handler = wasm::new_wasm_handler(
+                wasm_module_bytes,
+                logger,
+                lookup_data_manager,
+                0,
+                None,
+                WasmLimits::default(),
+                0,
+                Arc::new(AtomicU64::new(0)),
+                false,
+                false,
+            )?;
+            Ok(Box::new(move |request| handler.handle_invoke(request)))
+        })
+    }
+}
+
+/// The Wasmtime host, which the service uses if the Wasmtime backend is requested.
+#[cfg(feature = "wasmtime")]
+struct WasmtimeHost;
+
+#[cfg(feature = "wasmtime")]
+impl Host for WasmtimeHost {
+    fn invoke(
+        &mut self,
+        wasm_module_bytes: &[u8],
+        lookup_data: HashMap<Vec<u8>, Vec<u8>>,
+        request: &[u8],
+    ) -> anyhow::Result<Invocation> {
+        invoke(lookup_data, request, |logger, lookup_data_manager| {
+            let handler = wasm::new_wasmtime_handler(
+                wasm_module_bytes,
+                logger,
+                lookup_data_manager,
+                0,
+                None,
+                WasmLimits::default(),
+                false,
+                false,
+            )?;
+            Ok(Box::new(move |request| handler.handle_invoke(request)))
+        })
+    }
+}
+
+#[test]
+fn test_wasmi_host_conformance() {
+    let wasm_module_bytes =
+        oak_functions_abi_conformance::compile_module().expect("couldn't compile module");
+    let failures = oak_functions_abi_conformance::run(&mut WasmiHost, &wasm_module_bytes);
+    assert!(failures.is_empty(), "{:#?}", failures);
+}
+
+#[cfg(feature = "wasmtime")]
+#[test]
+fn test_wasmtime_host_conformance() {
+    let wasm_module_bytes =
+        oak_functions_abi_conformance::compile_module().expect("couldn't compile module");
+    let failures = oak_functions_abi_conformance::run(&mut WasmtimeHost, &wasm_module_bytes);
+    assert!(failures.is_empty(), "{:#?}", failures);
+}
@@ -16,9 +16,7 @@
 
 //! Oak Functions echo example.
 
-#[cfg_attr(not(test), no_mangle)]
-pub extern "C" fn main() {
-    let request = oak_functions_sdk::read_request().expect("couldn't read request body");
-    let response = request;
-    oak_functions_sdk::write_response(&response).expect("couldn't write response body");
+#[oak_functions_sdk::main]
+fn handle(request: Vec<u8>) -> Vec<u8> {
+    request
 }
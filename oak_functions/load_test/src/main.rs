@@ -14,50 +14,213 @@
 // limitations under the License.
 //
 
+//! Drives an Oak Functions instance with concurrent requests and reports throughput and latency
+//! statistics, so that performance regressions can be caught.
+
 use anyhow::Context;
 use bencher::stats::Stats;
+use clap::Parser;
 use oak_functions_client::OakFunctionsClient;
-use std::time::Instant;
+use rand::Rng;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 // From https://pantheon.corp.google.com/api-gateway/gateway/weather-lookup-grpc/location/europe-west2?project=oak-ci.
 const URL: &str = "https://weather-lookup-grpc-8tk01hn7.nw.gateway.dev";
 // Test request coordinates are defined in `oak_functions/lookup_data_generator/src/data.rs`.
-const REQUEST: &[u8] = br#"{"lat":0,"lng":0}"#;
-const TOTAL_REQUESTS: usize = 50;
+const REQUEST: &str = r#"{"lat":0,"lng":0}"#;
+// Upper bounds of the latency histogram buckets.
+const HISTOGRAM_BUCKETS_MILLIS: &[f64] = &[
+    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0,
+];
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let mut client = OakFunctionsClient::new(URL)
-        .await
-        .context("couldn't create client")?;
+#[derive(Parser, Clone, Debug)]
+#[command(about = "Oak Functions Load Test")]
+pub struct Opt {
+    #[arg(long, help = "URI of the Oak Functions application to load", default_value = URL)]
+    uri: String,
+
+    /// Number of clients sending requests concurrently.
+    #[arg(long, default_value = "1")]
+    concurrency: usize,
+
+    /// Total number of requests to send. Ignored if `duration_seconds` is set.
+    #[arg(long, default_value = "50")]
+    total_requests: usize,
+
+    /// Duration of the load test. If set, requests are sent until it elapses.
+    #[arg(long)]
+    duration_seconds: Option<u64>,
+
+    /// Request payload. Ignored if `random_payload_size_bytes` is set.
+    #[arg(long, default_value = REQUEST)]
+    request: String,
+
+    /// Sends random payloads whose size is uniformly distributed in the given inclusive range,
+    /// e.g. `100..=1000`.
+    #[arg(long, value_parser = parse_range)]
+    random_payload_size_bytes: Option<(usize, usize)>,
+
+    /// Performs the attestation and key exchange handshake for every request, instead of once per
+    /// client.
+    #[arg(long)]
+    handshake_per_request: bool,
+}
+
+fn parse_range(s: &str) -> Result<(usize, usize), String> {
+    let (min, max) = s
+        .split_once("..=")
+        .ok_or_else(|| String::from("expected a range of the form `min..=max`"))?;
+    let min = min.parse::<usize>().map_err(|err| err.to_string())?;
+    let max = max.parse::<usize>().map_err(|err| err.to_string())?;
+    if min > max {
+        return Err(String::from(
+            "the lower bound of the range is larger than the upper bound",
+        ));
+    }
+    Ok((min, max))
+}
+
+impl Opt {
+    fn payload(&self) -> Vec<u8> {
+        match self.random_payload_size_bytes {
+            Some((min, max)) => {
+                let mut rng = rand::thread_rng();
+                let mut payload = vec![0u8; rng.gen_range(min..=max)];
+                rng.fill(payload.as_mut_slice());
+                payload
+            }
+            None => self.request.as_bytes().to_vec(),
+        }
+    }
+}
 
-    let mut latencies_millis = Vec::<f64>::with_capacity(TOTAL_REQUESTS);
+/// Sends requests until the shared budget is exhausted or the deadline is reached, and returns the
+/// latency of each request in milliseconds.
+async fn run_client(
+    opt: Arc<Opt>,
+    remaining_requests: Arc<AtomicUsize>,
+    deadline: Option<Instant>,
+) -> anyhow::Result<Vec<f64>> {
+    let mut latencies_millis = Vec::new();
+    let mut persistent_client = if opt.handshake_per_request {
+        None
+    } else {
+        Some(
+            OakFunctionsClient::new(&opt.uri)
+                .await
+                .context("couldn't create client")?,
+        )
+    };
 
-    for i in 0..TOTAL_REQUESTS {
+    loop {
+        match deadline {
+            Some(deadline) if Instant::now() >= deadline => break,
+            Some(_) => {}
+            None => {
+                if remaining_requests
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+
+        let payload = opt.payload();
         let start = Instant::now();
-        let response = client
-            .invoke(REQUEST)
+        let mut fresh_client;
+        let client = match persistent_client.as_mut() {
+            Some(client) => client,
+            None => {
+                fresh_client = OakFunctionsClient::new(&opt.uri)
+                    .await
+                    .context("couldn't create client")?;
+                &mut fresh_client
+            }
+        };
+        client
+            .invoke(&payload)
             .await
             .context("couldn't invoke Oak Functions instance")?;
-        let elapsed_millis = start.elapsed().as_millis();
-        latencies_millis.push(elapsed_millis as f64);
+        latencies_millis.push(start.elapsed().as_secs_f64() * 1000.0);
+    }
+
+    Ok(latencies_millis)
+}
+
+fn print_histogram(latencies_millis: &[f64]) {
+    let mut lower_bound = 0.0;
+    for upper_bound in HISTOGRAM_BUCKETS_MILLIS
+        .iter()
+        .copied()
+        .chain(std::iter::once(f64::INFINITY))
+    {
+        let count = latencies_millis
+            .iter()
+            .filter(|latency| **latency >= lower_bound && **latency < upper_bound)
+            .count();
+        let bar = "#".repeat(count * 50 / latencies_millis.len().max(1));
         println!(
-            "#{:03}/{:03}: {:4?}ms: response: {:?}",
-            i,
-            TOTAL_REQUESTS,
-            elapsed_millis,
-            std::str::from_utf8(&response).unwrap()
+            "[{:6.0}, {:6.0})ms: {:6} {}",
+            lower_bound, upper_bound, count, bar
         );
+        lower_bound = upper_bound;
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let opt = Arc::new(Opt::parse());
+    let remaining_requests = Arc::new(AtomicUsize::new(opt.total_requests));
+    let deadline = opt
+        .duration_seconds
+        .map(|duration_seconds| Instant::now() + Duration::from_secs(duration_seconds));
+
+    let start = Instant::now();
+    let clients: Vec<_> = (0..opt.concurrency)
+        .map(|_| {
+            tokio::spawn(run_client(
+                opt.clone(),
+                remaining_requests.clone(),
+                deadline,
+            ))
+        })
+        .collect();
+    let mut latencies_millis = Vec::new();
+    for client in clients {
+        latencies_millis.extend(client.await.context("client task failed")??);
+    }
+    let elapsed = start.elapsed();
+
+    if latencies_millis.is_empty() {
+        anyhow::bail!("no requests were sent");
     }
+
     println!("-------- total --------");
+    println!("requests: {}", latencies_millis.len());
+    println!(
+        "throughput: {:.1} requests/s",
+        latencies_millis.len() as f64 / elapsed.as_secs_f64()
+    );
     println!("mean: {:4.0}ms", &latencies_millis.mean());
     println!("min: {:4.0}ms", &latencies_millis.min());
     println!("max: {:4.0}ms", &latencies_millis.max());
+    println!("p50: {:4.0}ms", &latencies_millis.percentile(50.0));
+    println!("p90: {:4.0}ms", &latencies_millis.percentile(90.0));
+    println!("p99: {:4.0}ms", &latencies_millis.percentile(99.0));
     // See https://en.wikipedia.org/wiki/Median_absolute_deviation.
     println!(
         "median absolute deviation: {:4.0}ms",
         &latencies_millis.median_abs_dev()
     );
+    println!("-------- histogram --------");
+    print_histogram(&latencies_millis);
 
     Ok(())
 }
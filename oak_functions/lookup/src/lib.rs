@@ -17,16 +17,25 @@
 
 extern crate alloc;
 
+mod sorted_table;
+
+pub use sorted_table::{SortedTable, SortedTableBuilder};
+
 use alloc::{
     boxed::Box,
     format,
     string::{String, ToString},
     sync::Arc,
+    vec,
     vec::Vec,
 };
+use core::sync::atomic::{AtomicU64, Ordering};
 use hashbrown::HashMap;
 use log::{info, Level};
-use oak_functions_abi::{proto::OakStatus, ExtensionHandle, StorageGetItemResponse};
+use oak_functions_abi::{
+    proto::OakStatus, ExtensionHandle, StorageGetItemResponse, StorageGetRangeRequest,
+    StorageGetRangeResponse,
+};
 use oak_functions_extension::{ExtensionFactory, OakApiNativeExtension};
 use oak_logger::OakLogger;
 use spinning_top::Spinlock;
@@ -57,6 +66,41 @@ where
     }
 }
 
+/// Creates extensions for range lookups, which return at most `max_entries` entries per lookup.
+pub struct RangeLookupFactory<L: OakLogger> {
+    manager: Arc<LookupDataManager<L>>,
+    max_entries: usize,
+}
+
+impl<L> RangeLookupFactory<L>
+where
+    L: OakLogger + 'static,
+{
+    pub fn new_boxed_extension_factory(
+        manager: Arc<LookupDataManager<L>>,
+        max_entries: usize,
+    ) -> anyhow::Result<Box<dyn ExtensionFactory<L>>> {
+        let range_lookup_factory = Self {
+            manager,
+            max_entries,
+        };
+        Ok(Box::new(range_lookup_factory))
+    }
+}
+
+impl<L> ExtensionFactory<L> for RangeLookupFactory<L>
+where
+    L: OakLogger + 'static,
+{
+    fn create(&self) -> anyhow::Result<Box<dyn OakApiNativeExtension>> {
+        let extension = RangeLookup {
+            lookup_data: self.manager.create_lookup_data(),
+            max_entries: self.max_entries,
+        };
+        Ok(Box::new(extension))
+    }
+}
+
 impl<L: OakLogger> OakApiNativeExtension for LookupData<L> {
     fn invoke(&mut self, request: Vec<u8>) -> Result<Vec<u8>, OakStatus> {
         // The request is the key to lookup.
@@ -95,10 +139,221 @@ impl<L: OakLogger> OakApiNativeExtension for LookupData<L> {
     }
 }
 
+/// Provides range lookups on shared lookup data.
+pub struct RangeLookup<L: OakLogger + Clone> {
+    lookup_data: LookupData<L>,
+    // The maximum number of entries that a single range lookup returns.
+    max_entries: usize,
+}
+
+impl<L: OakLogger> OakApiNativeExtension for RangeLookup<L> {
+    fn invoke(&mut self, request: Vec<u8>) -> Result<Vec<u8>, OakStatus> {
+        let request = StorageGetRangeRequest::try_from(request.as_slice()).map_err(|err| {
+            self.lookup_data.log_error(&format!(
+                "storage_get_range(): couldn't decode request: {:?}",
+                err
+            ));
+            OakStatus::ErrInvalidArgs
+        })?;
+        let start_to_log = request.start.iter().take(512).copied().collect::<Vec<_>>();
+        self.lookup_data.log_debug(&format!(
+            "storage_get_range(): start: {}",
+            format_bytes(&start_to_log)
+        ));
+        let response =
+            self.lookup_data
+                .get_range(&request.start, request.end.as_deref(), self.max_entries);
+        self.lookup_data.log_debug(&format!(
+            "storage_get_range(): found {} entries, truncated: {}",
+            response.entries.len(),
+            response.truncated
+        ));
+        Ok(response.into())
+    }
+
+    fn terminate(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn get_handle(&self) -> ExtensionHandle {
+        ExtensionHandle::LookupRangeHandle
+    }
+}
+
 // Data maintains the invariant on lookup data to have [at most one
 // value](https://github.com/project-oak/oak/tree/main/oak_functions/lookup/README.md#invariant-at-most-one-value)
 pub type Data = HashMap<Vec<u8>, Vec<u8>>;
 
+/// The times after which entries are no longer looked up, in seconds since the Unix epoch, by key.
+/// Keys of entries that never expire are left out.
+pub type Expiries = HashMap<Vec<u8>, u64>;
+
+/// Storage of the lookup data of a shard, which serves the lookups of requests. The data is never
+/// mutated once it has been built.
+pub trait LookupDataBackend: Send + Sync {
+    /// Gets the value of `key`, if any.
+    fn get(&self, key: &[u8]) -> Option<&[u8]>;
+
+    /// Gets the entries whose keys are at least `start` and, if given, less than `end`, ordered by
+    /// key. At most `limit` entries are returned.
+    fn range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Vec<(&[u8], &[u8])>;
+
+    /// Gets the number of entries.
+    fn len(&self) -> usize;
+
+    /// Whether there are no entries.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The kinds of [`LookupDataBackend`] that the lookup data can be stored in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// A hash map, with an index of its keys in order for range lookups. Every key and value is
+    /// allocated separately.
+    #[default]
+    HashMap,
+    /// A [`SortedTable`], which keeps all entries in a single buffer. Exact lookups take
+    /// logarithmic time, but the lookup data takes much less memory, which also applies to the
+    /// next lookup data while it is built.
+    SortedTable,
+}
+
+// The data of a shard in a hash map, with an index of its keys in order for range lookups.
+#[derive(Default)]
+struct HashMapBackend {
+    data: Data,
+    ordered_keys: Vec<Vec<u8>>,
+}
+
+impl HashMapBackend {
+    fn new(data: Data) -> Self {
+        let mut ordered_keys: Vec<Vec<u8>> = data.keys().cloned().collect();
+        ordered_keys.sort_unstable();
+        Self { data, ordered_keys }
+    }
+}
+
+impl LookupDataBackend for HashMapBackend {
+    fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.data.get(key).map(Vec::as_slice)
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Vec<(&[u8], &[u8])> {
+        let first = self
+            .ordered_keys
+            .partition_point(|key| key.as_slice() < start);
+        self.ordered_keys[first..]
+            .iter()
+            .take_while(|key| end.map_or(true, |end| key.as_slice() < end))
+            .take(limit)
+            .map(|key| (key.as_slice(), self.data[key].as_slice()))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+// The lookup data of a shard, together with the expiries of its entries.
+#[derive(Clone)]
+struct Shard {
+    data: Arc<dyn LookupDataBackend>,
+    expiries: Arc<Expiries>,
+    // The earliest expiry of any entry, so that shards without expired entries are skipped
+    // quickly when the lookup data is expired.
+    earliest_expiry: Option<u64>,
+}
+
+impl Shard {
+    fn new(data: Arc<dyn LookupDataBackend>, expiries: Expiries) -> Self {
+        Self {
+            data,
+            earliest_expiry: expiries.values().min().copied(),
+            expiries: Arc::new(expiries),
+        }
+    }
+
+    // Returns empty lookup data stored in `backend`.
+    fn empty(backend: Backend) -> Self {
+        match backend {
+            Backend::HashMap => Self::new(Arc::new(HashMapBackend::default()), Expiries::new()),
+            Backend::SortedTable => Self::new(Arc::new(SortedTable::default()), Expiries::new()),
+        }
+    }
+
+    // Whether the entry of `key` expired at `now`.
+    fn is_expired(&self, key: &[u8], now: u64) -> bool {
+        self.expiries
+            .get(key)
+            .map_or(false, |expires_at| *expires_at <= now)
+    }
+
+    // Whether any entry expired at `now`.
+    fn has_expired(&self, now: u64) -> bool {
+        self.earliest_expiry
+            .map_or(false, |expires_at| expires_at <= now)
+    }
+
+    fn get(&self, key: &[u8], now: u64) -> Option<&[u8]> {
+        if self.is_expired(key, now) {
+            return None;
+        }
+        self.data.get(key)
+    }
+
+    // Like `LookupDataBackend::range`, but skips the entries that expired at `now`.
+    fn range(
+        &self,
+        start: &[u8],
+        end: Option<&[u8]>,
+        limit: usize,
+        now: u64,
+    ) -> Vec<(&[u8], &[u8])> {
+        let mut entries = Vec::new();
+        let mut start = start.to_vec();
+        while entries.len() < limit {
+            let remaining = limit - entries.len();
+            let batch = self.data.range(&start, end, remaining);
+            let exhausted = batch.len() < remaining;
+            // Continue with the smallest key after the last key of the batch.
+            if let Some((last, _)) = batch.last() {
+                start = last.to_vec();
+                start.push(0);
+            }
+            entries.extend(
+                batch
+                    .into_iter()
+                    .filter(|(key, _)| !self.is_expired(key, now)),
+            );
+            if exhausted {
+                break;
+            }
+        }
+        entries
+    }
+
+    // Returns the shard without the entries that expired at `now`, stored in `backend`.
+    fn sweep(&self, now: u64, backend: Backend) -> Self {
+        let data: Data = self
+            .range(&[], None, usize::MAX, now)
+            .into_iter()
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect();
+        let expiries: Expiries = self
+            .expiries
+            .iter()
+            .filter(|(_, expires_at)| **expires_at > now)
+            .map(|(key, expires_at)| (key.clone(), *expires_at))
+            .collect();
+        let mut data_builder = DataBuilder::new(backend);
+        data_builder.extend(data, expiries);
+        data_builder.build()
+    }
+}
+
 #[derive(Default)]
 enum BuilderState {
     #[default]
@@ -109,33 +364,83 @@ enum BuilderState {
 // Incrementally build next lookup data keeping track of the state.
 #[derive(Default)]
 struct DataBuilder {
+    backend: Backend,
     data: Data,
+    table: SortedTableBuilder,
+    expiries: Expiries,
     state: BuilderState,
 }
 
 impl DataBuilder {
+    fn new(backend: Backend) -> Self {
+        Self {
+            backend,
+            ..Default::default()
+        }
+    }
+
     /// Build data from the builder and set the builder back to the initial state.
-    fn build(&mut self) -> Data {
+    fn build(&mut self) -> Shard {
         self.state = BuilderState::Empty;
-        core::mem::take(&mut self.data)
+        let data: Arc<dyn LookupDataBackend> = match self.backend {
+            Backend::HashMap => Arc::new(HashMapBackend::new(core::mem::take(&mut self.data))),
+            Backend::SortedTable => Arc::new(core::mem::take(&mut self.table).build()),
+        };
+        Shard::new(data, core::mem::take(&mut self.expiries))
+    }
+
+    /// Discards the data of the builder and sets it back to the initial state.
+    fn clear(&mut self) {
+        *self = Self::new(self.backend);
     }
 
     /// Extends the DataBuilder with new data.
     ///
     /// Note, if new data contains a key already present in the existing data, calling extend
-    /// overwrites the value.
-    fn extend(&mut self, new_data: Data) {
+    /// overwrites the value, and its expiry by the one in `new_expiries`, if any.
+    fn extend(&mut self, new_data: Data, new_expiries: Expiries) {
         self.state = BuilderState::Extending;
-        self.data.extend(new_data);
+        if !self.expiries.is_empty() {
+            for key in new_data.keys() {
+                self.expiries.remove(key);
+            }
+        }
+        self.expiries.extend(new_expiries);
+        match self.backend {
+            Backend::HashMap => self.data.extend(new_data),
+            Backend::SortedTable => self.table.extend(new_data),
+        }
     }
 }
 
+/// Returns the index of the shard that holds `key` if the lookup data is split into `shard_count`
+/// shards.
+///
+/// Keys are assigned to shards by their 64-bit FNV-1a hash modulo the number of shards, which
+/// producers of sharded lookup data must apply to split it.
+pub fn shard_index(key: &[u8], shard_count: usize) -> usize {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    let hash = key.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    });
+    (hash % shard_count as u64) as usize
+}
+
 /// Utility for managing lookup data.
 ///
 /// `LookupDataManager` can be used to create `LookupData` instances that share the underlying data.
 /// It can also update the underlying data. After updating the data, new `LookupData` instances will
 /// use the new data, but earlier instances will still used the earlier data.
 ///
+/// The data may be split into shards by [`shard_index`], which are updated independently, so that
+/// only one shard at a time has to be held twice in memory during updates. The data of every
+/// shard is stored in the same [`Backend`].
+///
+/// Entries may expire at a given time, after which they are no longer looked up. Since the enclave
+/// has no clock, the time of the lookup data only advances when it is set by
+/// [`LookupDataManager::expire_lookup_data`], which also reclaims the expired entries.
+///
 /// LookupDataManager maintains the invariants [consistent view on lookup
 /// data](https://github.com/project-oak/oak/tree/main/oak_functions/lookup/README.md#invariant-consistent-view-on-lookup-data) , and [shared
 /// lookup data](https://github.com/project-oak/oak/tree/main/oak_functions/lookup/README.md#invariant-shared-lookup-data)
@@ -145,10 +450,13 @@ impl DataBuilder {
 ///
 /// In the future we may replace both the mutex and the hash map with something like RCU.
 pub struct LookupDataManager<L: OakLogger + Clone> {
-    data: Spinlock<Arc<Data>>,
+    // The current data of every shard.
+    data: Spinlock<Vec<Shard>>,
     // Behind a lock, because we have multiple references to LookupDataManager and need to mutate
-    // data builder.
-    data_builder: Spinlock<DataBuilder>,
+    // data builder. One per shard.
+    data_builders: Spinlock<Vec<DataBuilder>>,
+    // The latest time that the lookup data was expired at, in seconds since the Unix epoch.
+    now: AtomicU64,
     logger: L,
 }
 
@@ -156,13 +464,14 @@ impl<L> LookupDataManager<L>
 where
     L: OakLogger + Clone,
 {
-    /// Creates a new instance with empty backing data.
+    /// Creates a new instance with empty backing data in a single shard.
     pub fn new_empty(logger: L) -> Self {
         Self {
-            data: Spinlock::new(Arc::new(Data::new())),
+            data: Spinlock::new(vec![Shard::empty(Backend::default())]),
             /// Incrementally builds the backing data that will be used by new `LookupData`
             /// instances when finished.
-            data_builder: Spinlock::new(DataBuilder::default()),
+            data_builders: Spinlock::new(vec![DataBuilder::default()]),
+            now: AtomicU64::new(0),
             logger,
         }
     }
@@ -170,55 +479,176 @@ where
     /// Creates an instance of LookupData populated with the given entries.
     pub fn for_test(data: Data, logger: L) -> Self {
         let test_manager = Self::new_empty(logger);
-        *test_manager.data.lock() = Arc::new(data);
+        *test_manager.data.lock() = vec![Shard::new(
+            Arc::new(HashMapBackend::new(data)),
+            Expiries::new(),
+        )];
         test_manager
     }
 
+    /// Splits the backing data into `shard_count` empty shards, discarding all current data.
+    pub fn set_shard_count(&self, shard_count: usize) -> anyhow::Result<()> {
+        if shard_count == 0 {
+            anyhow::bail!("lookup data needs at least one shard");
+        }
+        info!("Splitting lookup data into {} shards", shard_count);
+        let mut data_builders = self.data_builders.lock();
+        let backend = data_builders[0].backend;
+        self.reset(&mut data_builders, shard_count, backend);
+        Ok(())
+    }
+
+    /// Stores the lookup data of all shards in the given backend from now on, discarding all
+    /// current data.
+    pub fn set_backend(&self, backend: Backend) {
+        info!("Storing lookup data in backend {:?}", backend);
+        let mut data_builders = self.data_builders.lock();
+        let shard_count = data_builders.len();
+        self.reset(&mut data_builders, shard_count, backend);
+    }
+
+    // Replaces the data and data builders by empty ones. Takes the lock of the data builders, so
+    // that they are locked before the data like everywhere else.
+    fn reset(&self, data_builders: &mut Vec<DataBuilder>, shard_count: usize, backend: Backend) {
+        *data_builders = (0..shard_count)
+            .map(|_| DataBuilder::new(backend))
+            .collect();
+        let mut data = self.data.lock();
+        *data = (0..shard_count).map(|_| Shard::empty(backend)).collect();
+    }
+
     pub fn extend_next_lookup_data(&self, new_data: Data) {
-        info!("Start extending next lookup data");
+        self.extend_next_lookup_data_shard(0, new_data)
+            .expect("couldn't extend the first shard");
+    }
+
+    /// Extends the next data of the given shard. Fails if any of the keys belongs to another
+    /// shard, since it could not be looked up.
+    pub fn extend_next_lookup_data_shard(
+        &self,
+        shard: usize,
+        new_data: Data,
+    ) -> anyhow::Result<()> {
+        self.extend_next_lookup_data_shard_with_expiries(shard, new_data, Expiries::new())
+    }
+
+    /// Extends the next data of the given shard with entries that expire as given by
+    /// `new_expiries`. Fails if any of the keys belongs to another shard, since it could not be
+    /// looked up.
+    pub fn extend_next_lookup_data_shard_with_expiries(
+        &self,
+        shard: usize,
+        new_data: Data,
+        new_expiries: Expiries,
+    ) -> anyhow::Result<()> {
+        info!("Start extending next lookup data of shard {}", shard);
         {
-            let mut data_builder = self.data_builder.lock();
-            data_builder.extend(new_data);
+            let mut data_builders = self.data_builders.lock();
+            let shard_count = data_builders.len();
+            let data_builder = data_builders
+                .get_mut(shard)
+                .ok_or_else(|| anyhow::anyhow!("invalid lookup data shard {}", shard))?;
+            if shard_count > 1
+                && new_data
+                    .keys()
+                    .any(|key| shard_index(key, shard_count) != shard)
+            {
+                anyhow::bail!(
+                    "lookup data of shard {} contains keys of other shards",
+                    shard
+                );
+            }
+            data_builder.extend(new_data, new_expiries);
         }
-        info!("Finish extending next lookup data");
+        info!("Finish extending next lookup data of shard {}", shard);
+        Ok(())
     }
 
     // Finish building the next lookup data and replace the current lookup data in place.
     pub fn finish_next_lookup_data(&self) {
+        self.finish_next_lookup_data_shard(0)
+            .expect("couldn't finish the first shard");
+    }
+
+    // Finish building the next lookup data of the given shard and replace the current lookup data
+    // of the shard in place. Other shards are not affected.
+    pub fn finish_next_lookup_data_shard(&self, shard: usize) -> anyhow::Result<()> {
         let data_len;
         let next_data_len;
-        info!("Start replacing lookup data by next lookup data");
+        info!(
+            "Start replacing lookup data of shard {} by next lookup data",
+            shard
+        );
         {
-            let mut data_builder = self.data_builder.lock();
+            let mut data_builders = self.data_builders.lock();
+            let data_builder = data_builders
+                .get_mut(shard)
+                .ok_or_else(|| anyhow::anyhow!("invalid lookup data shard {}", shard))?;
             let next_data = data_builder.build();
-            next_data_len = next_data.len();
+            next_data_len = next_data.data.len();
             let mut data = self.data.lock();
-            *data = Arc::new(next_data);
-            data_len = data.len();
+            data[shard] = next_data;
+            data_len = data.iter().map(|shard| shard.data.len()).sum::<usize>();
         }
         info!(
             "Finished replacing lookup data with len {} by next lookup data with len {}",
             data_len, next_data_len
         );
+        Ok(())
     }
 
     pub fn abort_next_lookup_data(&self) {
-        info!("Start aborting next lookup data");
+        self.abort_next_lookup_data_shard(0)
+            .expect("couldn't abort the first shard");
+    }
+
+    pub fn abort_next_lookup_data_shard(&self, shard: usize) -> anyhow::Result<()> {
+        info!("Start aborting next lookup data of shard {}", shard);
         {
-            let mut data_builder = self.data_builder.lock();
+            let mut data_builders = self.data_builders.lock();
+            let data_builder = data_builders
+                .get_mut(shard)
+                .ok_or_else(|| anyhow::anyhow!("invalid lookup data shard {}", shard))?;
             // Clear the builder throwing away the intermediate result.
-            let _ = data_builder.build();
+            data_builder.clear();
+        }
+        info!("Finish aborting next lookup data of shard {}", shard);
+        Ok(())
+    }
+
+    /// Advances the time of the lookup data to `now`, in seconds since the Unix epoch, so that
+    /// entries that expired by then are no longer looked up, and reclaims them. The time never
+    /// goes back.
+    pub fn expire_lookup_data(&self, now: u64) {
+        let now = self.now.fetch_max(now, Ordering::SeqCst).max(now);
+        // Locks the data builders, so that no shard is replaced by next lookup data while it is
+        // swept. New `LookupData` instances skip the expired entries in the meantime.
+        let data_builders = self.data_builders.lock();
+        let backend = data_builders[0].backend;
+        for index in 0..data_builders.len() {
+            let shard = self.data.lock()[index].clone();
+            if !shard.has_expired(now) {
+                continue;
+            }
+            info!("Start reclaiming expired lookup data of shard {}", index);
+            let swept = shard.sweep(now, backend);
+            let reclaimed = shard.data.len() - swept.data.len();
+            self.data.lock()[index] = swept;
+            info!(
+                "Finish reclaiming {} expired entries of shard {}",
+                reclaimed, index
+            );
         }
-        info!("Finish aborting next lookup data");
     }
 
-    /// Creates a new `LookupData` instance with a reference to the current backing data.
+    /// Creates a new `LookupData` instance with a reference to the current backing data of all
+    /// shards.
     pub fn create_lookup_data(&self) -> LookupData<L> {
         let keys;
         let data = {
             let data = self.data.lock().clone();
-            keys = data.len();
-            LookupData::new(data, self.logger.clone())
+            keys = data.iter().map(|shard| shard.data.len()).sum::<usize>();
+            LookupData::new(data, self.now.load(Ordering::SeqCst), self.logger.clone())
         };
         info!("Created lookup data with len: {}", keys);
         data
@@ -227,7 +657,11 @@ where
 
 /// Provides access to shared lookup data.
 pub struct LookupData<L: OakLogger + Clone> {
-    data: Arc<Data>,
+    // The data of every shard, as it was when the instance was created.
+    data: Vec<Shard>,
+    // The time of the lookup data when the instance was created, at which expired entries are
+    // skipped.
+    now: u64,
     logger: L,
 }
 
@@ -235,23 +669,52 @@ impl<L> LookupData<L>
 where
     L: OakLogger + Clone,
 {
-    fn new(data: Arc<Data>, logger: L) -> Self {
-        Self { data, logger }
+    fn new(data: Vec<Shard>, now: u64, logger: L) -> Self {
+        Self { data, now, logger }
     }
 
-    /// Gets an individual entry from the backing data.
+    /// Gets an individual entry from the backing data. Expired entries are not found.
     pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
-        self.data.get(key).cloned()
+        self.data[shard_index(key, self.data.len())]
+            .get(key, self.now)
+            .map(<[u8]>::to_vec)
     }
 
-    /// Gets the number of entries in the backing data.
+    /// Gets the entries whose keys are at least `start` and, if given, less than `end` from the
+    /// backing data, ordered by key. At most `max_entries` entries are returned, and the response
+    /// indicates whether further entries were left out. Expired entries are skipped.
+    pub fn get_range(
+        &self,
+        start: &[u8],
+        end: Option<&[u8]>,
+        max_entries: usize,
+    ) -> StorageGetRangeResponse {
+        // Every shard contributes at most one entry more than requested, which is enough to tell
+        // whether entries were left out.
+        let mut entries: Vec<(&[u8], &[u8])> = Vec::new();
+        for shard in &self.data {
+            entries.extend(shard.range(start, end, max_entries.saturating_add(1), self.now));
+        }
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        StorageGetRangeResponse {
+            truncated: entries.len() > max_entries,
+            entries: entries
+                .into_iter()
+                .take(max_entries)
+                .map(|(key, value)| (key.to_vec(), value.to_vec()))
+                .collect(),
+        }
+    }
+
+    /// Gets the number of entries in the backing data, including expired entries that were not
+    /// reclaimed yet.
     pub fn len(&self) -> usize {
-        self.data.len()
+        self.data.iter().map(|shard| shard.data.len()).sum()
     }
 
     /// Whether the backing data is empty.
     pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+        self.data.iter().all(|shard| shard.data.is_empty())
     }
 
     /// Logs an error message.
@@ -372,6 +835,199 @@ mod tests {
         assert_eq!(lookup_data_2.len(), 1);
     }
 
+    #[test]
+    fn test_update_sharded_lookup_data() {
+        let manager = LookupDataManager::new_empty(TestLogger {});
+        manager.set_shard_count(2).unwrap();
+
+        let (shard_0, shard_1): (Vec<_>, Vec<_>) = create_test_data(0, 10)
+            .into_iter()
+            .partition(|(key, _)| shard_index(key, 2) == 0);
+        manager
+            .extend_next_lookup_data_shard(0, Data::from_iter(shard_0.clone()))
+            .unwrap();
+        manager
+            .extend_next_lookup_data_shard(1, Data::from_iter(shard_1.clone()))
+            .unwrap();
+
+        // Shards are replaced independently.
+        manager.finish_next_lookup_data_shard(0).unwrap();
+        let lookup_data_0 = manager.create_lookup_data();
+        manager.finish_next_lookup_data_shard(1).unwrap();
+        let lookup_data_1 = manager.create_lookup_data();
+
+        assert_eq!(lookup_data_0.len(), shard_0.len());
+        assert_eq!(lookup_data_1.len(), 10);
+        for (key, value) in shard_0.iter().chain(shard_1.iter()) {
+            assert_eq!(lookup_data_1.get(key).as_ref(), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_reject_keys_of_other_shards() {
+        let manager = LookupDataManager::new_empty(TestLogger {});
+        manager.set_shard_count(2).unwrap();
+
+        let data = create_test_data(0, 10);
+        assert!(manager.extend_next_lookup_data_shard(0, data).is_err());
+        assert!(manager
+            .extend_next_lookup_data_shard(2, Data::new())
+            .is_err());
+        assert!(manager.set_shard_count(0).is_err());
+    }
+
+    #[test]
+    fn test_get_range() {
+        let manager = LookupDataManager::new_empty(TestLogger {});
+        manager.set_shard_count(3).unwrap();
+        for shard in 0..3 {
+            let shard_data = create_test_data(0, 20)
+                .into_iter()
+                .filter(|(key, _)| shard_index(key, 3) == shard);
+            manager
+                .extend_next_lookup_data_shard(shard, Data::from_iter(shard_data))
+                .unwrap();
+            manager.finish_next_lookup_data_shard(shard).unwrap();
+        }
+        let lookup_data = manager.create_lookup_data();
+
+        let response = lookup_data.get_range(b"key12", Some(b"key15".as_slice()), 10);
+        assert_eq!(keys(&response), ["key12", "key13", "key14"]);
+        assert!(!response.truncated);
+
+        let response = lookup_data.get_range(b"key17", None, 3);
+        assert_eq!(keys(&response), ["key17", "key18", "key19"]);
+        assert!(response.truncated);
+
+        let response = lookup_data.get_range(b"key3", Some(b"key3".as_slice()), 10);
+        assert!(response.entries.is_empty());
+        assert!(!response.truncated);
+    }
+
+    #[test]
+    fn test_expire_lookup_data() {
+        let manager = LookupDataManager::new_empty(TestLogger {});
+        let expiries = Expiries::from_iter([(b"key1".to_vec(), 100), (b"key2".to_vec(), 200)]);
+        manager
+            .extend_next_lookup_data_shard_with_expiries(0, create_test_data(0, 5), expiries)
+            .unwrap();
+        manager.finish_next_lookup_data();
+        let lookup_data_0 = manager.create_lookup_data();
+
+        manager.expire_lookup_data(100);
+        let lookup_data_1 = manager.create_lookup_data();
+        assert_eq!(lookup_data_1.get(b"key1"), None);
+        assert_eq!(lookup_data_1.get(b"key2"), Some(b"value2".to_vec()));
+        let response = lookup_data_1.get_range(b"key0", None, 2);
+        assert_eq!(keys(&response), ["key0", "key2"]);
+        assert!(response.truncated);
+        // Expired entries are reclaimed, but earlier instances keep their view.
+        assert_eq!(lookup_data_1.len(), 4);
+        assert_eq!(lookup_data_0.get(b"key1"), Some(b"value1".to_vec()));
+
+        // The time never goes back.
+        manager.expire_lookup_data(50);
+        assert_eq!(manager.create_lookup_data().get(b"key1"), None);
+
+        // Entries that expired before they were added are skipped until they are reclaimed.
+        let expiries = Expiries::from_iter([(b"key3".to_vec(), 100)]);
+        manager
+            .extend_next_lookup_data_shard_with_expiries(0, create_test_data(0, 5), expiries)
+            .unwrap();
+        manager.finish_next_lookup_data();
+        let lookup_data = manager.create_lookup_data();
+        assert_eq!(lookup_data.len(), 5);
+        assert_eq!(lookup_data.get(b"key3"), None);
+        assert_eq!(lookup_data.get(b"key1"), Some(b"value1".to_vec()));
+        let response = lookup_data.get_range(b"key", None, 10);
+        assert_eq!(keys(&response), ["key0", "key1", "key2", "key4"]);
+        manager.expire_lookup_data(200);
+        assert_eq!(manager.create_lookup_data().len(), 4);
+    }
+
+    #[test]
+    fn test_sorted_table() {
+        let mut builder = SortedTableBuilder::default();
+        builder.extend(create_test_data(0, 10));
+        // Values of keys that were added before are overwritten.
+        builder.extend(Data::from_iter([(b"key3".to_vec(), b"new".to_vec())]));
+        builder.extend(create_test_data(10, 12));
+        let table = builder.build();
+
+        assert_eq!(table.len(), 12);
+        assert_eq!(table.get(b"key1"), Some(b"value1".as_slice()));
+        assert_eq!(table.get(b"key3"), Some(b"new".as_slice()));
+        assert_eq!(table.get(b"key11"), Some(b"value11".as_slice()));
+        assert_eq!(table.get(b"key12"), None);
+        assert_eq!(table.get(b""), None);
+
+        let keys: Vec<&[u8]> = table
+            .range(b"key1", Some(b"key2"), 10)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, [b"key1".as_slice(), b"key10", b"key11"]);
+        assert_eq!(table.range(b"key8", None, 10).len(), 2);
+        assert_eq!(table.range(b"key", None, 4).len(), 4);
+    }
+
+    #[test]
+    fn test_sorted_table_backend() {
+        let manager = LookupDataManager::new_empty(TestLogger {});
+        manager.set_shard_count(2).unwrap();
+        manager.set_backend(Backend::SortedTable);
+        for shard in 0..2 {
+            let shard_data = create_test_data(0, 20)
+                .into_iter()
+                .filter(|(key, _)| shard_index(key, 2) == shard);
+            manager
+                .extend_next_lookup_data_shard(shard, Data::from_iter(shard_data))
+                .unwrap();
+            manager.finish_next_lookup_data_shard(shard).unwrap();
+        }
+        let lookup_data = manager.create_lookup_data();
+
+        assert_eq!(lookup_data.len(), 20);
+        assert_eq!(lookup_data.get(b"key7"), Some(b"value7".to_vec()));
+        assert_eq!(lookup_data.get(b"key20"), None);
+        let response = lookup_data.get_range(b"key12", Some(b"key15".as_slice()), 10);
+        assert_eq!(keys(&response), ["key12", "key13", "key14"]);
+        assert!(!response.truncated);
+
+        // Changing the backend discards the data, but keeps the shards.
+        manager.set_backend(Backend::HashMap);
+        assert!(manager.create_lookup_data().is_empty());
+        let data = create_test_data(0, 10);
+        assert!(manager.extend_next_lookup_data_shard(0, data).is_err());
+    }
+
+    #[test]
+    fn test_range_lookup_extension() {
+        let manager = Arc::new(LookupDataManager::for_test(
+            create_test_data(0, 20),
+            TestLogger {},
+        ));
+        let factory = RangeLookupFactory::new_boxed_extension_factory(manager, 5).unwrap();
+        let mut extension = factory.create().unwrap();
+        assert_eq!(extension.get_handle(), ExtensionHandle::LookupRangeHandle);
+
+        let request = StorageGetRangeRequest::prefix(b"key1");
+        assert_eq!(request.end, Some(b"key2".to_vec()));
+        let response = extension.invoke(request.into()).unwrap();
+        let response = StorageGetRangeResponse::try_from(response.as_slice()).unwrap();
+        assert_eq!(
+            keys(&response),
+            ["key1", "key10", "key11", "key12", "key13"]
+        );
+        assert_eq!(response.entries[0].1, b"value1");
+        assert!(response.truncated);
+
+        assert_eq!(
+            extension.invoke(vec![1, 2, 3]),
+            Err(OakStatus::ErrInvalidArgs)
+        );
+    }
+
     #[test]
     fn test_format_bytes() {
         // Valid UTF-8 string.
@@ -380,6 +1036,15 @@ mod tests {
         assert_eq!("[0, 159, 146, 150]", format_bytes(&[0, 159, 146, 150]));
     }
 
+    // Returns the keys of the entries in `response` as strings.
+    fn keys(response: &StorageGetRangeResponse) -> Vec<String> {
+        response
+            .entries
+            .iter()
+            .map(|(key, _)| format_bytes(key))
+            .collect()
+    }
+
     // Create test data with size distinct keys between inclusive start and exclusive end.
     fn create_test_data(start: i32, end: i32) -> Data {
         HashMap::from_iter((start..end).map(|i| {
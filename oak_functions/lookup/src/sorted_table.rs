@@ -0,0 +1,146 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! A backend that keeps the lookup data of a shard in a single buffer of entries sorted by key,
+//! which takes considerably less memory than a hash map with separately allocated keys and values.
+
+use crate::{Data, LookupDataBackend};
+use alloc::vec::Vec;
+
+// Position of an entry in the buffer of a sorted table. The key of the entry starts at `offset`,
+// and is directly followed by its value.
+#[derive(Clone, Copy)]
+struct EntryIndex {
+    offset: usize,
+    key_len: u32,
+    value_len: u32,
+}
+
+impl EntryIndex {
+    fn key<'a>(&self, buffer: &'a [u8]) -> &'a [u8] {
+        &buffer[self.offset..self.offset + self.key_len as usize]
+    }
+
+    fn value<'a>(&self, buffer: &'a [u8]) -> &'a [u8] {
+        let start = self.offset + self.key_len as usize;
+        &buffer[start..start + self.value_len as usize]
+    }
+}
+
+/// Lookup data in a single buffer, with an index of the entries ordered by key. Exact lookups use
+/// a binary search of the index, and lookups return slices of the buffer without copying.
+#[derive(Default)]
+pub struct SortedTable {
+    buffer: Vec<u8>,
+    index: Vec<EntryIndex>,
+}
+
+impl SortedTable {
+    // Returns the position in the index of the first entry whose key is at least `key`.
+    fn lower_bound(&self, key: &[u8]) -> usize {
+        self.index
+            .partition_point(|entry| entry.key(&self.buffer) < key)
+    }
+}
+
+impl LookupDataBackend for SortedTable {
+    fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        let entry = self.index.get(self.lower_bound(key))?;
+        (entry.key(&self.buffer) == key).then(|| entry.value(&self.buffer))
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Vec<(&[u8], &[u8])> {
+        self.index[self.lower_bound(start)..]
+            .iter()
+            .map(|entry| (entry.key(&self.buffer), entry.value(&self.buffer)))
+            .take_while(|(key, _)| end.map_or(true, |end| *key < end))
+            .take(limit)
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+}
+
+/// Incrementally builds a [`SortedTable`]. Entries are appended to the buffer as they arrive, so
+/// that the lookup data being built takes no more memory than the finished table.
+#[derive(Default)]
+pub struct SortedTableBuilder {
+    buffer: Vec<u8>,
+    index: Vec<EntryIndex>,
+}
+
+impl SortedTableBuilder {
+    /// Adds the entries of `new_data`. Like for the other backends, a key that was added before is
+    /// overwritten.
+    ///
+    /// Panics if a key or value is larger than 4 GiB, which can't be sent to the enclave anyway.
+    pub fn extend(&mut self, new_data: Data) {
+        self.index.reserve(new_data.len());
+        for (key, value) in new_data {
+            self.index.push(EntryIndex {
+                offset: self.buffer.len(),
+                key_len: key.len().try_into().expect("key too large"),
+                value_len: value.len().try_into().expect("value too large"),
+            });
+            self.buffer.extend_from_slice(&key);
+            self.buffer.extend_from_slice(&value);
+        }
+    }
+
+    /// Sorts the entries by key and returns the finished table.
+    pub fn build(self) -> SortedTable {
+        let SortedTableBuilder { buffer, mut index } = self;
+        // The sort is stable and entries are appended, so the last entry of every key is the one
+        // that was added last.
+        index.sort_by(|a, b| a.key(&buffer).cmp(b.key(&buffer)));
+        let len = index.len();
+        let mut deduplicated: Vec<EntryIndex> = Vec::with_capacity(len);
+        for entry in index {
+            match deduplicated.last_mut() {
+                Some(last) if last.key(&buffer) == entry.key(&buffer) => *last = entry,
+                _ => deduplicated.push(entry),
+            }
+        }
+        if deduplicated.len() == len {
+            let mut table = SortedTable {
+                buffer,
+                index: deduplicated,
+            };
+            table.buffer.shrink_to_fit();
+            return table;
+        }
+        // Overwritten entries are dropped from the buffer, which also puts it in key order.
+        let mut compacted = Vec::with_capacity(
+            deduplicated
+                .iter()
+                .map(|entry| entry.key_len as usize + entry.value_len as usize)
+                .sum(),
+        );
+        for entry in deduplicated.iter_mut() {
+            let offset = compacted.len();
+            compacted.extend_from_slice(entry.key(&buffer));
+            compacted.extend_from_slice(entry.value(&buffer));
+            entry.offset = offset;
+        }
+        deduplicated.shrink_to_fit();
+        SortedTable {
+            buffer: compacted,
+            index: deduplicated,
+        }
+    }
+}
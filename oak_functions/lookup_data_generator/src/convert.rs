@@ -0,0 +1,153 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Conversion of human-authored lookup data (CSV or JSONL) into the binary lookup data format.
+
+use anyhow::{anyhow, Context};
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use bytes::BytesMut;
+use oak_functions_abi::proto::Entry;
+use p256::{
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    pkcs8::DecodePrivateKey,
+};
+use prost::Message;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// Format of the lookup data to convert.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum InputFormat {
+    /// One `key,value` pair per line. The key must not contain a comma.
+    Csv,
+    /// One `{"key": ..., "value": ...}` JSON object per line, optionally with the time after which
+    /// the entry expires as `"expires_at_unix_seconds"`.
+    Jsonl,
+}
+
+/// Size limits that every entry has to respect.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub max_key_size_bytes: usize,
+    pub max_value_size_bytes: usize,
+}
+
+/// Summary statistics about a set of lookup data entries.
+#[derive(Debug, Default)]
+pub struct Statistics {
+    pub entries: usize,
+    pub total_key_size_bytes: usize,
+    pub total_value_size_bytes: usize,
+    pub max_key_size_bytes: usize,
+    pub max_value_size_bytes: usize,
+}
+
+#[derive(Deserialize)]
+struct JsonEntry {
+    key: String,
+    value: String,
+    #[serde(default)]
+    expires_at_unix_seconds: u64,
+}
+
+/// Parses entries from `input` in the given format. Empty lines are ignored.
+pub fn parse_entries(input: &str, format: InputFormat) -> anyhow::Result<Vec<Entry>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            parse_entry(line, format).with_context(|| format!("couldn't parse line {}", index + 1))
+        })
+        .collect()
+}
+
+fn parse_entry(line: &str, format: InputFormat) -> anyhow::Result<Entry> {
+    match format {
+        InputFormat::Csv => {
+            let (key, value) = line
+                .split_once(',')
+                .ok_or_else(|| anyhow!("expected a `key,value` pair"))?;
+            Ok(Entry {
+                key: key.as_bytes().to_vec(),
+                value: value.as_bytes().to_vec(),
+                ..Default::default()
+            })
+        }
+        InputFormat::Jsonl => {
+            let entry: JsonEntry = serde_json::from_str(line).context("invalid JSON entry")?;
+            Ok(Entry {
+                key: entry.key.into_bytes(),
+                value: entry.value.into_bytes(),
+                expires_at_unix_seconds: entry.expires_at_unix_seconds,
+            })
+        }
+    }
+}
+
+/// Checks that all entries respect the size limits and that there are no duplicate keys, and
+/// returns summary statistics about the entries.
+pub fn validate_entries(entries: &[Entry], limits: Limits) -> anyhow::Result<Statistics> {
+    let mut keys = HashSet::new();
+    let mut statistics = Statistics::default();
+    for entry in entries {
+        if entry.key.len() > limits.max_key_size_bytes {
+            anyhow::bail!(
+                "key {:?} is {} bytes long, the maximum is {} bytes",
+                String::from_utf8_lossy(&entry.key),
+                entry.key.len(),
+                limits.max_key_size_bytes
+            );
+        }
+        if entry.value.len() > limits.max_value_size_bytes {
+            anyhow::bail!(
+                "value of key {:?} is {} bytes long, the maximum is {} bytes",
+                String::from_utf8_lossy(&entry.key),
+                entry.value.len(),
+                limits.max_value_size_bytes
+            );
+        }
+        if !keys.insert(&entry.key) {
+            anyhow::bail!("duplicate key {:?}", String::from_utf8_lossy(&entry.key));
+        }
+        statistics.entries += 1;
+        statistics.total_key_size_bytes += entry.key.len();
+        statistics.total_value_size_bytes += entry.value.len();
+        statistics.max_key_size_bytes = statistics.max_key_size_bytes.max(entry.key.len());
+        statistics.max_value_size_bytes = statistics.max_value_size_bytes.max(entry.value.len());
+    }
+    Ok(statistics)
+}
+
+/// Serializes entries into the binary lookup data format.
+pub fn serialize_entries(entries: &[Entry]) -> anyhow::Result<BytesMut> {
+    let mut buf = BytesMut::new();
+    for entry in entries {
+        entry
+            .encode_length_delimited(&mut buf)
+            .map_err(|err| anyhow!("couldn't encode entry: {err}"))?;
+    }
+    Ok(buf)
+}
+
+/// Signs `data` with the PEM-encoded PKCS#8 P-256 private key, and returns the Base64-encoded
+/// ASN.1 DER signature, in the same format that is used for signing endorsements.
+pub fn sign(data: &[u8], pem_private_key: &str) -> anyhow::Result<String> {
+    let signing_key = SigningKey::from_pkcs8_pem(pem_private_key)
+        .map_err(|err| anyhow::anyhow!("couldn't parse private key: {:?}", err))?;
+    let signature: Signature = signing_key.sign(data);
+    Ok(BASE64_STANDARD.encode(signature.to_der().as_bytes()))
+}
@@ -40,6 +40,7 @@ fn create_random_entry<R: Rng>(
     Entry {
         key: create_bytes(rng, key_size_bytes),
         value: create_bytes(rng, value_size_bytes),
+        ..Default::default()
     }
 }
 
@@ -61,6 +62,24 @@ pub fn generate_and_serialize_random_entries<R: Rng>(
     Ok(buf)
 }
 
+/// Generates the lookup entries `key_0` => `value_0`, `key_1` => `value_1`, and so on, and
+/// serializes them to bytes. The entries are predictable, so that scripted clients can check the
+/// responses.
+pub fn generate_and_serialize_sequential_entries(entries: usize) -> anyhow::Result<BytesMut> {
+    let mut buf = BytesMut::new();
+    for i in 0..entries {
+        let entry = Entry {
+            key: format!("key_{i}").into_bytes(),
+            value: format!("value_{i}").into_bytes(),
+            ..Default::default()
+        };
+        entry
+            .encode_length_delimited(&mut buf)
+            .map_err(|err| anyhow!("couldn't encode entry: {err}"))?;
+    }
+    Ok(buf)
+}
+
 #[derive(Serialize)]
 struct WeatherValue {
     temperature_degrees_celsius: i32,
@@ -72,6 +91,7 @@ fn create_weather_entry<R: Rng>(rng: &mut R, lat: i32, lng: i32) -> Entry {
     Entry {
         key: key.as_bytes().to_vec(),
         value: value.as_bytes().to_vec(),
+        ..Default::default()
     }
 }
 
@@ -150,6 +170,7 @@ pub fn generate_and_serialize_sparse_weather_entries<R: Rng>(
         let entry = Entry {
             key: key.to_vec(),
             value: value.as_bytes().to_vec(),
+            ..Default::default()
         };
         entry
             .encode_length_delimited(&mut buf)
@@ -160,6 +181,7 @@ pub fn generate_and_serialize_sparse_weather_entries<R: Rng>(
         let cell_entry = Entry {
             key: key.clone(),
             value: values.concat(),
+            ..Default::default()
         };
         cell_entry
             .encode_length_delimited(&mut buf)
@@ -16,11 +16,14 @@
 
 use anyhow::Context;
 use clap::Parser;
-use lookup_data_generator::data::{
-    generate_and_serialize_random_entries, generate_and_serialize_sparse_weather_entries,
-    generate_and_serialize_weather_entries,
+use lookup_data_generator::{
+    convert::{parse_entries, serialize_entries, sign, validate_entries, InputFormat, Limits},
+    data::{
+        generate_and_serialize_random_entries, generate_and_serialize_sequential_entries,
+        generate_and_serialize_sparse_weather_entries, generate_and_serialize_weather_entries,
+    },
 };
-use std::{fs::File, io::Write};
+use std::{fs::File, io::Write, path::PathBuf};
 
 #[derive(Parser, Clone, Debug)]
 #[command(about = "Oak Functions Lookup Data Generator")]
@@ -42,6 +45,11 @@ pub enum Command {
         #[arg(long, default_value = "100")]
         entries: usize,
     },
+    #[command(about = "Generate the key value pairs key_0 => value_0, key_1 => value_1, ...")]
+    Sequential {
+        #[arg(long, default_value = "100")]
+        entries: usize,
+    },
     #[command(about = "Generate entries for the weather lookup example with random values")]
     Weather {},
     #[command(
@@ -51,6 +59,23 @@ pub enum Command {
         #[arg(long, default_value = "100000")]
         entries: usize,
     },
+    #[command(
+        about = "Convert, validate and optionally sign key value pairs authored as CSV or JSONL"
+    )]
+    Convert {
+        #[arg(long)]
+        input_file_path: PathBuf,
+        #[arg(long, value_enum, default_value = "csv")]
+        format: InputFormat,
+        #[arg(long, default_value = "1024")]
+        max_key_size_bytes: usize,
+        #[arg(long, default_value = "1048576")]
+        max_value_size_bytes: usize,
+        /// PEM-encoded PKCS#8 P-256 private key. If set, the Base64-encoded signature over the
+        /// output file is written next to it, with a `.sig` extension.
+        #[arg(long)]
+        signing_key_path: Option<PathBuf>,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
@@ -68,14 +93,57 @@ fn main() -> anyhow::Result<()> {
             entries,
         )
         .context("couldn't generate random entries")?,
+        Command::Sequential { entries } => generate_and_serialize_sequential_entries(entries)
+            .context("couldn't generate sequential entries")?,
         Command::Weather {} => generate_and_serialize_weather_entries(&mut rng)
             .context("couldn't generate weather entries")?,
         Command::WeatherSparse { entries } => {
             generate_and_serialize_sparse_weather_entries(&mut rng, entries)
                 .context("couldn't generate sparse weather entries")?
         }
+        Command::Convert {
+            ref input_file_path,
+            format,
+            max_key_size_bytes,
+            max_value_size_bytes,
+            ..
+        } => {
+            let input =
+                std::fs::read_to_string(input_file_path).context("couldn't read input file")?;
+            let entries = parse_entries(&input, format).context("couldn't parse entries")?;
+            let statistics = validate_entries(
+                &entries,
+                Limits {
+                    max_key_size_bytes,
+                    max_value_size_bytes,
+                },
+            )
+            .context("invalid entries")?;
+            println!(
+                "converted {} entries: keys {} bytes in total ({} max), values {} bytes in total \
+                 ({} max)",
+                statistics.entries,
+                statistics.total_key_size_bytes,
+                statistics.max_key_size_bytes,
+                statistics.total_value_size_bytes,
+                statistics.max_value_size_bytes,
+            );
+            serialize_entries(&entries).context("couldn't serialize entries")?
+        }
     };
-    let mut file = File::create(opt.out_file_path).context("couldn't create out file")?;
+    let mut file = File::create(&opt.out_file_path).context("couldn't create out file")?;
     file.write_all(&buf).context("couldn't write to file")?;
+
+    if let Command::Convert {
+        signing_key_path: Some(signing_key_path),
+        ..
+    } = opt.cmd
+    {
+        let signing_key =
+            std::fs::read_to_string(signing_key_path).context("couldn't read signing key")?;
+        let signature = sign(&buf, &signing_key).context("couldn't sign lookup data")?;
+        std::fs::write(format!("{}.sig", opt.out_file_path), signature)
+            .context("couldn't write signature")?;
+    }
     Ok(())
 }
@@ -0,0 +1,377 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! A reference server that publishes versioned snapshots of lookup data over HTTP, together with
+//! shard manifests and deltas between versions.
+//!
+//! Snapshots are files named `<version>.bin` in the binary lookup data format (a sequence of
+//! length-delimited `Entry` messages), and versions are ordered by name. The snapshot directory is
+//! read on every request, so new snapshots are published by adding files to it.
+//!
+//! The server provides the following endpoints:
+//!
+//! - `GET /versions`: JSON list of the available versions, and the latest one
+//! - `GET /snapshots/<version>`: the snapshot
+//! - `GET /snapshots/<version>/manifest`: JSON manifest of the shards of the snapshot
+//! - `GET /snapshots/<version>/shards/<index>`: a shard of the snapshot, in the same format
+//! - `GET /deltas/<from>/<to>`: serialized `LookupDataDelta` between two versions
+//!
+//! All endpoints also answer `HEAD` requests, e.g. to check that the server is available without
+//! downloading anything.
+//!
+//! Every successful response has an `ETag` header containing the SHA-256 digest of its body, and
+//! requests with a matching `If-None-Match` header are answered with `304 Not Modified`.
+
+use anyhow::Context;
+use bytes::Buf;
+use hyper::{
+    header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use oak_functions_abi::proto::{Entry, LookupDataDelta};
+use prost::Message;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+};
+
+#[cfg(test)]
+mod tests;
+
+const SNAPSHOT_EXTENSION: &str = "bin";
+const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// The versions of the lookup data that are available.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Versions {
+    pub versions: Vec<String>,
+    pub latest: Option<String>,
+}
+
+/// Description of a single shard of a snapshot.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ShardInfo {
+    pub index: usize,
+    /// Hex-encoded SHA-256 digest of the shard, which is also its ETag.
+    pub sha256: String,
+    #[serde(rename = "sizeBytes")]
+    pub size_bytes: usize,
+    pub entries: usize,
+}
+
+/// Description of the shards of a snapshot.
+///
+/// Each entry belongs to the shard given by the first 8 bytes of the SHA-256 digest of its key,
+/// interpreted as a big-endian integer, modulo the number of shards.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ShardManifest {
+    pub version: String,
+    /// Hex-encoded SHA-256 digest of the full snapshot.
+    pub sha256: String,
+    pub shards: Vec<ShardInfo>,
+}
+
+/// Errors that are reported to the client.
+#[derive(Debug)]
+enum Error {
+    NotFound,
+    BadRequest(String),
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        Error::Internal(err)
+    }
+}
+
+/// A directory of lookup data snapshots.
+pub struct SnapshotStore {
+    directory: PathBuf,
+    shard_count: usize,
+}
+
+impl SnapshotStore {
+    pub fn new(directory: PathBuf, shard_count: usize) -> anyhow::Result<Self> {
+        if shard_count == 0 {
+            anyhow::bail!("the number of shards must be positive");
+        }
+        Ok(Self {
+            directory,
+            shard_count,
+        })
+    }
+
+    /// Returns the available versions, ordered from the oldest to the latest.
+    pub fn versions(&self) -> anyhow::Result<Vec<String>> {
+        let dir_entries = std::fs::read_dir(&self.directory).with_context(|| {
+            format!(
+                "couldn't read snapshot directory {}",
+                self.directory.display()
+            )
+        })?;
+        let mut versions = Vec::new();
+        for dir_entry in dir_entries {
+            let path = dir_entry
+                .context("couldn't read snapshot directory entry")?
+                .path();
+            if path
+                .extension()
+                .map_or(false, |extension| extension == SNAPSHOT_EXTENSION)
+            {
+                if let Some(version) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    if is_valid_version(version) {
+                        versions.push(version.to_string());
+                    }
+                }
+            }
+        }
+        versions.sort();
+        Ok(versions)
+    }
+
+    /// Returns the serialized snapshot with the given version.
+    fn snapshot(&self, version: &str) -> Result<Vec<u8>, Error> {
+        if !is_valid_version(version) {
+            return Err(Error::BadRequest(format!("invalid version {:?}", version)));
+        }
+        let path = self
+            .directory
+            .join(format!("{}.{}", version, SNAPSHOT_EXTENSION));
+        match std::fs::read(&path) {
+            Ok(bytes) => Ok(bytes),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(Error::NotFound),
+            Err(err) => Err(Error::Internal(
+                anyhow::Error::new(err).context(format!("couldn't read {}", path.display())),
+            )),
+        }
+    }
+
+    fn entries(&self, version: &str) -> Result<Vec<Entry>, Error> {
+        let snapshot = self.snapshot(version)?;
+        Ok(parse_entries(&snapshot)
+            .with_context(|| format!("couldn't parse snapshot {}", version))?)
+    }
+
+    /// Splits the snapshot with the given version into shards.
+    fn shards(&self, version: &str) -> Result<Vec<Vec<Entry>>, Error> {
+        let mut shards = vec![Vec::new(); self.shard_count];
+        for entry in self.entries(version)? {
+            shards[shard_index(&entry.key, self.shard_count)].push(entry);
+        }
+        Ok(shards)
+    }
+
+    fn manifest(&self, version: &str) -> Result<ShardManifest, Error> {
+        let snapshot = self.snapshot(version)?;
+        let shards = self
+            .shards(version)?
+            .iter()
+            .enumerate()
+            .map(|(index, entries)| {
+                let shard = serialize_entries(entries);
+                ShardInfo {
+                    index,
+                    sha256: sha256(&shard),
+                    size_bytes: shard.len(),
+                    entries: entries.len(),
+                }
+            })
+            .collect();
+        Ok(ShardManifest {
+            version: version.to_string(),
+            sha256: sha256(&snapshot),
+            shards,
+        })
+    }
+
+    fn shard(&self, version: &str, index: &str) -> Result<Vec<u8>, Error> {
+        let index: usize = index
+            .parse()
+            .map_err(|_| Error::BadRequest(format!("invalid shard index {:?}", index)))?;
+        let shards = self.shards(version)?;
+        let entries = shards.get(index).ok_or(Error::NotFound)?;
+        Ok(serialize_entries(entries))
+    }
+
+    /// Computes the changes that turn the snapshot with version `from` into the one with version
+    /// `to`.
+    fn delta(&self, from: &str, to: &str) -> Result<LookupDataDelta, Error> {
+        let from_entries = self.entries(from)?;
+        let to_entries = self.entries(to)?;
+        let from_map: HashMap<&[u8], &Entry> = from_entries
+            .iter()
+            .map(|entry| (entry.key.as_slice(), entry))
+            .collect();
+        let to_keys: HashSet<&[u8]> = to_entries
+            .iter()
+            .map(|entry| entry.key.as_slice())
+            .collect();
+        let upserted_entries = to_entries
+            .iter()
+            .filter(|entry| from_map.get(entry.key.as_slice()) != Some(entry))
+            .cloned()
+            .collect();
+        let removed_keys = from_entries
+            .iter()
+            .filter(|entry| !to_keys.contains(entry.key.as_slice()))
+            .map(|entry| entry.key.clone())
+            .collect();
+        Ok(LookupDataDelta {
+            upserted_entries,
+            removed_keys,
+        })
+    }
+
+    fn route(&self, path: &str) -> Result<(Vec<u8>, &'static str), Error> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            ["versions"] => {
+                let versions = self.versions()?;
+                let latest = versions.last().cloned();
+                Ok((to_json(&Versions { versions, latest })?, JSON_CONTENT_TYPE))
+            }
+            ["snapshots", version] => Ok((self.snapshot(version)?, BINARY_CONTENT_TYPE)),
+            ["snapshots", version, "manifest"] => {
+                Ok((to_json(&self.manifest(version)?)?, JSON_CONTENT_TYPE))
+            }
+            ["snapshots", version, "shards", index] => {
+                Ok((self.shard(version, index)?, BINARY_CONTENT_TYPE))
+            }
+            ["deltas", from, to] => {
+                Ok((self.delta(from, to)?.encode_to_vec(), BINARY_CONTENT_TYPE))
+            }
+            _ => Err(Error::NotFound),
+        }
+    }
+
+    /// Handles an HTTP request.
+    pub fn handle<B>(&self, request: &Request<B>) -> Response<Body> {
+        let head = match *request.method() {
+            Method::GET => false,
+            Method::HEAD => true,
+            _ => return error_response(StatusCode::METHOD_NOT_ALLOWED, String::new()),
+        };
+        match self.route(request.uri().path()) {
+            Ok((body, content_type)) => {
+                let etag = format!("\"{}\"", sha256(&body));
+                let not_modified = request
+                    .headers()
+                    .get(IF_NONE_MATCH)
+                    .map_or(false, |if_none_match| if_none_match == etag.as_str());
+                let response = Response::builder().header(ETAG, etag);
+                let response = if not_modified {
+                    response
+                        .status(StatusCode::NOT_MODIFIED)
+                        .body(Body::empty())
+                } else if head {
+                    response
+                        .header(CONTENT_TYPE, content_type)
+                        .body(Body::empty())
+                } else {
+                    response
+                        .header(CONTENT_TYPE, content_type)
+                        .body(Body::from(body))
+                };
+                response.expect("couldn't build response")
+            }
+            Err(Error::NotFound) => error_response(StatusCode::NOT_FOUND, String::new()),
+            Err(Error::BadRequest(message)) => error_response(StatusCode::BAD_REQUEST, message),
+            Err(Error::Internal(err)) => {
+                log::error!("couldn't handle request for {}: {:?}", request.uri(), err);
+                error_response(StatusCode::INTERNAL_SERVER_ERROR, String::new())
+            }
+        }
+    }
+}
+
+/// Serves the snapshots in `store` over HTTP.
+pub async fn serve(addr: SocketAddr, store: Arc<SnapshotStore>) -> anyhow::Result<()> {
+    let make_service = make_service_fn(move |_connection| {
+        let store = store.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let store = store.clone();
+                async move { Ok::<_, Infallible>(store.handle(&request)) }
+            }))
+        }
+    });
+    log::info!("serving lookup data on {}", addr);
+    Server::bind(&addr)
+        .serve(make_service)
+        .await
+        .context("lookup data server failed")
+}
+
+/// Returns the index of the shard that the entry with the given key belongs to.
+pub fn shard_index(key: &[u8], shard_count: usize) -> usize {
+    let digest = Sha256::digest(key);
+    let prefix = u64::from_be_bytes(digest[..8].try_into().expect("digest is too short"));
+    (prefix % shard_count as u64) as usize
+}
+
+/// Parses a sequence of length-delimited entries.
+pub fn parse_entries(mut bytes: &[u8]) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    while bytes.has_remaining() {
+        let entry = Entry::decode_length_delimited(&mut bytes)
+            .map_err(|err| anyhow::anyhow!("couldn't decode entry: {:?}", err))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Serializes entries as a sequence of length-delimited entries.
+pub fn serialize_entries(entries: &[Entry]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for entry in entries {
+        entry
+            .encode_length_delimited(&mut bytes)
+            .expect("couldn't encode entry");
+    }
+    bytes
+}
+
+/// Versions are used as file names, so they must not allow escaping the snapshot directory.
+fn is_valid_version(version: &str) -> bool {
+    !version.is_empty()
+        && !version.starts_with('.')
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn to_json<T: Serialize>(value: &T) -> anyhow::Result<Vec<u8>> {
+    serde_json::to_vec_pretty(value).context("couldn't serialize JSON")
+}
+
+fn error_response(status: StatusCode, message: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(message))
+        .expect("couldn't build response")
+}
@@ -0,0 +1,63 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Serves a directory of lookup data snapshots over HTTP.
+
+use clap::Parser;
+use oak_functions_lookup_data_server::{serve, SnapshotStore};
+use std::{
+    net::{Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+};
+
+#[derive(Parser, Clone)]
+#[command(about = "Oak Functions Lookup Data Server")]
+pub struct Opt {
+    #[arg(
+        long,
+        help = "Path to the directory containing the `<version>.bin` snapshots"
+    )]
+    snapshot_directory: PathBuf,
+
+    #[arg(
+        long,
+        default_value = "8090",
+        help = "Port on which to serve the snapshots"
+    )]
+    port: u16,
+
+    #[arg(
+        long,
+        default_value = "1",
+        help = "Number of shards that snapshots are split into"
+    )]
+    shard_count: usize,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let opt = Opt::parse();
+
+    let store = SnapshotStore::new(opt.snapshot_directory, opt.shard_count)?;
+    log::info!("found snapshot versions {:?}", store.versions()?);
+    serve(
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, opt.port)),
+        Arc::new(store),
+    )
+    .await
+}
@@ -0,0 +1,164 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+fn entry(key: &str, value: &str) -> Entry {
+    Entry {
+        key: key.as_bytes().to_vec(),
+        value: value.as_bytes().to_vec(),
+        ..Default::default()
+    }
+}
+
+fn create_store(shard_count: usize) -> (tempfile::TempDir, SnapshotStore) {
+    let directory = tempfile::tempdir().unwrap();
+    std::fs::write(
+        directory.path().join("v1.bin"),
+        serialize_entries(&[entry("a", "1"), entry("b", "2"), entry("c", "3")]),
+    )
+    .unwrap();
+    std::fs::write(
+        directory.path().join("v2.bin"),
+        serialize_entries(&[entry("a", "1"), entry("b", "20"), entry("d", "4")]),
+    )
+    .unwrap();
+    std::fs::write(directory.path().join("notes.txt"), "ignored").unwrap();
+    let store = SnapshotStore::new(directory.path().to_path_buf(), shard_count).unwrap();
+    (directory, store)
+}
+
+fn get(store: &SnapshotStore, path: &str) -> Response<Body> {
+    store.handle(&Request::get(path).body(()).unwrap())
+}
+
+async fn body_bytes(response: Response<Body>) -> Vec<u8> {
+    hyper::body::to_bytes(response.into_body())
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+#[tokio::test]
+async fn test_versions() {
+    let (_directory, store) = create_store(1);
+    let response = get(&store, "/versions");
+    assert_eq!(response.status(), StatusCode::OK);
+    let versions: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+    assert_eq!(
+        versions,
+        serde_json::json!({"versions": ["v1", "v2"], "latest": "v2"})
+    );
+}
+
+#[tokio::test]
+async fn test_snapshot() {
+    let (directory, store) = create_store(1);
+    let response = get(&store, "/snapshots/v1");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        body_bytes(response).await,
+        std::fs::read(directory.path().join("v1.bin")).unwrap()
+    );
+    assert_eq!(get(&store, "/snapshots/v3").status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        get(&store, "/snapshots/..%2Fv1").status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[test]
+fn test_etag() {
+    let (_directory, store) = create_store(1);
+    let etag = get(&store, "/snapshots/v1").headers()[ETAG].clone();
+    let request = Request::get("/snapshots/v1")
+        .header(IF_NONE_MATCH, etag.clone())
+        .body(())
+        .unwrap();
+    assert_eq!(store.handle(&request).status(), StatusCode::NOT_MODIFIED);
+    let request = Request::get("/snapshots/v2")
+        .header(IF_NONE_MATCH, etag)
+        .body(())
+        .unwrap();
+    assert_eq!(store.handle(&request).status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_shards() {
+    let shard_count = 4;
+    let (_directory, store) = create_store(shard_count);
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&body_bytes(get(&store, "/snapshots/v1/manifest")).await).unwrap();
+    assert_eq!(manifest["shards"].as_array().unwrap().len(), shard_count);
+
+    let mut entries = Vec::new();
+    for index in 0..shard_count {
+        let response = get(&store, &format!("/snapshots/v1/shards/{index}"));
+        assert_eq!(response.status(), StatusCode::OK);
+        let shard = parse_entries(&body_bytes(response).await).unwrap();
+        assert!(shard
+            .iter()
+            .all(|entry| shard_index(&entry.key, shard_count) == index));
+        assert_eq!(manifest["shards"][index]["entries"], shard.len());
+        entries.extend(shard);
+    }
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    assert_eq!(
+        entries,
+        vec![entry("a", "1"), entry("b", "2"), entry("c", "3")]
+    );
+
+    assert_eq!(
+        get(&store, &format!("/snapshots/v1/shards/{shard_count}")).status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn test_delta() {
+    let (_directory, store) = create_store(1);
+    let response = get(&store, "/deltas/v1/v2");
+    assert_eq!(response.status(), StatusCode::OK);
+    let delta = LookupDataDelta::decode(body_bytes(response).await.as_slice()).unwrap();
+    assert_eq!(
+        delta,
+        LookupDataDelta {
+            upserted_entries: vec![entry("b", "20"), entry("d", "4")],
+            removed_keys: vec![b"c".to_vec()],
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_head() {
+    let (_directory, store) = create_store(1);
+    let response = store.handle(&Request::head("/versions").body(()).unwrap());
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key(ETAG));
+    assert!(body_bytes(response).await.is_empty());
+    let response = store.handle(&Request::head("/snapshots/v3").body(()).unwrap());
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_method_not_allowed() {
+    let (_directory, store) = create_store(1);
+    let request = Request::post("/versions").body(()).unwrap();
+    assert_eq!(
+        store.handle(&request).status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+}
@@ -0,0 +1,307 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Differentially private metrics that Wasm modules report through the `report_metric` ABI.
+//!
+//! A Wasm module reports buckets by name, e.g. whenever a lookup misses. Every invocation counts
+//! at most once towards each bucket, no matter how often it reports it. The counts are aggregated
+//! over a batch of invocations and only released once the batch is complete, after adding discrete
+//! Laplace noise that makes the released counts differentially private with the configured epsilon.
+//! Counts of individual invocations are never released.
+//!
+//! Since a single invocation can change the count of every bucket by one, the noise is scaled to
+//! the number of buckets divided by epsilon.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec, vec::Vec};
+use anyhow::anyhow;
+use oak_functions_abi::{proto::OakStatus, ExtensionHandle};
+use oak_functions_extension::{ExtensionFactory, OakApiNativeExtension};
+use oak_logger::{Level, OakLogger};
+use rand_core::{OsRng, RngCore};
+use spinning_top::Spinlock;
+
+/// The largest supported scale of the noise, i.e. number of buckets divided by epsilon. Sampling
+/// the noise takes a number of random draws proportional to its scale.
+pub const MAX_NOISE_SCALE: f64 = 10_000.0;
+
+/// Configuration of the differentially private metrics.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrivateMetricsConfig {
+    /// The privacy budget that each released batch consumes.
+    pub epsilon: f64,
+    /// The number of invocations that are aggregated before the counts are released.
+    pub batch_size: usize,
+    /// The names of the buckets that Wasm modules may report.
+    pub buckets: Vec<String>,
+}
+
+impl PrivateMetricsConfig {
+    /// Returns `Ok(())` if the configuration can be used, otherwise returns `Err()` describing the
+    /// problem.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(self.epsilon.is_finite() && self.epsilon > 0.0) {
+            return Err(anyhow!("epsilon must be positive, got {}", self.epsilon));
+        }
+        if self.batch_size == 0 {
+            return Err(anyhow!("batch size must be positive"));
+        }
+        if self.buckets.is_empty() {
+            return Err(anyhow!("at least one bucket must be configured"));
+        }
+        if self.buckets.len() as f64 / self.epsilon > MAX_NOISE_SCALE {
+            return Err(anyhow!(
+                "epsilon {} is too small for {} buckets",
+                self.epsilon,
+                self.buckets.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Aggregates the buckets reported by invocations, and releases noised counts per batch.
+pub struct PrivateMetricsAggregator {
+    config: PrivateMetricsConfig,
+    batch: Spinlock<Batch>,
+}
+
+// The counts of the current, incomplete batch.
+struct Batch {
+    invocations: usize,
+    counts: Vec<u64>,
+}
+
+impl PrivateMetricsAggregator {
+    pub fn new(config: PrivateMetricsConfig) -> anyhow::Result<Self> {
+        config.validate()?;
+        let batch = Batch {
+            invocations: 0,
+            counts: vec![0; config.buckets.len()],
+        };
+        Ok(Self {
+            config,
+            batch: Spinlock::new(batch),
+        })
+    }
+
+    /// Returns the index of the bucket with the given name, if it is configured.
+    pub fn bucket_index(&self, name: &str) -> Option<usize> {
+        self.config.buckets.iter().position(|bucket| bucket == name)
+    }
+
+    /// Records an invocation that reported the buckets whose indices are set in `reported`.
+    ///
+    /// Returns the noised counts of all buckets if the invocation completed a batch.
+    pub fn record(&self, reported: &[bool]) -> Option<Vec<(String, i64)>> {
+        let mut batch = self.batch.lock();
+        batch.invocations += 1;
+        for (count, reported) in batch.counts.iter_mut().zip(reported) {
+            *count += *reported as u64;
+        }
+        if batch.invocations < self.config.batch_size {
+            return None;
+        }
+
+        let scale = self.config.buckets.len() as f64 / self.config.epsilon;
+        let released = self
+            .config
+            .buckets
+            .iter()
+            .zip(batch.counts.iter())
+            .map(|(bucket, count)| {
+                let noised = *count as i64 + discrete_laplace_noise(scale);
+                (bucket.clone(), noised)
+            })
+            .collect();
+        batch.invocations = 0;
+        batch.counts.iter_mut().for_each(|count| *count = 0);
+        Some(released)
+    }
+}
+
+pub struct PrivateMetricsFactory<L: OakLogger> {
+    aggregator: Arc<PrivateMetricsAggregator>,
+    logger: L,
+}
+
+impl<L> PrivateMetricsFactory<L>
+where
+    L: OakLogger + 'static,
+{
+    pub fn new_boxed_extension_factory(
+        config: PrivateMetricsConfig,
+        logger: L,
+    ) -> anyhow::Result<Box<dyn ExtensionFactory<L>>> {
+        let aggregator = Arc::new(PrivateMetricsAggregator::new(config)?);
+        let private_metrics_factory = Self { aggregator, logger };
+        Ok(Box::new(private_metrics_factory))
+    }
+}
+
+impl<L> ExtensionFactory<L> for PrivateMetricsFactory<L>
+where
+    L: OakLogger + 'static,
+{
+    fn create(&self) -> anyhow::Result<Box<dyn OakApiNativeExtension>> {
+        let extension = PrivateMetrics {
+            aggregator: self.aggregator.clone(),
+            reported: vec![false; self.aggregator.config.buckets.len()],
+            logger: self.logger.clone(),
+        };
+        Ok(Box::new(extension))
+    }
+}
+
+/// Collects the buckets reported by a single invocation, and records them when it terminates.
+pub struct PrivateMetrics<L: OakLogger> {
+    aggregator: Arc<PrivateMetricsAggregator>,
+    reported: Vec<bool>,
+    logger: L,
+}
+
+impl<L: OakLogger> OakApiNativeExtension for PrivateMetrics<L> {
+    fn invoke(&mut self, request: Vec<u8>) -> Result<Vec<u8>, OakStatus> {
+        // The request is the UTF-8 encoded name of the bucket.
+        let index = core::str::from_utf8(&request)
+            .ok()
+            .and_then(|name| self.aggregator.bucket_index(name))
+            .ok_or_else(|| {
+                self.logger.log_sensitive(
+                    Level::Warn,
+                    &format!("report_metric(): unknown bucket: {:?}", request),
+                );
+                OakStatus::ErrInvalidArgs
+            })?;
+        self.reported[index] = true;
+        Ok(Vec::new())
+    }
+
+    fn terminate(&mut self) -> anyhow::Result<()> {
+        if let Some(released) = self.aggregator.record(&self.reported) {
+            // The released counts are differentially private, so they can be logged publicly.
+            let counts: Vec<String> = released
+                .iter()
+                .map(|(bucket, count)| format!("{}={}", bucket, count))
+                .collect();
+            self.logger.log_public(
+                Level::Info,
+                &format!("private metrics: {}", counts.join(", ")),
+            );
+        }
+        Ok(())
+    }
+
+    fn get_handle(&self) -> ExtensionHandle {
+        ExtensionHandle::MetricsHandle
+    }
+}
+
+// Samples from the discrete Laplace distribution centered at zero with the given scale, i.e.
+// returns k with probability proportional to exp(-|k| / scale).
+//
+// Adding continuous Laplace noise in floating-point arithmetic leaks the exact count through the
+// low-order bits of the result (Mironov, 2012), even after rounding. Instead, the noise is the
+// difference of two geometrically distributed integers, each counting the consecutive successes of
+// a Bernoulli trial that compares a random `u64` with a fixed threshold. Floating-point arithmetic
+// only derives the threshold, which shifts the effective scale by a negligible relative error and
+// is independent of the counts.
+fn discrete_laplace_noise(scale: f64) -> i64 {
+    // Each trial succeeds with probability `threshold / 2^64`, i.e. about exp(-1 / scale).
+    let threshold = (libm::exp(-1.0 / scale) * u64::MAX as f64) as u64;
+    let geometric = || {
+        let mut successes = 0;
+        while OsRng.next_u64() < threshold {
+            successes += 1;
+        }
+        successes
+    };
+    geometric() - geometric()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn test_config(epsilon: f64) -> PrivateMetricsConfig {
+        PrivateMetricsConfig {
+            epsilon,
+            batch_size: 3,
+            buckets: vec!["hit".to_string(), "miss".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(test_config(1.0).validate().is_ok());
+        assert!(test_config(0.0).validate().is_err());
+        assert!(test_config(f64::INFINITY).validate().is_err());
+        let config = PrivateMetricsConfig {
+            batch_size: 0,
+            ..test_config(1.0)
+        };
+        assert!(config.validate().is_err());
+        let config = PrivateMetricsConfig {
+            buckets: Vec::new(),
+            ..test_config(1.0)
+        };
+        assert!(config.validate().is_err());
+        assert!(test_config(2.0 / MAX_NOISE_SCALE).validate().is_ok());
+        assert!(test_config(1.0 / MAX_NOISE_SCALE).validate().is_err());
+    }
+
+    #[test]
+    fn test_release_per_batch() {
+        // With a huge budget the noise is negligible, so the exact counts are released.
+        let aggregator = PrivateMetricsAggregator::new(test_config(1e12)).unwrap();
+        assert_eq!(aggregator.bucket_index("miss"), Some(1));
+        assert_eq!(aggregator.bucket_index("other"), None);
+
+        assert_eq!(aggregator.record(&[true, false]), None);
+        assert_eq!(aggregator.record(&[true, true]), None);
+        assert_eq!(
+            aggregator.record(&[false, false]),
+            Some(vec![("hit".to_string(), 2), ("miss".to_string(), 1)])
+        );
+
+        // The next batch starts from zero.
+        assert_eq!(aggregator.record(&[false, true]), None);
+        assert_eq!(aggregator.record(&[false, true]), None);
+        assert_eq!(
+            aggregator.record(&[false, true]),
+            Some(vec![("hit".to_string(), 0), ("miss".to_string(), 3)])
+        );
+    }
+
+    #[test]
+    fn test_discrete_laplace_noise() {
+        let samples = 10000;
+        let noise: Vec<i64> = (0..samples).map(|_| discrete_laplace_noise(2.0)).collect();
+        let mean = noise.iter().sum::<i64>() as f64 / samples as f64;
+        assert!((-0.15..0.15).contains(&mean));
+        // The mean absolute deviation of the discrete Laplace distribution with parameter
+        // q = exp(-1 / scale) is 2q / (1 - q^2).
+        let q = libm::exp(-0.5);
+        let expected = 2.0 * q / (1.0 - q * q);
+        let mean_absolute =
+            noise.iter().map(|noise| noise.abs()).sum::<i64>() as f64 / samples as f64;
+        assert!((expected - 0.1..expected + 0.1).contains(&mean_absolute));
+    }
+}
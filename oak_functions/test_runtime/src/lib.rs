@@ -0,0 +1,177 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! An in-process runtime for testing Oak Functions Wasm modules, which executes invocations
+//! directly instead of going through the launcher.
+
+use anyhow::Context;
+use oak_functions_abi::{proto::OakStatus, ExtensionHandle, Request};
+use oak_functions_extension::{ExtensionFactory, OakApiNativeExtension};
+use oak_functions_lookup::{LookupDataManager, LookupFactory, RangeLookupFactory};
+use oak_functions_wasm::WasmHandler;
+use oak_logger::{Level, OakLogger};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Logger for messages of the runtime itself, which forwards them to the `log` crate.
+#[derive(Clone, Default)]
+pub struct TestLogger {}
+
+impl OakLogger for TestLogger {
+    fn log_sensitive(&self, level: Level, message: &str) {
+        log::log!(level, "{}", message);
+    }
+
+    fn log_public(&self, level: Level, message: &str) {
+        log::log!(level, "{}", message);
+    }
+}
+
+/// Maximum number of entries that a range lookup of the module returns.
+pub const MAX_RANGE_LOOKUP_ENTRIES: usize = 1000;
+
+/// The outcome of a single invocation of the module.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Invocation {
+    /// The response body. Empty if the module panicked.
+    pub response: Vec<u8>,
+    /// The messages that the module logged during the invocation, in order.
+    pub logs: Vec<String>,
+}
+
+/// Runs a Wasm module with the given lookup data, and records the messages that the module logs.
+pub struct TestRuntime {
+    wasm_handler: WasmHandler<TestLogger>,
+    lookup_data_manager: Arc<LookupDataManager<TestLogger>>,
+    logs: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestRuntime {
+    /// Creates a runtime for the given compiled Wasm module.
+    pub fn new(
+        wasm_module_bytes: &[u8],
+        lookup_data: HashMap<Vec<u8>, Vec<u8>>,
+    ) -> anyhow::Result<Self> {
+        let logger = TestLogger::default();
+        let lookup_data_manager = Arc::new(LookupDataManager::for_test(
+            lookup_data.into_iter().collect(),
+            logger.clone(),
+        ));
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let extension_factories = vec![
+            LookupFactory::new_boxed_extension_factory(lookup_data_manager.clone())?,
+            RangeLookupFactory::new_boxed_extension_factory(
+                lookup_data_manager.clone(),
+                MAX_RANGE_LOOKUP_ENTRIES,
+            )?,
+            Box::new(RecordingLoggingFactory { logs: logs.clone() })
+                as Box<dyn ExtensionFactory<TestLogger>>,
+        ];
+        let wasm_handler = WasmHandler::create(wasm_module_bytes, extension_factories, logger)
+            .context("couldn't instantiate Wasm module")?;
+        Ok(Self {
+            wasm_handler,
+            lookup_data_manager,
+            logs,
+        })
+    }
+
+    /// Compiles the Rust crate with the given manifest to Wasm, and creates a runtime for it.
+    pub fn for_crate(
+        manifest_path: &str,
+        lookup_data: HashMap<Vec<u8>, Vec<u8>>,
+    ) -> anyhow::Result<Self> {
+        let wasm_module_bytes = oak_functions_test_utils::compile_rust_wasm(manifest_path, false)
+            .context("couldn't compile Wasm module")?;
+        Self::new(&wasm_module_bytes, lookup_data)
+    }
+
+    /// Invokes the module with the given request and returns the response body.
+    ///
+    /// If the module panics, the response body is empty.
+    pub fn invoke(&self, request: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let response = self.wasm_handler.handle_invoke(Request {
+            body: request.to_vec(),
+        })?;
+        Ok(response
+            .body()
+            .context("couldn't get response body")?
+            .to_vec())
+    }
+
+    /// Invokes the module with the given request, and returns the response body together with the
+    /// messages that the module logged during the invocation. Messages that were logged before and
+    /// not taken yet are discarded.
+    pub fn run(&self, request: &[u8]) -> anyhow::Result<Invocation> {
+        self.take_logs();
+        let response = self.invoke(request)?;
+        Ok(Invocation {
+            response,
+            logs: self.take_logs(),
+        })
+    }
+
+    /// Replaces the lookup data with the given entries, as an update of the lookup data by the
+    /// launcher would. Subsequent invocations see the new entries only.
+    pub fn set_lookup_data(&self, lookup_data: HashMap<Vec<u8>, Vec<u8>>) {
+        self.lookup_data_manager
+            .extend_next_lookup_data(lookup_data.into_iter().collect());
+        self.lookup_data_manager.finish_next_lookup_data();
+    }
+
+    /// Returns the messages that the module logged since the last call, in order.
+    pub fn take_logs(&self) -> Vec<String> {
+        std::mem::take(&mut *self.logs.lock().expect("couldn't lock logs"))
+    }
+}
+
+struct RecordingLoggingFactory {
+    logs: Arc<Mutex<Vec<String>>>,
+}
+
+impl ExtensionFactory<TestLogger> for RecordingLoggingFactory {
+    fn create(&self) -> anyhow::Result<Box<dyn OakApiNativeExtension>> {
+        Ok(Box::new(RecordingLogger {
+            logs: self.logs.clone(),
+        }))
+    }
+}
+
+/// Logging extension that records the messages instead of writing them to a log.
+struct RecordingLogger {
+    logs: Arc<Mutex<Vec<String>>>,
+}
+
+impl OakApiNativeExtension for RecordingLogger {
+    fn invoke(&mut self, request: Vec<u8>) -> Result<Vec<u8>, OakStatus> {
+        let message = String::from_utf8(request).map_err(|_| OakStatus::ErrInvalidArgs)?;
+        self.logs
+            .lock()
+            .map_err(|_| OakStatus::ErrInternal)?
+            .push(message);
+        Ok(Vec::new())
+    }
+
+    fn terminate(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn get_handle(&self) -> ExtensionHandle {
+        ExtensionHandle::LoggingHandle
+    }
+}
@@ -0,0 +1,82 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use oak_functions_test_runtime::{Invocation, TestRuntime};
+use std::{collections::HashMap, path::PathBuf};
+
+fn manifest_path(components: &[&str]) -> String {
+    // WORKSPACE_ROOT is set in .cargo/config.toml.
+    let mut path: PathBuf = [env!("WORKSPACE_ROOT")].iter().collect();
+    path.extend(components);
+    path.push("Cargo.toml");
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_lookup() {
+    let runtime = TestRuntime::for_crate(
+        &manifest_path(&["oak_functions", "examples", "key_value_lookup", "module"]),
+        HashMap::from([(b"key_0".to_vec(), b"value_0".to_vec())]),
+    )
+    .expect("couldn't create test runtime");
+
+    assert_eq!(runtime.invoke(b"key_0").unwrap(), b"value_0");
+    assert!(runtime.invoke(b"key_1").unwrap().is_empty());
+}
+
+#[test]
+fn test_logs() {
+    let runtime = TestRuntime::for_crate(
+        &manifest_path(&["oak_functions_sdk", "tests", "lookup_module"]),
+        HashMap::new(),
+    )
+    .expect("couldn't create test runtime");
+
+    assert_eq!(runtime.invoke(b"WriteLog").unwrap(), b"WriteLogResponse");
+    assert_eq!(runtime.take_logs(), vec!["WriteLog".to_string()]);
+    assert!(runtime.take_logs().is_empty());
+}
+
+#[test]
+fn test_run() {
+    let runtime = TestRuntime::for_crate(
+        &manifest_path(&["oak_functions_sdk", "tests", "lookup_module"]),
+        HashMap::new(),
+    )
+    .expect("couldn't create test runtime");
+
+    assert_eq!(
+        runtime.run(b"WriteLog").unwrap(),
+        Invocation {
+            response: b"WriteLogResponse".to_vec(),
+            logs: vec!["WriteLog".to_string()],
+        }
+    );
+}
+
+#[test]
+fn test_set_lookup_data() {
+    let runtime = TestRuntime::for_crate(
+        &manifest_path(&["oak_functions", "examples", "key_value_lookup", "module"]),
+        HashMap::from([(b"key_0".to_vec(), b"value_0".to_vec())]),
+    )
+    .expect("couldn't create test runtime");
+
+    runtime.set_lookup_data(HashMap::from([(b"key_1".to_vec(), b"value_1".to_vec())]));
+
+    assert!(runtime.invoke(b"key_0").unwrap().is_empty());
+    assert_eq!(runtime.invoke(b"key_1").unwrap(), b"value_1");
+}
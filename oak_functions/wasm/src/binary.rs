@@ -0,0 +1,135 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Minimal reading and writing of the Wasm binary format, for the rewrites that the enclave applies
+//! to modules before compiling them.
+//! <https://webassembly.github.io/spec/core/binary/index.html>
+
+use alloc::vec::Vec;
+
+const MAGIC_AND_VERSION: &[u8] = b"\0asm\x01\0\0\0";
+
+/// A section of a Wasm module.
+pub struct Section<'a> {
+    pub id: u8,
+    pub contents: &'a [u8],
+}
+
+/// Splits a Wasm module into its sections.
+pub fn parse_sections(module: &[u8]) -> anyhow::Result<Vec<Section<'_>>> {
+    let mut reader = Reader::new(module);
+    if reader.take(MAGIC_AND_VERSION.len())? != MAGIC_AND_VERSION {
+        anyhow::bail!("not a Wasm module");
+    }
+    let mut sections = Vec::new();
+    while !reader.is_empty() {
+        let id = reader.read_u8()?;
+        let size = reader.read_u32()? as usize;
+        let contents = reader.take(size)?;
+        sections.push(Section { id, contents });
+    }
+    Ok(sections)
+}
+
+/// Assembles a Wasm module from its sections, which must be in the order of the binary format.
+pub fn encode_sections(sections: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut module = MAGIC_AND_VERSION.to_vec();
+    for (id, contents) in sections {
+        module.push(*id);
+        write_u32(&mut module, contents.len() as u32);
+        module.extend_from_slice(contents);
+    }
+    module
+}
+
+/// Reads values of the Wasm binary format from a buffer.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            anyhow::bail!("truncated Wasm module");
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn read_u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads an unsigned LEB128 integer of at most 32 bits.
+    pub fn read_u32(&mut self) -> anyhow::Result<u32> {
+        let mut value: u32 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.read_u8()?;
+            let bits = u32::from(byte & 0x7f);
+            if shift == 28 && bits > 0x0f {
+                anyhow::bail!("invalid LEB128 integer in Wasm module");
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        anyhow::bail!("invalid LEB128 integer in Wasm module")
+    }
+
+    /// Skips a signed or unsigned LEB128 integer of at most `bits` bits.
+    pub fn skip_leb128(&mut self, bits: u32) -> anyhow::Result<()> {
+        for _ in 0..(bits + 6) / 7 {
+            if self.read_u8()? & 0x80 == 0 {
+                return Ok(());
+            }
+        }
+        anyhow::bail!("invalid LEB128 integer in Wasm module")
+    }
+
+    /// Skips a name or another vector of bytes.
+    pub fn skip_bytes(&mut self) -> anyhow::Result<()> {
+        let len = self.read_u32()? as usize;
+        self.take(len)?;
+        Ok(())
+    }
+}
+
+/// Writes `value` as an unsigned LEB128 integer.
+pub fn write_u32(buffer: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buffer.push(byte);
+            return;
+        }
+        buffer.push(byte | 0x80);
+    }
+}
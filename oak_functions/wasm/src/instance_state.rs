@@ -0,0 +1,245 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Resets the state of pooled instances besides their memory, i.e. their mutable globals and their
+//! tables, which a module may modify to keep state across invocations.
+//!
+//! Wasmi only gives access to the globals and tables that a module exports, so every mutable global
+//! and table that the module defines is additionally exported under a reserved name before the
+//! module is compiled. Their values right after instantiation are then restored before an instance
+//! is reused.
+
+use crate::binary::{encode_sections, parse_sections, write_u32, Reader};
+use alloc::{format, vec::Vec};
+
+/// Prefix of the names under which the mutable globals of a module are exported.
+const GLOBAL_EXPORT_PREFIX: &str = "__oak_functions_global_";
+/// Prefix of the names under which the tables of a module are exported.
+const TABLE_EXPORT_PREFIX: &str = "__oak_functions_table_";
+
+const IMPORT_SECTION_ID: u8 = 2;
+const TABLE_SECTION_ID: u8 = 4;
+const GLOBAL_SECTION_ID: u8 = 6;
+const EXPORT_SECTION_ID: u8 = 7;
+
+const EXTERNAL_KIND_FUNCTION: u8 = 0x00;
+const EXTERNAL_KIND_TABLE: u8 = 0x01;
+const EXTERNAL_KIND_MEMORY: u8 = 0x02;
+const EXTERNAL_KIND_GLOBAL: u8 = 0x03;
+
+const GLOBAL_MUTABLE: u8 = 0x01;
+
+/// Returns the module with every mutable global and table that it defines exported under a reserved
+/// name, so that [`InstanceState::snapshot`] can find them.
+pub fn export_mutable_state(module: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let sections = parse_sections(module)?;
+    let mut imported_tables = 0;
+    let mut imported_globals = 0;
+    let mut exports = Vec::new();
+    for section in &sections {
+        match section.id {
+            IMPORT_SECTION_ID => {
+                (imported_tables, imported_globals) = count_imports(section.contents)?;
+            }
+            TABLE_SECTION_ID => {
+                let count = Reader::new(section.contents).read_u32()?;
+                for index in imported_tables..imported_tables + count {
+                    exports.push((TABLE_EXPORT_PREFIX, EXTERNAL_KIND_TABLE, index));
+                }
+            }
+            GLOBAL_SECTION_ID => {
+                for index in mutable_globals(section.contents)? {
+                    let index = imported_globals + index;
+                    exports.push((GLOBAL_EXPORT_PREFIX, EXTERNAL_KIND_GLOBAL, index));
+                }
+            }
+            _ => {}
+        }
+    }
+    if exports.is_empty() {
+        return Ok(module.to_vec());
+    }
+
+    let mut export_section = Vec::new();
+    let existing_exports = sections
+        .iter()
+        .find(|section| section.id == EXPORT_SECTION_ID);
+    let mut reader = Reader::new(existing_exports.map_or(&[0][..], |section| section.contents));
+    let existing_count = reader.read_u32()?;
+    write_u32(&mut export_section, existing_count + exports.len() as u32);
+    export_section.extend_from_slice(reader.take(reader.len())?);
+    for (prefix, kind, index) in exports {
+        let name = format!("{}{}", prefix, index);
+        write_u32(&mut export_section, name.len() as u32);
+        export_section.extend_from_slice(name.as_bytes());
+        export_section.push(kind);
+        write_u32(&mut export_section, index);
+    }
+
+    // The export section follows the sections with IDs 1 to 6, and custom sections may be anywhere.
+    let mut rewritten: Vec<(u8, &[u8])> = Vec::with_capacity(sections.len() + 1);
+    let mut exports_written = false;
+    for section in &sections {
+        let after_exports = section.id != 0 && section.id > EXPORT_SECTION_ID;
+        if !exports_written && (section.id == EXPORT_SECTION_ID || after_exports) {
+            rewritten.push((EXPORT_SECTION_ID, &export_section));
+            exports_written = true;
+        }
+        if section.id != EXPORT_SECTION_ID {
+            rewritten.push((section.id, section.contents));
+        }
+    }
+    if !exports_written {
+        rewritten.push((EXPORT_SECTION_ID, &export_section));
+    }
+    Ok(encode_sections(&rewritten))
+}
+
+// Returns the number of imported tables and globals, which precede the tables and globals that the
+// module defines in their index spaces.
+fn count_imports(section: &[u8]) -> anyhow::Result<(u32, u32)> {
+    let mut reader = Reader::new(section);
+    let (mut tables, mut globals) = (0, 0);
+    for _ in 0..reader.read_u32()? {
+        reader.skip_bytes()?;
+        reader.skip_bytes()?;
+        match reader.read_u8()? {
+            EXTERNAL_KIND_FUNCTION => {
+                reader.read_u32()?;
+            }
+            EXTERNAL_KIND_TABLE => {
+                reader.read_u8()?;
+                skip_limits(&mut reader)?;
+                tables += 1;
+            }
+            EXTERNAL_KIND_MEMORY => skip_limits(&mut reader)?,
+            EXTERNAL_KIND_GLOBAL => {
+                reader.take(2)?;
+                globals += 1;
+            }
+            kind => anyhow::bail!("unsupported Wasm import kind {:#04x}", kind),
+        }
+    }
+    Ok((tables, globals))
+}
+
+fn skip_limits(reader: &mut Reader) -> anyhow::Result<()> {
+    let flags = reader.read_u8()?;
+    reader.read_u32()?;
+    if flags & 0x01 != 0 {
+        reader.read_u32()?;
+    }
+    Ok(())
+}
+
+// Returns the indices of the mutable globals among the globals that the module defines.
+fn mutable_globals(section: &[u8]) -> anyhow::Result<Vec<u32>> {
+    let mut reader = Reader::new(section);
+    let mut mutable = Vec::new();
+    for index in 0..reader.read_u32()? {
+        let _value_type = reader.read_u8()?;
+        if reader.read_u8()? == GLOBAL_MUTABLE {
+            mutable.push(index);
+        }
+        skip_constant_expression(&mut reader)?;
+    }
+    Ok(mutable)
+}
+
+// Skips the constant expression that initializes a global, up to and including its `end`.
+fn skip_constant_expression(reader: &mut Reader) -> anyhow::Result<()> {
+    loop {
+        match reader.read_u8()? {
+            // end
+            0x0b => return Ok(()),
+            // i32.const, i64.const
+            0x41 => reader.skip_leb128(32)?,
+            0x42 => reader.skip_leb128(64)?,
+            // f32.const, f64.const
+            0x43 => {
+                reader.take(4)?;
+            }
+            0x44 => {
+                reader.take(8)?;
+            }
+            // global.get, ref.func
+            0x23 | 0xd2 => {
+                reader.read_u32()?;
+            }
+            // ref.null
+            0xd0 => {
+                reader.read_u8()?;
+            }
+            // Arithmetic of the extended-const proposal.
+            0x6a | 0x6b | 0x6c | 0x7c | 0x7d | 0x7e => {}
+            opcode => anyhow::bail!("unsupported opcode {:#04x} in constant expression", opcode),
+        }
+    }
+}
+
+/// Values of the mutable globals and tables of an instance, as exported by
+/// [`export_mutable_state`].
+pub struct InstanceState {
+    globals: Vec<(wasmi::Global, wasmi::Value)>,
+    tables: Vec<(wasmi::Table, Vec<wasmi::Value>)>,
+}
+
+impl InstanceState {
+    /// Records the current values of the mutable globals and tables of `instance`.
+    pub fn snapshot(instance: &wasmi::Instance, store: impl wasmi::AsContext) -> Self {
+        let mut globals = Vec::new();
+        let mut tables = Vec::new();
+        for export in instance.exports(&store) {
+            let name = export.name();
+            if name.starts_with(GLOBAL_EXPORT_PREFIX) {
+                if let Some(global) = export.into_global() {
+                    globals.push((global, global.get(&store)));
+                }
+            } else if name.starts_with(TABLE_EXPORT_PREFIX) {
+                if let Some(table) = export.into_table() {
+                    let elements = (0..table.size(&store))
+                        .filter_map(|index| table.get(&store, index))
+                        .collect();
+                    tables.push((table, elements));
+                }
+            }
+        }
+        Self { globals, tables }
+    }
+
+    /// Restores the recorded values. Returns `false` if that is not possible because a table
+    /// grew, in which case the instance must not be reused.
+    pub fn restore(&self, mut store: impl wasmi::AsContextMut) -> anyhow::Result<bool> {
+        for (table, elements) in &self.tables {
+            if table.size(&store) as usize != elements.len() {
+                return Ok(false);
+            }
+        }
+        for (global, value) in &self.globals {
+            global
+                .set(&mut store, value.clone())
+                .map_err(|err| anyhow::anyhow!("couldn't reset Wasm global: {:?}", err))?;
+        }
+        for (table, elements) in &self.tables {
+            for (index, element) in elements.iter().enumerate() {
+                table
+                    .set(&mut store, index as u32, element.clone())
+                    .map_err(|err| anyhow::anyhow!("couldn't reset Wasm table: {:?}", err))?;
+            }
+        }
+        Ok(true)
+    }
+}
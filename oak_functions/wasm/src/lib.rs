@@ -23,19 +23,31 @@ extern crate alloc;
 #[cfg(test)]
 extern crate std;
 
+mod binary;
+mod instance_state;
+mod memory_limit;
 #[cfg(test)]
 mod tests;
+mod wasi;
+#[cfg(feature = "wasmtime")]
+mod wasmtime_backend;
 
+#[cfg(feature = "wasmtime")]
+pub use wasmtime_backend::WasmtimeHandler;
+
+use crate::instance_state::InstanceState;
 use alloc::{boxed::Box, format, sync::Arc, vec::Vec};
 use byteorder::{ByteOrder, LittleEndian};
+use core::sync::atomic::{AtomicU64, Ordering};
 use hashbrown::HashMap;
 use oak_functions_abi::{
     proto::{ExtensionHandle, OakStatus},
-    Request, Response, StatusCode,
+    Request, Response, ResponseMetadata, StatusCode,
 };
 use oak_functions_extension::{ExtensionFactory, OakApiNativeExtension};
 use oak_logger::{Level, OakLogger};
-use wasmi::{MemoryType, Store};
+use spinning_top::Spinlock;
+use wasmi::{core::TrapCode, MemoryType, Store};
 
 /// Fixed name of the function to start a Wasm. Every Oak Wasm module must provide this function.
 pub const MAIN_FUNCTION_NAME: &str = "main";
@@ -56,13 +68,68 @@ pub type AbiPointerOffset = u32;
 /// Type alias for the ExtensionHandle type, which must be cast into a ExtensionHandle.
 pub type AbiExtensionHandle = i32;
 
+/// Error returned when a Wasm invocation runs out of fuel before completing.
+///
+/// It can be recovered from an [`anyhow::Error`] via [`anyhow::Error::downcast_ref`], so that
+/// callers can distinguish invocations that exceeded their execution limit from other failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuelExhausted {
+    /// The amount of fuel the invocation was given.
+    pub fuel_limit: u64,
+}
+
+impl core::fmt::Display for FuelExhausted {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            formatter,
+            "Wasm invocation exhausted its fuel limit of {} units",
+            self.fuel_limit
+        )
+    }
+}
+
+/// Error returned when a Wasm invocation fails after its linear memory reached the configured
+/// maximum number of pages.
+///
+/// Like [`FuelExhausted`], it can be recovered from an [`anyhow::Error`] via
+/// [`anyhow::Error::downcast_ref`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimitExceeded {
+    /// The maximum number of 64 KiB pages the linear memory may grow to.
+    pub max_memory_pages: u32,
+}
+
+impl core::fmt::Display for MemoryLimitExceeded {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            formatter,
+            "Wasm invocation exceeded its memory limit of {} pages",
+            self.max_memory_pages
+        )
+    }
+}
+
+/// Limits on the resources that a single invocation of a Wasm module may consume.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WasmLimits {
+    /// Amount of fuel each invocation starts with. If set, invocations that run out of fuel are
+    /// aborted. If `None`, execution is not metered.
+    pub max_fuel: Option<u64>,
+    /// Maximum number of 64 KiB pages the linear memory of an invocation may grow to. Applies to
+    /// the memory that the module defines itself as well as to the one it may import. If `None`,
+    /// only the limits of Wasm itself apply.
+    pub max_memory_pages: Option<u32>,
+}
+
 /// `UserState` holds the user request bytes and response bytes for a particular execution of an Oak
 /// Wasm module. The `UserState` also holds a reference to the logger and the enabled extensions.
 pub struct UserState<L: OakLogger> {
     request_bytes: Vec<u8>,
     response_bytes: Vec<u8>,
+    response_metadata: ResponseMetadata,
     extensions: HashMap<ExtensionHandle, Box<dyn OakApiNativeExtension>>,
     logger: L,
+    wasi: wasi::WasiState,
 }
 
 /// Stubs a Wasm imported function in the provided linker.
@@ -87,13 +154,37 @@ macro_rules! stub_wasm_function {
     };
 }
 
+/// Defines a function of the WASI shim in the provided linker, which operates on the memory that
+/// the Wasm module exports.
+macro_rules! define_wasi_function {
+    ($linker:ident, $function_name:ident, ($($arg:ident: $t:ty),*)) => {
+        $linker.func_wrap(
+            wasi::MODULE,
+            stringify!($function_name),
+            |mut caller: wasmi::Caller<'_, UserState<L>>, $($arg: $t),*| {
+                let memory = caller
+                    .get_export(MEMORY_NAME)
+                    .and_then(wasmi::Extern::into_memory);
+                let memory = match memory {
+                    Some(memory) => memory,
+                    None => return Ok::<i32, wasmi::core::Trap>(wasi::ERRNO_FAULT),
+                };
+                let (memory, user_state) = memory.data_and_store_mut(&mut caller);
+                Ok(user_state.wasi.$function_name(memory, &user_state.logger, $($arg),*))
+            },
+        )
+        .expect(concat!("failed to define ", stringify!($function_name), " in linker"));
+    };
+}
+
 impl<L> UserState<L>
 where
     L: OakLogger,
 {
     /// Stores the user request bytes, extensions, and logger. The response bytes are initialized
     /// with the empty response because every request needs to have a response and we fixed the
-    /// empty response as the default response.
+    /// empty response as the default response. Likewise, the response metadata defaults to
+    /// `200 OK` without headers.
     fn new(
         request_bytes: Vec<u8>,
         extensions: HashMap<ExtensionHandle, Box<dyn OakApiNativeExtension>>,
@@ -102,8 +193,10 @@ where
         UserState {
             request_bytes,
             response_bytes: Vec::new(),
+            response_metadata: ResponseMetadata::default(),
             extensions,
             logger,
+            wasi: wasi::WasiState::default(),
         }
     }
 
@@ -144,6 +237,7 @@ where
             .debug_struct("UserState")
             .field("request_bytes", &self.request_bytes)
             .field("response_bytes", &self.response_bytes)
+            .field("response_metadata", &self.response_metadata)
             .field("extensions", &self.extensions)
             .finish()
     }
@@ -153,7 +247,7 @@ where
 /// exchange data with Oak Functions and need the Wasm module (or, more specifically,
 /// the [`OakCaller`]) to provide `alloc` for allocating memory. The [`OakLinker`] checks that the
 /// Wasm module provides `alloc` and `main`, which every Oak Wasm module must provide, and defines
-/// the memory which the [`OakCaller`] uses.
+/// the memory which the [`OakCaller`] uses. If enabled, it also exports the WASI shim.
 struct OakLinker<L: OakLogger> {
     linker: wasmi::Linker<UserState<L>>,
 }
@@ -162,14 +256,15 @@ impl<L> OakLinker<L>
 where
     L: OakLogger,
 {
-    fn new(engine: &wasmi::Engine, store: &mut Store<UserState<L>>) -> Self {
+    fn new(
+        engine: &wasmi::Engine,
+        store: &mut Store<UserState<L>>,
+        memory_type: MemoryType,
+        wasi: bool,
+    ) -> Self {
         let mut linker: wasmi::Linker<UserState<L>> = wasmi::Linker::new(engine);
 
         // Add memory to linker.
-        // TODO(#3783): Find a sensible value for initial pages.
-        let initial_pages = 100;
-        let memory_type =
-            MemoryType::new(initial_pages, None).expect("failed to define Wasm memory type");
         let memory =
             wasmi::Memory::new(store, memory_type).expect("failed to initialize Wasm memory");
         linker
@@ -221,6 +316,31 @@ where
             )
             .expect("failed to define write_response in linker");
 
+        linker
+            .func_wrap(
+                OAK_FUNCTIONS,
+                "write_response_metadata",
+                // The types in the signatures correspond to the parameters from
+                // oak_functions_abi/src/lib.rs.
+                |caller: wasmi::Caller<'_, UserState<L>>,
+                 buf_ptr: AbiPointer,
+                 buf_len: AbiPointerOffset| {
+                    let mut caller = match OakCaller::new(caller) {
+                        Ok(caller) => caller,
+                        Err(oak_status) => return Ok(oak_status as i32),
+                    };
+
+                    let status = caller.read_buffer(buf_ptr, buf_len).and_then(|buffer| {
+                        let user_state = caller.data_mut();
+                        let metadata = decode_response_metadata(user_state, &buffer)?;
+                        user_state.response_metadata = metadata;
+                        Ok(())
+                    });
+                    from_oak_status(status)
+                },
+            )
+            .expect("failed to define write_response_metadata in linker");
+
         linker
             .func_wrap(
                 OAK_FUNCTIONS,
@@ -259,6 +379,22 @@ where
             )
             .expect("failed to define invoke in linker");
 
+        if wasi {
+            wasi::for_each_wasi_function!(define_wasi_function, linker);
+            linker
+                .func_wrap(
+                    wasi::MODULE,
+                    "proc_exit",
+                    |caller: wasmi::Caller<'_, UserState<L>>, code: i32| {
+                        let message = format!("Wasm module exited with code {}", code);
+                        caller.data().log_error(&message);
+                        Err::<(), wasmi::core::Trap>(wasmi::core::Trap::new(message))
+                    },
+                )
+                .expect("failed to define proc_exit in linker");
+            return OakLinker { linker };
+        }
+
         // TODO(#3929): One of our dependency requires various WASI functions to be linked, but, to
         // the best of our knowledge, does not use them at run time. As a workaround, we stub
         // them for now but we should remove them, if possible.
@@ -440,10 +576,12 @@ where
 
         // Allocate the memory from the Wasm module.
         // `address` will hold the address where memory of size len was allocated.
-        let dest_ptr = self
-            .alloc
-            .call(&mut self.caller, len)
-            .expect("`alloc` call failed");
+        // This fails if the module can't grow its memory any further.
+        let dest_ptr = self.alloc.call(&mut self.caller, len).map_err(|err| {
+            self.data()
+                .log_error(&format!("`alloc` call failed: {:?}", err));
+            OakStatus::ErrInternal
+        })?;
 
         // Write to the allocated memory.
         self.write_buffer(&buf, dest_ptr)?;
@@ -491,12 +629,39 @@ where
     }
 }
 
+/// A Wasm instance together with the store that owns it. Pooled instances are kept in their
+/// pristine state, i.e. their memory, mutable globals and tables are reset before they are returned
+/// to the pool.
+struct PooledInstance<L: OakLogger> {
+    instance: wasmi::Instance,
+    store: Store<UserState<L>>,
+    // Mutable globals and tables of the instance right after instantiation.
+    pristine_state: InstanceState,
+    // Total fuel added to the store since its creation, to compute how much fuel is left.
+    fuel_added: u64,
+}
+
+/// Instances that can be reused for further invocations, so that the module doesn't need to be
+/// instantiated for every request.
+struct InstancePool<L: OakLogger> {
+    instances: Spinlock<Vec<PooledInstance<L>>>,
+    size: usize,
+    // Contents of the linear memory of a freshly instantiated module.
+    pristine_memory: Vec<u8>,
+}
+
 // An ephemeral request handler with a Wasm module for handling the requests.
 #[derive(Clone)]
 pub struct WasmHandler<L: OakLogger> {
     wasm_module: Arc<wasmi::Module>,
     extension_factories: Arc<Vec<Box<dyn ExtensionFactory<L>>>>,
     logger: L,
+    limits: WasmLimits,
+    memory_type: MemoryType,
+    instance_pool: Option<Arc<InstancePool<L>>>,
+    fuel_meter: Option<Arc<AtomicU64>>,
+    wasi: bool,
+    response_metadata: bool,
 }
 
 impl<L> WasmHandler<L>
@@ -508,45 +673,210 @@ where
         extension_factories: Vec<Box<dyn ExtensionFactory<L>>>,
         logger: L,
     ) -> anyhow::Result<Self> {
-        let engine = wasmi::Engine::default();
-        let module = wasmi::Module::new(&engine, wasm_module_bytes)
+        Self::create_with_limits(
+            wasm_module_bytes,
+            extension_factories,
+            logger,
+            WasmLimits::default(),
+        )
+    }
+
+    /// Creates a handler that enforces the given `limits` on every invocation.
+    ///
+    /// An invocation that runs out of fuel is aborted with a [`StatusCode::PolicyTimeViolation`]
+    /// response. An invocation that fails after its memory reached `max_memory_pages` results in a
+    /// [`MemoryLimitExceeded`] error.
+    pub fn create_with_limits(
+        wasm_module_bytes: &[u8],
+        extension_factories: Vec<Box<dyn ExtensionFactory<L>>>,
+        logger: L,
+        limits: WasmLimits,
+    ) -> anyhow::Result<Self> {
+        // TODO(#3783): Find a sensible value for initial pages.
+        let initial_pages = limits.max_memory_pages.map_or(100, |max| max.min(100));
+        let memory_type = MemoryType::new(initial_pages, limits.max_memory_pages)
+            .map_err(|err| anyhow::anyhow!("invalid Wasm memory limit: {:?}", err))?;
+
+        let limited_module;
+        let wasm_module_bytes = match limits.max_memory_pages {
+            Some(max_memory_pages) => {
+                limited_module = memory_limit::limit_memory(wasm_module_bytes, max_memory_pages)?;
+                &limited_module[..]
+            }
+            None => wasm_module_bytes,
+        };
+        let wasm_module_bytes = instance_state::export_mutable_state(wasm_module_bytes)?;
+
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(limits.max_fuel.is_some());
+        let engine = wasmi::Engine::new(&config);
+        let module = wasmi::Module::new(&engine, &wasm_module_bytes[..])
             .map_err(|err| anyhow::anyhow!("couldn't load module from buffer: {:?}", err))?;
 
         Ok(WasmHandler {
             wasm_module: Arc::new(module),
             extension_factories: Arc::new(extension_factories),
             logger,
+            limits,
+            memory_type,
+            instance_pool: None,
+            fuel_meter: None,
+            wasi: false,
+            response_metadata: false,
         })
     }
 
+    /// Stores the amount of fuel that each invocation consumed in `fuel_meter`, where it can be
+    /// read after the invocation. Fuel is only metered if the handler was created with a fuel
+    /// limit.
+    pub fn with_fuel_meter(mut self, fuel_meter: Arc<AtomicU64>) -> Self {
+        self.fuel_meter = Some(fuel_meter);
+        self
+    }
+
+    /// Sets whether the deterministic WASI shim is provided to the module, so that modules compiled
+    /// for `wasm32-wasi` can be instantiated. Lines that the module writes to stdout or stderr are
+    /// logged as sensitive logs. Must be called before [`WasmHandler::with_instance_pool`].
+    pub fn with_wasi(mut self, enabled: bool) -> Self {
+        self.wasi = enabled;
+        self
+    }
+
+    /// Sets whether the status code and headers that the module sets are prepended to the body of
+    /// the response, encoded as in [`ResponseMetadata::encode_with_body`]. Otherwise they are
+    /// dropped, and the body is passed on unchanged.
+    pub fn with_response_metadata(mut self, enabled: bool) -> Self {
+        self.response_metadata = enabled;
+        self
+    }
+
+    /// Keeps up to `size` instances of the module around and reuses them across invocations,
+    /// instead of instantiating the module for every request. The pool is filled upfront.
+    ///
+    /// An instance is only reused if its invocation completed successfully without growing its
+    /// memory or tables. Its memory, mutable globals and tables are then reset to the state right
+    /// after instantiation, so that no data leaks from one invocation to the next. When the pool is
+    /// exhausted, invocations fall back to instantiating the module.
+    pub fn with_instance_pool(mut self, size: usize) -> anyhow::Result<Self> {
+        let mut instances = Vec::with_capacity(size);
+        for _ in 0..size {
+            instances.push(self.instantiate(self.empty_user_state())?);
+        }
+        let pristine_memory = match instances.first() {
+            Some(pooled) => memory(&pooled.instance, &pooled.store)?
+                .data(&pooled.store)
+                .to_vec(),
+            None => Vec::new(),
+        };
+        self.instance_pool = Some(Arc::new(InstancePool {
+            instances: Spinlock::new(instances),
+            size,
+            pristine_memory,
+        }));
+        Ok(self)
+    }
+
     // Create an extension from every factory in the WasmHandler.
     fn create_extensions(
         &self,
     ) -> anyhow::Result<HashMap<ExtensionHandle, Box<dyn OakApiNativeExtension>>> {
-        let mut extensions = HashMap::new();
-        for factory in self.extension_factories.iter() {
-            let extension = factory.create()?;
-            extensions.insert(extension.get_handle(), extension);
+        create_extensions(&self.extension_factories)
+    }
+
+    // The user state of instances that are not handling a request.
+    fn empty_user_state(&self) -> UserState<L> {
+        UserState::new(Vec::new(), HashMap::new(), self.logger.clone())
+    }
+
+    // Instantiates the module in a new store.
+    fn instantiate(&self, user_state: UserState<L>) -> anyhow::Result<PooledInstance<L>> {
+        let module = self.wasm_module.clone();
+        let mut store = wasmi::Store::new(module.engine(), user_state);
+        let fuel_added = self.limits.max_fuel.unwrap_or_default();
+        if fuel_added > 0 {
+            store
+                .add_fuel(fuel_added)
+                .map_err(|err| anyhow::anyhow!("couldn't add fuel to store: {:?}", err))?;
         }
-        Ok(extensions)
+        let linker = OakLinker::new(module.engine(), &mut store, self.memory_type, self.wasi);
+        let (instance, store) = linker.instantiate(store, module)?;
+        let pristine_state = InstanceState::snapshot(&instance, &store);
+        Ok(PooledInstance {
+            instance,
+            store,
+            pristine_state,
+            fuel_added,
+        })
+    }
+
+    // Takes an instance from the pool and prepares it for the given user state, or instantiates
+    // the module if the pool is empty.
+    fn acquire_instance(&self, user_state: UserState<L>) -> anyhow::Result<PooledInstance<L>> {
+        let pooled = self
+            .instance_pool
+            .as_ref()
+            .and_then(|pool| pool.instances.lock().pop());
+        let mut pooled = match pooled {
+            Some(pooled) => pooled,
+            // For isolated requests we need to create a new store for every request.
+            None => return self.instantiate(user_state),
+        };
+        *pooled.store.data_mut() = user_state;
+
+        // Top up the fuel to the limit.
+        if let Some(max_fuel) = self.limits.max_fuel {
+            let fuel_consumed = pooled.store.fuel_consumed().unwrap_or_default();
+            let fuel_left = pooled.fuel_added - fuel_consumed;
+            pooled
+                .store
+                .add_fuel(max_fuel - fuel_left)
+                .map_err(|err| anyhow::anyhow!("couldn't add fuel to store: {:?}", err))?;
+            pooled.fuel_added += max_fuel - fuel_left;
+        }
+        Ok(pooled)
+    }
+
+    // Returns the instance to the pool if it can be reset to its pristine state and the pool is
+    // not full. Otherwise the instance is dropped.
+    fn release_instance(&self, mut pooled: PooledInstance<L>) -> anyhow::Result<()> {
+        let pool = match &self.instance_pool {
+            Some(pool) => pool,
+            None => return Ok(()),
+        };
+        *pooled.store.data_mut() = self.empty_user_state();
+
+        // Memory can't shrink, so instances whose memory grew are not reused.
+        let memory = memory(&pooled.instance, &pooled.store)?;
+        let data = memory.data_mut(&mut pooled.store);
+        if data.len() != pool.pristine_memory.len() {
+            return Ok(());
+        }
+        data.copy_from_slice(&pool.pristine_memory);
+        // Tables can't shrink either, so instances whose tables grew are not reused.
+        if !pooled.pristine_state.restore(&mut pooled.store)? {
+            return Ok(());
+        }
+
+        let mut instances = pool.instances.lock();
+        if instances.len() < pool.size {
+            instances.push(pooled);
+        }
+        Ok(())
     }
 
     /// Handles a call to invoke by getting the raw request bytes from the body of the request to
     /// invoke and returns a reponse to invoke setting the raw bytes in the body of the response.
     pub fn handle_invoke(&self, invoke_request: Request) -> anyhow::Result<Response> {
-        let module = self.wasm_module.clone();
-
         let user_state = UserState::new(
             invoke_request.body,
             self.create_extensions()?,
             self.logger.clone(),
         );
-        // For isolated requests we need to create a new store for every request.
-        let mut store = wasmi::Store::new(module.engine(), user_state);
-        let linker = OakLinker::new(module.engine(), &mut store);
-        let (instance, mut store) = linker.instantiate(store, module)?;
+        let mut pooled = self.acquire_instance(user_state)?;
+        let instance = pooled.instance;
+        let store = &mut pooled.store;
 
-        instance.exports(&store).for_each(|export| {
+        instance.exports(&*store).for_each(|export| {
             store
                 .data()
                 .logger
@@ -555,9 +885,19 @@ where
 
         // Invokes the Wasm module by calling main.
         let main = instance
-            .get_typed_func::<(), ()>(&store, MAIN_FUNCTION_NAME)
+            .get_typed_func::<(), ()>(&*store, MAIN_FUNCTION_NAME)
             .expect("couldn't get `main` export");
-        let result = main.call(&mut store, ());
+        let fuel_consumed_before = store.fuel_consumed();
+        let result = main.call(&mut *store, ());
+        if let (Some(fuel_meter), Some(before), Some(after)) = (
+            &self.fuel_meter,
+            fuel_consumed_before,
+            store.fuel_consumed(),
+        ) {
+            fuel_meter.store(after - before, Ordering::Relaxed);
+        }
+        let user_state = store.data_mut();
+        user_state.wasi.flush(&user_state.logger);
         store.data().logger.log_sensitive(
             Level::Info,
             &format!("running Wasm module completed with result: {:?}", result),
@@ -570,8 +910,53 @@ where
             .values_mut()
             .try_for_each(|e| e.terminate())?;
 
-        let invoke_response =
-            Response::create(StatusCode::Success, store.data().response_bytes.clone());
+        let out_of_fuel = matches!(
+            &result,
+            Err(trap) if matches!(trap.trap_code(), Some(TrapCode::OutOfFuel))
+        );
+        if out_of_fuel {
+            store.data().logger.log_sensitive(
+                Level::Warn,
+                &format!(
+                    "Wasm invocation ran out of fuel (limit: {:?})",
+                    self.limits.max_fuel
+                ),
+            );
+            return Ok(Response::create(
+                StatusCode::PolicyTimeViolation,
+                Vec::new(),
+            ));
+        }
+
+        // A `memory.grow` beyond the maximum is not a trap by itself, it just returns -1 to the
+        // module. Modules abort when they can't allocate, so a trap while the memory is at its
+        // maximum is attributed to the memory limit.
+        if let (Err(_), Some(max_memory_pages)) = (&result, self.limits.max_memory_pages) {
+            let memory = memory(&instance, store)?;
+            if u32::from(memory.current_pages(&*store)) >= max_memory_pages {
+                store.data().logger.log_sensitive(
+                    Level::Warn,
+                    &format!("Wasm invocation exceeded {} memory pages", max_memory_pages),
+                );
+                return Err(anyhow::Error::msg(MemoryLimitExceeded { max_memory_pages }));
+            }
+        }
+
+        let user_state = store.data();
+        let body = if self.response_metadata {
+            user_state
+                .response_metadata
+                .encode_with_body(&user_state.response_bytes)
+        } else {
+            user_state.response_bytes.clone()
+        };
+        let invoke_response = Response::create(StatusCode::Success, body);
+
+        // A trap may leave the instance in an inconsistent state, e.g. with a modified stack
+        // pointer, so only instances that completed successfully are reused.
+        if result.is_ok() {
+            self.release_instance(pooled)?;
+        }
         Ok(invoke_response)
     }
 }
@@ -583,10 +968,51 @@ impl<L: oak_logger::OakLogger> micro_rpc::Transport for WasmHandler<L> {
             body: request.to_vec(),
         };
         let response = self.handle_invoke(request)?;
+        if response.status == StatusCode::PolicyTimeViolation {
+            return Err(anyhow::Error::msg(FuelExhausted {
+                fuel_limit: self.limits.max_fuel.unwrap_or_default(),
+            }));
+        }
         Ok(response.body)
     }
 }
 
+// Create an extension from every factory.
+fn create_extensions<L: OakLogger>(
+    extension_factories: &[Box<dyn ExtensionFactory<L>>],
+) -> anyhow::Result<HashMap<ExtensionHandle, Box<dyn OakApiNativeExtension>>> {
+    let mut extensions = HashMap::new();
+    for factory in extension_factories.iter() {
+        let extension = factory.create()?;
+        extensions.insert(extension.get_handle(), extension);
+    }
+    Ok(extensions)
+}
+
+/// Returns the memory of the given Oak Wasm instance.
+fn memory<L: OakLogger>(
+    instance: &wasmi::Instance,
+    store: &Store<UserState<L>>,
+) -> anyhow::Result<wasmi::Memory> {
+    instance
+        .get_memory(store, MEMORY_NAME)
+        .ok_or(anyhow::anyhow!("couldn't find Wasm `memory` export"))
+}
+
+/// Decodes and validates the response metadata that a Wasm module wrote. Shared between the Wasm
+/// backends.
+fn decode_response_metadata<L: OakLogger>(
+    user_state: &UserState<L>,
+    buffer: &[u8],
+) -> Result<ResponseMetadata, OakStatus> {
+    ResponseMetadata::try_from(buffer)
+        .and_then(|metadata| metadata.validate().map(|()| metadata))
+        .map_err(|err| {
+            user_state.log_error(&format!("invalid response metadata: {:?}", err));
+            OakStatus::ErrInvalidArgs
+        })
+}
+
 /// A helper function to move between our specific result type `Result<(), OakStatus>` and the
 /// `wasmi` specific result type `Result<i32, wasmi::Trap>`.
 fn from_oak_status(result: Result<(), OakStatus>) -> Result<i32, wasmi::core::Trap> {
@@ -0,0 +1,83 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Caps the memories that a Wasm module defines itself.
+//!
+//! Modules compiled from Rust define and export their own memory instead of importing the one that
+//! the linker provides, so the limit of the linker's memory doesn't apply to them. Neither backend
+//! can limit the growth of a memory that the module defines, short of a resource limiter that Wasmi
+//! doesn't have. Instead, the maximum in the memory section of the module is lowered to the limit
+//! before the module is compiled, so that `memory.grow` beyond it fails as specified by Wasm.
+//! <https://webassembly.github.io/spec/core/binary/modules.html#memory-section>
+
+use crate::binary::{encode_sections, parse_sections, write_u32, Reader};
+use alloc::vec::Vec;
+
+const MEMORY_SECTION_ID: u8 = 5;
+// Flags of the limits of a memory that has no maximum, or a maximum. Other flags are used by the
+// threads and memory64 proposals, which are not enabled.
+const LIMITS_MIN: u8 = 0x00;
+const LIMITS_MIN_MAX: u8 = 0x01;
+
+/// Returns the module with the maximum of every memory that it defines lowered to `max_pages`.
+/// Fails if a memory initially has more than `max_pages` pages.
+pub fn limit_memory(module: &[u8], max_pages: u32) -> anyhow::Result<Vec<u8>> {
+    let sections = parse_sections(module)?;
+    let mut limited = Vec::with_capacity(sections.len());
+    for section in &sections {
+        let contents = if section.id == MEMORY_SECTION_ID {
+            limit_memory_section(section.contents, max_pages)?
+        } else {
+            section.contents.to_vec()
+        };
+        limited.push((section.id, contents));
+    }
+    let limited: Vec<(u8, &[u8])> = limited
+        .iter()
+        .map(|(id, contents)| (*id, &contents[..]))
+        .collect();
+    Ok(encode_sections(&limited))
+}
+
+fn limit_memory_section(section: &[u8], max_pages: u32) -> anyhow::Result<Vec<u8>> {
+    let mut reader = Reader::new(section);
+    let count = reader.read_u32()?;
+    let mut limited = Vec::new();
+    write_u32(&mut limited, count);
+    for _ in 0..count {
+        let flags = reader.read_u8()?;
+        let initial = reader.read_u32()?;
+        let maximum = match flags {
+            LIMITS_MIN => max_pages,
+            LIMITS_MIN_MAX => reader.read_u32()?.min(max_pages),
+            _ => anyhow::bail!("unsupported Wasm memory limits flags {:#04x}", flags),
+        };
+        if initial > max_pages {
+            anyhow::bail!(
+                "initial Wasm memory of {} pages exceeds the limit of {} pages",
+                initial,
+                max_pages
+            );
+        }
+        limited.push(LIMITS_MIN_MAX);
+        write_u32(&mut limited, initial);
+        write_u32(&mut limited, maximum);
+    }
+    if !reader.is_empty() {
+        anyhow::bail!("trailing bytes in Wasm memory section");
+    }
+    Ok(limited)
+}
@@ -15,12 +15,18 @@
 //
 
 use crate::{
-    AbiPointer, AbiPointerOffset, OakLinker, UserState, WasmHandler, ALLOC_FUNCTION_NAME,
+    decode_response_metadata, wasi::WasiState, AbiPointer, AbiPointerOffset, FuelExhausted,
+    MemoryLimitExceeded, OakLinker, UserState, WasmHandler, WasmLimits, ALLOC_FUNCTION_NAME,
     MEMORY_NAME,
 };
-use alloc::{string::ToString, vec::Vec};
+use alloc::{string::ToString, sync::Arc, vec::Vec};
 use byteorder::{ByteOrder, LittleEndian};
-use oak_functions_abi::{proto::OakStatus, ExtensionHandle, TestingRequest, TestingResponse};
+use core::sync::atomic::{AtomicU64, Ordering};
+use micro_rpc::Transport;
+use oak_functions_abi::{
+    proto::OakStatus, ExtensionHandle, Request, ResponseMetadata, StatusCode, TestingRequest,
+    TestingResponse,
+};
 use oak_functions_testing_extension::{TestingFactory, TestingLogger};
 
 #[test]
@@ -195,6 +201,409 @@ fn test_invoke_extension() {
     );
 }
 
+#[test]
+fn test_invoke_within_fuel_limit() {
+    let wasm_handler = create_wasm_handler_with_limits(WasmLimits {
+        max_fuel: Some(u64::MAX),
+        ..Default::default()
+    });
+    let response = wasm_handler
+        .handle_invoke(Request {
+            body: b"hello".to_vec(),
+        })
+        .expect("couldn't handle invoke");
+    assert_eq!(StatusCode::Success, response.status);
+}
+
+#[test]
+fn test_fuel_meter() {
+    let fuel_meter = Arc::new(AtomicU64::new(0));
+    let wasm_handler = create_wasm_handler_with_limits(WasmLimits {
+        max_fuel: Some(u64::MAX),
+        ..Default::default()
+    })
+    .with_fuel_meter(fuel_meter.clone());
+    wasm_handler
+        .handle_invoke(Request {
+            body: b"hello".to_vec(),
+        })
+        .expect("couldn't handle invoke");
+    let fuel_consumed = fuel_meter.load(Ordering::Relaxed);
+    assert!(fuel_consumed > 0);
+
+    // Only the fuel of the latest invocation is reported.
+    wasm_handler
+        .handle_invoke(Request {
+            body: b"hello".to_vec(),
+        })
+        .expect("couldn't handle invoke");
+    assert_eq!(fuel_meter.load(Ordering::Relaxed), fuel_consumed);
+}
+
+#[test]
+fn test_invoke_exceeding_fuel_limit() {
+    let mut wasm_handler = create_wasm_handler_with_limits(WasmLimits {
+        max_fuel: Some(1),
+        ..Default::default()
+    });
+    let response = wasm_handler
+        .handle_invoke(Request {
+            body: b"hello".to_vec(),
+        })
+        .expect("couldn't handle invoke");
+    assert_eq!(StatusCode::PolicyTimeViolation, response.status);
+
+    let err = wasm_handler.invoke(b"hello").unwrap_err();
+    assert_eq!(
+        Some(&FuelExhausted { fuel_limit: 1 }),
+        err.downcast_ref::<FuelExhausted>()
+    );
+}
+
+#[test]
+fn test_invoke_exceeding_memory_limit() {
+    // The memory of the module can't grow large enough to hold a request this large.
+    let max_memory_pages = 100;
+    let wasm_handler = create_wasm_handler_with_limits(WasmLimits {
+        max_memory_pages: Some(max_memory_pages),
+        ..Default::default()
+    });
+    let err = wasm_handler
+        .handle_invoke(Request {
+            body: alloc::vec![0; 8 * 1024 * 1024],
+        })
+        .unwrap_err();
+    assert_eq!(
+        Some(&MemoryLimitExceeded { max_memory_pages }),
+        err.downcast_ref::<MemoryLimitExceeded>()
+    );
+}
+
+// A module that defines its own memory, without a maximum, and grows it by 10 pages, one page at a
+// time. It traps if the memory can't grow.
+const GROWING_MODULE: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32)
+            i32.const 0)
+        (func (export "main") (local $pages i32)
+            (loop
+                (if (i32.eq (memory.grow (i32.const 1)) (i32.const -1))
+                    (then unreachable))
+                (local.set $pages (i32.add (local.get $pages) (i32.const 1)))
+                (br_if 0 (i32.lt_u (local.get $pages) (i32.const 10))))))
+"#;
+
+#[test]
+fn test_invoke_growing_own_memory() {
+    let wasm_module_bytes = wat::parse_str(GROWING_MODULE).unwrap();
+    let wasm_handler =
+        WasmHandler::create(&wasm_module_bytes, Vec::new(), TestingLogger::for_test())
+            .expect("couldn't create WasmHandler");
+    let response = wasm_handler
+        .handle_invoke(Request { body: Vec::new() })
+        .expect("couldn't handle invoke");
+    assert_eq!(StatusCode::Success, response.status);
+}
+
+#[test]
+fn test_invoke_growing_own_memory_beyond_limit() {
+    let wasm_module_bytes = wat::parse_str(GROWING_MODULE).unwrap();
+    let max_memory_pages = 5;
+    let wasm_handler = WasmHandler::create_with_limits(
+        &wasm_module_bytes,
+        Vec::new(),
+        TestingLogger::for_test(),
+        WasmLimits {
+            max_memory_pages: Some(max_memory_pages),
+            ..Default::default()
+        },
+    )
+    .expect("couldn't create WasmHandler");
+    let err = wasm_handler
+        .handle_invoke(Request { body: Vec::new() })
+        .unwrap_err();
+    assert_eq!(
+        Some(&MemoryLimitExceeded { max_memory_pages }),
+        err.downcast_ref::<MemoryLimitExceeded>()
+    );
+}
+
+#[test]
+fn test_create_with_initial_memory_beyond_limit() {
+    let wasm_module_bytes = wat::parse_str(GROWING_MODULE.replace(
+        "(memory (export \"memory\") 1)",
+        "(memory (export \"memory\") 2)",
+    ))
+    .unwrap();
+    let result = WasmHandler::create_with_limits(
+        &wasm_module_bytes,
+        Vec::new(),
+        TestingLogger::for_test(),
+        WasmLimits {
+            max_memory_pages: Some(1),
+            ..Default::default()
+        },
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_create_with_invalid_memory_limit() {
+    let logger = TestingLogger::for_test();
+    let wasm_module_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_module_bytes = std::fs::read(wasm_module_path).unwrap();
+    // Wasm memory can't have more than 65536 pages.
+    let limits = WasmLimits {
+        max_memory_pages: Some(65537),
+        ..Default::default()
+    };
+    let result =
+        WasmHandler::create_with_limits(&wasm_module_bytes[..], Vec::new(), logger, limits);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_invoke_with_instance_pool() {
+    let wasm_handler = create_wasm_handler_with_limits(WasmLimits::default())
+        .with_instance_pool(1)
+        .expect("couldn't create instance pool");
+
+    // Every invocation must only see its own request, even though the instance is reused.
+    for body in [b"first".to_vec(), b"second".to_vec(), Vec::new()] {
+        let response = wasm_handler
+            .handle_invoke(Request { body: body.clone() })
+            .expect("couldn't handle invoke");
+        assert_eq!(StatusCode::Success, response.status);
+        assert_eq!(body, response.body);
+    }
+
+    let instance_pool = wasm_handler
+        .instance_pool
+        .expect("no instance pool in WasmHandler");
+    assert_eq!(1, instance_pool.instances.lock().len());
+}
+
+// A module that keeps state in a mutable global and a table, which it doesn't export. It responds
+// with the value of the global and whether the first table element is null, and then changes both.
+const STATEFUL_MODULE: &str = r#"
+    (module
+        (import "oak_functions" "write_response" (func $write_response (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (global $counter (mut i32) (i32.const 0))
+        (table $table 1 funcref)
+        (elem (i32.const 0) func $noop)
+        (func $noop)
+        (func (export "alloc") (param i32) (result i32)
+            i32.const 0)
+        (func (export "main")
+            (i32.store (i32.const 0) (global.get $counter))
+            (i32.store (i32.const 4) (ref.is_null (table.get $table (i32.const 0))))
+            (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+            (table.set $table (i32.const 0) (ref.null func))
+            (drop (call $write_response (i32.const 0) (i32.const 8)))))
+"#;
+
+#[test]
+fn test_invoke_with_instance_pool_resets_globals_and_tables() {
+    let wasm_module_bytes = wat::parse_str(STATEFUL_MODULE).unwrap();
+    let wasm_handler =
+        WasmHandler::create(&wasm_module_bytes, Vec::new(), TestingLogger::for_test())
+            .expect("couldn't create WasmHandler")
+            .with_instance_pool(1)
+            .expect("couldn't create instance pool");
+
+    for _ in 0..3 {
+        let response = wasm_handler
+            .handle_invoke(Request { body: Vec::new() })
+            .expect("couldn't handle invoke");
+        assert_eq!(StatusCode::Success, response.status);
+        assert_eq!(alloc::vec![0; 8], response.body);
+    }
+
+    let instance_pool = wasm_handler
+        .instance_pool
+        .expect("no instance pool in WasmHandler");
+    assert_eq!(1, instance_pool.instances.lock().len());
+}
+
+#[test]
+fn test_invoke_with_wasi() {
+    let wasm_handler = create_wasm_handler_with_limits(WasmLimits::default())
+        .with_wasi(true)
+        .with_instance_pool(1)
+        .expect("couldn't create instance pool");
+
+    let response = wasm_handler
+        .handle_invoke(Request {
+            body: b"hello".to_vec(),
+        })
+        .expect("couldn't handle invoke");
+    assert_eq!(StatusCode::Success, response.status);
+    assert_eq!(b"hello".to_vec(), response.body);
+}
+
+#[test]
+fn test_invoke_with_response_metadata() {
+    let wasm_handler =
+        create_wasm_handler_with_limits(WasmLimits::default()).with_response_metadata(true);
+
+    let response = wasm_handler
+        .handle_invoke(Request {
+            body: b"hello".to_vec(),
+        })
+        .expect("couldn't handle invoke");
+    assert_eq!(StatusCode::Success, response.status);
+    // The echo module doesn't set any metadata, so the defaults are prepended.
+    let (metadata, body) =
+        ResponseMetadata::decode_with_body(&response.body).expect("couldn't decode metadata");
+    assert_eq!(ResponseMetadata::default(), metadata);
+    assert_eq!(b"hello", body);
+}
+
+#[test]
+fn test_decode_response_metadata() {
+    let test_state = create_test_state();
+    let user_state = test_state.store.data();
+
+    let metadata = ResponseMetadata {
+        status: 404,
+        headers: alloc::vec![("content-type".to_string(), "text/plain".to_string())],
+    };
+    let decoded = decode_response_metadata(user_state, &Vec::from(&metadata));
+    assert_eq!(Ok(metadata), decoded);
+
+    let invalid_status = ResponseMetadata {
+        status: 1000,
+        headers: Vec::new(),
+    };
+    let decoded = decode_response_metadata(user_state, &Vec::from(&invalid_status));
+    assert_eq!(Err(OakStatus::ErrInvalidArgs), decoded);
+
+    let disallowed_header = ResponseMetadata {
+        status: 200,
+        headers: alloc::vec![("set-cookie".to_string(), "id=1".to_string())],
+    };
+    let decoded = decode_response_metadata(user_state, &Vec::from(&disallowed_header));
+    assert_eq!(Err(OakStatus::ErrInvalidArgs), decoded);
+
+    let header_injection = ResponseMetadata {
+        status: 200,
+        headers: alloc::vec![("etag".to_string(), "1\r\nset-cookie: id=1".to_string())],
+    };
+    let decoded = decode_response_metadata(user_state, &Vec::from(&header_injection));
+    assert_eq!(Err(OakStatus::ErrInvalidArgs), decoded);
+
+    assert_eq!(
+        Err(OakStatus::ErrInvalidArgs),
+        decode_response_metadata(user_state, b"\xc8")
+    );
+}
+
+#[test]
+fn test_wasi_fd_write() {
+    let logger = TestingLogger::for_test();
+    let mut wasi = WasiState::default();
+    let mut memory = alloc::vec![0; 64];
+    // Two iovecs at address 0, pointing to "hello " at address 32 and "world\n" at address 40.
+    LittleEndian::write_u32_into(&[32, 6, 40, 6], &mut memory[..16]);
+    memory[32..38].copy_from_slice(b"hello ");
+    memory[40..46].copy_from_slice(b"world\n");
+
+    assert_eq!(0, wasi.fd_write(&mut memory, &logger, 1, 0, 2, 16));
+    assert_eq!(12, LittleEndian::read_u32(&memory[16..20]));
+    // Only stdout and stderr can be written to.
+    assert_eq!(8, wasi.fd_write(&mut memory, &logger, 3, 0, 2, 16));
+    // The second iovec points beyond the memory.
+    LittleEndian::write_u32(&mut memory[8..12], 60);
+    assert_eq!(21, wasi.fd_write(&mut memory, &logger, 2, 0, 2, 16));
+}
+
+#[test]
+fn test_wasi_is_deterministic() {
+    let logger = TestingLogger::for_test();
+    let mut memory = alloc::vec![0; 64];
+
+    let mut wasi = WasiState::default();
+    assert_eq!(0, wasi.clock_time_get(&mut memory, &logger, 0, 0, 0));
+    assert_eq!(0, wasi.clock_time_get(&mut memory, &logger, 1, 0, 8));
+    assert_eq!(1_000, LittleEndian::read_u64(&memory[0..8]));
+    assert_eq!(2_000, LittleEndian::read_u64(&memory[8..16]));
+    assert_eq!(28, wasi.clock_time_get(&mut memory, &logger, 4, 0, 0));
+
+    assert_eq!(0, wasi.random_get(&mut memory, &logger, 16, 20));
+    let first = memory[16..36].to_vec();
+    assert_eq!(0, wasi.random_get(&mut memory, &logger, 16, 20));
+    assert_ne!(first, memory[16..36]);
+    // Every invocation starts with a fresh state, and gets the same values.
+    assert_eq!(
+        0,
+        WasiState::default().random_get(&mut memory, &logger, 16, 20)
+    );
+    assert_eq!(first, memory[16..36]);
+    assert_eq!(21, wasi.random_get(&mut memory, &logger, 60, 8));
+
+    memory[40..48].fill(0xff);
+    assert_eq!(0, wasi.args_sizes_get(&mut memory, &logger, 40, 44));
+    assert_eq!([0; 8], memory[40..48]);
+    assert_eq!(8, wasi.fd_prestat_get(&mut memory, &logger, 3, 0));
+}
+
+#[cfg(feature = "wasmtime")]
+#[test]
+fn test_invoke_with_wasmtime() {
+    let logger = TestingLogger::for_test();
+    let wasm_module_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_module_bytes = std::fs::read(wasm_module_path).unwrap();
+    let wasm_handler = crate::WasmtimeHandler::create_with_limits(
+        &wasm_module_bytes[..],
+        Vec::new(),
+        logger,
+        WasmLimits::default(),
+    )
+    .expect("couldn't create WasmtimeHandler");
+
+    let response = wasm_handler
+        .handle_invoke(Request {
+            body: b"hello".to_vec(),
+        })
+        .expect("couldn't handle invoke");
+    assert_eq!(StatusCode::Success, response.status);
+    assert_eq!(b"hello".to_vec(), response.body);
+}
+
+#[cfg(feature = "wasmtime")]
+#[test]
+fn test_invoke_growing_own_memory_beyond_limit_with_wasmtime() {
+    let wasm_module_bytes = wat::parse_str(GROWING_MODULE).unwrap();
+    let max_memory_pages = 5;
+    let wasm_handler = crate::WasmtimeHandler::create_with_limits(
+        &wasm_module_bytes,
+        Vec::new(),
+        TestingLogger::for_test(),
+        WasmLimits {
+            max_memory_pages: Some(max_memory_pages),
+            ..Default::default()
+        },
+    )
+    .expect("couldn't create WasmtimeHandler");
+    let err = wasm_handler
+        .handle_invoke(Request { body: Vec::new() })
+        .unwrap_err();
+    assert_eq!(
+        Some(&MemoryLimitExceeded { max_memory_pages }),
+        err.downcast_ref::<MemoryLimitExceeded>()
+    );
+}
+
+fn create_wasm_handler_with_limits(limits: WasmLimits) -> WasmHandler<TestingLogger> {
+    let logger = TestingLogger::for_test();
+    let wasm_module_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_module_bytes = std::fs::read(wasm_module_path).unwrap();
+    WasmHandler::create_with_limits(&wasm_module_bytes[..], Vec::new(), logger, limits)
+        .expect("couldn't create WasmHandler")
+}
+
 struct TestState {
     instance: wasmi::Instance,
     store: wasmi::Store<UserState<TestingLogger>>,
@@ -223,7 +632,7 @@ fn create_test_state() -> TestState {
 
     let module = wasm_handler.wasm_module;
     let mut store = wasmi::Store::new(module.engine(), user_state);
-    let linker = OakLinker::new(module.engine(), &mut store);
+    let linker = OakLinker::new(module.engine(), &mut store, wasm_handler.memory_type, false);
     let (instance, store) = linker
         .instantiate(store, module)
         .expect("couldn't instantiate Wasm module");
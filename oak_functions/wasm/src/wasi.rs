@@ -0,0 +1,391 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! An opt-in subset of WASI preview 1, so that modules compiled for `wasm32-wasi` (e.g. by Rust or
+//! TinyGo) can be instantiated.
+//! <https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md>
+//!
+//! The shim gives modules no access to anything outside of their invocation, and behaves the same
+//! in every invocation:
+//!
+//! - there are no arguments, environment variables, files or preopened directories
+//! - all clocks are virtual: they start at zero and advance by one microsecond whenever they are
+//!   read
+//! - `random_get` returns a fixed pseudo-random sequence, which must not be used for cryptography
+//! - stdin is empty, and lines written to stdout and stderr are logged as sensitive logs
+//! - `proc_exit` aborts the invocation
+//!
+//! The functions operate on the linear memory of the module, so that both backends share them.
+
+use alloc::{format, string::String, vec::Vec};
+use oak_logger::{Level, OakLogger};
+
+/// The name of the module from which WASI functions are imported.
+pub(crate) const MODULE: &str = "wasi_snapshot_preview1";
+
+// Error numbers of WASI preview 1.
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+pub(crate) const ERRNO_FAULT: i32 = 21;
+const ERRNO_INVAL: i32 = 28;
+const ERRNO_SPIPE: i32 = 70;
+
+const STDIN: i32 = 0;
+const STDOUT: i32 = 1;
+const STDERR: i32 = 2;
+
+/// The realtime, monotonic, process CPU time and thread CPU time clocks.
+const CLOCK_COUNT: i32 = 4;
+const CLOCK_TICK_NANOS: u64 = 1_000;
+
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const RIGHT_FD_READ: u64 = 1 << 1;
+const RIGHT_FD_WRITE: u64 = 1 << 6;
+
+/// Invokes `$define!($linker, <function>, (<parameters>))` for every function of the shim except
+/// `proc_exit`, which doesn't return to the module and is defined by each backend directly.
+macro_rules! for_each_wasi_function {
+    ($define:ident, $linker:ident) => {
+        $define!($linker, args_get, (argv: i32, argv_buf: i32));
+        $define!($linker, args_sizes_get, (argc: i32, argv_buf_size: i32));
+        $define!($linker, environ_get, (environ: i32, environ_buf: i32));
+        $define!($linker, environ_sizes_get, (count: i32, buf_size: i32));
+        $define!($linker, clock_res_get, (id: i32, resolution: i32));
+        $define!(
+            $linker,
+            clock_time_get,
+            (id: i32, precision: i64, time: i32)
+        );
+        $define!($linker, fd_close, (fd: i32));
+        $define!($linker, fd_fdstat_get, (fd: i32, stat: i32));
+        $define!($linker, fd_prestat_get, (fd: i32, prestat: i32));
+        $define!(
+            $linker,
+            fd_prestat_dir_name,
+            (fd: i32, path: i32, path_len: i32)
+        );
+        $define!(
+            $linker,
+            fd_read,
+            (fd: i32, iovs: i32, iovs_len: i32, nread: i32)
+        );
+        $define!(
+            $linker,
+            fd_seek,
+            (fd: i32, offset: i64, whence: i32, newoffset: i32)
+        );
+        $define!(
+            $linker,
+            fd_write,
+            (fd: i32, iovs: i32, iovs_len: i32, nwritten: i32)
+        );
+        $define!($linker, random_get, (buf: i32, buf_len: i32));
+        $define!($linker, sched_yield, ());
+    };
+}
+
+pub(crate) use for_each_wasi_function;
+
+/// The state of the shim during a single invocation. All functions return a WASI error number,
+/// and take the linear memory of the module and the logger of the invocation.
+#[derive(Debug, Default)]
+pub(crate) struct WasiState {
+    // Virtual time in nanoseconds, shared by all clocks.
+    clock_nanos: u64,
+    // State of the SplitMix64 generator behind `random_get`.
+    random_state: u64,
+    // Output written to stdout and stderr that doesn't end with a newline yet.
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl WasiState {
+    pub(crate) fn args_get(
+        &mut self,
+        _memory: &mut [u8],
+        _logger: &impl OakLogger,
+        _argv: i32,
+        _argv_buf: i32,
+    ) -> i32 {
+        ERRNO_SUCCESS
+    }
+
+    pub(crate) fn args_sizes_get(
+        &mut self,
+        memory: &mut [u8],
+        _logger: &impl OakLogger,
+        argc: i32,
+        argv_buf_size: i32,
+    ) -> i32 {
+        write_zero_sizes(memory, argc, argv_buf_size)
+    }
+
+    pub(crate) fn environ_get(
+        &mut self,
+        _memory: &mut [u8],
+        _logger: &impl OakLogger,
+        _environ: i32,
+        _environ_buf: i32,
+    ) -> i32 {
+        ERRNO_SUCCESS
+    }
+
+    pub(crate) fn environ_sizes_get(
+        &mut self,
+        memory: &mut [u8],
+        _logger: &impl OakLogger,
+        count: i32,
+        buf_size: i32,
+    ) -> i32 {
+        write_zero_sizes(memory, count, buf_size)
+    }
+
+    pub(crate) fn clock_res_get(
+        &mut self,
+        memory: &mut [u8],
+        _logger: &impl OakLogger,
+        id: i32,
+        resolution: i32,
+    ) -> i32 {
+        if !(0..CLOCK_COUNT).contains(&id) {
+            return ERRNO_INVAL;
+        }
+        write_bytes(memory, resolution, &CLOCK_TICK_NANOS.to_le_bytes())
+    }
+
+    pub(crate) fn clock_time_get(
+        &mut self,
+        memory: &mut [u8],
+        _logger: &impl OakLogger,
+        id: i32,
+        _precision: i64,
+        time: i32,
+    ) -> i32 {
+        if !(0..CLOCK_COUNT).contains(&id) {
+            return ERRNO_INVAL;
+        }
+        self.clock_nanos += CLOCK_TICK_NANOS;
+        write_bytes(memory, time, &self.clock_nanos.to_le_bytes())
+    }
+
+    pub(crate) fn fd_close(
+        &mut self,
+        _memory: &mut [u8],
+        _logger: &impl OakLogger,
+        fd: i32,
+    ) -> i32 {
+        match fd {
+            STDIN | STDOUT | STDERR => ERRNO_SUCCESS,
+            _ => ERRNO_BADF,
+        }
+    }
+
+    pub(crate) fn fd_fdstat_get(
+        &mut self,
+        memory: &mut [u8],
+        _logger: &impl OakLogger,
+        fd: i32,
+        stat: i32,
+    ) -> i32 {
+        let rights = match fd {
+            STDIN => RIGHT_FD_READ,
+            STDOUT | STDERR => RIGHT_FD_WRITE,
+            _ => return ERRNO_BADF,
+        };
+        // The file type, the flags at offset 2, the base rights at offset 8 and the inheriting
+        // rights at offset 16.
+        let mut fdstat = [0; 24];
+        fdstat[0] = FILETYPE_CHARACTER_DEVICE;
+        fdstat[8..16].copy_from_slice(&rights.to_le_bytes());
+        write_bytes(memory, stat, &fdstat)
+    }
+
+    pub(crate) fn fd_prestat_get(
+        &mut self,
+        _memory: &mut [u8],
+        _logger: &impl OakLogger,
+        _fd: i32,
+        _prestat: i32,
+    ) -> i32 {
+        // There are no preopened directories.
+        ERRNO_BADF
+    }
+
+    pub(crate) fn fd_prestat_dir_name(
+        &mut self,
+        _memory: &mut [u8],
+        _logger: &impl OakLogger,
+        _fd: i32,
+        _path: i32,
+        _path_len: i32,
+    ) -> i32 {
+        ERRNO_BADF
+    }
+
+    pub(crate) fn fd_read(
+        &mut self,
+        memory: &mut [u8],
+        _logger: &impl OakLogger,
+        fd: i32,
+        _iovs: i32,
+        _iovs_len: i32,
+        nread: i32,
+    ) -> i32 {
+        // Stdin is always at its end.
+        match fd {
+            STDIN => write_bytes(memory, nread, &0u32.to_le_bytes()),
+            _ => ERRNO_BADF,
+        }
+    }
+
+    pub(crate) fn fd_seek(
+        &mut self,
+        _memory: &mut [u8],
+        _logger: &impl OakLogger,
+        fd: i32,
+        _offset: i64,
+        _whence: i32,
+        _newoffset: i32,
+    ) -> i32 {
+        match fd {
+            STDIN | STDOUT | STDERR => ERRNO_SPIPE,
+            _ => ERRNO_BADF,
+        }
+    }
+
+    pub(crate) fn fd_write(
+        &mut self,
+        memory: &mut [u8],
+        logger: &impl OakLogger,
+        fd: i32,
+        iovs: i32,
+        iovs_len: i32,
+        nwritten: i32,
+    ) -> i32 {
+        let output = match fd {
+            STDOUT => &mut self.stdout,
+            STDERR => &mut self.stderr,
+            _ => return ERRNO_BADF,
+        };
+        let mut written: u32 = 0;
+        for index in 0..iovs_len {
+            // Every iovec consists of a pointer to a buffer and its length.
+            let iov = iovs.wrapping_add(index.wrapping_mul(8));
+            let buffer = read_u32(memory, iov)
+                .zip(read_u32(memory, iov.wrapping_add(4)))
+                .and_then(|(buf, buf_len)| slice(memory, buf as i32, buf_len as i32));
+            match buffer {
+                Some(buffer) => {
+                    output.extend_from_slice(buffer);
+                    written = written.wrapping_add(buffer.len() as u32);
+                }
+                None => return ERRNO_FAULT,
+            }
+        }
+        log_lines(output, fd, logger);
+        write_bytes(memory, nwritten, &written.to_le_bytes())
+    }
+
+    pub(crate) fn random_get(
+        &mut self,
+        memory: &mut [u8],
+        _logger: &impl OakLogger,
+        buf: i32,
+        buf_len: i32,
+    ) -> i32 {
+        let buffer = match slice_mut(memory, buf, buf_len) {
+            Some(buffer) => buffer,
+            None => return ERRNO_FAULT,
+        };
+        for chunk in buffer.chunks_mut(8) {
+            // SplitMix64, see <https://prng.di.unimi.it/splitmix64.c>.
+            self.random_state = self.random_state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut value = self.random_state;
+            value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+            value ^= value >> 31;
+            chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+        }
+        ERRNO_SUCCESS
+    }
+
+    pub(crate) fn sched_yield(&mut self, _memory: &mut [u8], _logger: &impl OakLogger) -> i32 {
+        ERRNO_SUCCESS
+    }
+
+    /// Logs the output that doesn't end with a newline yet, at the end of an invocation.
+    pub(crate) fn flush(&mut self, logger: &impl OakLogger) {
+        for (fd, output) in [(STDOUT, &mut self.stdout), (STDERR, &mut self.stderr)] {
+            if !output.is_empty() {
+                output.push(b'\n');
+                log_lines(output, fd, logger);
+            }
+        }
+    }
+}
+
+// Logs and removes every complete line of output that the module wrote to `fd`.
+fn log_lines(output: &mut Vec<u8>, fd: i32, logger: &impl OakLogger) {
+    let (stream, level) = match fd {
+        STDERR => ("stderr", Level::Warn),
+        _ => ("stdout", Level::Info),
+    };
+    while let Some(end) = output.iter().position(|byte| *byte == b'\n') {
+        let line: Vec<u8> = output.drain(..=end).collect();
+        logger.log_sensitive(
+            level,
+            &format!(
+                "Wasm module {}: {}",
+                stream,
+                String::from_utf8_lossy(&line[..end])
+            ),
+        );
+    }
+}
+
+// Writes zero to the count and the buffer size of an empty list of strings.
+fn write_zero_sizes(memory: &mut [u8], count: i32, buf_size: i32) -> i32 {
+    match write_bytes(memory, count, &0u32.to_le_bytes()) {
+        ERRNO_SUCCESS => write_bytes(memory, buf_size, &0u32.to_le_bytes()),
+        errno => errno,
+    }
+}
+
+// Returns the `len` bytes of memory at `ptr`, which are interpreted as unsigned integers.
+fn slice(memory: &[u8], ptr: i32, len: i32) -> Option<&[u8]> {
+    let start = ptr as u32 as usize;
+    memory.get(start..start.checked_add(len as u32 as usize)?)
+}
+
+fn slice_mut(memory: &mut [u8], ptr: i32, len: i32) -> Option<&mut [u8]> {
+    let start = ptr as u32 as usize;
+    memory.get_mut(start..start.checked_add(len as u32 as usize)?)
+}
+
+fn read_u32(memory: &[u8], ptr: i32) -> Option<u32> {
+    let bytes = slice(memory, ptr, 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn write_bytes(memory: &mut [u8], ptr: i32, bytes: &[u8]) -> i32 {
+    match slice_mut(memory, ptr, bytes.len() as i32) {
+        Some(destination) => {
+            destination.copy_from_slice(bytes);
+            ERRNO_SUCCESS
+        }
+        None => ERRNO_FAULT,
+    }
+}
@@ -0,0 +1,540 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Wasm business logic provider based on [Wasmtime](https://github.com/bytecodealliance/wasmtime),
+//! which compiles the Wasm module to native code. Only available with the `wasmtime` feature, for
+//! environments that allow JIT compilation.
+
+use crate::{
+    create_extensions, decode_response_metadata, memory_limit::limit_memory, wasi,
+    AbiExtensionHandle, AbiPointer, AbiPointerOffset, FuelExhausted, MemoryLimitExceeded,
+    UserState, WasmLimits, ALLOC_FUNCTION_NAME, MAIN_FUNCTION_NAME, MEMORY_NAME, OAK_FUNCTIONS,
+};
+use alloc::{boxed::Box, format, sync::Arc, vec::Vec};
+use byteorder::{ByteOrder, LittleEndian};
+use oak_functions_abi::{proto::OakStatus, Request, Response, StatusCode};
+use oak_functions_extension::ExtensionFactory;
+use oak_logger::{Level, OakLogger};
+use wasmtime::{Caller, Engine, Linker, Memory, MemoryType, Module, Store, Trap, TypedFunc};
+
+/// Maximum number of pages of a 32-bit Wasm memory.
+const MAX_MEMORY_PAGES: u32 = 65536;
+
+/// Stubs a Wasm imported function in the provided linker, like `stub_wasm_function` does for the
+/// Wasmi backend.
+macro_rules! stub_wasmtime_function {
+    ($linker:ident, $function_mod:ident . $function_name:ident, ($($t:ty),*) -> $r:ty) => {
+        $linker.func_wrap(
+            stringify!($function_mod),
+            stringify!($function_name),
+            |caller: Caller<'_, UserState<L>>, $(_: $t),*| -> anyhow::Result<$r> {
+                caller
+                    .data()
+                    .log_error(concat!("called stubbed ", stringify!($function_mod), ".", stringify!($function_name)));
+                Err(anyhow::anyhow!(
+                    concat!("called stubbed ", stringify!($function_mod), ".", stringify!($function_name))))
+            },
+        )
+        .expect(concat!("failed to define ", stringify!($function_mod), ".", stringify!($function_name), " in linker"));
+    };
+}
+
+/// Defines a function of the WASI shim in the provided linker, like `define_wasi_function` does
+/// for the Wasmi backend.
+macro_rules! define_wasmtime_wasi_function {
+    ($linker:ident, $function_name:ident, ($($arg:ident: $t:ty),*)) => {
+        $linker.func_wrap(
+            wasi::MODULE,
+            stringify!($function_name),
+            |mut caller: Caller<'_, UserState<L>>, $($arg: $t),*| -> anyhow::Result<i32> {
+                let memory = caller
+                    .get_export(MEMORY_NAME)
+                    .and_then(|export| export.into_memory());
+                let memory = match memory {
+                    Some(memory) => memory,
+                    None => return Ok(wasi::ERRNO_FAULT),
+                };
+                let (memory, user_state) = memory.data_and_store_mut(&mut caller);
+                Ok(user_state.wasi.$function_name(memory, &user_state.logger, $($arg),*))
+            },
+        )?;
+    };
+}
+
+/// Exports the functions from the ABI of Oak Functions to a Wasm module executed by Wasmtime. See
+/// `OakLinker` for the Wasmi counterpart, which this has to be kept consistent with.
+fn new_linker<L: OakLogger + 'static>(
+    engine: &Engine,
+    store: &mut Store<UserState<L>>,
+    memory_type: MemoryType,
+    wasi: bool,
+) -> anyhow::Result<Linker<UserState<L>>> {
+    let mut linker: Linker<UserState<L>> = Linker::new(engine);
+
+    // Add memory to linker.
+    let memory = Memory::new(&mut *store, memory_type)?;
+    linker.define(&*store, OAK_FUNCTIONS, MEMORY_NAME, memory)?;
+
+    linker.func_wrap(
+        OAK_FUNCTIONS,
+        // Corresponds to the OAK_FUNCTIONS ABI function [`read_request`](https://github.com/project-oak/oak/blob/main/docs/oak_functions_abi.md#read_request).
+        "read_request",
+        |caller: Caller<'_, UserState<L>>, buf_ptr_ptr: AbiPointer, buf_len_ptr: AbiPointer| {
+            let mut caller = match WasmtimeCaller::new(caller) {
+                Ok(caller) => caller,
+                Err(oak_status) => return oak_status as i32,
+            };
+
+            let request_bytes = caller.data().request_bytes.clone();
+            let status = caller.alloc_and_write(buf_ptr_ptr, buf_len_ptr, request_bytes);
+            from_oak_status(status)
+        },
+    )?;
+
+    linker.func_wrap(
+        OAK_FUNCTIONS,
+        // Corresponds to the OAK_FUNCTIONS ABI function [`write_response`](https://github.com/project-oak/oak/blob/main/docs/oak_functions_abi.md#write_response).
+        "write_response",
+        |caller: Caller<'_, UserState<L>>, buf_ptr: AbiPointer, buf_len: AbiPointerOffset| {
+            let mut caller = match WasmtimeCaller::new(caller) {
+                Ok(caller) => caller,
+                Err(oak_status) => return oak_status as i32,
+            };
+
+            let status = caller.read_buffer(buf_ptr, buf_len).map(|buffer| {
+                caller.data_mut().response_bytes = buffer;
+            });
+            from_oak_status(status)
+        },
+    )?;
+
+    linker.func_wrap(
+        OAK_FUNCTIONS,
+        "write_response_metadata",
+        |caller: Caller<'_, UserState<L>>, buf_ptr: AbiPointer, buf_len: AbiPointerOffset| {
+            let mut caller = match WasmtimeCaller::new(caller) {
+                Ok(caller) => caller,
+                Err(oak_status) => return oak_status as i32,
+            };
+
+            let status = caller.read_buffer(buf_ptr, buf_len).and_then(|buffer| {
+                let user_state = caller.data_mut();
+                let metadata = decode_response_metadata(user_state, &buffer)?;
+                user_state.response_metadata = metadata;
+                Ok(())
+            });
+            from_oak_status(status)
+        },
+    )?;
+
+    linker.func_wrap(
+        OAK_FUNCTIONS,
+        // Corresponds to the OAK_FUNCTIONS ABI function [`invoke`](https://github.com/project-oak/oak/blob/main/docs/oak_functions_abi.md#invoke).
+        "invoke",
+        |caller: Caller<'_, UserState<L>>,
+         handle: AbiExtensionHandle,
+         request_ptr: AbiPointer,
+         request_len: AbiPointerOffset,
+         response_ptr_ptr: AbiPointer,
+         response_len_ptr: AbiPointer| {
+            let mut caller = match WasmtimeCaller::new(caller) {
+                Ok(caller) => caller,
+                Err(oak_status) => return oak_status as i32,
+            };
+
+            let status = caller
+                .read_buffer(request_ptr, request_len)
+                .map_err(|err| {
+                    caller.data().log_error(&format!(
+                        "Handle {:?}: Unable to read input from guest memory: {:?}",
+                        handle, err
+                    ));
+                    err
+                })
+                .and_then(|request| {
+                    let user_state = caller.data_mut();
+                    let extension = user_state.get_extension(handle)?;
+                    let response = extension.invoke(request)?;
+                    caller.alloc_and_write(response_ptr_ptr, response_len_ptr, response)
+                });
+            from_oak_status(status)
+        },
+    )?;
+
+    if wasi {
+        wasi::for_each_wasi_function!(define_wasmtime_wasi_function, linker);
+        linker.func_wrap(
+            wasi::MODULE,
+            "proc_exit",
+            |caller: Caller<'_, UserState<L>>, code: i32| -> anyhow::Result<()> {
+                let message = format!("Wasm module exited with code {}", code);
+                caller.data().log_error(&message);
+                Err(anyhow::Error::msg(message))
+            },
+        )?;
+        return Ok(linker);
+    }
+
+    // TODO(#3929): Remove these stubs together with the ones of the Wasmi backend.
+    stub_wasmtime_function!(
+        linker,
+        wasi_snapshot_preview1.clock_time_get,
+        (i32, i64, i32) -> i32
+    );
+    stub_wasmtime_function!(
+        linker,
+        wasi_snapshot_preview1.proc_exit,
+        (i32) -> ()
+    );
+    stub_wasmtime_function!(
+        linker,
+        wasi_snapshot_preview1.environ_sizes_get,
+        (i32, i32) -> i32
+    );
+    stub_wasmtime_function!(
+        linker,
+        wasi_snapshot_preview1.environ_get,
+        (i32, i32) -> i32
+    );
+    stub_wasmtime_function!(
+        linker,
+        wasi_snapshot_preview1.fd_close,
+        (i32) -> i32
+    );
+    stub_wasmtime_function!(
+        linker,
+        wasi_snapshot_preview1.fd_write,
+        (i32, i32, i32, i32) -> i32
+    );
+    stub_wasmtime_function!(
+        linker,
+        wasi_snapshot_preview1.fd_read,
+        (i32, i32, i32, i32) -> i32
+    );
+    stub_wasmtime_function!(
+        linker,
+        wasi_snapshot_preview1.fd_seek,
+        (i32, i64, i32, i32) -> i32
+    );
+
+    Ok(linker)
+}
+
+/// Provides functionality for reading from the Wasm memory, as well as allocating and writing to
+/// the Wasm memory, like `OakCaller` does for the Wasmi backend.
+struct WasmtimeCaller<'a, L: OakLogger> {
+    caller: Caller<'a, UserState<L>>,
+    alloc: TypedFunc<i32, AbiPointer>,
+    memory: Memory,
+}
+
+impl<'a, L> WasmtimeCaller<'a, L>
+where
+    L: OakLogger,
+{
+    fn new(mut caller: Caller<'a, UserState<L>>) -> Result<Self, OakStatus> {
+        let alloc = caller
+            .get_export(ALLOC_FUNCTION_NAME)
+            .and_then(|export| export.into_func())
+            .ok_or_else(|| {
+                caller
+                    .data()
+                    .log_error(&format!("failed to get exported {}", ALLOC_FUNCTION_NAME));
+                OakStatus::ErrInternal
+            })?;
+
+        let typed_alloc = alloc.typed(&caller).map_err(|_| {
+            caller.data().log_error(&format!(
+                "exported {} could not be typed",
+                ALLOC_FUNCTION_NAME
+            ));
+            OakStatus::ErrInternal
+        })?;
+
+        let memory = caller
+            .get_export(MEMORY_NAME)
+            .and_then(|export| export.into_memory())
+            .ok_or_else(|| {
+                caller
+                    .data()
+                    .log_error(&format!("failed to get exported {}", MEMORY_NAME));
+                OakStatus::ErrInternal
+            })?;
+
+        Ok(WasmtimeCaller {
+            caller,
+            alloc: typed_alloc,
+            memory,
+        })
+    }
+
+    /// Reads the buffer starting at address `buf_ptr` with length `buf_len` from the Wasm memory.
+    fn read_buffer(
+        &mut self,
+        buf_ptr: AbiPointer,
+        buf_len: AbiPointerOffset,
+    ) -> Result<Vec<u8>, OakStatus> {
+        let mut buf = alloc::vec![0; buf_len as usize];
+        self.memory
+            .read(&self.caller, buf_ptr as usize, &mut buf)
+            .map_err(|err| {
+                self.data().log_error(&format!(
+                    "Unable to read buffer from guest memory: {:?}",
+                    err
+                ));
+                OakStatus::ErrInvalidArgs
+            })?;
+        Ok(buf)
+    }
+
+    /// Writes the given `buffer` by allocating `buffer.len()` Wasm memory and writing the address
+    /// of the allocated memory to `dest_ptr_ptr` and the length to `dest_len_ptr`.
+    fn alloc_and_write(
+        &mut self,
+        buf_ptr_ptr: AbiPointer,
+        buf_ptr_len: AbiPointer,
+        buf: Vec<u8>,
+    ) -> Result<(), OakStatus> {
+        let len = buf.len() as i32;
+
+        // This fails if the module can't grow its memory any further.
+        let dest_ptr = self.alloc.call(&mut self.caller, len).map_err(|err| {
+            self.data()
+                .log_error(&format!("`alloc` call failed: {:?}", err));
+            OakStatus::ErrInternal
+        })?;
+
+        self.write_buffer(&buf, dest_ptr)?;
+        self.write_u32(dest_ptr, buf_ptr_ptr)?;
+        self.write_u32(len as u32, buf_ptr_len)?;
+        Ok(())
+    }
+
+    /// Writes the buffer `source` at the address `dest` of the Wasm memory.
+    fn write_buffer(&mut self, source: &[u8], dest: AbiPointer) -> Result<(), OakStatus> {
+        self.memory
+            .write(&mut self.caller, dest as usize, source)
+            .map_err(|err| {
+                self.data().log_error(&format!(
+                    "Unable to write buffer into guest memory: {:?}",
+                    err
+                ));
+                OakStatus::ErrInvalidArgs
+            })
+    }
+
+    /// Writes the u32 `value` at the `address` of the Wasm memory.
+    fn write_u32(&mut self, value: u32, address: AbiPointer) -> Result<(), OakStatus> {
+        let value_bytes = &mut [0; 4];
+        LittleEndian::write_u32(value_bytes, value);
+        self.write_buffer(value_bytes, address)
+    }
+
+    fn data_mut(&mut self) -> &mut UserState<L> {
+        self.caller.data_mut()
+    }
+
+    fn data(&self) -> &UserState<L> {
+        self.caller.data()
+    }
+}
+
+/// A request handler that executes the Wasm module with Wasmtime. It exposes the same ABI and
+/// enforces the same limits as [`crate::WasmHandler`], but does not pool instances.
+#[derive(Clone)]
+pub struct WasmtimeHandler<L: OakLogger> {
+    wasm_module: Module,
+    extension_factories: Arc<Vec<Box<dyn ExtensionFactory<L>>>>,
+    logger: L,
+    limits: WasmLimits,
+    memory_type: MemoryType,
+    wasi: bool,
+    response_metadata: bool,
+}
+
+impl<L> WasmtimeHandler<L>
+where
+    L: OakLogger + 'static,
+{
+    /// Compiles the Wasm module and creates a handler that enforces the given `limits` on every
+    /// invocation.
+    pub fn create_with_limits(
+        wasm_module_bytes: &[u8],
+        extension_factories: Vec<Box<dyn ExtensionFactory<L>>>,
+        logger: L,
+        limits: WasmLimits,
+    ) -> anyhow::Result<Self> {
+        if limits.max_memory_pages > Some(MAX_MEMORY_PAGES) {
+            anyhow::bail!(
+                "invalid Wasm memory limit: at most {} pages are supported",
+                MAX_MEMORY_PAGES
+            );
+        }
+        // Keep the initial memory consistent with the Wasmi backend.
+        let initial_pages = limits.max_memory_pages.map_or(100, |max| max.min(100));
+        let memory_type = MemoryType::new(initial_pages, limits.max_memory_pages);
+
+        let limited_module;
+        let wasm_module_bytes = match limits.max_memory_pages {
+            Some(max_memory_pages) => {
+                limited_module = limit_memory(wasm_module_bytes, max_memory_pages)?;
+                &limited_module[..]
+            }
+            None => wasm_module_bytes,
+        };
+
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(limits.max_fuel.is_some());
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wasm_module_bytes)
+            .map_err(|err| anyhow::anyhow!("couldn't load module from buffer: {:?}", err))?;
+
+        Ok(WasmtimeHandler {
+            wasm_module: module,
+            extension_factories: Arc::new(extension_factories),
+            logger,
+            limits,
+            memory_type,
+            wasi: false,
+            response_metadata: false,
+        })
+    }
+
+    /// Sets whether the deterministic WASI shim is provided to the module, like
+    /// [`crate::WasmHandler::with_wasi`].
+    pub fn with_wasi(mut self, enabled: bool) -> Self {
+        self.wasi = enabled;
+        self
+    }
+
+    /// Sets whether the response metadata is prepended to the body of the response, like
+    /// [`crate::WasmHandler::with_response_metadata`].
+    pub fn with_response_metadata(mut self, enabled: bool) -> Self {
+        self.response_metadata = enabled;
+        self
+    }
+
+    /// Handles a call to invoke by getting the raw request bytes from the body of the request to
+    /// invoke and returns a reponse to invoke setting the raw bytes in the body of the response.
+    pub fn handle_invoke(&self, invoke_request: Request) -> anyhow::Result<Response> {
+        let user_state = UserState::new(
+            invoke_request.body,
+            create_extensions(&self.extension_factories)?,
+            self.logger.clone(),
+        );
+        // For isolated requests we need to create a new store for every request.
+        let mut store = Store::new(self.wasm_module.engine(), user_state);
+        if let Some(max_fuel) = self.limits.max_fuel {
+            store.add_fuel(max_fuel)?;
+        }
+        let linker = new_linker(
+            self.wasm_module.engine(),
+            &mut store,
+            self.memory_type.clone(),
+            self.wasi,
+        )?;
+        let instance = linker
+            .instantiate(&mut store, &self.wasm_module)
+            .map_err(|err| anyhow::anyhow!("failed to instantiate Wasm module: {:?}", err))?;
+
+        // Check that the instance exports "alloc" and a memory.
+        instance
+            .get_typed_func::<i32, AbiPointer>(&mut store, ALLOC_FUNCTION_NAME)
+            .map_err(|err| anyhow::anyhow!("couldn't validate `alloc` export: {:?}", err))?;
+        let memory = instance
+            .get_memory(&mut store, MEMORY_NAME)
+            .ok_or(anyhow::anyhow!("couldn't find Wasm `memory` export"))?;
+
+        // Invokes the Wasm module by calling main.
+        let main = instance
+            .get_typed_func::<(), ()>(&mut store, MAIN_FUNCTION_NAME)
+            .map_err(|err| anyhow::anyhow!("couldn't validate `main` export: {:?}", err))?;
+        let result = main.call(&mut store, ());
+        let user_state = store.data_mut();
+        user_state.wasi.flush(&user_state.logger);
+        store.data().logger.log_sensitive(
+            Level::Info,
+            &format!("running Wasm module completed with result: {:?}", result),
+        );
+
+        // Terminate the extensions.
+        store
+            .data_mut()
+            .extensions
+            .values_mut()
+            .try_for_each(|e| e.terminate())?;
+
+        if let Err(err) = &result {
+            if err.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) {
+                store.data().logger.log_sensitive(
+                    Level::Warn,
+                    &format!(
+                        "Wasm invocation ran out of fuel (limit: {:?})",
+                        self.limits.max_fuel
+                    ),
+                );
+                return Ok(Response::create(
+                    StatusCode::PolicyTimeViolation,
+                    Vec::new(),
+                ));
+            }
+
+            // As with the Wasmi backend, a trap while the memory is at its maximum is attributed
+            // to the memory limit.
+            if let Some(max_memory_pages) = self.limits.max_memory_pages {
+                if memory.size(&store) >= max_memory_pages as u64 {
+                    store.data().logger.log_sensitive(
+                        Level::Warn,
+                        &format!("Wasm invocation exceeded {} memory pages", max_memory_pages),
+                    );
+                    return Err(anyhow::Error::msg(MemoryLimitExceeded { max_memory_pages }));
+                }
+            }
+        }
+
+        let user_state = store.data();
+        let body = if self.response_metadata {
+            user_state
+                .response_metadata
+                .encode_with_body(&user_state.response_bytes)
+        } else {
+            user_state.response_bytes.clone()
+        };
+        Ok(Response::create(StatusCode::Success, body))
+    }
+}
+
+impl<L: OakLogger + 'static> micro_rpc::Transport for WasmtimeHandler<L> {
+    type Error = anyhow::Error;
+    fn invoke(&mut self, request: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let request = Request {
+            body: request.to_vec(),
+        };
+        let response = self.handle_invoke(request)?;
+        if response.status == StatusCode::PolicyTimeViolation {
+            return Err(anyhow::Error::msg(FuelExhausted {
+                fuel_limit: self.limits.max_fuel.unwrap_or_default(),
+            }));
+        }
+        Ok(response.body)
+    }
+}
+
+/// Converts the result of a host function to the status code returned to the Wasm module.
+fn from_oak_status(result: Result<(), OakStatus>) -> i32 {
+    let oak_status = result.err().unwrap_or(OakStatus::Ok);
+    oak_status as i32
+}
@@ -0,0 +1,353 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Preparation of Wasm modules for Oak Functions: inspection and stripping of custom sections,
+//! signing, a check of the exports that the enclave calls, and a policy on the size, the custom
+//! sections and the signature of modules that the launcher accepts.
+//!
+//! Custom sections (e.g. debug names or producer information) are not needed to run a module, but
+//! they are part of the measured module and count towards its size.
+//! <https://webassembly.github.io/spec/core/binary/modules.html#sections>
+//!
+//! Modules are signed with ECDSA P-256 over SHA-256. The signature is either kept in a detached
+//! file, as Base64-encoded ASN.1 DER (the same format as for lookup data), or embedded as ASN.1 DER
+//! in the [`SIGNATURE_SECTION_NAME`] custom section, in which case it covers the module without
+//! that section.
+
+#[cfg(test)]
+mod tests;
+
+use anyhow::{anyhow, Context};
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use p256::ecdsa::{
+    signature::{Signer, Verifier},
+    Signature, SigningKey, VerifyingKey,
+};
+use std::{ops::Range, str::FromStr};
+
+/// The magic number and version 1 of the Wasm binary format.
+const PREAMBLE: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+const CUSTOM_SECTION_ID: u8 = 0;
+
+/// The name of the custom section that holds the embedded signature of a module.
+pub const SIGNATURE_SECTION_NAME: &str = "oak_signature";
+
+const EXPORT_SECTION_ID: u8 = 7;
+
+const FUNCTION_EXPORT_KIND: u8 = 0;
+
+/// The functions that the enclave calls, and that every module must therefore export.
+pub const REQUIRED_FUNCTION_EXPORTS: [&str; 2] = ["main", "alloc"];
+
+/// Names of the known sections, indexed by section id.
+const SECTION_NAMES: [&str; 13] = [
+    "custom",
+    "type",
+    "import",
+    "function",
+    "table",
+    "memory",
+    "global",
+    "export",
+    "start",
+    "element",
+    "code",
+    "data",
+    "datacount",
+];
+
+/// A section of a Wasm module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    /// The id of the section. Custom sections have id 0.
+    pub id: u8,
+    /// The name of a custom section, `None` for all other sections.
+    pub name: Option<String>,
+    /// The byte range of the whole section in the module, including its id and size.
+    pub range: Range<usize>,
+}
+
+impl Section {
+    pub fn is_custom(&self) -> bool {
+        self.id == CUSTOM_SECTION_ID
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.range.len()
+    }
+
+    /// Returns a human-readable description of the section, for reports.
+    pub fn description(&self) -> String {
+        match &self.name {
+            Some(name) => format!("custom ({name})"),
+            None => SECTION_NAMES
+                .get(self.id as usize)
+                .map_or_else(|| format!("unknown ({})", self.id), |name| name.to_string()),
+        }
+    }
+}
+
+/// Splits a Wasm module into its sections, without validating their contents.
+pub fn parse_sections(module: &[u8]) -> anyhow::Result<Vec<Section>> {
+    if !module.starts_with(&PREAMBLE) {
+        return Err(anyhow!("not a version 1 Wasm module"));
+    }
+    let mut sections = Vec::new();
+    let mut offset = PREAMBLE.len();
+    while offset < module.len() {
+        let start = offset;
+        let id = module[offset];
+        offset += 1;
+        let size = read_u32(module, &mut offset).context("couldn't read section size")? as usize;
+        let end = offset
+            .checked_add(size)
+            .filter(|end| *end <= module.len())
+            .ok_or_else(|| anyhow!("section at offset {start} exceeds the module"))?;
+        let name = if id == CUSTOM_SECTION_ID {
+            let name_length = read_u32(module, &mut offset)
+                .context("couldn't read custom section name")?
+                as usize;
+            let name = module
+                .get(offset..offset + name_length)
+                .filter(|_| offset + name_length <= end)
+                .ok_or_else(|| anyhow!("custom section name at offset {offset} is truncated"))?;
+            Some(
+                String::from_utf8(name.to_vec())
+                    .context("custom section name is not valid UTF-8")?,
+            )
+        } else {
+            None
+        };
+        sections.push(Section {
+            id,
+            name,
+            range: start..end,
+        });
+        offset = end;
+    }
+    Ok(sections)
+}
+
+/// Returns a copy of the module without any custom sections, except for the ones named in `keep`.
+pub fn strip_custom_sections(module: &[u8], keep: &[String]) -> anyhow::Result<Vec<u8>> {
+    let mut stripped = PREAMBLE.to_vec();
+    for section in parse_sections(module)? {
+        let keep_section = match &section.name {
+            Some(name) => keep.contains(name),
+            None => true,
+        };
+        if keep_section {
+            stripped.extend_from_slice(&module[section.range]);
+        }
+    }
+    Ok(stripped)
+}
+
+/// Returns a copy of the module with a signature by `signing_key` embedded in the
+/// [`SIGNATURE_SECTION_NAME`] custom section. An existing signature section is replaced.
+pub fn sign_module(module: &[u8], signing_key: &SigningKey) -> anyhow::Result<Vec<u8>> {
+    let (_, unsigned_module) = split_signature(module)?;
+    let signature: Signature = signing_key.sign(&unsigned_module);
+    let signature = signature.to_der();
+    let name = SIGNATURE_SECTION_NAME.as_bytes();
+
+    let mut content = encode_u32(name.len() as u32);
+    content.extend_from_slice(name);
+    content.extend_from_slice(signature.as_bytes());
+    let mut signed_module = unsigned_module;
+    signed_module.push(CUSTOM_SECTION_ID);
+    signed_module.extend(encode_u32(content.len() as u32));
+    signed_module.extend(content);
+    Ok(signed_module)
+}
+
+/// Parses a PEM-encoded x509/PKIX P-256 public key, against which module signatures are verified.
+/// Returns the names of the functions that the module exports.
+pub fn parse_function_exports(module: &[u8]) -> anyhow::Result<Vec<String>> {
+    let mut exports = Vec::new();
+    for section in parse_sections(module)? {
+        if section.id != EXPORT_SECTION_ID {
+            continue;
+        }
+        // The exports follow the section id and the section size.
+        let mut offset = section.range.start + 1;
+        read_u32(module, &mut offset)?;
+        let count = read_u32(module, &mut offset).context("couldn't read export count")?;
+        for _ in 0..count {
+            let name_length = read_u32(module, &mut offset)? as usize;
+            let name = module
+                .get(offset..offset + name_length)
+                .filter(|_| offset + name_length < section.range.end)
+                .ok_or_else(|| anyhow!("export name at offset {offset} is truncated"))?;
+            let name =
+                String::from_utf8(name.to_vec()).context("export name is not valid UTF-8")?;
+            offset += name_length;
+            let kind = module[offset];
+            offset += 1;
+            read_u32(module, &mut offset).context("couldn't read export index")?;
+            if kind == FUNCTION_EXPORT_KIND {
+                exports.push(name);
+            }
+        }
+    }
+    Ok(exports)
+}
+
+/// Returns `Ok(())` if the module exports all of [`REQUIRED_FUNCTION_EXPORTS`], otherwise returns
+/// `Err()` naming the first missing one. The signatures of the functions are only checked when
+/// the enclave instantiates the module.
+pub fn check_exports(module: &[u8]) -> anyhow::Result<()> {
+    let exports = parse_function_exports(module)?;
+    match REQUIRED_FUNCTION_EXPORTS
+        .iter()
+        .find(|name| !exports.iter().any(|export| export == *name))
+    {
+        Some(name) => Err(anyhow!("Wasm module doesn't export function {name}")),
+        None => Ok(()),
+    }
+}
+
+pub fn parse_public_key(pem_public_key: &str) -> anyhow::Result<VerifyingKey> {
+    VerifyingKey::from_str(pem_public_key).context("couldn't parse P-256 public key")
+}
+
+/// Restrictions on the Wasm modules that are accepted for loading.
+#[derive(Clone, Debug, Default)]
+pub struct ModulePolicy {
+    /// The maximum size of the module. Not restricted if `None`.
+    pub max_size_bytes: Option<usize>,
+    /// The names of the custom sections that the module may contain. Not restricted if `None`.
+    /// The signature section is always allowed.
+    pub allowed_custom_sections: Option<Vec<String>>,
+    /// The public key with which the module must be signed. Signatures are not checked if `None`.
+    pub trusted_public_key: Option<VerifyingKey>,
+}
+
+impl ModulePolicy {
+    /// Returns `Ok(())` if the module complies with the policy, otherwise returns `Err()`
+    /// describing the first violation.
+    ///
+    /// If the policy requires a signature, it is taken from `detached_signature` if set, and from
+    /// the signature section of the module otherwise.
+    pub fn check(&self, module: &[u8], detached_signature: Option<&[u8]>) -> anyhow::Result<()> {
+        if let Some(max_size_bytes) = self.max_size_bytes {
+            if module.len() > max_size_bytes {
+                return Err(anyhow!(
+                    "Wasm module is {} bytes, the maximum is {} bytes",
+                    module.len(),
+                    max_size_bytes
+                ));
+            }
+        }
+        if let Some(allowed_custom_sections) = &self.allowed_custom_sections {
+            for section in parse_sections(module)? {
+                if let Some(name) = section.name {
+                    if name != SIGNATURE_SECTION_NAME && !allowed_custom_sections.contains(&name) {
+                        return Err(anyhow!(
+                            "Wasm module contains unexpected custom section {name}"
+                        ));
+                    }
+                }
+            }
+        }
+        if let Some(trusted_public_key) = &self.trusted_public_key {
+            verify_signature(module, detached_signature, trusted_public_key)
+                .context("Wasm module signature verification failed")?;
+        }
+        Ok(())
+    }
+}
+
+fn verify_signature(
+    module: &[u8],
+    detached_signature: Option<&[u8]>,
+    public_key: &VerifyingKey,
+) -> anyhow::Result<()> {
+    let (signature, signed_module) = match detached_signature {
+        Some(detached_signature) => {
+            let signature = std::str::from_utf8(detached_signature)
+                .ok()
+                .and_then(|signature| BASE64_STANDARD.decode(signature.trim()).ok())
+                .context("detached signature is not valid Base64")?;
+            (signature, module.to_vec())
+        }
+        None => {
+            let (signature, unsigned_module) = split_signature(module)?;
+            let signature = signature.context("Wasm module is not signed")?;
+            (signature, unsigned_module)
+        }
+    };
+    let signature = Signature::from_der(&signature).context("invalid ASN.1 signature")?;
+    public_key
+        .verify(&signed_module, &signature)
+        .context("signature doesn't match the trusted public key")
+}
+
+/// Splits a module into the payload of its signature section, if any, and the module without it.
+fn split_signature(module: &[u8]) -> anyhow::Result<(Option<Vec<u8>>, Vec<u8>)> {
+    let mut signature = None;
+    let mut unsigned_module = PREAMBLE.to_vec();
+    for section in parse_sections(module)? {
+        if section.name.as_deref() != Some(SIGNATURE_SECTION_NAME) {
+            unsigned_module.extend_from_slice(&module[section.range]);
+            continue;
+        }
+        if signature.is_some() {
+            return Err(anyhow!(
+                "Wasm module contains more than one signature section"
+            ));
+        }
+        // The payload follows the section id, the section size and the name.
+        let mut offset = section.range.start + 1;
+        read_u32(module, &mut offset)?;
+        let name_length = read_u32(module, &mut offset)? as usize;
+        signature = Some(module[offset + name_length..section.range.end].to_vec());
+    }
+    Ok((signature, unsigned_module))
+}
+
+/// Encodes an unsigned 32-bit integer with LEB128.
+fn encode_u32(mut value: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Reads an unsigned LEB128-encoded 32-bit integer, and advances the offset past it.
+fn read_u32(bytes: &[u8], offset: &mut usize) -> anyhow::Result<u32> {
+    let mut result = 0u32;
+    // A 32-bit integer is encoded in at most 5 bytes.
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*offset).context("unexpected end of module")?;
+        *offset += 1;
+        result |= ((byte & 0x7f) as u32)
+            .checked_shl(shift)
+            .filter(|value| value >> shift == (byte & 0x7f) as u32)
+            .context("integer too large")?;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    Err(anyhow!("integer too large"))
+}
@@ -0,0 +1,87 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Strips the custom sections from a Wasm module, reports the size of its sections, and optionally
+//! signs it.
+
+use anyhow::Context;
+use clap::Parser;
+use oak_functions_wasm_prepare::{
+    parse_sections, sign_module, strip_custom_sections, ModulePolicy,
+};
+use p256::{ecdsa::SigningKey, pkcs8::DecodePrivateKey};
+use std::path::PathBuf;
+use ubyte::ByteUnit;
+
+#[derive(Parser, Clone)]
+#[command(about = "Oak Functions Wasm Module Preparation")]
+pub struct Opt {
+    #[arg(long, help = "Path to the Wasm module to prepare")]
+    input: PathBuf,
+
+    #[arg(long, help = "Path where the prepared module is written")]
+    output: PathBuf,
+
+    #[arg(long, help = "Name of a custom section to keep, may be repeated")]
+    keep_custom_section: Vec<String>,
+
+    #[arg(long, help = "Fail if the prepared module is larger than this")]
+    max_size_bytes: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Path to a PEM-encoded PKCS#8 P-256 private key, with which a signature is embedded \
+                in the prepared module"
+    )]
+    signing_key: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::parse();
+
+    let module = std::fs::read(&opt.input).context("couldn't read Wasm module")?;
+    for section in parse_sections(&module)? {
+        println!(
+            "{:>24}: {}",
+            section.description(),
+            ByteUnit::Byte(section.size_bytes() as u64)
+        );
+    }
+
+    let mut prepared = strip_custom_sections(&module, &opt.keep_custom_section)?;
+    if let Some(signing_key) = &opt.signing_key {
+        let signing_key =
+            std::fs::read_to_string(signing_key).context("couldn't read signing key")?;
+        let signing_key = SigningKey::from_pkcs8_pem(&signing_key)
+            .map_err(|err| anyhow::anyhow!("couldn't parse signing key: {:?}", err))?;
+        prepared = sign_module(&prepared, &signing_key).context("couldn't sign Wasm module")?;
+    }
+    println!(
+        "size: {} before, {} after preparation",
+        ByteUnit::Byte(module.len() as u64),
+        ByteUnit::Byte(prepared.len() as u64)
+    );
+
+    ModulePolicy {
+        max_size_bytes: opt.max_size_bytes,
+        allowed_custom_sections: Some(opt.keep_custom_section),
+        trusted_public_key: None,
+    }
+    .check(&prepared, None)?;
+
+    std::fs::write(&opt.output, prepared).context("couldn't write prepared Wasm module")?;
+    Ok(())
+}
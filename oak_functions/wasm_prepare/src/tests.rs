@@ -0,0 +1,203 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+const TEST_SIGNING_KEY: [u8; 32] = [7; 32];
+
+/// A module with an empty type section, a `name` and a `producers` custom section.
+fn test_module() -> Vec<u8> {
+    [
+        PREAMBLE.as_slice(),
+        // Type section with zero types.
+        &[1, 1, 0],
+        custom_section("name", b"names").as_slice(),
+        custom_section("producers", b"rustc").as_slice(),
+    ]
+    .concat()
+}
+
+fn custom_section(name: &str, payload: &[u8]) -> Vec<u8> {
+    let content_length = 1 + name.len() + payload.len();
+    assert!(content_length < 0x80 && name.len() < 0x80);
+    [
+        &[CUSTOM_SECTION_ID, content_length as u8, name.len() as u8],
+        name.as_bytes(),
+        payload,
+    ]
+    .concat()
+}
+
+#[test]
+fn test_parse_sections() {
+    let module = test_module();
+    let sections = parse_sections(&module).unwrap();
+    let descriptions: Vec<String> = sections.iter().map(Section::description).collect();
+    assert_eq!(
+        descriptions,
+        vec!["type", "custom (name)", "custom (producers)"]
+    );
+    assert_eq!(
+        sections.iter().map(Section::size_bytes).sum::<usize>(),
+        module.len() - PREAMBLE.len()
+    );
+}
+
+#[test]
+fn test_parse_sections_rejects_truncated_module() {
+    let module = test_module();
+    assert!(parse_sections(&module[..module.len() - 1]).is_err());
+    assert!(parse_sections(&module[..4]).is_err());
+}
+
+#[test]
+fn test_read_u32() {
+    let mut offset = 0;
+    assert_eq!(read_u32(&[0xe5, 0x8e, 0x26], &mut offset).unwrap(), 624485);
+    assert_eq!(offset, 3);
+    assert!(read_u32(&[0xff, 0xff, 0xff, 0xff, 0x7f], &mut 0).is_err());
+    assert!(read_u32(&[0x80], &mut 0).is_err());
+}
+
+#[test]
+fn test_strip_custom_sections() {
+    let module = test_module();
+    let stripped = strip_custom_sections(&module, &["producers".to_string()]).unwrap();
+    let names: Vec<Option<String>> = parse_sections(&stripped)
+        .unwrap()
+        .into_iter()
+        .map(|section| section.name)
+        .collect();
+    assert_eq!(names, vec![None, Some("producers".to_string())]);
+
+    let stripped = strip_custom_sections(&module, &[]).unwrap();
+    assert_eq!(stripped, [PREAMBLE.as_slice(), &[1, 1, 0]].concat());
+}
+
+#[test]
+fn test_module_policy() {
+    let module = test_module();
+    assert!(ModulePolicy::default().check(&module, None).is_ok());
+
+    let size_policy = ModulePolicy {
+        max_size_bytes: Some(module.len() - 1),
+        ..Default::default()
+    };
+    assert!(size_policy.check(&module, None).is_err());
+
+    let section_policy = ModulePolicy {
+        allowed_custom_sections: Some(vec!["name".to_string()]),
+        ..Default::default()
+    };
+    assert!(section_policy.check(&module, None).is_err());
+
+    let section_policy = ModulePolicy {
+        allowed_custom_sections: Some(vec!["name".to_string(), "producers".to_string()]),
+        ..Default::default()
+    };
+    assert!(section_policy.check(&module, None).is_ok());
+}
+
+#[test]
+fn test_check_exports() {
+    // Export section with the functions `main` and `alloc`, and the memory `memory`.
+    let exports: &[u8] = &[
+        7, 25, 3, 4, b'm', b'a', b'i', b'n', 0, 0, 5, b'a', b'l', b'l', b'o', b'c', 0, 1, 6, b'm',
+        b'e', b'm', b'o', b'r', b'y', 2, 0,
+    ];
+    let module = [test_module().as_slice(), exports].concat();
+    assert_eq!(
+        parse_function_exports(&module).unwrap(),
+        vec!["main", "alloc"]
+    );
+    assert!(check_exports(&module).is_ok());
+    assert!(check_exports(&test_module()).is_err());
+
+    // The same section, but with `alloc` exported as a global.
+    let mut module = module;
+    let kind_offset = module.len() - 11;
+    module[kind_offset] = 3;
+    assert_eq!(parse_function_exports(&module).unwrap(), vec!["main"]);
+    assert!(check_exports(&module).is_err());
+}
+
+#[test]
+fn test_encode_u32() {
+    for value in [0, 1, 127, 128, 624485, u32::MAX] {
+        let encoded = encode_u32(value);
+        let mut offset = 0;
+        assert_eq!(read_u32(&encoded, &mut offset).unwrap(), value);
+        assert_eq!(offset, encoded.len());
+    }
+}
+
+#[test]
+fn test_embedded_signature() {
+    let signing_key = SigningKey::from_bytes(&TEST_SIGNING_KEY).unwrap();
+    let module = test_module();
+    let signed_module = sign_module(&module, &signing_key).unwrap();
+    assert_eq!(split_signature(&signed_module).unwrap().1, module);
+    // Signing again replaces the signature.
+    let resigned_module = sign_module(&signed_module, &signing_key).unwrap();
+    assert_eq!(split_signature(&resigned_module).unwrap().1, module);
+
+    let policy = ModulePolicy {
+        allowed_custom_sections: Some(vec!["name".to_string(), "producers".to_string()]),
+        trusted_public_key: Some(signing_key.verifying_key()),
+        ..Default::default()
+    };
+    assert!(policy.check(&signed_module, None).is_ok());
+    assert!(policy.check(&resigned_module, None).is_ok());
+    assert!(policy.check(&module, None).is_err());
+
+    let mut tampered_module = signed_module.clone();
+    // Changes the payload of the `name` section.
+    tampered_module[PREAMBLE.len() + 3 + 7] ^= 1;
+    assert!(policy.check(&tampered_module, None).is_err());
+
+    let other_key = SigningKey::from_bytes(&[8; 32]).unwrap();
+    let other_policy = ModulePolicy {
+        trusted_public_key: Some(other_key.verifying_key()),
+        ..Default::default()
+    };
+    assert!(other_policy.check(&signed_module, None).is_err());
+}
+
+#[test]
+fn test_detached_signature() {
+    let signing_key = SigningKey::from_bytes(&TEST_SIGNING_KEY).unwrap();
+    let module = test_module();
+    let signature: Signature = signing_key.sign(&module);
+    let detached_signature = format!(
+        "{}\n",
+        BASE64_STANDARD.encode(signature.to_der().as_bytes())
+    );
+
+    let policy = ModulePolicy {
+        trusted_public_key: Some(signing_key.verifying_key()),
+        ..Default::default()
+    };
+    assert!(policy
+        .check(&module, Some(detached_signature.as_bytes()))
+        .is_ok());
+    assert!(policy
+        .check(
+            &module[..module.len() - 1],
+            Some(detached_signature.as_bytes())
+        )
+        .is_err());
+    assert!(policy.check(&module, Some(b"not base64")).is_err());
+}
@@ -22,7 +22,7 @@
 extern crate alloc;
 
 pub use crate::proto::ExtensionHandle;
-use alloc::{string::String, vec::Vec};
+use alloc::{string::String, vec, vec::Vec};
 use core::mem::size_of;
 use serde::{Deserialize, Serialize};
 
@@ -155,6 +155,109 @@ impl Response {
     }
 }
 
+/// Response headers that a Wasm module may set. Other headers are rejected, since they could
+/// interfere with the transport of the response.
+pub const ALLOWED_RESPONSE_HEADERS: [&str; 5] = [
+    "cache-control",
+    "content-language",
+    "content-type",
+    "etag",
+    "last-modified",
+];
+
+/// HTTP status code and headers that a Wasm module attaches to its response. Modules that don't set
+/// them respond with `200 OK` and no headers.
+///
+/// See REQUEST_RESPONSE_ENCODING.MD in the crate root.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResponseMetadata {
+    pub status: u16,
+    /// Header names and values, in the order in which they were set. Names are lowercase and must
+    /// be contained in [`ALLOWED_RESPONSE_HEADERS`].
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for ResponseMetadata {
+    fn default() -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+        }
+    }
+}
+
+impl ResponseMetadata {
+    /// Checks that the status code is in the range of HTTP status codes, and that only allowed
+    /// headers with values that can't break out of their header line are set.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(100..=599).contains(&self.status) {
+            anyhow::bail!("invalid status code {}", self.status)
+        }
+        for (name, value) in self.headers.iter() {
+            if !ALLOWED_RESPONSE_HEADERS.contains(&name.as_str()) {
+                anyhow::bail!("header {} is not allowed", name)
+            }
+            if value.chars().any(char::is_control) {
+                anyhow::bail!("value of header {} contains control characters", name)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the encoding of the metadata, followed by the given response body.
+    pub fn encode_with_body(&self, body: &[u8]) -> Vec<u8> {
+        let mut headers = Vec::new();
+        for (name, value) in self.headers.iter() {
+            put_length_prefixed(&mut headers, name.as_bytes());
+            put_length_prefixed(&mut headers, value.as_bytes());
+        }
+        let mut result = Vec::with_capacity(2 + size_of::<u64>() + headers.len() + body.len());
+        result.extend_from_slice(&self.status.to_le_bytes());
+        put_length_prefixed(&mut result, &headers);
+        result.extend_from_slice(body);
+        result
+    }
+
+    /// Splits the encoding created by [`ResponseMetadata::encode_with_body`] into the metadata and
+    /// the response body.
+    pub fn decode_with_body(bytes: &[u8]) -> anyhow::Result<(Self, &[u8])> {
+        if bytes.len() < 2 {
+            anyhow::bail!("invalid buffer: missing status code")
+        }
+        let (status, mut buffer) = bytes.split_at(2);
+        let status = u16::from_le_bytes([status[0], status[1]]);
+        let headers_bytes = take_length_prefixed(&mut buffer)?;
+        let mut headers_buffer = headers_bytes.as_slice();
+        let mut headers = Vec::new();
+        while !headers_buffer.is_empty() {
+            let name = take_length_prefixed(&mut headers_buffer)?;
+            let value = take_length_prefixed(&mut headers_buffer)?;
+            match (String::from_utf8(name), String::from_utf8(value)) {
+                (Ok(name), Ok(value)) => headers.push((name, value)),
+                _ => anyhow::bail!("invalid buffer: header is not valid UTF-8"),
+            }
+        }
+        Ok((Self { status, headers }, buffer))
+    }
+}
+
+impl From<&ResponseMetadata> for Vec<u8> {
+    fn from(metadata: &ResponseMetadata) -> Self {
+        metadata.encode_with_body(&[])
+    }
+}
+
+impl TryFrom<&[u8]> for ResponseMetadata {
+    type Error = anyhow::Error;
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        let (metadata, rest) = ResponseMetadata::decode_with_body(buffer)?;
+        if !rest.is_empty() {
+            anyhow::bail!("invalid buffer: {} trailing bytes", rest.len())
+        }
+        Ok(metadata)
+    }
+}
+
 /// Holds the optional value from the storage.
 pub struct StorageGetItemResponse {
     pub value: Option<Vec<u8>>,
@@ -209,6 +312,140 @@ impl TryFrom<&[u8]> for StorageGetItemResponse {
     }
 }
 
+/// Requests the entries of the lookup data whose keys are at least `start` and, if set, less than
+/// `end`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageGetRangeRequest {
+    pub start: Vec<u8>,
+    pub end: Option<Vec<u8>>,
+}
+
+impl StorageGetRangeRequest {
+    /// Creates a request for the entries whose keys start with `prefix`.
+    pub fn prefix(prefix: &[u8]) -> Self {
+        // The end of the range is the shortest key that is larger than all keys with the prefix. It
+        // doesn't exist if the prefix consists of `0xff` bytes only.
+        let mut end = prefix.to_vec();
+        while let Some(last) = end.pop() {
+            if last < u8::MAX {
+                end.push(last + 1);
+                return StorageGetRangeRequest {
+                    start: prefix.to_vec(),
+                    end: Some(end),
+                };
+            }
+        }
+        StorageGetRangeRequest {
+            start: prefix.to_vec(),
+            end: None,
+        }
+    }
+}
+
+impl From<StorageGetRangeRequest> for Vec<u8> {
+    fn from(request: StorageGetRangeRequest) -> Self {
+        // Temporary manual serialisation to avoid `bincode` when using `no_std`.
+        // TODO(#2975): Replace this with a microRPC implemenation when the ABI is converted.
+        let mut result = Vec::new();
+        put_length_prefixed(&mut result, &request.start);
+        if let Some(end) = request.end {
+            put_length_prefixed(&mut result, &end);
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for StorageGetRangeRequest {
+    type Error = anyhow::Error;
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        // The encoding consists of the length-prefixed start key, followed by the length-prefixed
+        // end key if it is set.
+        let mut buffer = buffer;
+        let start = take_length_prefixed(&mut buffer)?;
+        let end = if buffer.is_empty() {
+            None
+        } else {
+            Some(take_length_prefixed(&mut buffer)?)
+        };
+        if !buffer.is_empty() {
+            anyhow::bail!("invalid buffer: {} trailing bytes", buffer.len())
+        }
+        Ok(StorageGetRangeRequest { start, end })
+    }
+}
+
+/// Holds the entries of a range lookup, ordered by key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageGetRangeResponse {
+    pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Whether further entries in the range were left out, because the runtime limits the number
+    /// of entries per range lookup.
+    pub truncated: bool,
+}
+
+impl From<StorageGetRangeResponse> for Vec<u8> {
+    fn from(response: StorageGetRangeResponse) -> Self {
+        // Temporary manual serialisation to avoid `bincode` when using `no_std`.
+        // TODO(#2975): Replace this with a microRPC implemenation when the ABI is converted.
+        let mut result = vec![response.truncated as u8];
+        for (key, value) in response.entries {
+            put_length_prefixed(&mut result, &key);
+            put_length_prefixed(&mut result, &value);
+        }
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for StorageGetRangeResponse {
+    type Error = anyhow::Error;
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        // The encoding consists of a byte for the `truncated` flag, followed by the length-prefixed
+        // key and value of every entry.
+        let (truncated, mut buffer) = match buffer.split_first() {
+            Some((0, buffer)) => (false, buffer),
+            Some((1, buffer)) => (true, buffer),
+            _ => anyhow::bail!("invalid buffer: missing or invalid truncated flag"),
+        };
+        let mut entries = Vec::new();
+        while !buffer.is_empty() {
+            let key = take_length_prefixed(&mut buffer)?;
+            let value = take_length_prefixed(&mut buffer)?;
+            entries.push((key, value));
+        }
+        Ok(StorageGetRangeResponse { entries, truncated })
+    }
+}
+
+// Appends `bytes` to `buffer`, prefixed with their length as a little-endian u64.
+fn put_length_prefixed(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+// Removes bytes that are prefixed with their length as a little-endian u64 from the front of
+// `buffer`, and returns them.
+fn take_length_prefixed(buffer: &mut &[u8]) -> anyhow::Result<Vec<u8>> {
+    const LENGTH_SIZE: usize = size_of::<u64>();
+    if buffer.len() < LENGTH_SIZE {
+        anyhow::bail!("invalid buffer: buffer too small")
+    }
+    let mut len_buffer = [0; LENGTH_SIZE];
+    len_buffer.copy_from_slice(&buffer[..LENGTH_SIZE]);
+    let len = u64::from_le_bytes(len_buffer);
+
+    let rest = &buffer[LENGTH_SIZE..];
+    if (rest.len() as u64) < len {
+        anyhow::bail!(
+            "invalid buffer: expected {} bytes, but found {}",
+            len,
+            rest.len()
+        )
+    }
+    let (bytes, rest) = rest.split_at(len as usize);
+    *buffer = rest;
+    Ok(bytes.to_vec())
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum TestingRequest {
     Echo(String),
@@ -232,6 +469,10 @@ extern "C" {
     /// See [`write_response`](https://github.com/project-oak/oak/blob/main/docs/oak_functions_abi.md#write_response).
     pub fn write_response(buf_ptr: *const u8, buf_len: usize) -> u32;
 
+    /// Sets the status code and headers of the response to the encoding of a [`ResponseMetadata`]
+    /// in the given buffer.
+    pub fn write_response_metadata(buf_ptr: *const u8, buf_len: usize) -> u32;
+
     /// See [`invoke`](https://github.com/project-oak/oak/blob/main/docs/oak_functions_abi.md#invoke).
     pub fn invoke(
         handle: ExtensionHandle,
@@ -0,0 +1,146 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+#![feature(iter_intersperse)]
+
+//! This crate allows compiling the protobuf description of an Oak Functions application to typed
+//! Rust code in `build.rs` scripts, both for the Wasm module and for its clients, so that both
+//! sides encode requests and responses in the same way.
+
+use prost_build::Service;
+use std::path::Path;
+
+/// The side of an Oak Functions application to generate code for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// The Wasm module, built with `oak_functions_sdk`.
+    Module,
+    /// A client of the application, built with `oak_functions_client`.
+    Client,
+}
+
+/// Compile Rust code for the given target from the services in the provided protobuf file.
+///
+/// Each service describes an Oak Functions application, and must have exactly one method, whose
+/// input and output types are the request and response of the application. In addition to the
+/// messages, `compile` generates the following objects for a service called `TestName` with a
+/// method `Handle(TestRequest) returns (TestResponse)`:
+///
+/// - for [`Target::Module`], implementations of `oak_functions_sdk::handler::FromRequest` for
+///   `TestRequest` and of `oak_functions_sdk::handler::IntoResponse` for `TestResponse`, so that a
+///   function annotated with `#[oak_functions_sdk::main]` can take and return them directly.
+/// - for [`Target::Client`], a struct named `TestNameClient`, which wraps an
+///   `oak_functions_client::OakFunctionsClient` and exposes an async method `handle`, which takes a
+///   `TestRequest` and returns a `TestResponse`.
+///
+/// The generated code refers to `prost`, so the crate using it must depend on `prost`. Client code
+/// also refers to `anyhow`.
+pub fn compile(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>], target: Target) {
+    protos.iter().for_each(|filename| {
+        println!(
+            "cargo:rerun-if-changed={}",
+            filename.as_ref().as_os_str().to_string_lossy()
+        )
+    });
+    let mut config = prost_build::Config::new();
+    config.service_generator(Box::new(ServiceGenerator { target }));
+    config
+        .compile_protos(protos, includes)
+        .expect("couldn't compile protobuffer schema");
+}
+
+struct ServiceGenerator {
+    target: Target,
+}
+
+impl prost_build::ServiceGenerator for ServiceGenerator {
+    fn generate(&mut self, service: Service, buf: &mut String) {
+        let code = match self.target {
+            Target::Module => generate_module(&service),
+            Target::Client => generate_client(&service),
+        };
+        *buf += "\n";
+        *buf += &code.expect("couldn't generate Oak Functions application code");
+    }
+}
+
+/// Generate the conversions between raw bytes and the request and response types, which are used
+/// by the `main` function generated by `#[oak_functions_sdk::main]`.
+fn generate_module(service: &Service) -> anyhow::Result<String> {
+    let (request_type, response_type) = request_and_response_types(service)?;
+    let lines = vec![
+        format!("impl ::oak_functions_sdk::handler::FromRequest for {request_type} {{"),
+        format!("    fn from_request(body: ::prost::alloc::vec::Vec<u8>) -> Result<Self, ::prost::alloc::string::String> {{"),
+        format!("        <Self as ::prost::Message>::decode(body.as_slice())"),
+        format!("            .map_err(|err| ::prost::alloc::format!(\"couldn't decode {request_type}: {{:?}}\", err))"),
+        format!("    }}"),
+        format!("}}"),
+        format!(""),
+        format!("impl ::oak_functions_sdk::handler::IntoResponse for {response_type} {{"),
+        format!("    fn into_response(self) -> Result<::prost::alloc::vec::Vec<u8>, ::prost::alloc::string::String> {{"),
+        format!("        Ok(::prost::Message::encode_to_vec(&self))"),
+        format!("    }}"),
+        format!("}}"),
+        format!(""),
+    ];
+    Ok(lines.into_iter().intersperse("\n".to_string()).collect())
+}
+
+/// Generate the typed client struct, which wraps an `OakFunctionsClient`.
+fn generate_client(service: &Service) -> anyhow::Result<String> {
+    let (request_type, response_type) = request_and_response_types(service)?;
+    let client_name = format!("{}Client", service.name);
+    let method_name = &service.methods[0].name;
+    let lines = vec![
+        format!("pub struct {client_name} {{"),
+        format!("    inner: ::oak_functions_client::OakFunctionsClient,"),
+        format!("}}"),
+        format!(""),
+        format!("impl {client_name} {{"),
+        format!("    pub fn new(inner: ::oak_functions_client::OakFunctionsClient) -> Self {{"),
+        format!("        Self {{ inner }}"),
+        format!("    }}"),
+        format!(""),
+        format!("    pub async fn {method_name}(&mut self, request: &{request_type}) -> Result<{response_type}, ::oak_functions_client::Error> {{"),
+        format!("        let response_bytes = self.inner.invoke(&::prost::Message::encode_to_vec(request)).await?;"),
+        format!("        <{response_type} as ::prost::Message>::decode(response_bytes.as_slice()).map_err(|err| {{"),
+        format!("            ::oak_functions_client::Error::Invocation(::anyhow::anyhow!(\"couldn't decode {response_type}: {{:?}}\", err))"),
+        format!("        }})"),
+        format!("    }}"),
+        format!("}}"),
+        format!(""),
+    ];
+    Ok(lines.into_iter().intersperse("\n".to_string()).collect())
+}
+
+/// Returns the request and response types of the single method of the service.
+fn request_and_response_types(service: &Service) -> anyhow::Result<(String, String)> {
+    if service.methods.len() != 1 {
+        anyhow::bail!(
+            "service {} must have exactly one method, found {}",
+            service.name,
+            service.methods.len()
+        );
+    }
+    let method = &service.methods[0];
+    if method.client_streaming || method.server_streaming {
+        anyhow::bail!("method {} must not be streaming", method.proto_name);
+    }
+    Ok((
+        method.input_type.to_string(),
+        method.output_type.to_string(),
+    ))
+}
@@ -0,0 +1,85 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Blocking variant of the Oak Functions client, for callers that don't run inside an async
+//! runtime, such as threaded services and FFI layers.
+//!
+//! Each client owns a single-threaded Tokio runtime that drives the underlying async client. The
+//! methods must not be called from within an async runtime, since blocking its worker threads
+//! would stall other tasks.
+
+use crate::{AttestedServer, Error, ReferenceValues};
+use anyhow::Context;
+use oak_client::ResumptionTicket;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+pub struct OakFunctionsClient {
+    runtime: Runtime,
+    inner: crate::OakFunctionsClient,
+}
+
+impl OakFunctionsClient {
+    /// See [`crate::OakFunctionsClient::new`].
+    pub fn new(uri: &str) -> Result<Self, Error> {
+        let runtime = new_runtime()?;
+        let inner = runtime.block_on(crate::OakFunctionsClient::new(uri))?;
+        Ok(Self { runtime, inner })
+    }
+
+    /// See [`crate::OakFunctionsClient::new_with_reference_values`].
+    pub fn new_with_reference_values(
+        uri: &str,
+        reference_values: &ReferenceValues,
+    ) -> Result<Self, Error> {
+        let runtime = new_runtime()?;
+        let inner = runtime.block_on(crate::OakFunctionsClient::new_with_reference_values(
+            uri,
+            reference_values,
+        ))?;
+        Ok(Self { runtime, inner })
+    }
+
+    /// See [`crate::OakFunctionsClient::resume`].
+    pub fn resume(uri: &str, ticket: &ResumptionTicket) -> Result<Self, Error> {
+        let runtime = new_runtime()?;
+        let inner = runtime.block_on(crate::OakFunctionsClient::resume(uri, ticket))?;
+        Ok(Self { runtime, inner })
+    }
+
+    /// See [`crate::OakFunctionsClient::resumption_ticket`].
+    pub fn resumption_ticket(&self, lifetime: Duration) -> ResumptionTicket {
+        self.inner.resumption_ticket(lifetime)
+    }
+
+    /// See [`crate::OakFunctionsClient::attested_server`].
+    pub fn attested_server(&self) -> Result<Option<AttestedServer>, Error> {
+        self.inner.attested_server()
+    }
+
+    /// See [`crate::OakFunctionsClient::invoke`].
+    pub fn invoke(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.runtime.block_on(self.inner.invoke(request))
+    }
+}
+
+fn new_runtime() -> Result<Runtime, Error> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("couldn't create Tokio runtime")
+        .map_err(Error::Connection)
+}
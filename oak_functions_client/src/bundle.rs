@@ -0,0 +1,180 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! This module provides a self-contained bundle of verification collateral, which allows verifying
+//! the endorsement of a server binary without any network access.
+
+use crate::rekor::verify_rekor_log_entry;
+use anyhow::Context;
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+
+/// All the collateral that is needed to verify an endorsement of a server binary offline.
+///
+/// Binary values are stored as Base64-encoded strings, so that the bundle can be serialized as a
+/// single JSON document.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct VerificationBundle {
+    /// Base64-encoded endorsement statement about the server binary.
+    pub endorsement: String,
+
+    /// Base64-encoded Rekor LogEntry that proves the inclusion of the endorsement in the
+    /// transparency log, as downloaded from Rekor.
+    #[serde(rename = "rekorLogEntry")]
+    pub rekor_log_entry: String,
+
+    /// PEM-encoded public key of the Rekor instance that created the LogEntry.
+    #[serde(rename = "rekorPublicKey")]
+    pub rekor_public_key: String,
+
+    /// PEM-encoded public key of the product team that signed the endorsement.
+    #[serde(rename = "endorserPublicKey")]
+    pub endorser_public_key: String,
+
+    /// Base64-encoded DER certificates of the TEE certificate chain, ordered from the leaf to the
+    /// root.
+    #[serde(default, rename = "teeCertificates")]
+    pub tee_certificates: Vec<String>,
+
+    /// Base64-encoded DER certificate revocation lists for the TEE certificate chain.
+    #[serde(default, rename = "certificateRevocationLists")]
+    pub certificate_revocation_lists: Vec<String>,
+}
+
+impl VerificationBundle {
+    /// Assembles a bundle from the raw contents of the individual collateral files.
+    pub fn assemble(
+        endorsement_bytes: &[u8],
+        log_entry_bytes: &[u8],
+        rekor_public_key_bytes: &[u8],
+        endorser_public_key_bytes: &[u8],
+        tee_certificates: &[Vec<u8>],
+        certificate_revocation_lists: &[Vec<u8>],
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            endorsement: BASE64_STANDARD.encode(endorsement_bytes),
+            rekor_log_entry: BASE64_STANDARD.encode(log_entry_bytes),
+            rekor_public_key: String::from_utf8(rekor_public_key_bytes.to_vec())
+                .context("Rekor's public key is not PEM-encoded")?,
+            endorser_public_key: String::from_utf8(endorser_public_key_bytes.to_vec())
+                .context("endorser's public key is not PEM-encoded")?,
+            tee_certificates: tee_certificates
+                .iter()
+                .map(|certificate| BASE64_STANDARD.encode(certificate))
+                .collect(),
+            certificate_revocation_lists: certificate_revocation_lists
+                .iter()
+                .map(|crl| BASE64_STANDARD.encode(crl))
+                .collect(),
+        })
+    }
+
+    /// Parses a bundle from its JSON representation.
+    pub fn from_json(bytes: &[u8]) -> anyhow::Result<Self> {
+        serde_json::from_slice(bytes).context("couldn't parse verification bundle")
+    }
+
+    /// Serializes the bundle into its JSON representation.
+    pub fn to_json(&self) -> anyhow::Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).context("couldn't serialize verification bundle")
+    }
+
+    /// Verifies the endorsement in this bundle without accessing the network.
+    ///
+    /// Returns `Ok(())` if the verification succeeds, otherwise returns `Err()`.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        let endorsement_bytes = BASE64_STANDARD
+            .decode(&self.endorsement)
+            .context("couldn't decode Base64 endorsement")?;
+        let log_entry_bytes = BASE64_STANDARD
+            .decode(&self.rekor_log_entry)
+            .context("couldn't decode Base64 Rekor LogEntry")?;
+        verify_rekor_log_entry(
+            &log_entry_bytes,
+            self.rekor_public_key.as_bytes(),
+            self.endorser_public_key.as_bytes(),
+            &endorsement_bytes,
+        )?;
+
+        // Make sure that the TEE collateral is well-formed, even though neither the certificate
+        // chain nor the revocation lists are verified yet.
+        // TODO(#3641): Verify the TEE certificate chain against the CRLs once the AMD SEV-SNP
+        // verifier is implemented.
+        self.tee_certificates()?;
+        self.certificate_revocation_lists()?;
+
+        Ok(())
+    }
+
+    /// Returns the SHA-256 digest of the server binary that the endorsement is about, i.e. the
+    /// digest of the only subject of the in-toto statement.
+    ///
+    /// The digest must be compared against the attested measurement of the server, since verifying
+    /// the bundle only shows that the endorsement is genuine, not that the server runs the endorsed
+    /// binary.
+    pub fn endorsed_digest(&self) -> anyhow::Result<Vec<u8>> {
+        let endorsement_bytes = BASE64_STANDARD
+            .decode(&self.endorsement)
+            .context("couldn't decode Base64 endorsement")?;
+        let statement: Statement = serde_json::from_slice(&endorsement_bytes)
+            .context("couldn't parse endorsement statement")?;
+        let [subject] = statement.subject.as_slice() else {
+            anyhow::bail!(
+                "expected a single subject in the endorsement, found {}",
+                statement.subject.len()
+            );
+        };
+        hex::decode(&subject.digest.sha256).context("couldn't decode endorsed SHA-256 digest")
+    }
+
+    /// Returns the decoded DER certificates of the TEE certificate chain.
+    pub fn tee_certificates(&self) -> anyhow::Result<Vec<Vec<u8>>> {
+        decode_all(&self.tee_certificates).context("couldn't decode TEE certificates")
+    }
+
+    /// Returns the decoded DER certificate revocation lists.
+    pub fn certificate_revocation_lists(&self) -> anyhow::Result<Vec<Vec<u8>>> {
+        decode_all(&self.certificate_revocation_lists)
+            .context("couldn't decode certificate revocation lists")
+    }
+}
+
+// The parts of an in-toto statement that identify the endorsed binary.
+#[derive(Deserialize)]
+struct Statement {
+    subject: Vec<Subject>,
+}
+
+#[derive(Deserialize)]
+struct Subject {
+    digest: Digest,
+}
+
+#[derive(Deserialize)]
+struct Digest {
+    sha256: String,
+}
+
+fn decode_all(values: &[String]) -> anyhow::Result<Vec<Vec<u8>>> {
+    values
+        .iter()
+        .map(|value| {
+            BASE64_STANDARD
+                .decode(value)
+                .context("couldn't decode Base64 value")
+        })
+        .collect()
+}
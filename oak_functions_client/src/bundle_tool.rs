@@ -0,0 +1,85 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Assembles all verification collateral into a single bundle that can be consumed by the Oak
+//! Functions client without network access.
+
+use anyhow::Context;
+use clap::Parser;
+use oak_functions_client::bundle::VerificationBundle;
+use std::{fs, path::PathBuf};
+
+#[derive(Parser, Clone)]
+#[command(about = "Oak Functions Verification Bundle Tool")]
+pub struct Opt {
+    #[arg(
+        long,
+        help = "Path to the endorsement statement about the server binary"
+    )]
+    endorsement: PathBuf,
+
+    #[arg(long, help = "Path to the Rekor LogEntry of the endorsement")]
+    rekor_log_entry: PathBuf,
+
+    #[arg(long, help = "Path to the PEM-encoded public key of Rekor")]
+    rekor_public_key: PathBuf,
+
+    #[arg(long, help = "Path to the PEM-encoded public key of the endorser")]
+    endorser_public_key: PathBuf,
+
+    #[arg(
+        long,
+        help = "Paths to the DER-encoded TEE certificates, from the leaf to the root"
+    )]
+    tee_certificate: Vec<PathBuf>,
+
+    #[arg(long, help = "Paths to the DER-encoded certificate revocation lists")]
+    crl: Vec<PathBuf>,
+
+    #[arg(long, help = "Path where the resulting bundle is written")]
+    output: PathBuf,
+}
+
+fn read_all(paths: &[PathBuf]) -> anyhow::Result<Vec<Vec<u8>>> {
+    paths
+        .iter()
+        .map(|path| fs::read(path).with_context(|| format!("couldn't read {}", path.display())))
+        .collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let opt = Opt::parse();
+
+    let bundle = VerificationBundle::assemble(
+        &fs::read(&opt.endorsement).context("couldn't read endorsement file")?,
+        &fs::read(&opt.rekor_log_entry).context("couldn't read Rekor LogEntry file")?,
+        &fs::read(&opt.rekor_public_key).context("couldn't read Rekor's public key file")?,
+        &fs::read(&opt.endorser_public_key).context("couldn't read endorser's public key file")?,
+        &read_all(&opt.tee_certificate)?,
+        &read_all(&opt.crl)?,
+    )?;
+
+    // Refuse to produce a bundle that would not pass verification on the offline side.
+    bundle
+        .verify()
+        .context("couldn't verify assembled bundle")?;
+
+    fs::write(&opt.output, bundle.to_json()?).context("couldn't write verification bundle")?;
+    log::info!("wrote verification bundle to {}", opt.output.display());
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use core::fmt;
+
+/// Errors returned by the Oak Functions client, classified by the stage of the protocol in which
+/// they happened.
+#[derive(Debug)]
+pub enum Error {
+    /// The client couldn't connect to the server.
+    Connection(anyhow::Error),
+    /// The server's attestation evidence couldn't be obtained, or it didn't pass verification.
+    Attestation(anyhow::Error),
+    /// The request couldn't be encrypted, sent or handled, or the response couldn't be decrypted.
+    Invocation(anyhow::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Connection(err) => write!(f, "connection error: {:?}", err),
+            Error::Attestation(err) => write!(f, "attestation error: {:?}", err),
+            Error::Invocation(err) => write!(f, "invocation error: {:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -13,13 +13,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Client library for Oak Functions.
+//!
+//! The client verifies the attestation evidence of the server, encrypts requests to the attested
+//! public key, sends them over gRPC, and decrypts the responses. A blocking variant of the client
+//! is available in the [`blocking`] module.
+
+pub mod blocking;
+pub mod bundle;
+mod error;
 pub mod rekor;
+pub mod verifier;
 
+pub use crate::{
+    error::Error,
+    verifier::{AttestedServer, ReferenceValues},
+};
 use anyhow::Context;
 use oak_client::{
     proto::oak::session::v1::streaming_session_client::StreamingSessionClient,
-    transport::GrpcStreamingTransport, OakClient,
+    transport::GrpcStreamingTransport, OakClient, ResumptionTicket,
 };
+use std::time::Duration;
 use tonic::transport::Channel;
 
 #[cfg(test)]
@@ -30,23 +45,84 @@ pub struct OakFunctionsClient {
 }
 
 impl OakFunctionsClient {
-    pub async fn new(uri: &str) -> anyhow::Result<Self> {
-        let channel = Channel::from_shared(uri.to_string())
-            .context("couldn't create gRPC channel")?
-            .connect()
-            .await
-            .context("couldn't connect via gRPC channel")?;
-        let transport = GrpcStreamingTransport::new(StreamingSessionClient::new(channel));
+    /// Connects to the server at `uri` without checking its configuration against reference
+    /// values.
+    pub async fn new(uri: &str) -> Result<Self, Error> {
+        let transport = connect(uri).await.map_err(Error::Connection)?;
         let oak_client = OakClient::create(transport)
             .await
-            .context("couldn't create Oak client")?;
+            .context("couldn't create Oak client")
+            .map_err(Error::Attestation)?;
+        Ok(Self { oak_client })
+    }
+
+    /// Connects to the server at `uri`, and checks that the configuration in its attestation
+    /// evidence matches `reference_values`.
+    pub async fn new_with_reference_values(
+        uri: &str,
+        reference_values: &ReferenceValues,
+    ) -> Result<Self, Error> {
+        let transport = connect(uri).await.map_err(Error::Connection)?;
+        let oak_client = OakClient::create_with_verifier(transport, reference_values)
+            .await
+            .context("couldn't create Oak client")
+            .map_err(Error::Attestation)?;
+        Ok(Self { oak_client })
+    }
+
+    /// Reconnects to the server and resumes a previously attested session using `ticket`.
+    pub async fn resume(uri: &str, ticket: &ResumptionTicket) -> Result<Self, Error> {
+        let transport = connect(uri).await.map_err(Error::Connection)?;
+        let oak_client = OakClient::resume(transport, ticket)
+            .context("couldn't resume Oak client")
+            .map_err(Error::Attestation)?;
         Ok(Self { oak_client })
     }
 
-    pub async fn invoke(&mut self, request: &[u8]) -> anyhow::Result<Vec<u8>> {
+    /// Creates a ticket for resuming the current session, which is valid for `lifetime`.
+    pub fn resumption_ticket(&self, lifetime: Duration) -> ResumptionTicket {
+        self.oak_client.resumption_ticket(lifetime)
+    }
+
+    /// Returns the identity of the server that was attested when the session was created, or
+    /// `None` if the session was resumed.
+    pub fn attested_server(&self) -> Result<Option<AttestedServer>, Error> {
+        self.oak_client
+            .attestation_evidence()
+            .map(AttestedServer::from_evidence)
+            .transpose()
+            .map_err(Error::Attestation)
+    }
+
+    pub async fn invoke(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
         self.oak_client
             .invoke(request)
             .await
             .context("error invoking Oak Functions instance")
+            .map_err(Error::Invocation)
     }
 }
+
+/// Requests fresh attestation evidence from the server at `uri`, checks it against
+/// `reference_values`, and returns the attested identity of the server, without invoking it.
+pub async fn attest(
+    uri: &str,
+    reference_values: &ReferenceValues,
+) -> Result<AttestedServer, Error> {
+    OakFunctionsClient::new_with_reference_values(uri, reference_values)
+        .await?
+        .attested_server()?
+        .context("no attestation evidence")
+        .map_err(Error::Attestation)
+}
+
+async fn connect(uri: &str) -> anyhow::Result<GrpcStreamingTransport> {
+    let channel = Channel::from_shared(uri.to_string())
+        .context("couldn't create gRPC channel")?
+        .connect()
+        .await
+        .context("couldn't connect via gRPC channel")?;
+    Ok(GrpcStreamingTransport::new(StreamingSessionClient::new(
+        channel,
+    )))
+}
@@ -16,12 +16,19 @@
 
 //! Sends a gRPC request to an Oak Functions application and checks that the response has the
 //! correct format.
+//!
+//! The request payload can be passed inline, read from a file, or read from standard input, which
+//! makes the client usable for smoke tests and debugging from a terminal.
 
 use anyhow::Context;
 use clap::Parser;
 use oak_functions_abi::Request;
-use oak_functions_client::OakFunctionsClient;
+use oak_functions_client::{bundle::VerificationBundle, OakFunctionsClient, ReferenceValues};
 use regex::Regex;
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 const TWO_MIB: usize = 2 * 1024 * 1024;
 const LARGE_MESSAGE: [u8; TWO_MIB] = [0; TWO_MIB];
@@ -39,10 +46,22 @@ pub struct Opt {
     #[arg(
         long,
         help = "request payload",
-        required_unless_present = "test_large_message"
+        required_unless_present_any = &["test_large_message", "request_file", "attest_only"]
     )]
     request: Option<String>,
 
+    /// Path to a file containing the request payload, or `-` to read it from standard input.
+    #[arg(long, conflicts_with = "request")]
+    request_file: Option<PathBuf>,
+
+    /// Hex-encoded SHA-256 hash of the Wasm module that the server is expected to have loaded.
+    #[arg(long)]
+    expected_wasm_hash: Option<String>,
+
+    /// Constant size to which the server is expected to pad responses.
+    #[arg(long)]
+    expected_constant_response_size: Option<u32>,
+
     /// Optional, only for testing.
     #[arg(long, help = "expected response body, for testing")]
     expected_response_pattern: Option<String>,
@@ -51,6 +70,17 @@ pub struct Opt {
     #[arg(long, requires_all = &["request", "expected_response_pattern"])]
     iterations: Option<usize>,
 
+    /// Path to a verification bundle that is checked without network access before connecting.
+    /// The server must attest to the Wasm module that the bundle endorses. The TEE certificates
+    /// and revocation lists of the bundle are not verified yet.
+    #[arg(long, help = "verification bundle to check before connecting")]
+    verification_bundle: Option<PathBuf>,
+
+    /// Only verify the attestation evidence of the server and print its attested identity,
+    /// without sending a request.
+    #[arg(long, conflicts_with_all = &["request", "request_file", "test_large_message"])]
+    attest_only: bool,
+
     /// Test sending a large message
     #[arg(long, conflicts_with_all = &["request", "expected_response_pattern", "iterations"])]
     test_large_message: bool,
@@ -61,7 +91,47 @@ async fn main() -> anyhow::Result<()> {
     env_logger::init();
     let opt = Opt::parse();
 
-    let mut client = OakFunctionsClient::new(&opt.uri)
+    let mut expected_wasm_hash = opt
+        .expected_wasm_hash
+        .as_ref()
+        .map(hex::decode)
+        .transpose()
+        .context("couldn't decode expected Wasm hash")?;
+    if let Some(ref verification_bundle_path) = opt.verification_bundle {
+        let bundle_bytes =
+            std::fs::read(verification_bundle_path).context("couldn't read verification bundle")?;
+        let bundle = VerificationBundle::from_json(&bundle_bytes)?;
+        bundle
+            .verify()
+            .context("couldn't verify verification bundle")?;
+        // The endorsement is only meaningful if the server attests to the endorsed module.
+        let endorsed_digest = bundle.endorsed_digest()?;
+        if expected_wasm_hash
+            .as_ref()
+            .map_or(false, |hash| *hash != endorsed_digest)
+        {
+            anyhow::bail!("expected Wasm hash differs from the endorsed digest");
+        }
+        expected_wasm_hash = Some(endorsed_digest);
+    }
+
+    let reference_values = ReferenceValues {
+        wasm_hash: expected_wasm_hash,
+        constant_response_size: opt.expected_constant_response_size,
+    };
+    if opt.attest_only {
+        let attested_server = oak_functions_client::attest(&opt.uri, &reference_values)
+            .await
+            .context("couldn't attest Oak Functions server")?;
+        println!(
+            "Encryption public key: {}",
+            hex::encode(&attested_server.encryption_public_key)
+        );
+        println!("Wasm hash: {}", hex::encode(&attested_server.wasm_hash));
+        return Ok(());
+    }
+
+    let mut client = OakFunctionsClient::new_with_reference_values(&opt.uri, &reference_values)
         .await
         .context("couldn't create Oak Functions client")?;
 
@@ -78,29 +148,46 @@ async fn main() -> anyhow::Result<()> {
 
     let iterations = opt.iterations.unwrap_or(1);
 
-    let request = opt.request.unwrap();
+    let request = match (opt.request, opt.request_file) {
+        (Some(request), _) => request.into_bytes(),
+        (None, Some(request_file)) => read_request_file(&request_file)?,
+        (None, None) => anyhow::bail!("no request payload provided"),
+    };
 
     println!(
         "req: {:?}",
         Request {
-            body: request.as_bytes().to_vec(),
+            body: request.clone(),
         }
     );
 
     for _ in 0..iterations {
         let response = client
-            .invoke(request.as_bytes())
+            .invoke(&request)
             .await
             .context("couldn't invoke Oak Functions")?;
 
         println!("Response: {:?}", response);
-        let response_body = std::str::from_utf8(&response).unwrap();
+        let response_body = String::from_utf8_lossy(&response);
         println!("Response: {:?}", response_body);
         if let Some(ref expected) = opt.expected_response_pattern {
             let re = Regex::new(expected).unwrap();
-            assert!(re.is_match(response_body));
+            assert!(re.is_match(&response_body));
         }
     }
 
     Ok(())
 }
+
+fn read_request_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if path.as_os_str() == "-" {
+        let mut request = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut request)
+            .context("couldn't read request from standard input")?;
+        Ok(request)
+    } else {
+        std::fs::read(path)
+            .with_context(|| format!("couldn't read request file {}", path.display()))
+    }
+}
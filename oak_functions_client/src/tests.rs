@@ -14,7 +14,13 @@
 // limitations under the License.
 //
 
-use crate::rekor::*;
+use crate::{bundle::VerificationBundle, rekor::*, AttestedServer, ReferenceValues};
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use oak_client::{
+    proto::oak::session::v1::AttestationEvidence, verifier::AttestationEvidenceVerifier,
+};
+use oak_functions_abi::proto::{ConfigurationReport, ServerPolicy};
+use prost::Message;
 use std::fs;
 
 #[test]
@@ -76,3 +82,145 @@ fn test_verify_rekor_signature() {
 
     assert!(result.is_ok());
 }
+
+fn test_verification_bundle() -> VerificationBundle {
+    let endorsement_bytes =
+        fs::read("testdata/endorsement.json").expect("couldn't read endorsement file");
+    let log_entry_bytes = fs::read("testdata/logentry.json").expect("couldn't read log entry file");
+    let rekor_pem_bytes =
+        fs::read("testdata/rekor_public_key.pem").expect("couldn't read Rekor's public key file");
+    let pubkey_pem_bytes =
+        fs::read("testdata/ec_public.pem").expect("couldn't read product team's public key file");
+
+    VerificationBundle::assemble(
+        &endorsement_bytes,
+        &log_entry_bytes,
+        &rekor_pem_bytes,
+        &pubkey_pem_bytes,
+        &[],
+        &[],
+    )
+    .expect("couldn't assemble verification bundle")
+}
+
+#[test]
+fn test_verify_verification_bundle() {
+    let bundle = test_verification_bundle();
+    let serialized = bundle.to_json().expect("couldn't serialize bundle");
+    let parsed = VerificationBundle::from_json(&serialized).expect("couldn't parse bundle");
+
+    assert_eq!(bundle, parsed);
+    let result = parsed.verify();
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_verify_verification_bundle_with_tampered_endorsement() {
+    let mut bundle = test_verification_bundle();
+    bundle.endorsement = BASE64_STANDARD.encode(b"tampered endorsement");
+
+    assert!(bundle.verify().is_err());
+}
+
+#[test]
+fn test_verification_bundle_endorsed_digest() {
+    let bundle = test_verification_bundle();
+
+    assert_eq!(
+        bundle
+            .endorsed_digest()
+            .expect("couldn't get endorsed digest"),
+        hex::decode("15dc16c42a4ac9ed77f337a4a3065a63e444c29c18c8cf69d6a6b4ae678dca5c").unwrap()
+    );
+}
+
+#[test]
+fn test_verification_bundle_endorsed_digest_without_subject() {
+    let mut bundle = test_verification_bundle();
+    bundle.endorsement = BASE64_STANDARD.encode(br#"{"subject": []}"#);
+
+    assert!(bundle.endorsed_digest().is_err());
+}
+
+fn test_attestation_evidence() -> AttestationEvidence {
+    let configuration_report = ConfigurationReport {
+        wasm_hash: get_sha256(b"test Wasm module").to_vec(),
+        policy: Some(ServerPolicy {
+            constant_response_size_bytes: 1024,
+            constant_processing_time_ms: 0,
+        }),
+        extensions: vec![],
+        sensitive_logging: false,
+        response_metadata: false,
+        pad_responses: true,
+        reject_plaintext_sessions: false,
+    };
+    AttestationEvidence {
+        configuration: configuration_report.encode_to_vec(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_verify_reference_values() {
+    let reference_values = ReferenceValues {
+        wasm_hash: Some(get_sha256(b"test Wasm module").to_vec()),
+        constant_response_size: Some(1024),
+    };
+
+    let result = reference_values.verify(&test_attestation_evidence(), &[]);
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_verify_reference_values_with_unexpected_wasm_hash() {
+    let reference_values = ReferenceValues {
+        wasm_hash: Some(get_sha256(b"other Wasm module").to_vec()),
+        ..Default::default()
+    };
+
+    assert!(reference_values
+        .verify(&test_attestation_evidence(), &[])
+        .is_err());
+}
+
+#[test]
+fn test_verify_reference_values_without_padding() {
+    let reference_values = ReferenceValues {
+        constant_response_size: Some(1024),
+        ..Default::default()
+    };
+    let mut configuration_report =
+        ConfigurationReport::decode(test_attestation_evidence().configuration.as_ref())
+            .expect("couldn't decode configuration report");
+    configuration_report.pad_responses = false;
+    let evidence = AttestationEvidence {
+        configuration: configuration_report.encode_to_vec(),
+        ..Default::default()
+    };
+
+    assert!(reference_values.verify(&evidence, &[]).is_err());
+}
+
+#[test]
+fn test_attested_server_from_evidence() {
+    let evidence = AttestationEvidence {
+        encryption_public_key: b"test public key".to_vec(),
+        ..test_attestation_evidence()
+    };
+
+    let attested_server = AttestedServer::from_evidence(&evidence).unwrap();
+    assert_eq!(attested_server.encryption_public_key, b"test public key");
+    assert_eq!(
+        attested_server.wasm_hash,
+        get_sha256(b"test Wasm module").to_vec()
+    );
+    assert_eq!(
+        attested_server
+            .configuration
+            .policy
+            .unwrap()
+            .constant_response_size_bytes,
+        1024
+    );
+}
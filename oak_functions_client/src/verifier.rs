@@ -0,0 +1,96 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use anyhow::Context;
+use oak_client::{
+    proto::oak::session::v1::AttestationEvidence, verifier::AttestationEvidenceVerifier,
+};
+use oak_functions_abi::proto::ConfigurationReport;
+use prost::Message;
+
+/// Expected values of the Oak Functions configuration that the server reports in its attestation
+/// evidence. Values that are `None` are not checked.
+///
+/// Only the reported configuration is checked: the attestation report itself, and hence whether
+/// the evidence was generated for the nonce of the client, is not appraised yet.
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceValues {
+    /// Expected SHA-256 hash of the Wasm module.
+    pub wasm_hash: Option<Vec<u8>>,
+    /// Expected size to which the server pads responses. Servers that don't pad responses are
+    /// rejected.
+    pub constant_response_size: Option<u32>,
+}
+
+/// The identity of a server, as reported in attestation evidence that passed verification.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttestedServer {
+    /// Public key of the server to which requests are encrypted.
+    pub encryption_public_key: Vec<u8>,
+    /// SHA-256 hash of the Wasm module that the server has loaded.
+    pub wasm_hash: Vec<u8>,
+    /// The complete configuration that the server reports.
+    pub configuration: ConfigurationReport,
+}
+
+impl AttestedServer {
+    pub fn from_evidence(evidence: &AttestationEvidence) -> anyhow::Result<Self> {
+        let configuration = ConfigurationReport::decode(evidence.configuration.as_ref())
+            .context("couldn't decode configuration report")?;
+        Ok(Self {
+            encryption_public_key: evidence.encryption_public_key.clone(),
+            wasm_hash: configuration.wasm_hash.clone(),
+            configuration,
+        })
+    }
+}
+
+impl AttestationEvidenceVerifier for ReferenceValues {
+    // TODO(#3641): Appraise the attestation report, including the nonce in its report data.
+    fn verify(&self, evidence: &AttestationEvidence, _nonce: &[u8]) -> anyhow::Result<()> {
+        let configuration_report = ConfigurationReport::decode(evidence.configuration.as_ref())
+            .context("couldn't decode configuration report")?;
+
+        if let Some(ref wasm_hash) = self.wasm_hash {
+            if *wasm_hash != configuration_report.wasm_hash {
+                anyhow::bail!(
+                    "unexpected Wasm hash: expected {}, got {}",
+                    hex::encode(wasm_hash),
+                    hex::encode(&configuration_report.wasm_hash)
+                );
+            }
+        }
+
+        if let Some(constant_response_size) = self.constant_response_size {
+            if !configuration_report.pad_responses {
+                anyhow::bail!("responses are not padded to a constant size");
+            }
+            let reported_constant_response_size = configuration_report
+                .policy
+                .context("no policy in configuration report")?
+                .constant_response_size_bytes;
+            if constant_response_size != reported_constant_response_size {
+                anyhow::bail!(
+                    "unexpected constant response size: expected {}, got {}",
+                    constant_response_size,
+                    reported_constant_response_size
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -24,8 +24,12 @@ use oak_crypto::{
     proto::oak::crypto::v1::EncryptedResponse,
 };
 use oak_functions_launcher::{
-    proto::oak::functions::{InvokeRequest, OakFunctionsAsyncClient},
-    LookupDataConfig,
+    compression::Compression,
+    proto::oak::functions::{
+        InvokeRequest, LookupDataBackend, OakFunctionsAsyncClient, WasmBackend,
+    },
+    retry::RetryPolicy,
+    LookupDataConfig, LookupDataSource, ResponsePolicy, WasmConfig,
 };
 use oak_launcher_utils::launcher;
 use prost::Message;
@@ -91,17 +95,37 @@ fn run_bench(b: &mut Bencher, config: &OakFunctionsTestConfig) {
     let constant_response_size: u32 = 1024;
 
     let lookup_data_config = LookupDataConfig {
-        lookup_data_path: config.lookup_data_path.to_path_buf(),
+        lookup_data_source: LookupDataSource::File(config.lookup_data_path.to_path_buf()),
         update_interval: None,
         max_chunk_size: ByteUnit::Gibibyte(2),
+        compression: Compression::None,
+        initial_load_retry: RetryPolicy::default(),
+        start_without_lookup_data: false,
+        backend: LookupDataBackend::HashMap,
+        expiry_interval: None,
     };
 
-    let (launched_instance, connector_handle, _) = runtime
+    let (launched_instance, connector_handle, _, _) = runtime
         .block_on(oak_functions_launcher::create(
             launcher::GuestMode::Virtualized(params),
             lookup_data_config,
-            config.wasm_path.to_path_buf(),
-            constant_response_size,
+            std::fs::read(&config.wasm_path).expect("couldn't read Wasm module"),
+            ResponsePolicy {
+                constant_response_size,
+                ..Default::default()
+            },
+            WasmConfig {
+                max_fuel: None,
+                max_memory_pages: None,
+                instance_pool_size: 0,
+                backend: WasmBackend::Wasmi,
+                max_range_lookup_entries: 0,
+                private_metrics: None,
+                sensitive_logging: false,
+                wasi: false,
+                response_metadata: false,
+                reject_plaintext_sessions: false,
+            },
         ))
         .expect("Failed to create launcher");
     log::info!("created launcher instance");
@@ -30,6 +30,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         },
     )?;
 
+    // Generate gRPC code for consuming requests from a message queue.
+    generate_grpc_code(
+        "../",
+        &["oak_functions_launcher/proto/queue.proto"],
+        CodegenOptions {
+            build_client: true,
+            ..Default::default()
+        },
+    )?;
+
     // Generate micro RPC code for exchanging messages with the enclave.
     micro_rpc_build::compile(
         &[format!(
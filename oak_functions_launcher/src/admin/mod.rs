@@ -0,0 +1,133 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Administrative HTTP endpoint, which is only served on the loopback interface.
+//!
+//! `POST /refresh-lookup-data` refreshes the lookup data of all routes right away, instead of
+//! waiting for the next periodic update, and responds with the number of entries per route.
+
+use crate::LookupDataRefresher;
+use anyhow::Context;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Serialize;
+use std::{
+    convert::Infallible,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+
+pub const REFRESH_LOOKUP_DATA_PATH: &str = "/refresh-lookup-data";
+
+/// Outcome of the refresh of the lookup data of a route.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RefreshResult {
+    pub route: String,
+
+    /// Number of entries of the refreshed lookup data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<usize>,
+
+    /// Why the lookup data couldn't be refreshed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Serves the admin endpoint on `port` of the loopback interface, for the lookup data of the
+/// given routes.
+pub async fn serve(
+    port: u16,
+    refreshers: Vec<(String, LookupDataRefresher)>,
+) -> anyhow::Result<()> {
+    let refreshers = Arc::new(refreshers);
+    let make_service = make_service_fn(move |_connection| {
+        let refreshers = refreshers.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let refreshers = refreshers.clone();
+                async move { Ok::<_, Infallible>(handle(request, &refreshers).await) }
+            }))
+        }
+    });
+    Server::bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        .serve(make_service)
+        .await
+        .context("admin server failed")
+}
+
+/// Refreshes the lookup data of every route, one after the other.
+pub async fn refresh_lookup_data(
+    refreshers: &[(String, LookupDataRefresher)],
+) -> Vec<RefreshResult> {
+    let mut results = Vec::new();
+    for (route, refresher) in refreshers {
+        let result = match refresher.refresh().await {
+            Ok(entries) => {
+                log::info!(
+                    "refreshed lookup data of route {} with {} entries",
+                    route,
+                    entries
+                );
+                RefreshResult {
+                    route: route.clone(),
+                    entries: Some(entries),
+                    error: None,
+                }
+            }
+            Err(err) => {
+                log::warn!("couldn't refresh lookup data of route {}: {:?}", route, err);
+                RefreshResult {
+                    route: route.clone(),
+                    entries: None,
+                    error: Some(format!("{:#}", err)),
+                }
+            }
+        };
+        results.push(result);
+    }
+    results
+}
+
+async fn handle(
+    request: Request<Body>,
+    refreshers: &[(String, LookupDataRefresher)],
+) -> Response<Body> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::POST, REFRESH_LOOKUP_DATA_PATH) => {
+            let results = refresh_lookup_data(refreshers).await;
+            let status = if results.iter().all(|result| result.error.is_none()) {
+                StatusCode::OK
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+            let body = serde_json::to_vec_pretty(&results).expect("couldn't serialize results");
+            Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+        }
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    response.expect("couldn't build response")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,56 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[tokio::test]
+async fn test_handle() {
+    let request = |method: Method, path: &str| {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = handle(request(Method::POST, REFRESH_LOOKUP_DATA_PATH), &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(&body[..], b"[]");
+
+    let response = handle(request(Method::GET, REFRESH_LOOKUP_DATA_PATH), &[]).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_serialize_refresh_result() {
+    let results = vec![
+        RefreshResult {
+            route: "/".to_string(),
+            entries: Some(3),
+            error: None,
+        },
+        RefreshResult {
+            route: "/other".to_string(),
+            entries: None,
+            error: Some("no lookup data available".to_string()),
+        },
+    ];
+    assert_eq!(
+        serde_json::to_string(&results).unwrap(),
+        r#"[{"route":"/","entries":3},{"route":"/other","error":"no lookup data available"}]"#
+    );
+}
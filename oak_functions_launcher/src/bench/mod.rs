@@ -0,0 +1,195 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Benchmark mode of the launcher, which sends synthetic requests from a corpus to the enclave at
+//! a fixed rate, and reports latency percentiles and the fuel that the Wasm module consumed.
+//!
+//! Requests are encrypted to the public key of the enclave and sent through the same `Invoke` path
+//! as client requests, but without the network servers of the launcher.
+
+use crate::proto::oak::functions::{InvokeRequest, OakFunctionsAsyncClient};
+use anyhow::Context;
+use oak_crypto::{encryptor::ClientEncryptor, proto::oak::crypto::v1::EncryptedResponse};
+use oak_launcher_utils::channel::ConnectorHandle;
+use prost::Message;
+use std::{
+    fmt,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const EMPTY_ASSOCIATED_DATA: &[u8] = b"";
+
+/// Load that the benchmark generates.
+#[derive(Clone, Debug)]
+pub struct BenchConfig {
+    /// Request bodies, which are sent in order and repeated as needed.
+    pub corpus: Vec<Vec<u8>>,
+    /// Number of requests that are sent per second, regardless of whether earlier requests have
+    /// completed.
+    pub qps: u32,
+    /// How long requests are sent for.
+    pub duration: Duration,
+}
+
+/// Reads a corpus with one request body per line. Empty lines are ignored.
+pub fn read_corpus(path: &Path) -> anyhow::Result<Vec<Vec<u8>>> {
+    let corpus = std::fs::read(path)
+        .with_context(|| format!("couldn't read corpus file {}", path.display()))?;
+    let corpus: Vec<Vec<u8>> = corpus
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(<[u8]>::to_vec)
+        .collect();
+    if corpus.is_empty() {
+        anyhow::bail!("corpus file {} is empty", path.display());
+    }
+    Ok(corpus)
+}
+
+/// Results of a benchmark.
+#[derive(Clone, Debug, Default)]
+pub struct BenchReport {
+    /// Time from the first request until the last response.
+    pub elapsed: Duration,
+    /// Latencies of the successful requests, in ascending order.
+    pub latencies: Vec<Duration>,
+    /// Number of failed requests.
+    pub errors: usize,
+    /// Fuel consumed by the successful requests, in ascending order. Empty if the enclave doesn't
+    /// report fuel consumption.
+    pub fuel: Vec<u64>,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requests = self.latencies.len() + self.errors;
+        writeln!(
+            f,
+            "requests: {} ({} failed) in {:.1}s, {:.1} requests/s",
+            requests,
+            self.errors,
+            self.elapsed.as_secs_f64(),
+            requests as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+        )?;
+        match percentiles(&self.latencies) {
+            Some([p50, p90, p99, max]) => writeln!(
+                f,
+                "latency: p50 {:.1}ms, p90 {:.1}ms, p99 {:.1}ms, max {:.1}ms",
+                p50.as_secs_f64() * 1000.0,
+                p90.as_secs_f64() * 1000.0,
+                p99.as_secs_f64() * 1000.0,
+                max.as_secs_f64() * 1000.0
+            )?,
+            None => writeln!(f, "latency: no successful requests")?,
+        }
+        match percentiles(&self.fuel) {
+            Some([p50, p90, p99, max]) => write!(
+                f,
+                "fuel: p50 {}, p90 {}, p99 {}, max {}",
+                p50, p90, p99, max
+            ),
+            None => write!(
+                f,
+                "fuel: not reported, requires --max-wasm-fuel and --insecure-sensitive-logging"
+            ),
+        }
+    }
+}
+
+// Returns the 50th, 90th and 99th percentile and the maximum of the sorted values, by the nearest
+// rank method.
+fn percentiles<T: Copy>(sorted: &[T]) -> Option<[T; 4]> {
+    let rank = |percentile: usize| {
+        let index = (sorted.len() * percentile + 99) / 100;
+        sorted[index.saturating_sub(1)]
+    };
+    (!sorted.is_empty()).then(|| [rank(50), rank(90), rank(99), rank(100)])
+}
+
+/// Sends requests from the corpus to the enclave at the configured rate until the duration
+/// elapses, and waits for all of them to complete.
+pub async fn run(
+    config: BenchConfig,
+    connector_handle: ConnectorHandle,
+    encryption_public_key: Vec<u8>,
+) -> anyhow::Result<BenchReport> {
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / config.qps as f64));
+    let start = Instant::now();
+    let deadline = start + config.duration;
+    let mut invocations = Vec::new();
+    for payload in config.corpus.iter().cycle() {
+        interval.tick().await;
+        if Instant::now() >= deadline {
+            break;
+        }
+        let connector_handle = connector_handle.clone();
+        let encryption_public_key = encryption_public_key.clone();
+        let payload = payload.clone();
+        invocations.push(tokio::spawn(async move {
+            let start = Instant::now();
+            let result = invoke(connector_handle, &encryption_public_key, &payload).await;
+            (start.elapsed(), result)
+        }));
+    }
+
+    let mut report = BenchReport::default();
+    for invocation in invocations {
+        let (latency, result) = invocation.await.context("invocation task failed")?;
+        match result {
+            Ok(fuel_consumed) => {
+                report.latencies.push(latency);
+                if fuel_consumed > 0 {
+                    report.fuel.push(fuel_consumed);
+                }
+            }
+            Err(err) => {
+                log::warn!("benchmark request failed: {:?}", err);
+                report.errors += 1;
+            }
+        }
+    }
+    report.elapsed = start.elapsed();
+    report.latencies.sort_unstable();
+    report.fuel.sort_unstable();
+    Ok(report)
+}
+
+// Sends a single request, and returns the fuel it consumed as reported by the enclave.
+async fn invoke(
+    connector_handle: ConnectorHandle,
+    encryption_public_key: &[u8],
+    payload: &[u8],
+) -> anyhow::Result<u64> {
+    let mut client_encryptor = ClientEncryptor::create(encryption_public_key)?;
+    let encrypted_request = client_encryptor.encrypt(payload, EMPTY_ASSOCIATED_DATA)?;
+    let invoke_request = InvokeRequest {
+        body: encrypted_request.encode_to_vec(),
+    };
+    let mut client = OakFunctionsAsyncClient::new(connector_handle);
+    let invoke_response = client
+        .invoke(&invoke_request)
+        .await
+        .flatten()
+        .map_err(|err| anyhow::anyhow!("error invoking the enclave: {:?}", err))?;
+    let encrypted_response = EncryptedResponse::decode(invoke_response.body.as_slice())
+        .context("couldn't decode encrypted response")?;
+    client_encryptor.decrypt(&encrypted_response)?;
+    Ok(invoke_response.fuel_consumed)
+}
+
+#[cfg(test)]
+mod tests;
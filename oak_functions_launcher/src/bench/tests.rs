@@ -0,0 +1,39 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_percentiles() {
+    assert_eq!(percentiles::<u64>(&[]), None);
+    assert_eq!(percentiles(&[7]), Some([7, 7, 7, 7]));
+    let values: Vec<u64> = (1..=200).collect();
+    assert_eq!(percentiles(&values), Some([100, 180, 198, 200]));
+}
+
+#[test]
+fn test_report() {
+    let report = BenchReport {
+        elapsed: Duration::from_secs(2),
+        latencies: vec![Duration::from_millis(10), Duration::from_millis(20)],
+        errors: 2,
+        fuel: vec![],
+    };
+    let report = report.to_string();
+    assert!(report.contains("requests: 4 (2 failed) in 2.0s, 2.0 requests/s"));
+    assert!(report.contains("latency: p50 10.0ms, p90 20.0ms, p99 20.0ms, max 20.0ms"));
+    assert!(report.contains("fuel: not reported"));
+}
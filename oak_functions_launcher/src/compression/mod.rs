@@ -0,0 +1,121 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Decompression of lookup data, which may be stored or served compressed.
+
+use anyhow::Context;
+use std::io::{Read, Write};
+
+/// Compression format of lookup data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Not compressed.
+    #[default]
+    None,
+    /// Compressed with gzip.
+    Gzip,
+    /// Compressed with Zstandard.
+    Zstd,
+}
+
+impl Compression {
+    /// Value of the `Accept-Encoding` header of requests for lookup data.
+    pub const ACCEPT_ENCODING: &'static str = "gzip, zstd";
+
+    /// Returns the compression format for the value of a `Content-Encoding` header. Only a single
+    /// encoding is supported.
+    pub fn from_content_encoding(content_encoding: &str) -> anyhow::Result<Self> {
+        match content_encoding.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => Ok(Compression::None),
+            "gzip" | "x-gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            other => anyhow::bail!("unsupported content encoding {}", other),
+        }
+    }
+
+    /// Decompresses `data`, which is returned as is if it is not compressed.
+    pub fn decompress(self, data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        match self {
+            Compression::None => return Ok(data),
+            Compression::Gzip => flate2::read::MultiGzDecoder::new(data.as_slice())
+                .read_to_end(&mut decompressed)
+                .context("couldn't decompress gzip data")?,
+            Compression::Zstd => zstd::stream::read::Decoder::new(data.as_slice())?
+                .read_to_end(&mut decompressed)
+                .context("couldn't decompress zstd data")?,
+        };
+        Ok(decompressed)
+    }
+
+    /// Returns a decompressor for data that arrives in chunks.
+    pub fn decompressor(self) -> anyhow::Result<Decompressor> {
+        let decompressor = match self {
+            Compression::None => Decompressor::None,
+            Compression::Gzip => Decompressor::Gzip(flate2::write::MultiGzDecoder::new(Vec::new())),
+            Compression::Zstd => Decompressor::Zstd(zstd::stream::write::Decoder::new(Vec::new())?),
+        };
+        Ok(decompressor)
+    }
+}
+
+/// Decompresses data chunk by chunk, so that neither the compressed nor the decompressed data has
+/// to be held at once.
+pub enum Decompressor {
+    None,
+    Gzip(flate2::write::MultiGzDecoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+impl Decompressor {
+    /// Decompresses the next chunk, and returns the data that could be decompressed so far and was
+    /// not returned yet.
+    pub fn decompress(&mut self, chunk: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Decompressor::None => Ok(chunk.to_vec()),
+            Decompressor::Gzip(decoder) => {
+                decoder
+                    .write_all(chunk)
+                    .context("couldn't decompress gzip data")?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            Decompressor::Zstd(decoder) => {
+                decoder
+                    .write_all(chunk)
+                    .context("couldn't decompress zstd data")?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+        }
+    }
+
+    /// Returns the rest of the decompressed data once all chunks were decompressed.
+    pub fn finish(self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Decompressor::None => Ok(Vec::new()),
+            Decompressor::Gzip(decoder) => {
+                decoder.finish().context("couldn't decompress gzip data")
+            }
+            Decompressor::Zstd(mut decoder) => {
+                decoder.flush().context("couldn't decompress zstd data")?;
+                Ok(decoder.into_inner())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
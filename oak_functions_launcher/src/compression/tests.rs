@@ -0,0 +1,75 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_from_content_encoding() {
+    assert_eq!(
+        Compression::from_content_encoding("identity").unwrap(),
+        Compression::None
+    );
+    assert_eq!(
+        Compression::from_content_encoding("GZIP").unwrap(),
+        Compression::Gzip
+    );
+    assert_eq!(
+        Compression::from_content_encoding("zstd").unwrap(),
+        Compression::Zstd
+    );
+    assert!(Compression::from_content_encoding("br").is_err());
+    assert!(Compression::from_content_encoding("gzip, zstd").is_err());
+}
+
+#[test]
+fn test_decompress() {
+    let data = b"lookup data".repeat(100);
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&data).unwrap();
+    let gzip = encoder.finish().unwrap();
+    assert_eq!(Compression::Gzip.decompress(gzip).unwrap(), data);
+
+    let zstd = zstd::stream::encode_all(data.as_slice(), 0).unwrap();
+    assert_eq!(Compression::Zstd.decompress(zstd).unwrap(), data);
+
+    assert_eq!(Compression::None.decompress(data.clone()).unwrap(), data);
+    assert!(Compression::Gzip.decompress(data).is_err());
+}
+
+#[test]
+fn test_decompress_chunks() {
+    let data = b"lookup data".repeat(1000);
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&data).unwrap();
+    let gzip = encoder.finish().unwrap();
+    let zstd = zstd::stream::encode_all(data.as_slice(), 0).unwrap();
+
+    for (compression, compressed) in [
+        (Compression::Gzip, gzip),
+        (Compression::Zstd, zstd),
+        (Compression::None, data.clone()),
+    ] {
+        let mut decompressor = compression.decompressor().unwrap();
+        let mut decompressed = Vec::new();
+        for chunk in compressed.chunks(7) {
+            decompressed.extend(decompressor.decompress(chunk).unwrap());
+        }
+        decompressed.extend(decompressor.finish().unwrap());
+        assert_eq!(decompressed, data, "{:?}", compression);
+    }
+}
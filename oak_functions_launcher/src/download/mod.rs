@@ -0,0 +1,371 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Download of lookup data from a lookup data server, as implemented by
+//! `oak_functions_lookup_data_server`.
+//!
+//! The version list is requested conditionally with its ETag, and once a version has been
+//! downloaded, newer versions are fetched as deltas. Unchanged lookup data is therefore neither
+//! downloaded nor parsed again. Responses may be compressed with gzip or Zstandard, as indicated
+//! by their `Content-Encoding` header.
+//!
+//! Alternatively, a snapshot of the lookup data is downloaded from an object in cloud storage,
+//! conditional on the ETag of the object.
+//!
+//! Snapshots are decompressed and parsed as they arrive, so that they are never held serialized
+//! in full next to the parsed lookup data.
+//!
+//! The downloaded lookup data is shared with the caller rather than copied, and is sent to the
+//! enclave in chunks that are encoded one at a time. While a snapshot is parsed, the previous
+//! lookup data is only held by the caller, if at all, so the peak memory is about twice the lookup
+//! data. Applying a delta copies the lookup data only if the caller still holds the previous
+//! version.
+//!
+//! If the integrity of the lookup data is verified, every snapshot is hashed as it arrives and
+//! checked against the published digest or signature before it is returned, and deltas are not
+//! used, as they can't be checked.
+
+use crate::{
+    compression::Compression,
+    integrity::LookupDataIntegrity,
+    lookup::{insert_entry, unix_time_now, LookupEntries, LookupEntriesParser},
+    object_store::{StorageCredentials, StorageObject},
+    proxy::{ProxyConfig, ProxyConnector},
+};
+use anyhow::Context;
+use hyper::{
+    body::{Bytes, HttpBody},
+    header::{ACCEPT_ENCODING, CONTENT_ENCODING, ETAG, IF_NONE_MATCH},
+    http::request,
+    Body, Client, Method, Request, StatusCode,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use oak_functions_abi::proto::LookupDataDelta;
+use prost::Message;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+struct Versions {
+    latest: Option<String>,
+}
+
+// A response whose body has yet to be read.
+struct StreamingResponse {
+    url: String,
+    status: StatusCode,
+    etag: Option<String>,
+    compression: Compression,
+    body: Body,
+}
+
+/// Keeps track of the lookup data that was downloaded last, so that only changes are downloaded.
+pub struct LookupDataDownloader {
+    client: Client<HttpsConnector<ProxyConnector>>,
+    base_url: String,
+    // Set if the lookup data is downloaded from cloud storage instead of a lookup data server.
+    object: Option<StorageObject>,
+    // ETag of the version list, or of the storage object.
+    etag: Option<String>,
+    version: Option<String>,
+    entries: Arc<LookupEntries>,
+    // How the lookup data is verified, if at all.
+    integrity: Option<LookupDataIntegrity>,
+    // Set if the expected digest or signature is downloaded from cloud storage.
+    integrity_object: Option<StorageObject>,
+}
+
+impl LookupDataDownloader {
+    /// Creates a downloader for the lookup data server at `url`, or for the storage object that a
+    /// `gs://` or `s3://` URL references, which is requested with `credentials`. Servers and
+    /// storage are reached through `proxy` if it is set. If `integrity` is set, downloaded lookup
+    /// data that doesn't match the published digest or signature is rejected.
+    pub fn new(
+        url: &str,
+        proxy: Option<ProxyConfig>,
+        credentials: StorageCredentials,
+        integrity: Option<LookupDataIntegrity>,
+    ) -> anyhow::Result<Self> {
+        let connector = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(ProxyConnector::new(proxy));
+        let integrity_object = match &integrity {
+            Some(integrity) => StorageObject::parse(integrity.url(), credentials.clone())?,
+            None => None,
+        };
+        Ok(Self {
+            client: Client::builder().build(connector),
+            base_url: url.trim_end_matches('/').to_string(),
+            object: StorageObject::parse(url, credentials)?,
+            etag: None,
+            version: None,
+            entries: Arc::default(),
+            integrity,
+            integrity_object,
+        })
+    }
+
+    /// Returns the latest lookup data, or `None` if it has not changed since the last call.
+    pub async fn fetch(&mut self) -> anyhow::Result<Option<Arc<LookupEntries>>> {
+        if let Some(object) = &mut self.object {
+            let request = object.request(Method::GET).await?;
+            return self.fetch_object(request).await;
+        }
+
+        let (status, versions_etag, body) = self.get("/versions", self.etag.as_deref()).await?;
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let versions: Versions =
+            serde_json::from_slice(&body).context("couldn't parse lookup data versions")?;
+        let latest = versions
+            .latest
+            .context("lookup data server has no versions")?;
+        if self.version.as_ref() == Some(&latest) {
+            self.etag = versions_etag;
+            return Ok(None);
+        }
+
+        let delta = match &self.version {
+            Some(current) if self.integrity.is_none() => self
+                .fetch_delta(current, &latest)
+                .await
+                .map_err(|err| log::warn!("couldn't download lookup data delta: {:?}", err))
+                .ok(),
+            _ => None,
+        };
+        match delta {
+            Some(delta) => {
+                let entries = Arc::make_mut(&mut self.entries);
+                for key in delta.removed_keys {
+                    entries.remove(&key);
+                }
+                let now = unix_time_now();
+                for entry in delta.upserted_entries {
+                    insert_entry(entries, entry, now);
+                }
+            }
+            None => {
+                // Release the previous lookup data before the snapshot is parsed. Should that
+                // fail, the next fetch downloads a snapshot again.
+                self.version = None;
+                self.entries = Arc::default();
+                self.entries = Arc::new(self.fetch_snapshot(&latest).await?);
+            }
+        }
+        log::info!("downloaded lookup data version {}", latest);
+        self.version = Some(latest);
+        self.etag = versions_etag;
+        Ok(Some(Arc::clone(&self.entries)))
+    }
+
+    /// Checks that the lookup data is available, with a HEAD request for the version list of the
+    /// lookup data server or for the storage object, which doesn't download the lookup data.
+    pub async fn probe(&mut self) -> anyhow::Result<()> {
+        let request = match &mut self.object {
+            Some(object) => object.request(Method::HEAD).await?,
+            None => Request::head(format!("{}/versions", self.base_url)),
+        };
+        let request = request.body(Body::empty())?;
+        let url = request.uri().to_string();
+        let response = self
+            .client
+            .request(request)
+            .await
+            .with_context(|| format!("couldn't request {}", url))?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "HEAD request for {} failed with status {}",
+                url,
+                response.status()
+            );
+        }
+        Ok(())
+    }
+
+    /// Downloads the snapshot in the storage object with `request`, unless the object is
+    /// unchanged since the last download.
+    async fn fetch_object(
+        &mut self,
+        request: request::Builder,
+    ) -> anyhow::Result<Option<Arc<LookupEntries>>> {
+        let response = self.send_streaming(request, self.etag.as_deref()).await?;
+        if response.status == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let etag = response.etag.clone();
+        let entries = self.parse(response).await?;
+        log::info!("downloaded lookup data object with ETag {:?}", etag);
+        self.etag = etag;
+        Ok(Some(Arc::new(entries)))
+    }
+
+    async fn fetch_delta(&self, from: &str, to: &str) -> anyhow::Result<LookupDataDelta> {
+        let (_, _, body) = self.get(&format!("/deltas/{}/{}", from, to), None).await?;
+        LookupDataDelta::decode(body).context("couldn't decode lookup data delta")
+    }
+
+    async fn fetch_snapshot(&mut self, version: &str) -> anyhow::Result<LookupEntries> {
+        let url = format!("{}/snapshots/{}", self.base_url, version);
+        let response = self.send_streaming(Request::get(url), None).await?;
+        self.parse(response).await
+    }
+
+    /// Decompresses and parses the lookup data in the body of `response` as it arrives. If
+    /// configured, the lookup data is hashed on the way and verified before it is returned.
+    async fn parse(&mut self, response: StreamingResponse) -> anyhow::Result<LookupEntries> {
+        let StreamingResponse {
+            url,
+            compression,
+            mut body,
+            ..
+        } = response;
+        let mut decompressor = compression.decompressor()?;
+        let mut parser = LookupEntriesParser::new();
+        let mut digest = self.integrity.as_ref().map(|_| Sha256::new());
+        let mut consume = |lookup_data: Vec<u8>| {
+            if let Some(digest) = &mut digest {
+                digest.update(&lookup_data);
+            }
+            parser
+                .push(&lookup_data)
+                .with_context(|| format!("couldn't parse lookup data from {}", url))
+        };
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.with_context(|| format!("couldn't read response from {}", url))?;
+            let lookup_data = decompressor
+                .decompress(&chunk)
+                .with_context(|| format!("couldn't decompress response from {}", url))?;
+            consume(lookup_data)?;
+        }
+        let lookup_data = decompressor
+            .finish()
+            .with_context(|| format!("couldn't decompress response from {}", url))?;
+        consume(lookup_data)?;
+        if let Some(digest) = digest {
+            self.verify(digest).await?;
+        }
+        parser
+            .finish()
+            .with_context(|| format!("couldn't parse lookup data from {}", url))
+    }
+
+    /// Checks the digest of the downloaded lookup data against the published digest or signature,
+    /// if configured. Lookup data that doesn't match is rejected, so that it never reaches the
+    /// enclave.
+    async fn verify(&mut self, lookup_data_digest: Sha256) -> anyhow::Result<()> {
+        let integrity = match &self.integrity {
+            Some(integrity) => integrity.clone(),
+            None => return Ok(()),
+        };
+        let request = match &mut self.integrity_object {
+            Some(object) => object.request(Method::GET).await?,
+            None => Request::get(integrity.url()),
+        };
+        let (_, _, expected) = self
+            .send(request, None)
+            .await
+            .context("couldn't download expected digest or signature")?;
+        integrity
+            .verify_digest(lookup_data_digest, &expected)
+            .map_err(|err| {
+                log::error!(
+                    "rejected lookup data that doesn't match {}: {:?}",
+                    integrity.url(),
+                    err
+                );
+                err.context("lookup data failed integrity verification")
+            })
+    }
+
+    /// Sends a GET request for `path` on the lookup data server.
+    async fn get(
+        &self,
+        path: &str,
+        etag: Option<&str>,
+    ) -> anyhow::Result<(StatusCode, Option<String>, Bytes)> {
+        let url = format!("{}{}", self.base_url, path);
+        self.send(Request::get(url), etag).await
+    }
+
+    /// Sends a request, conditional on `etag` if it is set, and returns the status, the ETag and
+    /// the decompressed body of the response.
+    async fn send(
+        &self,
+        request: request::Builder,
+        etag: Option<&str>,
+    ) -> anyhow::Result<(StatusCode, Option<String>, Bytes)> {
+        let response = self.send_streaming(request, etag).await?;
+        let url = response.url;
+        let body = hyper::body::to_bytes(response.body)
+            .await
+            .with_context(|| format!("couldn't read response from {}", url))?;
+        let body = match response.compression {
+            Compression::None => body,
+            compression => compression
+                .decompress(body.to_vec())
+                .map(Bytes::from)
+                .with_context(|| format!("couldn't decompress response from {}", url))?,
+        };
+        Ok((response.status, response.etag, body))
+    }
+
+    /// Sends a request, conditional on `etag` if it is set, without reading the body of the
+    /// response.
+    async fn send_streaming(
+        &self,
+        request: request::Builder,
+        etag: Option<&str>,
+    ) -> anyhow::Result<StreamingResponse> {
+        let mut request = request.header(ACCEPT_ENCODING, Compression::ACCEPT_ENCODING);
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let request = request.body(Body::empty())?;
+        let url = request.uri().to_string();
+        let response = self
+            .client
+            .request(request)
+            .await
+            .with_context(|| format!("couldn't request {}", url))?;
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::NOT_MODIFIED {
+            anyhow::bail!("request for {} failed with status {}", url, status);
+        }
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(String::from);
+        let compression = match response.headers().get(CONTENT_ENCODING) {
+            Some(encoding) => Compression::from_content_encoding(encoding.to_str()?)?,
+            None => Compression::None,
+        };
+        Ok(StreamingResponse {
+            url,
+            status,
+            etag,
+            compression,
+            body: response.into_body(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
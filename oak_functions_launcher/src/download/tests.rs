@@ -0,0 +1,145 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[tokio::test]
+async fn test_download_lookup_data() {
+    use crate::lookup::LookupValue;
+    use oak_functions_abi::proto::Entry;
+    use oak_functions_lookup_data_server::{serialize_entries, SnapshotStore};
+    use std::sync::Arc;
+
+    fn entry(key: &str, value: &str) -> Entry {
+        Entry {
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    }
+    fn lookup_data(entries: &[(&str, &str)]) -> LookupEntries {
+        entries
+            .iter()
+            .map(|(key, value)| {
+                let value = LookupValue {
+                    value: value.as_bytes().to_vec(),
+                    expires_at: None,
+                };
+                (key.as_bytes().to_vec(), value)
+            })
+            .collect()
+    }
+
+    let directory = tempfile::tempdir().unwrap();
+    std::fs::write(
+        directory.path().join("v1.bin"),
+        serialize_entries(&[entry("a", "1"), entry("b", "2")]),
+    )
+    .unwrap();
+    let store = SnapshotStore::new(directory.path().to_path_buf(), 1).unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    tokio::spawn(oak_functions_lookup_data_server::serve(
+        addr,
+        Arc::new(store),
+    ));
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let mut downloader = LookupDataDownloader::new(
+        &format!("http://{addr}/"),
+        None,
+        StorageCredentials::default(),
+        None,
+    )
+    .unwrap();
+    let snapshot = downloader.fetch().await.unwrap().unwrap();
+    assert_eq!(*snapshot, lookup_data(&[("a", "1"), ("b", "2")]));
+    assert_eq!(downloader.fetch().await.unwrap(), None);
+    let snapshot_address = Arc::as_ptr(&snapshot);
+    drop(snapshot);
+
+    std::fs::write(
+        directory.path().join("v2.bin"),
+        serialize_entries(&[entry("a", "10"), entry("c", "3")]),
+    )
+    .unwrap();
+    let updated = downloader.fetch().await.unwrap().unwrap();
+    assert_eq!(*updated, lookup_data(&[("a", "10"), ("c", "3")]));
+    assert_eq!(downloader.fetch().await.unwrap(), None);
+    // The lookup data is shared with the caller, and the delta was applied in place since the
+    // caller no longer held the previous version, so it was never copied.
+    assert_eq!(Arc::as_ptr(&updated), snapshot_address);
+    assert_eq!(Arc::strong_count(&updated), 2);
+}
+
+#[tokio::test]
+async fn test_download_compressed_lookup_data() {
+    use crate::lookup::LookupValue;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server,
+    };
+    use oak_functions_abi::proto::Entry;
+    use oak_functions_lookup_data_server::serialize_entries;
+    use std::{convert::Infallible, io::Write};
+
+    // Serves the versions compressed with Zstandard and the snapshot compressed with gzip.
+    async fn handle(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let (encoding, body) = match request.uri().path() {
+            "/versions" => {
+                let versions = br#"{"latest":"v1"}"#;
+                ("zstd", zstd::stream::encode_all(&versions[..], 0).unwrap())
+            }
+            _ => {
+                let snapshot = serialize_entries(&[Entry {
+                    key: b"a".to_vec(),
+                    value: b"1".to_vec(),
+                    ..Default::default()
+                }]);
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&snapshot).unwrap();
+                ("gzip", encoder.finish().unwrap())
+            }
+        };
+        Ok(Response::builder()
+            .header(CONTENT_ENCODING, encoding)
+            .body(body.into())
+            .unwrap())
+    }
+
+    let make_service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    let mut downloader = LookupDataDownloader::new(
+        &format!("http://{addr}"),
+        None,
+        StorageCredentials::default(),
+        None,
+    )
+    .unwrap();
+    let value = LookupValue {
+        value: b"1".to_vec(),
+        expires_at: None,
+    };
+    assert_eq!(
+        downloader.fetch().await.unwrap(),
+        Some(Arc::new([(b"a".to_vec(), value)].into_iter().collect()))
+    );
+}
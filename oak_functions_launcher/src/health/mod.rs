@@ -0,0 +1,92 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! HTTP endpoints for liveness and readiness probes, e.g. of Kubernetes.
+//!
+//! `GET /healthz` succeeds as long as the launcher is running. `GET /readyz` only succeeds once the
+//! Wasm modules of all routes passed the module policy and were loaded into their enclaves, and the
+//! initial lookup data was loaded, and fails again while the launcher drains in-flight requests
+//! before shutting down.
+
+use anyhow::Context;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// The path of the liveness probe.
+pub const HEALTH_PATH: &str = "/healthz";
+
+/// The path of the readiness probe.
+pub const READINESS_PATH: &str = "/readyz";
+
+/// Whether the launcher is ready to serve requests. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct Readiness {
+    ready: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::SeqCst);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+}
+
+/// Serves [`HEALTH_PATH`] and [`READINESS_PATH`], the latter according to `readiness`.
+pub async fn serve(addr: SocketAddr, readiness: Readiness) -> anyhow::Result<()> {
+    let make_service = make_service_fn(move |_connection| {
+        let readiness = readiness.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let ready = readiness.is_ready();
+                async move { Ok::<_, Infallible>(handle(request, ready)) }
+            }))
+        }
+    });
+    Server::bind(&addr)
+        .serve(make_service)
+        .await
+        .context("health server failed")
+}
+
+fn handle(request: Request<Body>, ready: bool) -> Response<Body> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, HEALTH_PATH) => Response::builder().body(Body::from("ok")),
+        (&Method::GET, READINESS_PATH) if ready => Response::builder().body(Body::from("ready")),
+        (&Method::GET, READINESS_PATH) => Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(Body::from("not ready")),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    response.expect("couldn't build response")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,47 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_handle() {
+    let request = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+    assert_eq!(handle(request(HEALTH_PATH), false).status(), StatusCode::OK);
+    assert_eq!(handle(request(HEALTH_PATH), true).status(), StatusCode::OK);
+    assert_eq!(
+        handle(request(READINESS_PATH), false).status(),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    assert_eq!(
+        handle(request(READINESS_PATH), true).status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        handle(request("/other"), true).status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[test]
+fn test_readiness() {
+    let readiness = Readiness::default();
+    assert!(!readiness.is_ready());
+    readiness.set_ready(true);
+    assert!(readiness.is_ready());
+    readiness.set_ready(false);
+    assert!(!readiness.is_ready());
+}
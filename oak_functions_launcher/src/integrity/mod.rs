@@ -0,0 +1,103 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Verification of downloaded lookup data against what was published, before it replaces the
+//! lookup data in the enclave.
+//!
+//! The lookup data is checked either against a hex-encoded SHA-256 digest (e.g. as written by
+//! `sha256sum`), or against a Base64-encoded ASN.1 DER P-256 signature, as written by
+//! `oak_functions_lookup_data_generator convert --signing-key-path`. Both are downloaded from their
+//! own URL whenever the lookup data changed, and cover the uncompressed lookup data.
+
+use anyhow::Context;
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use ecdsa::hazmat::VerifyPrimitive;
+use p256::{
+    ecdsa::{Signature, VerifyingKey},
+    elliptic_curve::ops::Reduce,
+    PublicKey, Scalar,
+};
+use sha2::{Digest, Sha256};
+
+/// How downloaded lookup data is verified.
+#[derive(Clone, Debug)]
+pub enum LookupDataIntegrity {
+    /// The lookup data must match the SHA-256 digest at `digest_url`.
+    Sha256 { digest_url: String },
+    /// The lookup data must be signed with the private key of `public_key`, with the signature at
+    /// `signature_url`.
+    Signature {
+        signature_url: String,
+        public_key: VerifyingKey,
+    },
+}
+
+impl LookupDataIntegrity {
+    /// Returns the URL of the expected digest or signature.
+    pub fn url(&self) -> &str {
+        match self {
+            Self::Sha256 { digest_url } => digest_url,
+            Self::Signature { signature_url, .. } => signature_url,
+        }
+    }
+
+    /// Checks `lookup_data` against `expected`, the digest or signature downloaded from
+    /// [`Self::url`].
+    pub fn verify(&self, lookup_data: &[u8], expected: &[u8]) -> anyhow::Result<()> {
+        self.verify_digest(Sha256::new_with_prefix(lookup_data), expected)
+    }
+
+    /// Like [`Self::verify`], but for lookup data that was hashed as it arrived. Both the digest
+    /// and the signature cover the SHA-256 digest of the lookup data.
+    pub fn verify_digest(&self, lookup_data_digest: Sha256, expected: &[u8]) -> anyhow::Result<()> {
+        let expected = std::str::from_utf8(expected).context("expected value is not UTF-8")?;
+        match self {
+            Self::Sha256 { .. } => {
+                // Like in the output of `sha256sum`, the digest may be followed by a file name.
+                let expected_digest = expected
+                    .split_whitespace()
+                    .next()
+                    .context("expected digest is empty")?;
+                let digest = hex::encode(lookup_data_digest.finalize());
+                if !digest.eq_ignore_ascii_case(expected_digest) {
+                    anyhow::bail!(
+                        "SHA-256 digest {} doesn't match the expected digest {}",
+                        digest,
+                        expected_digest
+                    );
+                }
+            }
+            Self::Signature { public_key, .. } => {
+                let signature = BASE64_STANDARD
+                    .decode(expected.trim())
+                    .context("signature is not valid Base64")?;
+                let signature =
+                    Signature::from_der(&signature).context("invalid ASN.1 signature")?;
+                // The digest was computed with a different version of `sha2` than the one `p256`
+                // uses, so the signature is verified against the prehashed digest.
+                let prehashed = Scalar::from_be_bytes_reduced(lookup_data_digest.finalize());
+                PublicKey::from(public_key)
+                    .as_affine()
+                    .verify_prehashed(prehashed, &signature)
+                    .context("signature doesn't match the trusted public key")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,66 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_verify_digest() {
+    let integrity = LookupDataIntegrity::Sha256 {
+        digest_url: "http://localhost/lookup_data.bin.sha256".to_string(),
+    };
+    let lookup_data = b"lookup data";
+    let digest = hex::encode(Sha256::digest(lookup_data));
+    assert!(integrity.verify(lookup_data, digest.as_bytes()).is_ok());
+    let sha256sum = format!("{}  lookup_data.bin\n", digest.to_uppercase());
+    assert!(integrity.verify(lookup_data, sha256sum.as_bytes()).is_ok());
+    assert!(integrity.verify(b"other data", digest.as_bytes()).is_err());
+    assert!(integrity.verify(lookup_data, b"").is_err());
+
+    let mut lookup_data_digest = Sha256::new();
+    lookup_data_digest.update(b"lookup ");
+    lookup_data_digest.update(b"data");
+    assert!(integrity
+        .verify_digest(lookup_data_digest, digest.as_bytes())
+        .is_ok());
+}
+
+#[test]
+fn test_verify_signature() {
+    use p256::ecdsa::{signature::Signer, SigningKey};
+
+    let signing_key = SigningKey::from_bytes(&[7; 32]).unwrap();
+    let integrity = LookupDataIntegrity::Signature {
+        signature_url: "http://localhost/lookup_data.bin.sig".to_string(),
+        public_key: signing_key.verifying_key(),
+    };
+    let lookup_data = b"lookup data";
+    let signature: Signature = signing_key.sign(lookup_data);
+    let signature = BASE64_STANDARD.encode(signature.to_der().as_bytes());
+    assert!(integrity.verify(lookup_data, signature.as_bytes()).is_ok());
+    assert!(integrity
+        .verify(b"other data", signature.as_bytes())
+        .is_err());
+    assert!(integrity.verify(lookup_data, b"not a signature").is_err());
+
+    let other_key = SigningKey::from_bytes(&[8; 32]).unwrap();
+    let other_integrity = LookupDataIntegrity::Signature {
+        signature_url: integrity.url().to_string(),
+        public_key: other_key.verifying_key(),
+    };
+    assert!(other_integrity
+        .verify(lookup_data, signature.as_bytes())
+        .is_err());
+}
@@ -0,0 +1,136 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! A non-sensitive HTTP endpoint that reports the identity of the running code, so that operators
+//! and clients can cross-check it without a full attestation round-trip.
+//!
+//! The digests are computed over the same values that appear in the attestation evidence, and are
+//! hex-encoded. They are updated when the Wasm module is replaced.
+
+use crate::proto::oak::functions::PublicKeyInfo;
+use anyhow::Context;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use oak_functions_abi::proto::ConfigurationReport;
+use prost::Message;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{convert::Infallible, net::SocketAddr};
+use tokio::sync::watch;
+
+/// The path at which the measurement is served.
+pub const MEASUREMENT_PATH: &str = "/measurement";
+
+/// The identity of the running code.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Measurement {
+    /// SHA-256 digest of the loaded Wasm module, as reported in the configuration.
+    #[serde(rename = "wasmModuleSha256")]
+    pub wasm_module_sha256: String,
+
+    /// SHA-256 digest of the serialized `ConfigurationReport` that is bound to the attestation.
+    #[serde(rename = "configurationSha256")]
+    pub configuration_sha256: String,
+
+    /// Version of the launcher crate.
+    #[serde(rename = "launcherVersion")]
+    pub launcher_version: String,
+
+    /// SHA-256 digest of the launcher binary that is running.
+    #[serde(rename = "launcherBinarySha256")]
+    pub launcher_binary_sha256: String,
+}
+
+impl Measurement {
+    /// Creates the measurement from the serialized `ConfigurationReport` returned by the enclave
+    /// and the hex-encoded digest of the launcher binary.
+    pub fn new(configuration: &[u8], launcher_binary_sha256: String) -> anyhow::Result<Self> {
+        let configuration_report = ConfigurationReport::decode(configuration)
+            .context("couldn't decode configuration report")?;
+        Ok(Self {
+            wasm_module_sha256: hex::encode(configuration_report.wasm_hash),
+            configuration_sha256: hex::encode(Sha256::digest(configuration)),
+            launcher_version: env!("CARGO_PKG_VERSION").to_string(),
+            launcher_binary_sha256,
+        })
+    }
+}
+
+/// Returns the hex-encoded SHA-256 digest of the running launcher binary.
+pub fn launcher_binary_sha256() -> anyhow::Result<String> {
+    let launcher_binary = std::env::current_exe()
+        .and_then(std::fs::read)
+        .context("couldn't read launcher binary")?;
+    Ok(hex::encode(Sha256::digest(launcher_binary)))
+}
+
+/// Serves the measurement of the code described by the current `public_key_info` as JSON on
+/// [`MEASUREMENT_PATH`].
+pub async fn serve(
+    addr: SocketAddr,
+    public_key_info: watch::Receiver<PublicKeyInfo>,
+) -> anyhow::Result<()> {
+    // The launcher binary does not change while it is running, so it is only hashed once.
+    let launcher_binary_sha256 = launcher_binary_sha256()?;
+    let make_service = make_service_fn(move |_connection| {
+        let public_key_info = public_key_info.clone();
+        let launcher_binary_sha256 = launcher_binary_sha256.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let body = measurement_body(&public_key_info, launcher_binary_sha256.clone());
+                async move { Ok::<_, Infallible>(handle(request, body)) }
+            }))
+        }
+    });
+    Server::bind(&addr)
+        .serve(make_service)
+        .await
+        .context("introspection server failed")
+}
+
+fn measurement_body(
+    public_key_info: &watch::Receiver<PublicKeyInfo>,
+    launcher_binary_sha256: String,
+) -> anyhow::Result<Vec<u8>> {
+    let measurement = Measurement::new(
+        &public_key_info.borrow().configuration,
+        launcher_binary_sha256,
+    )?;
+    serde_json::to_vec_pretty(&measurement).context("couldn't serialize measurement")
+}
+
+fn handle(request: Request<Body>, body: anyhow::Result<Vec<u8>>) -> Response<Body> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, MEASUREMENT_PATH) => match body {
+            Ok(body) => Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body)),
+            Err(err) => {
+                log::warn!("couldn't compute measurement: {:?}", err);
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::empty())
+            }
+        },
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    response.expect("couldn't build response")
+}
@@ -18,8 +18,27 @@
 #![feature(result_flattening)]
 #![feature(array_chunks)]
 
+pub mod admin;
+pub mod bench;
+pub mod compression;
+mod download;
+pub mod health;
+pub mod integrity;
+pub mod introspection;
+pub mod logging;
 mod lookup;
+pub mod merge;
+pub mod metrics;
+pub mod object_store;
+pub mod private_metrics;
+pub mod proxy;
+pub mod queue;
+pub mod reload;
+pub mod retry;
+pub mod schedule;
 pub mod server;
+pub mod trace;
+mod watch;
 
 pub mod proto {
     pub mod oak {
@@ -27,9 +46,17 @@ pub mod proto {
             #![allow(dead_code)]
             use prost::Message;
             include!(concat!(env!("OUT_DIR"), "/oak.functions.rs"));
+
+            pub mod queue {
+                pub mod v1 {
+                    #![allow(clippy::return_self_not_must_use)]
+                    tonic::include_proto!("oak.functions.queue.v1");
+                }
+            }
         }
         pub mod session {
             pub mod v1 {
+                #![allow(clippy::large_enum_variant)]
                 #![allow(clippy::return_self_not_must_use)]
                 tonic::include_proto!("oak.session.v1");
             }
@@ -37,57 +64,406 @@ pub mod proto {
     }
 }
 
-use crate::proto::oak::functions::{
-    InitializeRequest, InitializeResponse, OakFunctionsAsyncClient,
+use crate::{
+    compression::Compression,
+    download::LookupDataDownloader,
+    integrity::LookupDataIntegrity,
+    merge::MergeConfig,
+    object_store::StorageCredentials,
+    private_metrics::PrivateMetricsConfig,
+    proto::oak::functions::{
+        InitializeRequest, InitializeResponse, LookupDataBackend, OakFunctionsAsyncClient,
+        WasmBackend,
+    },
+    proxy::ProxyConfig,
+    retry::RetryPolicy,
+    watch::FileVersion,
 };
 use anyhow::Context;
 use oak_launcher_utils::{
     channel::{self, ConnectorHandle},
     launcher,
 };
-use std::{fs, path::PathBuf, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, Mutex};
 use ubyte::ByteUnit;
 
+/// Where the lookup data is loaded from.
+#[derive(Clone)]
+pub enum LookupDataSource {
+    /// A local file in the binary lookup data format.
+    File(PathBuf),
+    /// The base URL of a lookup data server, as implemented by `oak_functions_lookup_data_server`,
+    /// or a `gs://` or `s3://` URL of an object in cloud storage that contains the lookup data,
+    /// which are reached through the proxy if it is set. Downloaded lookup data is verified as
+    /// given by `integrity`, if set.
+    Url {
+        url: String,
+        proxy: Option<ProxyConfig>,
+        credentials: StorageCredentials,
+        integrity: Option<LookupDataIntegrity>,
+    },
+    /// The base URLs of lookup data servers, each of which serves one shard of the lookup data.
+    /// The keys of the shard at index `i` must be assigned to it by
+    /// `oak_functions_lookup::shard_index`. Shards are refreshed independently.
+    Shards {
+        urls: Vec<String>,
+        proxy: Option<ProxyConfig>,
+        credentials: StorageCredentials,
+    },
+    /// Several lookup data files, servers or storage objects, each refreshed at its own interval,
+    /// whose lookup data is merged into one keyspace as configured.
+    Merged {
+        config: MergeConfig,
+        proxy: Option<ProxyConfig>,
+        credentials: StorageCredentials,
+    },
+}
+
+#[derive(Clone)]
 pub struct LookupDataConfig {
-    pub lookup_data_path: PathBuf,
+    pub lookup_data_source: LookupDataSource,
     // Only periodically updates if interval is given.
     pub update_interval: Option<Duration>,
     pub max_chunk_size: ByteUnit,
+    // Compression of the lookup data file. Downloaded lookup data is decompressed as indicated by
+    // the `Content-Encoding` of the responses instead.
+    pub compression: Compression,
+    // How the initial load of the lookup data is retried.
+    pub initial_load_retry: RetryPolicy,
+    // Whether to start serving with empty lookup data once all attempts of the initial load
+    // failed, rather than failing to start.
+    pub start_without_lookup_data: bool,
+    // How the enclave stores the lookup data.
+    pub backend: LookupDataBackend,
+    // How often the enclave is told the current time, after which it no longer looks up entries
+    // that expired by then. Entries only expire when the lookup data is loaded if not given.
+    pub expiry_interval: Option<Duration>,
+}
+
+/// How the enclave executes the Wasm module.
+#[derive(Clone)]
+pub struct WasmConfig {
+    // Fuel that each invocation may consume. Not limited if not given.
+    pub max_fuel: Option<u64>,
+    // Number of memory pages that each invocation may grow to. Not limited if not given.
+    pub max_memory_pages: Option<u32>,
+    // Number of instances that the enclave reuses across invocations. Zero disables pooling.
+    pub instance_pool_size: u32,
+    pub backend: WasmBackend,
+    // Maximum number of entries that a range lookup of the Wasm module returns. Zero disables
+    // range lookups.
+    pub max_range_lookup_entries: u32,
+    // Differentially private metrics that the Wasm module may report. Disabled if not given.
+    pub private_metrics: Option<PrivateMetricsConfig>,
+    // Whether the enclave emits sensitive logs, such as the logs of the Wasm module. Reported in
+    // the attestation evidence.
+    pub sensitive_logging: bool,
+    // Whether the Wasm module may import the deterministic WASI shim of the enclave.
+    pub wasi: bool,
+    // Whether the status code and headers set by the Wasm module are prepended to its responses.
+    pub response_metadata: bool,
+    // Whether the enclave rejects requests that are not encrypted. Reported in the attestation
+    // evidence.
+    pub reject_plaintext_sessions: bool,
+}
+
+impl Default for WasmConfig {
+    fn default() -> Self {
+        Self {
+            max_fuel: None,
+            max_memory_pages: None,
+            instance_pool_size: 0,
+            backend: WasmBackend::Wasmi,
+            max_range_lookup_entries: 0,
+            private_metrics: None,
+            sensitive_logging: false,
+            wasi: false,
+            response_metadata: false,
+            reject_plaintext_sessions: false,
+        }
+    }
+}
+
+/// How responses are protected against side channels that reveal information about them.
+#[derive(Clone, Copy, Default)]
+pub struct ResponsePolicy {
+    // Size to which responses are padded. Reported in the attestation evidence.
+    pub constant_response_size: u32,
+    // Whether the enclave pads responses to `constant_response_size`.
+    pub pad_responses: bool,
+    // Time after the start of an invocation at which its response is returned. Zero disables the
+    // policy. It is enforced by the launcher rather than the enclave, so it is not attested.
+    pub constant_processing_time_ms: u32,
+}
+
+impl ResponsePolicy {
+    /// Returns the time after which responses are returned, if the policy is enabled.
+    pub fn constant_processing_time(&self) -> Option<Duration> {
+        Some(Duration::from_millis(
+            self.constant_processing_time_ms.into(),
+        ))
+        .filter(|_| self.constant_processing_time_ms > 0)
+    }
 }
 
+/// Refreshes the lookup data in an enclave on demand, in addition to its periodic updates.
+#[derive(Clone)]
+pub struct LookupDataRefresher {
+    connector_handle: ConnectorHandle,
+    config: LookupDataConfig,
+    // Held while lookup data is sent to the enclave, so that updates of the same shard don't
+    // interleave.
+    update_lock: Arc<Mutex<()>>,
+}
+
+impl LookupDataRefresher {
+    /// Loads the lookup data from its source and sends it to the enclave right away. Returns the
+    /// number of entries of the lookup data.
+    pub async fn refresh(&self) -> anyhow::Result<usize> {
+        let _guard = self.update_lock.lock().await;
+        let mut client = OakFunctionsAsyncClient::new(self.connector_handle.clone());
+        update_lookup_data(&mut client, &self.config).await
+    }
+}
+
+/// Launches an enclave, loads lookup data into it and initializes it with `wasm_module`, which
+/// must already have been checked, as the enclave runs it as is.
 pub async fn create(
     mode: launcher::GuestMode,
     lookup_data_config: LookupDataConfig,
-    wasm_path: PathBuf,
-    constant_response_size: u32,
+    wasm_module: Vec<u8>,
+    response_policy: ResponsePolicy,
+    wasm_config: WasmConfig,
 ) -> Result<
     (
         Box<dyn launcher::GuestInstance>,
         channel::ConnectorHandle,
         InitializeResponse,
+        LookupDataRefresher,
     ),
     Box<dyn std::error::Error>,
 > {
     let (launched_instance, connector_handle) = launcher::launch(mode).await?;
-    setup_lookup_data(connector_handle.clone(), lookup_data_config).await?;
-    let intialize_response =
-        intialize_enclave(connector_handle.clone(), &wasm_path, constant_response_size).await?;
-    Ok((launched_instance, connector_handle, intialize_response))
+    let refresher = LookupDataRefresher {
+        connector_handle: connector_handle.clone(),
+        config: lookup_data_config,
+        update_lock: Arc::new(Mutex::new(())),
+    };
+    setup_lookup_data(&refresher).await?;
+    let intialize_response = intialize_enclave(
+        connector_handle.clone(),
+        wasm_module,
+        response_policy,
+        wasm_config,
+    )
+    .await?;
+    Ok((
+        launched_instance,
+        connector_handle,
+        intialize_response,
+        refresher,
+    ))
 }
 
-// Initially loads lookup data and spawns task to periodically refresh lookup data.
+// Initially loads lookup data and spawns task to refresh lookup data when it changes.
 async fn setup_lookup_data(
-    connector_handle: channel::ConnectorHandle,
-    config: LookupDataConfig,
+    refresher: &LookupDataRefresher,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = OakFunctionsAsyncClient::new(connector_handle);
+    let connector_handle = refresher.connector_handle.clone();
+    let config = refresher.config.clone();
+    let update_lock = refresher.update_lock.clone();
+    let mut client = OakFunctionsAsyncClient::new(connector_handle.clone());
+
+    let shard_count = match &config.lookup_data_source {
+        LookupDataSource::Shards { urls, .. } => urls.len(),
+        _ => 1,
+    };
+    if shard_count > 1 || config.backend != LookupDataBackend::HashMap {
+        lookup::set_shard_count(&mut client, shard_count, config.backend).await?;
+    }
+
+    match config.lookup_data_source.clone() {
+        LookupDataSource::File(lookup_data_path) => {
+            // Determine the version before loading, so that changes during loading cause another
+            // update.
+            let mut version = FileVersion::of(&lookup_data_path).ok();
+
+            // Block for [invariant that lookup data is fully loaded](https://github.com/project-oak/oak/tree/main/oak_functions/lookup/README.md#invariant-fully-loaded-lookup-data)
+            let result = config
+                .initial_load_retry
+                .retry("load lookup data", || {
+                    let mut client = OakFunctionsAsyncClient::new(connector_handle.clone());
+                    let config = &config;
+                    async move { update_lookup_data(&mut client, config).await }
+                })
+                .await;
+            if let Err(err) = result {
+                start_without_lookup_data(&config, err)?;
+                // Reload the file on the first periodic update even if it didn't change.
+                version = None;
+            }
+
+            // Spawn task to periodically refresh lookup data.
+            if config.update_interval.is_some() {
+                tokio::spawn(setup_periodic_update(
+                    client,
+                    config,
+                    update_lock,
+                    lookup_data_path,
+                    version,
+                ));
+            }
+        }
+        LookupDataSource::Url {
+            url,
+            proxy,
+            credentials,
+            integrity,
+        } => {
+            // Block for [invariant that lookup data is fully loaded](https://github.com/project-oak/oak/tree/main/oak_functions/lookup/README.md#invariant-fully-loaded-lookup-data)
+            let downloader = load_initial_shard(
+                &connector_handle,
+                &config,
+                &url,
+                &proxy,
+                &credentials,
+                &integrity,
+                0,
+            )
+            .await?;
+
+            // Spawn task to periodically refresh lookup data.
+            if config.update_interval.is_some() {
+                tokio::spawn(setup_periodic_download(
+                    client,
+                    config,
+                    update_lock,
+                    downloader,
+                    0,
+                    Duration::ZERO,
+                ));
+            }
+        }
+        LookupDataSource::Shards {
+            urls,
+            proxy,
+            credentials,
+        } => {
+            let mut downloaders = Vec::new();
+            for (shard, url) in urls.iter().enumerate() {
+                // Block for [invariant that lookup data is fully loaded](https://github.com/project-oak/oak/tree/main/oak_functions/lookup/README.md#invariant-fully-loaded-lookup-data)
+                let downloader = load_initial_shard(
+                    &connector_handle,
+                    &config,
+                    url,
+                    &proxy,
+                    &credentials,
+                    &None,
+                    shard as u32,
+                )
+                .await?;
+                downloaders.push(downloader);
+            }
+
+            // Spawn a task per shard to periodically refresh it. The refreshes are staggered
+            // evenly over the update interval, so that at most one shard is held twice in the
+            // enclave at a time.
+            if let Some(update_interval) = config.update_interval {
+                let shard_count = downloaders.len() as u32;
+                for (shard, downloader) in downloaders.into_iter().enumerate() {
+                    let shard = shard as u32;
+                    tokio::spawn(setup_periodic_download(
+                        OakFunctionsAsyncClient::new(connector_handle.clone()),
+                        config.clone(),
+                        update_lock.clone(),
+                        downloader,
+                        shard,
+                        update_interval * shard / shard_count,
+                    ));
+                }
+            }
+        }
+        LookupDataSource::Merged {
+            config: merge_config,
+            proxy,
+            credentials,
+        } => {
+            merge::setup(
+                &connector_handle,
+                &config,
+                &merge_config,
+                &proxy,
+                &credentials,
+                update_lock,
+            )
+            .await?;
+        }
+    }
 
-    // Block for [invariant that lookup data is fully loaded](https://github.com/project-oak/oak/tree/main/oak_functions/lookup/README.md#invariant-fully-loaded-lookup-data)
-    update_lookup_data(&mut client, &config).await?;
+    // The enclave has no clock, so lookup data entries only expire as the launcher advances its
+    // time.
+    if let Some(expiry_interval) = refresher.config.expiry_interval {
+        let client = OakFunctionsAsyncClient::new(connector_handle);
+        tokio::spawn(setup_periodic_expiry(client, expiry_interval));
+    }
+    Ok(())
+}
 
-    // Spawn task to periodically refresh lookup data.
+// Initially downloads the given shard of the lookup data and sends it to the enclave, retrying as
+// configured. Returns the downloader for the periodic updates of the shard.
+async fn load_initial_shard(
+    connector_handle: &ConnectorHandle,
+    config: &LookupDataConfig,
+    url: &str,
+    proxy: &Option<ProxyConfig>,
+    credentials: &StorageCredentials,
+    integrity: &Option<LookupDataIntegrity>,
+    shard: u32,
+) -> anyhow::Result<LookupDataDownloader> {
+    let result = config
+        .initial_load_retry
+        .retry(
+            &format!("load lookup data shard {shard}"),
+            move || async move {
+                let mut client = OakFunctionsAsyncClient::new(connector_handle.clone());
+                let mut downloader = LookupDataDownloader::new(
+                    url,
+                    proxy.clone(),
+                    credentials.clone(),
+                    integrity.clone(),
+                )?;
+                let lookup_data = downloader
+                    .fetch()
+                    .await?
+                    .with_context(|| format!("no lookup data available for shard {shard}"))?;
+                lookup::send_lookup_data(&mut client, shard, &lookup_data, config.max_chunk_size)
+                    .await?;
+                Ok(downloader)
+            },
+        )
+        .await;
+    match result {
+        Ok(downloader) => Ok(downloader),
+        Err(err) => {
+            start_without_lookup_data(config, err)?;
+            // A fresh downloader has no ETag, so that the first periodic update loads the shard.
+            LookupDataDownloader::new(url, proxy.clone(), credentials.clone(), integrity.clone())
+        }
+    }
+}
+
+// Decides whether to serve requests although the initial load of lookup data failed for good,
+// which breaks the invariant that lookup data is fully loaded until a periodic update succeeds.
+fn start_without_lookup_data(config: &LookupDataConfig, err: anyhow::Error) -> anyhow::Result<()> {
+    if !config.start_without_lookup_data {
+        return Err(err);
+    }
+    log::warn!("couldn't load initial lookup data: {:?}", err);
     if config.update_interval.is_some() {
-        tokio::spawn(setup_periodic_update(client, config));
+        log::warn!("serving without lookup data until a periodic update loads it");
+    } else {
+        log::warn!("serving without lookup data, which is never loaded as updates are disabled");
     }
     Ok(())
 }
@@ -95,46 +471,216 @@ async fn setup_lookup_data(
 async fn setup_periodic_update(
     mut client: OakFunctionsAsyncClient<ConnectorHandle>,
     config: LookupDataConfig,
+    update_lock: Arc<Mutex<()>>,
+    lookup_data_path: PathBuf,
+    mut version: Option<FileVersion>,
 ) {
     // Only set periodic update if an interval is given.
     let mut interval =
         tokio::time::interval(config.update_interval.expect("No update interval given."));
+
+    // Update as soon as the file changes where file system notifications are available, and fall
+    // back to checking the file on every interval otherwise. The watcher must be kept alive for
+    // notifications to be delivered.
+    let (change_sender, mut change_receiver) = mpsc::channel(1);
+    let _watcher = watch::watch_file(&lookup_data_path, change_sender)
+        .map_err(|err| log::warn!("couldn't watch lookup data for changes: {:?}", err))
+        .ok();
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            Some(()) = change_receiver.recv() => {},
+        }
+        let current_version = FileVersion::of(&lookup_data_path).ok();
+        if current_version.is_some() && current_version == version {
+            continue;
+        }
+        // Ignore errors in updates of lookup data after the initial update, and retry later.
+        let _guard = update_lock.lock().await;
+        if update_lookup_data(&mut client, &config).await.is_ok() {
+            log::info!("updated lookup data");
+            version = current_version;
+        }
+    }
+}
+
+// Periodically downloads the given shard of the lookup data, starting after `delay`.
+async fn setup_periodic_download(
+    mut client: OakFunctionsAsyncClient<ConnectorHandle>,
+    config: LookupDataConfig,
+    update_lock: Arc<Mutex<()>>,
+    mut downloader: LookupDataDownloader,
+    shard: u32,
+    delay: Duration,
+) {
+    // Only set periodic update if an interval is given.
+    let mut interval = tokio::time::interval_at(
+        tokio::time::Instant::now() + delay,
+        config.update_interval.expect("No update interval given."),
+    );
     loop {
-        // Wait before updating because we just loaded the lookup data.
         interval.tick().await;
-        let _ = update_lookup_data(&mut client, &config).await;
-        // Ignore errors in updates of lookup data after the initial update.
+        // Only update the lookup data in the enclave if new data was downloaded. Ignore errors in
+        // updates of lookup data after the initial update, and retry later.
+        let result = match downloader.fetch().await {
+            Ok(Some(lookup_data)) => {
+                let _guard = update_lock.lock().await;
+                lookup::send_lookup_data(&mut client, shard, &lookup_data, config.max_chunk_size)
+                    .await
+            }
+            Ok(None) => continue,
+            Err(err) => Err(err),
+        };
+        metrics::observe_lookup_data_update(&result);
+        if let Err(err) = result {
+            log::warn!("couldn't update lookup data shard {}: {:?}", shard, err);
+        }
+    }
+}
+
+// Periodically tells the enclave the current time, so that expired lookup data entries are no
+// longer looked up and are reclaimed.
+async fn setup_periodic_expiry(
+    mut client: OakFunctionsAsyncClient<ConnectorHandle>,
+    expiry_interval: Duration,
+) {
+    let mut interval = tokio::time::interval(expiry_interval);
+    loop {
+        interval.tick().await;
+        if let Err(err) = lookup::expire_lookup_data(&mut client).await {
+            log::warn!("couldn't expire lookup data: {:?}", err);
+        }
     }
 }
 
-// Trigger loading of lookup data from lookup data source.
+// Trigger loading of lookup data from lookup data source. Returns the number of entries.
 // Public for convenient testing.
 pub async fn update_lookup_data(
     client: &mut OakFunctionsAsyncClient<ConnectorHandle>,
     config: &LookupDataConfig,
-) -> anyhow::Result<()> {
-    lookup::update_lookup_data(client, &config.lookup_data_path, config.max_chunk_size).await
+) -> anyhow::Result<usize> {
+    let result = async {
+        let urls = match &config.lookup_data_source {
+            LookupDataSource::File(lookup_data_path) => {
+                let lookup_data = lookup::load_lookup_data(lookup_data_path, config.compression)?;
+                let entries = lookup_data.len();
+                lookup::send_lookup_data(client, 0, &lookup_data, config.max_chunk_size).await?;
+                return Ok(entries);
+            }
+            LookupDataSource::Merged {
+                config: merge_config,
+                proxy,
+                credentials,
+            } => {
+                let lookup_data = merge::load(merge_config, proxy, credentials).await?;
+                let entries = lookup_data.len();
+                lookup::send_lookup_data(client, 0, &lookup_data, config.max_chunk_size).await?;
+                return Ok(entries);
+            }
+            LookupDataSource::Url {
+                url,
+                proxy,
+                credentials,
+                integrity,
+            } => vec![(url, proxy, credentials, integrity.clone())],
+            LookupDataSource::Shards {
+                urls,
+                proxy,
+                credentials,
+            } => urls
+                .iter()
+                .map(|url| (url, proxy, credentials, None))
+                .collect(),
+        };
+        let mut entries = 0;
+        for (shard, (url, proxy, credentials, integrity)) in urls.into_iter().enumerate() {
+            let mut downloader =
+                LookupDataDownloader::new(url, proxy.clone(), credentials.clone(), integrity)?;
+            let lookup_data = downloader
+                .fetch()
+                .await?
+                .context("no lookup data available")?;
+            entries += lookup_data.len();
+            lookup::send_lookup_data(client, shard as u32, &lookup_data, config.max_chunk_size)
+                .await?;
+        }
+        Ok(entries)
+    }
+    .await;
+    metrics::observe_lookup_data_update(&result);
+    result
+}
+
+/// Checks that the lookup data source is reachable without loading it into an enclave. A lookup
+/// data file is read and parsed, while lookup data servers and storage objects only receive a
+/// HEAD request, so that the lookup data is not downloaded.
+pub async fn check_lookup_data_source(config: &LookupDataConfig) -> anyhow::Result<()> {
+    let (urls, proxy, credentials) = match &config.lookup_data_source {
+        LookupDataSource::File(lookup_data_path) => {
+            let lookup_data = lookup::load_lookup_data(lookup_data_path, config.compression)?;
+            log::info!(
+                "lookup data file {} has {} entries",
+                lookup_data_path.display(),
+                lookup_data.len()
+            );
+            return Ok(());
+        }
+        LookupDataSource::Merged {
+            config: merge_config,
+            proxy,
+            credentials,
+        } => return merge::check(merge_config, proxy, credentials).await,
+        LookupDataSource::Url {
+            url,
+            proxy,
+            credentials,
+            ..
+        } => (std::slice::from_ref(url), proxy, credentials),
+        LookupDataSource::Shards {
+            urls,
+            proxy,
+            credentials,
+        } => (urls.as_slice(), proxy, credentials),
+    };
+    for url in urls {
+        LookupDataDownloader::new(url, proxy.clone(), credentials.clone(), None)?
+            .probe()
+            .await
+            .with_context(|| format!("lookup data source {url} is not available"))?;
+        log::info!("lookup data source {} is available", url);
+    }
+    Ok(())
 }
 
 // Loads application config (including wasm bytes) into the enclave and returns a remote attestation
 // evidence.
 async fn intialize_enclave(
     connector_handle: channel::ConnectorHandle,
-    wasm: &PathBuf,
-    constant_response_size: u32,
+    wasm_module: Vec<u8>,
+    response_policy: ResponsePolicy,
+    wasm_config: WasmConfig,
 ) -> Result<InitializeResponse, Box<dyn std::error::Error>> {
-    let wasm_bytes = fs::read(wasm)
-        .with_context(|| format!("couldn't read Wasm file {}", wasm.display()))
-        .unwrap();
     log::info!(
-        "read Wasm file from disk {} ({})",
-        &wasm.display(),
-        ubyte::ByteUnit::Byte(wasm_bytes.len() as u64)
+        "sending Wasm module ({})",
+        ubyte::ByteUnit::Byte(wasm_module.len() as u64)
     );
 
     let request = InitializeRequest {
-        wasm_module: wasm_bytes,
-        constant_response_size,
+        wasm_module,
+        constant_response_size: response_policy.constant_response_size,
+        // Zero disables the respective limit in the enclave.
+        max_fuel: wasm_config.max_fuel.unwrap_or_default(),
+        max_memory_pages: wasm_config.max_memory_pages.unwrap_or_default(),
+        instance_pool_size: wasm_config.instance_pool_size,
+        wasm_backend: wasm_config.backend as i32,
+        pad_responses: response_policy.pad_responses,
+        max_range_lookup_entries: wasm_config.max_range_lookup_entries,
+        private_metrics: wasm_config.private_metrics.map(Into::into),
+        sensitive_logging: wasm_config.sensitive_logging,
+        wasi: wasm_config.wasi,
+        response_metadata: wasm_config.response_metadata,
+        reject_plaintext_sessions: wasm_config.reject_plaintext_sessions,
     };
 
     let mut client = OakFunctionsAsyncClient::new(connector_handle);
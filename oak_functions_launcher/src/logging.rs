@@ -0,0 +1,55 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Initialization of the logger of the launcher, with an optional structured JSON format for
+//! ingestion by log pipelines.
+
+use std::io::Write;
+
+/// Format of the log records.
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines, as formatted by `env_logger`.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Initializes the logger. The level and filters are taken from the `RUST_LOG` environment
+/// variable, but `level` overrides the default level if set.
+///
+/// The launcher runs outside the enclave and never has access to the plaintext of requests, so all
+/// of its log records are marked as public.
+pub fn init(level: Option<log::LevelFilter>, format: LogFormat) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if let Some(level) = level {
+        builder.filter_level(level);
+    }
+    if let LogFormat::Json = format {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+                "sensitivity": "public",
+            });
+            writeln!(buf, "{}", line)
+        });
+    }
+    builder.init();
+}
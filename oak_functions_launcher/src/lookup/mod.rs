@@ -0,0 +1,330 @@
+//
+// Copyright 2022 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::{
+    channel::ConnectorHandle,
+    compression::Compression,
+    metrics,
+    proto::oak::functions::{
+        AbortNextLookupDataRequest, ExpireLookupDataRequest, ExtendNextLookupDataRequest,
+        FinishNextLookupDataRequest, LookupDataBackend, LookupDataChunk, LookupDataEntry,
+        OakFunctionsAsyncClient, SetLookupDataShardCountRequest,
+    },
+    trace::{Span, SpanKind},
+};
+use anyhow::{anyhow, Context};
+use hashbrown::HashMap;
+use oak_functions_abi::proto::Entry;
+use prost::Message;
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use ubyte::ByteUnit;
+
+/// The value of a lookup data entry, and the time after which the entry is no longer looked up, in
+/// seconds since the Unix epoch, if it expires.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupValue {
+    pub value: Vec<u8>,
+    pub expires_at: Option<u64>,
+}
+
+/// Lookup data as loaded by the launcher, by key.
+pub type LookupEntries = HashMap<Vec<u8>, LookupValue>;
+
+struct UpdateClient<'a, I: Iterator<Item = LookupDataChunk>> {
+    inner: &'a mut OakFunctionsAsyncClient<ConnectorHandle>,
+    // The shard of the lookup data that is updated.
+    shard: u32,
+    chunks: I,
+}
+
+impl<I: Iterator<Item = LookupDataChunk>> UpdateClient<'_, I> {
+    // Sends all chunks to the Oak Functions Service.
+    async fn update(&mut self) -> anyhow::Result<()> {
+        let mut next = self.chunks.next();
+        while next.is_some() {
+            self.extend(next).await?;
+            next = self.chunks.next();
+        }
+        self.finish().await
+    }
+
+    async fn extend(&mut self, chunk: Option<LookupDataChunk>) -> anyhow::Result<()> {
+        let _ = self
+            .inner
+            .extend_next_lookup_data(&ExtendNextLookupDataRequest {
+                chunk,
+                shard: self.shard,
+            })
+            .await
+            .flatten()
+            .map_err(|err| anyhow!(format!("error handling client request: {:?}", err)))?;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> anyhow::Result<()> {
+        let _ = self
+            .inner
+            .finish_next_lookup_data(&FinishNextLookupDataRequest { shard: self.shard })
+            .await
+            .flatten()
+            .map_err(|err| anyhow!(format!("error handling client request: {:?}", err)));
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    async fn abort(&mut self) -> anyhow::Result<()> {
+        let _ = self
+            .inner
+            .abort_next_lookup_data(&AbortNextLookupDataRequest { shard: self.shard })
+            .await
+            .flatten()
+            .map_err(|err| anyhow!(format!("error handling client request: {:?}", err)));
+        Ok(())
+    }
+}
+
+// Splits the lookup data in the enclave into `shard_count` shards stored in `backend`, and discards
+// its current lookup data.
+pub async fn set_shard_count(
+    client: &mut OakFunctionsAsyncClient<ConnectorHandle>,
+    shard_count: usize,
+    backend: LookupDataBackend,
+) -> anyhow::Result<()> {
+    client
+        .set_lookup_data_shard_count(&SetLookupDataShardCountRequest {
+            shard_count: shard_count.try_into().context("too many shards")?,
+            backend: backend as i32,
+        })
+        .await
+        .flatten()
+        .map_err(|err| anyhow!("couldn't set lookup data shard count: {:?}", err))?;
+    Ok(())
+}
+
+// Tells the enclave the current time, after which it no longer looks up entries that expired by
+// then.
+pub async fn expire_lookup_data(
+    client: &mut OakFunctionsAsyncClient<ConnectorHandle>,
+) -> anyhow::Result<()> {
+    client
+        .expire_lookup_data(&ExpireLookupDataRequest {
+            now_unix_seconds: unix_time_now(),
+        })
+        .await
+        .flatten()
+        .map_err(|err| anyhow!("couldn't expire lookup data: {:?}", err))?;
+    Ok(())
+}
+
+// Encodes lookup data and sends it to the client, replacing the given shard.
+pub async fn send_lookup_data(
+    client: &mut OakFunctionsAsyncClient<ConnectorHandle>,
+    shard: u32,
+    lookup_data: &LookupEntries,
+    max_chunk_size: ByteUnit,
+) -> anyhow::Result<()> {
+    let entries = lookup_data.len();
+    let size_bytes = lookup_data
+        .iter()
+        .map(|(key, value)| key.len() + value.value.len())
+        .sum();
+    let mut span = Span::start("UpdateLookupData", SpanKind::Internal, None);
+    span.set_attribute("oak.shard", shard);
+    span.set_attribute("oak.lookup_data.entries", entries);
+    let chunks = chunk_up_lookup_data(lookup_data, max_chunk_size);
+
+    let result = UpdateClient {
+        inner: client,
+        shard,
+        chunks,
+    }
+    .update()
+    .await;
+    if let Err(err) = &result {
+        span.set_error(format!("{:?}", err));
+    }
+    result?;
+    metrics::set_lookup_data_size(shard, entries, size_bytes);
+    Ok(())
+}
+
+// Splits lookup data into chunks of at most `max_chunk_size`, which are encoded as they are
+// consumed, so that only the chunk being sent is held next to the lookup data.
+fn chunk_up_lookup_data(
+    source_lookup_data: &LookupEntries,
+    max_chunk_size: ByteUnit,
+) -> impl Iterator<Item = LookupDataChunk> + '_ {
+    // We will add the estimated size of ever LookupDataEntry, and to account for the LookupData
+    // overhead, we generously estimate 50 bytes.
+    let chunk_overhead = ByteUnit::Byte(50);
+    // Overestimate delimiter size based on https://github.com/tokio-rs/prost/blob/0c350dc6ad3cd61dc9a1398dffab5ac312f3b245/src/lib.rs#L55
+    let overestimated_delimiter_size = ByteUnit::Byte(10);
+    // A tag and a varint of at most 10 bytes.
+    let overestimated_expiry_size = ByteUnit::Byte(11);
+
+    let mut source = source_lookup_data.iter().peekable();
+    // Even empty lookup data is sent as one (empty) chunk.
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let mut estimated_chunk_size = chunk_overhead;
+        let mut entries = Vec::new();
+        while let Some((key, LookupValue { value, expires_at })) = source.peek() {
+            let mut estimated_entry_size = overestimated_delimiter_size
+                + ByteUnit::Byte(key.len() as u64)
+                + ByteUnit::Byte(value.len() as u64);
+            if expires_at.is_some() {
+                estimated_entry_size += overestimated_expiry_size;
+            }
+
+            // If the next element would exceed the maximum chunk size, leave it for the next chunk.
+            if !entries.is_empty() && estimated_chunk_size + estimated_entry_size > max_chunk_size {
+                break;
+            }
+            estimated_chunk_size += estimated_entry_size;
+
+            entries.push(LookupDataEntry {
+                key: key.to_vec(),
+                value: value.clone(),
+                expires_at_unix_seconds: expires_at.unwrap_or_default(),
+            });
+            source.next();
+        }
+        done = source.peek().is_none();
+        Some(LookupDataChunk { items: entries })
+    })
+}
+
+pub fn load_lookup_data(
+    file_path: &Path,
+    compression: Compression,
+) -> anyhow::Result<LookupEntries> {
+    let bytes = fs::read(file_path).map_err(|error| {
+        anyhow!(
+            "couldn't read the lookup data file {}: {}",
+            file_path.display(),
+            error
+        )
+    })?;
+    let bytes = compression.decompress(bytes).with_context(|| {
+        format!(
+            "couldn't decompress the lookup data file {}",
+            file_path.display()
+        )
+    })?;
+    parse_lookup_entries(bytes.as_slice())
+}
+
+// Parses lookup data, leaving out entries that already expired.
+pub(crate) fn parse_lookup_entries<B: prost::bytes::Buf>(
+    lookup_data_buffer: B,
+) -> anyhow::Result<LookupEntries> {
+    let mut lookup_data_buffer = lookup_data_buffer;
+    let mut entries = HashMap::new();
+    let now = unix_time_now();
+    while lookup_data_buffer.has_remaining() {
+        let entry = Entry::decode_length_delimited(&mut lookup_data_buffer)
+            .context("couldn't decode entry")?;
+        insert_entry(&mut entries, entry, now);
+    }
+    Ok(entries)
+}
+
+/// Parses lookup data that arrives in chunks, so that only the parsed entries and the rest of the
+/// last chunk have to be held, rather than all of the serialized lookup data.
+pub(crate) struct LookupEntriesParser {
+    entries: LookupEntries,
+    // Data of the entry that was not received completely yet.
+    pending: Vec<u8>,
+    now: u64,
+}
+
+impl LookupEntriesParser {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: LookupEntries::new(),
+            pending: Vec::new(),
+            now: unix_time_now(),
+        }
+    }
+
+    /// Parses the entries that were completed by the next chunk of the lookup data, leaving out
+    /// entries that already expired.
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> anyhow::Result<()> {
+        self.pending.extend_from_slice(chunk);
+        let mut remaining = self.pending.as_slice();
+        loop {
+            let mut entry_buffer = remaining;
+            let entry_len = match prost::decode_length_delimiter(&mut entry_buffer) {
+                Ok(entry_len) => entry_len,
+                // A length delimiter takes at most 10 bytes, of which not all may have arrived.
+                Err(_) if remaining.len() < 10 => break,
+                Err(err) => return Err(err).context("couldn't decode entry length"),
+            };
+            if entry_buffer.len() < entry_len {
+                break;
+            }
+            let entry =
+                Entry::decode(&entry_buffer[..entry_len]).context("couldn't decode entry")?;
+            insert_entry(&mut self.entries, entry, self.now);
+            remaining = &entry_buffer[entry_len..];
+        }
+        let parsed = self.pending.len() - remaining.len();
+        self.pending.drain(..parsed);
+        Ok(())
+    }
+
+    /// Returns the parsed lookup data once all chunks were pushed.
+    pub(crate) fn finish(self) -> anyhow::Result<LookupEntries> {
+        if !self.pending.is_empty() {
+            anyhow::bail!(
+                "lookup data ends with an incomplete entry of {} bytes",
+                self.pending.len()
+            );
+        }
+        Ok(self.entries)
+    }
+}
+
+// Inserts an entry into lookup data, or removes its key instead if the entry expired at `now`.
+pub(crate) fn insert_entry(lookup_data: &mut LookupEntries, entry: Entry, now: u64) {
+    let expires_at = Some(entry.expires_at_unix_seconds).filter(|expires_at| *expires_at != 0);
+    if expires_at.map_or(false, |expires_at| expires_at <= now) {
+        lookup_data.remove(&entry.key);
+        return;
+    }
+    let value = LookupValue {
+        value: entry.value,
+        expires_at,
+    };
+    lookup_data.insert(entry.key, value);
+}
+
+// Returns the current time in seconds since the Unix epoch.
+pub(crate) fn unix_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests;
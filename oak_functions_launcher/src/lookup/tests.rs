@@ -0,0 +1,152 @@
+//
+// Copyright 2022 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_chunk_up_lookup_data_in_bound() {
+    let max_chunk_size = ByteUnit::Kibibyte(1);
+
+    // Create data with 8 entries with 100 bytes each.
+    let mut data = hashbrown::HashMap::new();
+    for i in 0..8 {
+        let key = format!("{:050}", i).into_bytes();
+        let value = LookupValue {
+            value: key.clone(),
+            expires_at: None,
+        };
+        data.insert(key, value);
+    }
+    let chunks: Vec<_> = chunk_up_lookup_data(&data, max_chunk_size).collect();
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].items.len(), 8)
+}
+
+#[test]
+fn test_chunk_up_lookup_data_exceed_bound() {
+    let max_chunk_size = ByteUnit::Kibibyte(1);
+
+    // Create data with 9 entries with 100 bytes each accounting for the added overhead.
+    let mut data = hashbrown::HashMap::new();
+    for i in 0..9 {
+        let key = format!("{:050}", i).into_bytes();
+        let value = LookupValue {
+            value: key.clone(),
+            expires_at: None,
+        };
+        data.insert(key, value);
+    }
+
+    let chunks: Vec<_> = chunk_up_lookup_data(&data, max_chunk_size).collect();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].items.len(), 8);
+    assert_eq!(chunks[1].items.len(), 1)
+}
+
+#[test]
+fn test_chunk_up_lookup_data_empty() {
+    let max_chunk_size = ByteUnit::Kibibyte(1);
+    let data = hashbrown::HashMap::new();
+    let chunks: Vec<_> = chunk_up_lookup_data(&data, max_chunk_size).collect();
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].items.len(), 0)
+}
+
+#[test]
+fn test_load_compressed_lookup_data() {
+    let entry = Entry {
+        key: b"key".to_vec(),
+        value: b"value".to_vec(),
+        ..Default::default()
+    };
+    let file = tempfile::NamedTempFile::new().unwrap();
+    fs::write(
+        file.path(),
+        zstd::stream::encode_all(entry.encode_length_delimited_to_vec().as_slice(), 0).unwrap(),
+    )
+    .unwrap();
+
+    let lookup_data = load_lookup_data(file.path(), Compression::Zstd).unwrap();
+    let value = lookup_data.get(b"key".as_slice()).unwrap();
+    assert_eq!(value.value, b"value");
+    assert!(load_lookup_data(file.path(), Compression::None).is_err());
+}
+
+#[test]
+fn test_parse_expiring_lookup_data() {
+    let now = unix_time_now();
+    let entries = [
+        ("expired", now - 1),
+        ("expiring", now + 3600),
+        ("permanent", 0),
+    ];
+    let mut buf = Vec::new();
+    for (key, expires_at_unix_seconds) in entries {
+        let entry = Entry {
+            key: key.as_bytes().to_vec(),
+            value: b"value".to_vec(),
+            expires_at_unix_seconds,
+        };
+        entry.encode_length_delimited(&mut buf).unwrap();
+    }
+
+    let mut lookup_data = parse_lookup_entries(buf.as_slice()).unwrap();
+    assert_eq!(lookup_data.len(), 2);
+    assert_eq!(
+        lookup_data[b"expiring".as_slice()].expires_at,
+        Some(now + 3600)
+    );
+    assert_eq!(lookup_data[b"permanent".as_slice()].expires_at, None);
+
+    // Upserting an entry that expired removes the key.
+    let entry = Entry {
+        key: b"permanent".to_vec(),
+        value: b"value".to_vec(),
+        expires_at_unix_seconds: now,
+    };
+    insert_entry(&mut lookup_data, entry, now);
+    assert!(!lookup_data.contains_key(b"permanent".as_slice()));
+
+    let chunks: Vec<_> = chunk_up_lookup_data(&lookup_data, ByteUnit::Kibibyte(1)).collect();
+    assert_eq!(chunks[0].items[0].expires_at_unix_seconds, now + 3600);
+}
+
+#[test]
+fn test_parse_lookup_data_in_chunks() {
+    let mut buf = Vec::new();
+    for i in 0..100 {
+        let entry = Entry {
+            key: format!("key{}", i).into_bytes(),
+            value: vec![7; i * 10],
+            ..Default::default()
+        };
+        entry.encode_length_delimited(&mut buf).unwrap();
+    }
+
+    for chunk_size in [1, 9, 1000, buf.len()] {
+        let mut parser = LookupEntriesParser::new();
+        for chunk in buf.chunks(chunk_size) {
+            parser.push(chunk).unwrap();
+        }
+        let lookup_data = parser.finish().unwrap();
+        assert_eq!(lookup_data, parse_lookup_entries(buf.as_slice()).unwrap());
+        assert_eq!(lookup_data.len(), 100);
+    }
+
+    let mut parser = LookupEntriesParser::new();
+    parser.push(&buf[..buf.len() - 1]).unwrap();
+    assert!(parser.finish().is_err());
+}
@@ -0,0 +1,433 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Merging of lookup data from several sources into the single keyspace that the Wasm module sees.
+//!
+//! Every source is loaded and refreshed independently, at its own interval. Whenever a source
+//! changes, the latest lookup data of all sources is merged and sent to the enclave, so that the
+//! enclave always holds a merge of complete sources.
+
+use crate::{
+    compression::Compression,
+    download::LookupDataDownloader,
+    lookup::{self, LookupEntries},
+    metrics,
+    object_store::StorageCredentials,
+    proto::oak::functions::OakFunctionsAsyncClient,
+    proxy::ProxyConfig,
+    watch::FileVersion,
+    LookupDataConfig,
+};
+use anyhow::Context;
+use oak_launcher_utils::channel::ConnectorHandle;
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::Mutex;
+
+/// How keys that occur in more than one source are resolved.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// The value from the source that was loaded most recently wins. Sources that are loaded
+    /// together, e.g. initially, are loaded in the order in which they are configured.
+    #[default]
+    LastWriterWins,
+    /// The keys of every source are prefixed with the `prefix` of the source, so that keys of
+    /// different sources never conflict.
+    Namespaced,
+}
+
+/// A single source of lookup data.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SourceConfig {
+    /// Path to a lookup data file. Exactly one of `path` and `url` must be set.
+    pub path: Option<PathBuf>,
+
+    /// Compression of the lookup data file.
+    #[serde(default)]
+    pub compression: Compression,
+
+    /// Base URL of a lookup data server, or URL of an object in cloud storage, as for
+    /// `--lookup-data-url`.
+    pub url: Option<String>,
+
+    /// Interval at which the source is checked for changes.
+    #[serde(default = "default_refresh_interval_seconds")]
+    pub refresh_interval_seconds: u64,
+
+    /// Prefix of the keys of the source. Required by, and only allowed with, the namespaced
+    /// conflict policy.
+    pub prefix: Option<String>,
+}
+
+fn default_refresh_interval_seconds() -> u64 {
+    600
+}
+
+impl SourceConfig {
+    fn description(&self) -> String {
+        match (&self.path, &self.url) {
+            (Some(path), _) => path.display().to_string(),
+            (None, Some(url)) => url.clone(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Lookup data sources and how they are merged, as read from the TOML config file, e.g.
+///
+/// ```toml
+/// conflict_policy = "namespaced"
+///
+/// [[source]]
+/// url = "http://producer-a:8090"
+/// refresh_interval_seconds = 60
+/// prefix = "a/"
+///
+/// [[source]]
+/// path = "producer_b.bin.gz"
+/// compression = "gzip"
+/// prefix = "b/"
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MergeConfig {
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+
+    #[serde(rename = "source")]
+    pub sources: Vec<SourceConfig>,
+}
+
+impl MergeConfig {
+    /// Reads the configuration from a TOML file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let config = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "couldn't read lookup data sources config file {}",
+                path.display()
+            )
+        })?;
+        Self::parse(&config)
+    }
+
+    fn parse(config: &str) -> anyhow::Result<Self> {
+        let config: Self =
+            toml::from_str(config).context("couldn't parse lookup data sources config")?;
+        if config.sources.is_empty() {
+            anyhow::bail!("at least one lookup data source must be configured");
+        }
+        for (index, source) in config.sources.iter().enumerate() {
+            if source.path.is_some() == source.url.is_some() {
+                anyhow::bail!("lookup data source {index} must have exactly one of path and url");
+            }
+            if source.refresh_interval_seconds == 0 {
+                anyhow::bail!("refresh interval of lookup data source {index} must be positive");
+            }
+            match (config.conflict_policy, &source.prefix) {
+                (ConflictPolicy::Namespaced, None) => {
+                    anyhow::bail!("lookup data source {index} needs a prefix to be namespaced")
+                }
+                (ConflictPolicy::Namespaced, Some(prefix)) if prefix.is_empty() => {
+                    anyhow::bail!("prefix of lookup data source {index} must not be empty")
+                }
+                (ConflictPolicy::LastWriterWins, Some(_)) => {
+                    anyhow::bail!("prefixes are only allowed with the namespaced conflict policy")
+                }
+                _ => {}
+            }
+        }
+        // Keys of different sources could still conflict if one prefix started with another.
+        let prefixes: Vec<&String> = config
+            .sources
+            .iter()
+            .filter_map(|source| source.prefix.as_ref())
+            .collect();
+        for (index, prefix) in prefixes.iter().enumerate() {
+            for other in &prefixes[index + 1..] {
+                if prefix.starts_with(other.as_str()) || other.starts_with(prefix.as_str()) {
+                    anyhow::bail!("prefixes {prefix:?} and {other:?} overlap");
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// The latest lookup data of every source.
+struct Merger {
+    conflict_policy: ConflictPolicy,
+    prefixes: Vec<Vec<u8>>,
+    // The lookup data of every source, if it was loaded, with the sequence number of its load.
+    sources: Vec<Option<(u64, Arc<LookupEntries>)>>,
+    loads: u64,
+}
+
+impl Merger {
+    fn new(config: &MergeConfig) -> Self {
+        Self {
+            conflict_policy: config.conflict_policy,
+            prefixes: config
+                .sources
+                .iter()
+                .map(|source| source.prefix.clone().unwrap_or_default().into_bytes())
+                .collect(),
+            sources: vec![None; config.sources.len()],
+            loads: 0,
+        }
+    }
+
+    /// Replaces the lookup data of the source at `index`.
+    fn update(&mut self, index: usize, lookup_data: Arc<LookupEntries>) {
+        self.loads += 1;
+        self.sources[index] = Some((self.loads, lookup_data));
+    }
+
+    /// Returns the merged lookup data of all sources.
+    fn merge(&self) -> LookupEntries {
+        let mut sources: Vec<(u64, &[u8], &LookupEntries)> = self
+            .sources
+            .iter()
+            .zip(&self.prefixes)
+            .filter_map(|(source, prefix)| {
+                let (load, lookup_data) = source.as_ref()?;
+                Some((*load, prefix.as_slice(), lookup_data.as_ref()))
+            })
+            .collect();
+        // Later loads overwrite the values of earlier ones.
+        sources.sort_by_key(|(load, _, _)| *load);
+        let mut merged = LookupEntries::new();
+        for (_, prefix, lookup_data) in sources {
+            for (key, value) in lookup_data {
+                let key = match self.conflict_policy {
+                    ConflictPolicy::LastWriterWins => key.clone(),
+                    ConflictPolicy::Namespaced => [prefix, key.as_slice()].concat(),
+                };
+                merged.insert(key, value.clone());
+            }
+        }
+        merged
+    }
+}
+
+/// Loads the lookup data of a single source, unless it is unchanged since the last load.
+enum SourceLoader {
+    File {
+        path: PathBuf,
+        compression: Compression,
+        version: Option<FileVersion>,
+    },
+    Download(LookupDataDownloader),
+}
+
+impl SourceLoader {
+    fn new(
+        source: &SourceConfig,
+        proxy: &Option<ProxyConfig>,
+        credentials: &StorageCredentials,
+    ) -> anyhow::Result<Self> {
+        match (&source.path, &source.url) {
+            (Some(path), _) => Ok(Self::File {
+                path: path.clone(),
+                compression: source.compression,
+                version: None,
+            }),
+            (None, Some(url)) => Ok(Self::Download(LookupDataDownloader::new(
+                url,
+                proxy.clone(),
+                credentials.clone(),
+                None,
+            )?)),
+            (None, None) => anyhow::bail!("lookup data source has neither path nor url"),
+        }
+    }
+
+    async fn fetch(&mut self) -> anyhow::Result<Option<Arc<LookupEntries>>> {
+        match self {
+            Self::File {
+                path,
+                compression,
+                version,
+            } => {
+                // Determine the version before loading, so that changes during loading cause
+                // another load.
+                let current_version = FileVersion::of(path).ok();
+                if current_version.is_some() && current_version == *version {
+                    return Ok(None);
+                }
+                let lookup_data = lookup::load_lookup_data(path, *compression)?;
+                *version = current_version;
+                Ok(Some(Arc::new(lookup_data)))
+            }
+            Self::Download(downloader) => downloader.fetch().await,
+        }
+    }
+
+    /// Checks that the source is available, without downloading it.
+    async fn probe(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::File { .. } => self.fetch().await.map(|_| ()),
+            Self::Download(downloader) => downloader.probe().await,
+        }
+    }
+}
+
+/// Initially loads all sources and sends their merged lookup data to the enclave, retrying each
+/// source as configured. Then spawns a task per source that refreshes it at its own interval,
+/// unless updates are disabled.
+pub(crate) async fn setup(
+    connector_handle: &ConnectorHandle,
+    config: &LookupDataConfig,
+    merge_config: &MergeConfig,
+    proxy: &Option<ProxyConfig>,
+    credentials: &StorageCredentials,
+    update_lock: Arc<Mutex<()>>,
+) -> anyhow::Result<()> {
+    let mut merger = Merger::new(merge_config);
+    let mut loaders = Vec::new();
+    for (index, source) in merge_config.sources.iter().enumerate() {
+        let description = format!("load lookup data source {}", source.description());
+        let result = config
+            .initial_load_retry
+            .retry(&description, move || async move {
+                let mut loader = SourceLoader::new(source, proxy, credentials)?;
+                let lookup_data = loader.fetch().await?.context("no lookup data available")?;
+                Ok((loader, lookup_data))
+            })
+            .await;
+        match result {
+            Ok((loader, lookup_data)) => {
+                merger.update(index, lookup_data);
+                loaders.push(loader);
+            }
+            Err(err) => {
+                crate::start_without_lookup_data(config, err)?;
+                // A fresh loader loads the source on the first periodic update.
+                loaders.push(SourceLoader::new(source, proxy, credentials)?);
+            }
+        }
+    }
+
+    // Block for [invariant that lookup data is fully loaded](https://github.com/project-oak/oak/tree/main/oak_functions/lookup/README.md#invariant-fully-loaded-lookup-data)
+    let mut client = OakFunctionsAsyncClient::new(connector_handle.clone());
+    {
+        let _guard = update_lock.lock().await;
+        lookup::send_lookup_data(&mut client, 0, &merger.merge(), config.max_chunk_size).await?;
+    }
+
+    if config.update_interval.is_none() {
+        return Ok(());
+    }
+    let merger = Arc::new(Mutex::new(merger));
+    for (index, (source, loader)) in merge_config.sources.iter().zip(loaders).enumerate() {
+        tokio::spawn(refresh_periodically(
+            OakFunctionsAsyncClient::new(connector_handle.clone()),
+            config.clone(),
+            update_lock.clone(),
+            merger.clone(),
+            loader,
+            index,
+            Duration::from_secs(source.refresh_interval_seconds),
+        ));
+    }
+    Ok(())
+}
+
+// Periodically loads the source at `index`, and sends the merged lookup data to the enclave
+// whenever the source changed.
+async fn refresh_periodically(
+    mut client: OakFunctionsAsyncClient<ConnectorHandle>,
+    config: LookupDataConfig,
+    update_lock: Arc<Mutex<()>>,
+    merger: Arc<Mutex<Merger>>,
+    mut loader: SourceLoader,
+    index: usize,
+    refresh_interval: Duration,
+) {
+    let mut interval = tokio::time::interval_at(
+        tokio::time::Instant::now() + refresh_interval,
+        refresh_interval,
+    );
+    loop {
+        interval.tick().await;
+        // Ignore errors in updates of lookup data after the initial update, and retry later.
+        let result = match loader.fetch().await {
+            Ok(Some(lookup_data)) => {
+                // The merger stays locked until the merge is sent, so that merges are sent to
+                // the enclave in the order in which they are made.
+                let mut merger = merger.lock().await;
+                merger.update(index, lookup_data);
+                let merged = merger.merge();
+                let _guard = update_lock.lock().await;
+                lookup::send_lookup_data(&mut client, 0, &merged, config.max_chunk_size).await
+            }
+            Ok(None) => continue,
+            Err(err) => Err(err),
+        };
+        metrics::observe_lookup_data_update(&result);
+        if let Err(err) = result {
+            log::warn!("couldn't update lookup data source {}: {:?}", index, err);
+        }
+    }
+}
+
+/// Loads all sources right away, in the order in which they are configured, and returns their
+/// merged lookup data.
+pub(crate) async fn load(
+    merge_config: &MergeConfig,
+    proxy: &Option<ProxyConfig>,
+    credentials: &StorageCredentials,
+) -> anyhow::Result<LookupEntries> {
+    let mut merger = Merger::new(merge_config);
+    for (index, source) in merge_config.sources.iter().enumerate() {
+        let lookup_data = SourceLoader::new(source, proxy, credentials)?
+            .fetch()
+            .await?
+            .with_context(|| format!("no lookup data available from {}", source.description()))?;
+        merger.update(index, lookup_data);
+    }
+    Ok(merger.merge())
+}
+
+/// Checks that all sources are available. Lookup data files are read and parsed, while lookup
+/// data servers and storage objects only receive a HEAD request.
+pub(crate) async fn check(
+    merge_config: &MergeConfig,
+    proxy: &Option<ProxyConfig>,
+    credentials: &StorageCredentials,
+) -> anyhow::Result<()> {
+    for source in &merge_config.sources {
+        SourceLoader::new(source, proxy, credentials)?
+            .probe()
+            .await
+            .with_context(|| {
+                format!(
+                    "lookup data source {} is not available",
+                    source.description()
+                )
+            })?;
+        log::info!("lookup data source {} is available", source.description());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,118 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_parse() {
+    let config = MergeConfig::parse(
+        r#"
+            conflict_policy = "namespaced"
+
+            [[source]]
+            url = "http://producer-a:8090"
+            refresh_interval_seconds = 60
+            prefix = "a/"
+
+            [[source]]
+            path = "producer_b.bin.gz"
+            compression = "gzip"
+            prefix = "b/"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config,
+        MergeConfig {
+            conflict_policy: ConflictPolicy::Namespaced,
+            sources: vec![
+                SourceConfig {
+                    path: None,
+                    compression: Compression::None,
+                    url: Some("http://producer-a:8090".to_string()),
+                    refresh_interval_seconds: 60,
+                    prefix: Some("a/".to_string()),
+                },
+                SourceConfig {
+                    path: Some(PathBuf::from("producer_b.bin.gz")),
+                    compression: Compression::Gzip,
+                    url: None,
+                    refresh_interval_seconds: 600,
+                    prefix: Some("b/".to_string()),
+                },
+            ],
+        }
+    );
+
+    for invalid_config in [
+        "source = []",
+        "[[source]]\npath = \"a.bin\"\nurl = \"http://a\"",
+        "[[source]]\npath = \"a.bin\"\nrefresh_interval_seconds = 0",
+        "[[source]]\npath = \"a.bin\"\nprefix = \"a/\"",
+        "conflict_policy = \"namespaced\"\n[[source]]\npath = \"a.bin\"",
+        "conflict_policy = \"namespaced\"\n[[source]]\npath = \"a.bin\"\nprefix = \"a\"\n\
+         [[source]]\npath = \"b.bin\"\nprefix = \"ab\"",
+    ] {
+        assert!(MergeConfig::parse(invalid_config).is_err());
+    }
+}
+
+#[test]
+fn test_merge() {
+    use crate::lookup::LookupValue;
+
+    fn lookup_data(entries: &[(&str, &str)]) -> LookupEntries {
+        entries
+            .iter()
+            .map(|(key, value)| {
+                let value = LookupValue {
+                    value: value.as_bytes().to_vec(),
+                    expires_at: None,
+                };
+                (key.as_bytes().to_vec(), value)
+            })
+            .collect()
+    }
+    let source = |prefix: Option<&str>| SourceConfig {
+        path: Some(PathBuf::from("lookup_data.bin")),
+        compression: Compression::None,
+        url: None,
+        refresh_interval_seconds: 600,
+        prefix: prefix.map(String::from),
+    };
+
+    let mut merger = Merger::new(&MergeConfig {
+        conflict_policy: ConflictPolicy::LastWriterWins,
+        sources: vec![source(None), source(None)],
+    });
+    merger.update(0, Arc::new(lookup_data(&[("a", "0"), ("b", "0")])));
+    merger.update(1, Arc::new(lookup_data(&[("b", "1"), ("c", "1")])));
+    assert_eq!(
+        merger.merge(),
+        lookup_data(&[("a", "0"), ("b", "1"), ("c", "1")])
+    );
+    merger.update(0, Arc::new(lookup_data(&[("b", "2")])));
+    assert_eq!(merger.merge(), lookup_data(&[("b", "2"), ("c", "1")]));
+
+    let mut merger = Merger::new(&MergeConfig {
+        conflict_policy: ConflictPolicy::Namespaced,
+        sources: vec![source(Some("x/")), source(Some("y/"))],
+    });
+    merger.update(1, Arc::new(lookup_data(&[("b", "1")])));
+    assert_eq!(merger.merge(), lookup_data(&[("y/b", "1")]));
+    merger.update(0, Arc::new(lookup_data(&[("b", "0")])));
+    assert_eq!(merger.merge(), lookup_data(&[("x/b", "0"), ("y/b", "1")]));
+}
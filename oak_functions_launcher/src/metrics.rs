@@ -0,0 +1,187 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Prometheus metrics about the invocations handled by the launcher and the lookup data it
+//! provides to the enclave.
+//!
+//! Invocation latencies are measured on the host, so they include the execution of the Wasm module
+//! in the enclave as well as the communication with the enclave.
+
+use anyhow::Context;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, Encoder,
+    HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use std::{convert::Infallible, net::SocketAddr, time::Instant};
+
+/// The path at which the metrics are served.
+pub const METRICS_PATH: &str = "/metrics";
+
+static INVOCATIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "oak_functions_launcher_invocations_total",
+        "Number of invocations, by transport, route and outcome.",
+        &["transport", "route", "outcome"]
+    )
+    .expect("couldn't register metric")
+});
+
+static INVOCATION_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "oak_functions_launcher_invocation_duration_seconds",
+        "Duration of invocations of the enclave, by transport and route.",
+        &["transport", "route"]
+    )
+    .expect("couldn't register metric")
+});
+
+static REJECTED_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "oak_functions_launcher_rejected_requests_total",
+        "Number of invocations rejected because they exceeded a request limit, by transport, route \
+         and reason.",
+        &["transport", "route", "reason"]
+    )
+    .expect("couldn't register metric")
+});
+
+static LOOKUP_DATA_ENTRIES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "oak_functions_launcher_lookup_data_entries",
+        "Number of entries of the lookup data that was last sent to the enclave, by shard.",
+        &["shard"]
+    )
+    .expect("couldn't register metric")
+});
+
+static LOOKUP_DATA_SIZE_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "oak_functions_launcher_lookup_data_size_bytes",
+        "Total size of the keys and values of the lookup data that was last sent to the enclave, by \
+         shard.",
+        &["shard"]
+    )
+    .expect("couldn't register metric")
+});
+
+static LOOKUP_DATA_UPDATES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "oak_functions_launcher_lookup_data_updates_total",
+        "Number of attempted updates of the lookup data, by outcome.",
+        &["outcome"]
+    )
+    .expect("couldn't register metric")
+});
+
+/// Records an invocation of the Wasm module of `route`, received via `transport`, that started at
+/// `start`.
+pub fn observe_invocation<T, E>(
+    transport: &str,
+    route: &str,
+    start: Instant,
+    result: &Result<T, E>,
+) {
+    INVOCATION_DURATION
+        .with_label_values(&[transport, route])
+        .observe(start.elapsed().as_secs_f64());
+    INVOCATIONS
+        .with_label_values(&[transport, route, outcome(result)])
+        .inc();
+}
+
+/// Records an invocation of the Wasm module of `route`, received via `transport`, that was rejected
+/// for `reason` before reaching the enclave.
+pub fn observe_rejection(transport: &str, route: &str, reason: &str) {
+    REJECTED_REQUESTS
+        .with_label_values(&[transport, route, reason])
+        .inc();
+}
+
+/// Records an attempted update of the lookup data.
+pub fn observe_lookup_data_update<T, E>(result: &Result<T, E>) {
+    LOOKUP_DATA_UPDATES
+        .with_label_values(&[outcome(result)])
+        .inc();
+}
+
+/// Records the size of the lookup data that was sent to the enclave for `shard`.
+pub fn set_lookup_data_size(shard: u32, entries: usize, size_bytes: usize) {
+    let shard = shard.to_string();
+    LOOKUP_DATA_ENTRIES
+        .with_label_values(&[&shard])
+        .set(entries as i64);
+    LOOKUP_DATA_SIZE_BYTES
+        .with_label_values(&[&shard])
+        .set(size_bytes as i64);
+}
+
+fn outcome<T, E>(result: &Result<T, E>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(_) => "error",
+    }
+}
+
+/// Serves the metrics in the Prometheus text format on [`METRICS_PATH`].
+pub async fn serve(addr: SocketAddr) -> anyhow::Result<()> {
+    // Register all metrics, so that they are exported before they are first recorded.
+    Lazy::force(&INVOCATIONS);
+    Lazy::force(&INVOCATION_DURATION);
+    Lazy::force(&REJECTED_REQUESTS);
+    Lazy::force(&LOOKUP_DATA_ENTRIES);
+    Lazy::force(&LOOKUP_DATA_SIZE_BYTES);
+    Lazy::force(&LOOKUP_DATA_UPDATES);
+
+    let make_service = make_service_fn(|_connection| async {
+        Ok::<_, Infallible>(service_fn(|request| async move {
+            Ok::<_, Infallible>(handle(request))
+        }))
+    });
+    Server::bind(&addr)
+        .serve(make_service)
+        .await
+        .context("metrics server failed")
+}
+
+fn handle(request: Request<Body>) -> Response<Body> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, METRICS_PATH) => {
+            let encoder = TextEncoder::new();
+            let mut body = Vec::new();
+            match encoder.encode(&prometheus::gather(), &mut body) {
+                Ok(()) => Response::builder()
+                    .header(CONTENT_TYPE, encoder.format_type())
+                    .body(Body::from(body)),
+                Err(err) => {
+                    log::error!("couldn't encode metrics: {:?}", err);
+                    Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .body(Body::empty())
+                }
+            }
+        }
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    response.expect("couldn't build response")
+}
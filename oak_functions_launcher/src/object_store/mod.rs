@@ -0,0 +1,315 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Download of lookup data snapshots from objects in Google Cloud Storage and Amazon S3, which are
+//! referenced by `gs://bucket/object` and `s3://bucket/key` URLs respectively.
+//!
+//! Requests for Google Cloud Storage are authenticated with an OAuth 2.0 access token, which is
+//! either read from a file or requested from the metadata server of the Compute Engine instance.
+//! Requests for Amazon S3 are signed with AWS Signature Version 4.
+
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use hyper::{
+    client::HttpConnector, header::AUTHORIZATION, http::request, Body, Client, Method, Request,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// URL from which the metadata server of a Compute Engine instance provides access tokens of the
+/// default service account.
+const METADATA_SERVER_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Time before the expiry of an access token at which it is refreshed.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Payload hash of signed S3 requests, whose payload is not signed.
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// Credentials with which objects are downloaded from cloud storage.
+#[derive(Clone, Default)]
+pub struct StorageCredentials {
+    /// How requests for objects in Google Cloud Storage are authenticated.
+    pub gcs: GcsCredentials,
+    /// Credentials for Amazon S3. Objects are requested anonymously if not given.
+    pub aws: Option<AwsCredentials>,
+}
+
+/// How requests for objects in Google Cloud Storage are authenticated.
+#[derive(Clone, Debug, Default)]
+pub enum GcsCredentials {
+    /// Objects are requested anonymously.
+    #[default]
+    Anonymous,
+    /// A file that contains an OAuth 2.0 access token. The file is read for every request, so
+    /// that the token can be refreshed while the launcher is running.
+    AccessTokenFile(PathBuf),
+    /// Access tokens of the default service account are requested from the metadata server of
+    /// the Compute Engine instance, and refreshed before they expire.
+    MetadataServer,
+}
+
+/// Credentials with which requests for objects in Amazon S3 are signed.
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Token of temporary credentials.
+    pub session_token: Option<String>,
+    pub region: String,
+}
+
+impl AwsCredentials {
+    /// Returns the credentials configured in the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+    /// and `AWS_SESSION_TOKEN` environment variables, if any. The region is taken from
+    /// `AWS_REGION` or `AWS_DEFAULT_REGION`, and defaults to `us-east-1`.
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            access_key_id: env_var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: env_var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: env_var("AWS_SESSION_TOKEN"),
+            region: env_var("AWS_REGION")
+                .or_else(|| env_var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|| "us-east-1".to_string()),
+        })
+    }
+
+    /// Returns the value of the `Authorization` header of a request with `method` for `path`,
+    /// following AWS Signature Version 4. `headers` are the signed headers with lowercase names,
+    /// which must include `host`, `x-amz-content-sha256` and `x-amz-date`.
+    fn authorization(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &BTreeMap<&str, String>,
+    ) -> String {
+        let timestamp = &headers["x-amz-date"];
+        let scope = format!("{}/{}/s3/aws4_request", &timestamp[..8], self.region);
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers.keys().copied().collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "{}\n{}\n\n{}\n{}\n{}",
+            method, path, canonical_headers, signed_headers, headers["x-amz-content-sha256"]
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            hex::encode(Sha256::digest(canonical_request))
+        );
+
+        let key = [&timestamp[..8], self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", self.secret_access_key).into_bytes(),
+                |key, data| hmac_sha256(&key, data),
+            );
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id,
+            scope,
+            signed_headers,
+            hex::encode(hmac_sha256(&key, &string_to_sign))
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Location {
+    Gcs { bucket: String, object: String },
+    S3 { bucket: String, key: String },
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// An object in cloud storage that contains a snapshot of the lookup data.
+pub struct StorageObject {
+    location: Location,
+    credentials: StorageCredentials,
+    // Access token from the metadata server and the time at which it expires.
+    access_token: Option<(String, Instant)>,
+    metadata_client: Client<HttpConnector>,
+}
+
+impl StorageObject {
+    /// Returns the object referenced by `url`, or `None` if `url` is not a `gs://` or `s3://`
+    /// URL.
+    pub fn parse(url: &str, credentials: StorageCredentials) -> anyhow::Result<Option<Self>> {
+        let location = match parse_location(url)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        Ok(Some(Self {
+            location,
+            credentials,
+            access_token: None,
+            metadata_client: Client::new(),
+        }))
+    }
+
+    /// Returns an authenticated request with `method` (GET or HEAD) for the object.
+    pub async fn request(&mut self, method: Method) -> anyhow::Result<request::Builder> {
+        match &self.location {
+            Location::Gcs { bucket, object } => {
+                let url = format!(
+                    "https://storage.googleapis.com/{}/{}",
+                    bucket,
+                    encode_path(object)
+                );
+                let request = Request::builder().method(method).uri(url);
+                Ok(match self.gcs_access_token().await? {
+                    Some(token) => request.header(AUTHORIZATION, format!("Bearer {}", token)),
+                    None => request,
+                })
+            }
+            Location::S3 { bucket, key } => {
+                let region = match &self.credentials.aws {
+                    Some(aws) => aws.region.as_str(),
+                    None => "us-east-1",
+                };
+                let host = format!("{}.s3.{}.amazonaws.com", bucket, region);
+                let path = format!("/{}", encode_path(key));
+                let request = Request::builder()
+                    .method(method.clone())
+                    .uri(format!("https://{}{}", host, path));
+                let aws = match &self.credentials.aws {
+                    Some(aws) => aws,
+                    None => return Ok(request),
+                };
+
+                let mut headers = BTreeMap::from([
+                    ("host", host),
+                    ("x-amz-content-sha256", UNSIGNED_PAYLOAD.to_string()),
+                    (
+                        "x-amz-date",
+                        chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
+                    ),
+                ]);
+                if let Some(session_token) = &aws.session_token {
+                    headers.insert("x-amz-security-token", session_token.clone());
+                }
+                let authorization = aws.authorization(&method, &path, &headers);
+                Ok(headers
+                    .into_iter()
+                    .fold(request, |request, (name, value)| {
+                        request.header(name, value)
+                    })
+                    .header(AUTHORIZATION, authorization))
+            }
+        }
+    }
+
+    /// Returns the access token for Google Cloud Storage, if requests are authenticated.
+    async fn gcs_access_token(&mut self) -> anyhow::Result<Option<String>> {
+        match &self.credentials.gcs {
+            GcsCredentials::Anonymous => Ok(None),
+            GcsCredentials::AccessTokenFile(path) => {
+                let token = std::fs::read_to_string(path).with_context(|| {
+                    format!("couldn't read access token file {}", path.display())
+                })?;
+                Ok(Some(token.trim().to_string()))
+            }
+            GcsCredentials::MetadataServer => {
+                if let Some((token, expiry)) = &self.access_token {
+                    if Instant::now() + TOKEN_EXPIRY_MARGIN < *expiry {
+                        return Ok(Some(token.clone()));
+                    }
+                }
+                let token = self.request_metadata_server_token().await?;
+                let expiry = Instant::now() + Duration::from_secs(token.expires_in);
+                self.access_token = Some((token.access_token.clone(), expiry));
+                Ok(Some(token.access_token))
+            }
+        }
+    }
+
+    async fn request_metadata_server_token(&self) -> anyhow::Result<AccessToken> {
+        let request = Request::get(METADATA_SERVER_TOKEN_URL)
+            .header("Metadata-Flavor", "Google")
+            .body(Body::empty())?;
+        let response = self
+            .metadata_client
+            .request(request)
+            .await
+            .context("couldn't request access token from metadata server")?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("request for access token failed with status {}", status);
+        }
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .context("couldn't read access token from metadata server")?;
+        serde_json::from_slice(&body).context("couldn't parse access token")
+    }
+}
+
+/// Parses a `gs://bucket/object` or `s3://bucket/key` URL, and returns `None` for other URLs.
+fn parse_location(url: &str) -> anyhow::Result<Option<Location>> {
+    let (scheme, path) = match url.split_once("://") {
+        Some((scheme, path)) if scheme == "gs" || scheme == "s3" => (scheme, path),
+        _ => return Ok(None),
+    };
+    let (bucket, name) = path
+        .split_once('/')
+        .filter(|(bucket, name)| !bucket.is_empty() && !name.is_empty())
+        .with_context(|| format!("URL {} has no bucket or object name", url))?;
+    let (bucket, name) = (bucket.to_string(), name.to_string());
+    Ok(Some(match scheme {
+        "gs" => Location::Gcs {
+            bucket,
+            object: name,
+        },
+        _ => Location::S3 { bucket, key: name },
+    }))
+}
+
+/// Percent-encodes an object name for use in the path of a URL, keeping the slashes.
+fn encode_path(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,97 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Configuration of the differentially private metrics that the Wasm module reports through the
+//! `report_metric` ABI.
+//!
+//! The enclave aggregates the reported buckets over batches of invocations and only releases the
+//! counts of complete batches, with discrete Laplace noise according to the privacy budget. The
+//! released counts are logged by the enclave.
+
+use crate::proto::oak::functions;
+use anyhow::Context;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Privacy budget and buckets of the metrics, as read from the TOML config file, e.g.
+///
+/// ```toml
+/// epsilon = 1.0
+/// batch_size = 1000
+/// buckets = ["lookup_hit", "lookup_miss"]
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PrivateMetricsConfig {
+    /// Privacy budget that each released batch of counts consumes.
+    pub epsilon: f64,
+
+    /// Number of invocations that are aggregated before the counts are released.
+    pub batch_size: u32,
+
+    /// Names of the buckets that the Wasm module may report.
+    pub buckets: Vec<String>,
+}
+
+impl PrivateMetricsConfig {
+    /// Reads the configuration from a TOML file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let config = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "couldn't read private metrics config file {}",
+                path.display()
+            )
+        })?;
+        Self::parse(&config)
+    }
+
+    fn parse(config: &str) -> anyhow::Result<Self> {
+        let config: Self =
+            toml::from_str(config).context("couldn't parse private metrics config")?;
+        if !(config.epsilon.is_finite() && config.epsilon > 0.0) {
+            anyhow::bail!("epsilon must be positive, got {}", config.epsilon);
+        }
+        if config.batch_size == 0 {
+            anyhow::bail!("batch size must be positive");
+        }
+        if config.buckets.is_empty() {
+            anyhow::bail!("at least one bucket must be configured");
+        }
+        // The enclave rejects configurations whose noise would take too long to sample, see
+        // `oak_functions_private_metrics::MAX_NOISE_SCALE`.
+        if config.buckets.len() as f64 / config.epsilon > 10_000.0 {
+            anyhow::bail!(
+                "epsilon {} is too small for {} buckets",
+                config.epsilon,
+                config.buckets.len()
+            );
+        }
+        Ok(config)
+    }
+}
+
+impl From<PrivateMetricsConfig> for functions::PrivateMetricsConfig {
+    fn from(config: PrivateMetricsConfig) -> Self {
+        Self {
+            epsilon: config.epsilon,
+            batch_size: config.batch_size,
+            buckets: config.buckets,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
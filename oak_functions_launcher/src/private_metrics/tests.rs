@@ -0,0 +1,47 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_parse() {
+    let config = PrivateMetricsConfig::parse(
+        r#"
+            epsilon = 0.5
+            batch_size = 100
+            buckets = ["lookup_hit", "lookup_miss"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config,
+        PrivateMetricsConfig {
+            epsilon: 0.5,
+            batch_size: 100,
+            buckets: vec!["lookup_hit".to_string(), "lookup_miss".to_string()],
+        }
+    );
+
+    for invalid_config in [
+        "epsilon = 0.5\nbatch_size = 100\nbuckets = []",
+        "epsilon = 0.0\nbatch_size = 100\nbuckets = [\"a\"]",
+        "epsilon = 0.5\nbatch_size = 0\nbuckets = [\"a\"]",
+        "epsilon = 0.0001\nbatch_size = 100\nbuckets = [\"a\", \"b\"]",
+        "epsilon = 0.5\nbuckets = [\"a\"]",
+    ] {
+        assert!(PrivateMetricsConfig::parse(invalid_config).is_err());
+    }
+}
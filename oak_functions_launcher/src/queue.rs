@@ -0,0 +1,165 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Consumption of requests from a message queue, as an alternative or in addition to serving
+//! client sessions, for batch or asynchronous processing.
+//!
+//! Queued requests contain the same encrypted bodies as the `InvokeRequest` messages of a client
+//! session, and are handled by the enclave in exactly the same way. The responses are published
+//! to the output of the queue, correlated by the identifier of the request.
+
+use crate::{
+    metrics,
+    proto::oak::functions::{
+        queue::v1::{
+            publish_request, request_queue_client::RequestQueueClient, PublishRequest, PullRequest,
+        },
+        InvokeRequest, OakFunctionsAsyncClient,
+    },
+    proxy::ProxyConfig,
+    server::DEFAULT_ROUTE,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use oak_launcher_utils::channel::ConnectorHandle;
+use std::time::{Duration, Instant};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tower::service_fn;
+
+pub use crate::proto::oak::functions::queue::v1::QueuedRequest;
+
+/// Maximum number of requests that are pulled from the queue at once.
+const MAX_MESSAGES_PER_PULL: u32 = 16;
+
+/// A source of queued requests.
+#[async_trait]
+pub trait RequestSource: Send {
+    /// Returns the next pending requests, or an empty list if there are none.
+    async fn pull(&mut self) -> anyhow::Result<Vec<QueuedRequest>>;
+}
+
+/// A destination for the responses to queued requests.
+#[async_trait]
+pub trait ResponseSink: Send {
+    /// Publishes the encrypted response to the request with the given identifier, or the error
+    /// that prevented the request from being handled.
+    async fn publish(&mut self, id: String, result: anyhow::Result<Vec<u8>>) -> anyhow::Result<()>;
+}
+
+/// A queue that is accessed via the pull-based `RequestQueue` gRPC service.
+#[derive(Clone)]
+pub struct GrpcQueue {
+    client: RequestQueueClient<Channel>,
+}
+
+impl GrpcQueue {
+    /// Connects to the queue at `address`, through `proxy` if it is set.
+    pub async fn connect(address: String, proxy: Option<ProxyConfig>) -> anyhow::Result<Self> {
+        let endpoint = Endpoint::from_shared(address.clone())
+            .with_context(|| format!("invalid request queue address {}", address))?;
+        let channel = match proxy {
+            Some(proxy) => {
+                endpoint
+                    .connect_with_connector(service_fn(move |uri: Uri| {
+                        let proxy = proxy.clone();
+                        async move { proxy.connect(uri).await }
+                    }))
+                    .await
+            }
+            None => endpoint.connect().await,
+        }
+        .with_context(|| format!("couldn't connect to request queue at {}", address))?;
+        Ok(Self {
+            client: RequestQueueClient::new(channel),
+        })
+    }
+}
+
+#[async_trait]
+impl RequestSource for GrpcQueue {
+    async fn pull(&mut self) -> anyhow::Result<Vec<QueuedRequest>> {
+        let response = self
+            .client
+            .pull(PullRequest {
+                max_messages: MAX_MESSAGES_PER_PULL,
+            })
+            .await
+            .context("couldn't pull requests from queue")?;
+        Ok(response.into_inner().requests)
+    }
+}
+
+#[async_trait]
+impl ResponseSink for GrpcQueue {
+    async fn publish(&mut self, id: String, result: anyhow::Result<Vec<u8>>) -> anyhow::Result<()> {
+        let result = match result {
+            Ok(encrypted_body) => publish_request::Result::EncryptedBody(encrypted_body),
+            Err(err) => publish_request::Result::Error(format!("{:?}", err)),
+        };
+        self.client
+            .publish(PublishRequest {
+                id,
+                result: Some(result),
+            })
+            .await
+            .context("couldn't publish response to queue")?;
+        Ok(())
+    }
+}
+
+/// Consumes requests from `source`, handles them in the enclave and publishes the responses to
+/// `sink`. Polls the source every `poll_interval` while it is empty.
+///
+/// Only returns if the source or the sink fail.
+pub async fn consume<Source: RequestSource, Sink: ResponseSink>(
+    mut source: Source,
+    mut sink: Sink,
+    connector_handle: ConnectorHandle,
+    poll_interval: Duration,
+) -> anyhow::Result<()> {
+    loop {
+        let requests = source.pull().await?;
+        if requests.is_empty() {
+            tokio::time::sleep(poll_interval).await;
+            continue;
+        }
+        for request in requests {
+            log::debug!("handling queued request {:?}", request.id);
+            let start = Instant::now();
+            let result = invoke(connector_handle.clone(), request.encrypted_body).await;
+            metrics::observe_invocation("queue", DEFAULT_ROUTE, start, &result);
+            if let Err(err) = &result {
+                log::warn!("couldn't handle queued request {:?}: {:?}", request.id, err);
+            }
+            sink.publish(request.id, result).await?;
+        }
+    }
+}
+
+async fn invoke(
+    connector_handle: ConnectorHandle,
+    encrypted_body: Vec<u8>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut client = OakFunctionsAsyncClient::new(connector_handle);
+    let response = client
+        .invoke(&InvokeRequest {
+            body: encrypted_body,
+        })
+        .await
+        .flatten()
+        .map_err(|err| anyhow::anyhow!("error handling queued request: {:?}", err))?;
+    Ok(response.body)
+}
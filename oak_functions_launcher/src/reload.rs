@@ -0,0 +1,121 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Hot reload of the Wasm module.
+//!
+//! When the Wasm file changes, the new module is checked against the same policy as at startup and
+//! sent to the enclave, which atomically swaps it in. Since the enclave handles invocations one at
+//! a time, in-flight invocations complete on the old module. The enclave generates a new key pair
+//! and attestation evidence for the new module, which are published to the servers through a
+//! watch channel, so that clients attest to the module that actually handles their requests.
+//!
+//! If the new module is rejected, either by the policy or by the enclave, the old module stays in
+//! place. A detached signature is read again together with the module, so the signature file
+//! should be replaced before the module.
+
+use crate::{
+    proto::oak::functions::{OakFunctionsAsyncClient, PublicKeyInfo, ReplaceWasmModuleRequest},
+    watch::{self, FileVersion},
+};
+use anyhow::Context;
+use oak_functions_wasm_prepare::ModulePolicy;
+use oak_launcher_utils::channel::ConnectorHandle;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// How often the Wasm file is checked for changes where file system notifications are not
+/// available.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Replaces the Wasm module in the enclave whenever the file at `wasm_path` changes, and publishes
+/// the resulting public key info to `public_key_info`. If set, the detached signature of the
+/// module is read from `signature_path`.
+///
+/// Never returns under normal operation.
+pub async fn watch_wasm_module(
+    connector_handle: ConnectorHandle,
+    wasm_path: PathBuf,
+    signature_path: Option<PathBuf>,
+    policy: ModulePolicy,
+    public_key_info: tokio::sync::watch::Sender<PublicKeyInfo>,
+) -> anyhow::Result<()> {
+    let mut client = OakFunctionsAsyncClient::new(connector_handle);
+    let mut version = FileVersion::of(&wasm_path).ok();
+
+    // The watcher must be kept alive for notifications to be delivered.
+    let (change_sender, mut change_receiver) = mpsc::channel(1);
+    let _watcher = watch::watch_file(&wasm_path, change_sender)
+        .map_err(|err| log::warn!("couldn't watch Wasm module for changes: {:?}", err))
+        .ok();
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            Some(()) = change_receiver.recv() => {},
+        }
+        let current_version = FileVersion::of(&wasm_path).ok();
+        if current_version.is_none() || current_version == version {
+            continue;
+        }
+        // Only record the version once the module has been replaced, so that a module that was
+        // rejected because it was still being written is retried.
+        let replaced =
+            replace_wasm_module(&mut client, &wasm_path, signature_path.as_deref(), &policy).await;
+        match replaced {
+            Ok(new_public_key_info) => {
+                log::info!("replaced Wasm module from {}", wasm_path.display());
+                version = current_version;
+                public_key_info
+                    .send(new_public_key_info)
+                    .context("all servers terminated")?;
+            }
+            Err(err) => log::warn!(
+                "couldn't replace Wasm module, keeping the old one: {:?}",
+                err
+            ),
+        }
+    }
+}
+
+async fn replace_wasm_module(
+    client: &mut OakFunctionsAsyncClient<ConnectorHandle>,
+    wasm_path: &Path,
+    signature_path: Option<&Path>,
+    policy: &ModulePolicy,
+) -> anyhow::Result<PublicKeyInfo> {
+    let wasm_module = fs::read(wasm_path)
+        .with_context(|| format!("couldn't read Wasm file {}", wasm_path.display()))?;
+    let signature = signature_path
+        .map(fs::read)
+        .transpose()
+        .context("couldn't read Wasm signature file")?;
+    policy
+        .check(&wasm_module, signature.as_deref())
+        .context("Wasm module rejected by policy")?;
+    let response = client
+        .replace_wasm_module(&ReplaceWasmModuleRequest { wasm_module })
+        .await
+        .flatten()
+        .map_err(|status| anyhow::anyhow!("enclave rejected Wasm module: {:?}", status))?;
+    response
+        .public_key_info
+        .context("no public key info returned")
+}
@@ -0,0 +1,95 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Retries of failed operations with exponential backoff.
+
+use rand::Rng;
+use std::{future::Future, time::Duration};
+
+/// How an operation is retried after it failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one. One disables retries.
+    pub max_attempts: u32,
+    /// Delay before the first retry, which doubles with every further retry.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between attempts.
+    pub max_backoff: Duration,
+    /// Fraction between 0 and 1 by which every delay is randomly shortened or lengthened, so that
+    /// many launchers don't retry in lockstep.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the next attempt after `failed_attempts` attempts failed.
+    pub fn backoff(&self, failed_attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failed_attempts.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff);
+        if self.jitter > 0.0 {
+            backoff.mul_f64(rand::thread_rng().gen_range(1.0 - self.jitter..=1.0 + self.jitter))
+        } else {
+            backoff
+        }
+    }
+
+    /// Runs `operation` until it succeeds or all attempts failed, in which case the error of the
+    /// last attempt is returned. `description` names the operation in the logs.
+    pub async fn retry<T, F, Fut>(&self, description: &str, mut operation: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut failed_attempts = 0;
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    failed_attempts += 1;
+                    if failed_attempts >= self.max_attempts {
+                        return Err(err);
+                    }
+                    let backoff = self.backoff(failed_attempts);
+                    log::warn!(
+                        "couldn't {} (attempt {} of {}), retrying in {:?}: {:?}",
+                        description,
+                        failed_attempts,
+                        self.max_attempts,
+                        backoff,
+                        err
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
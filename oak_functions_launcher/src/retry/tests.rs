@@ -0,0 +1,75 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_backoff() {
+    let policy = RetryPolicy {
+        max_attempts: 10,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(500),
+        jitter: 0.0,
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    assert_eq!(policy.backoff(4), Duration::from_millis(500));
+    assert_eq!(policy.backoff(100), Duration::from_millis(500));
+
+    let policy = RetryPolicy {
+        jitter: 0.5,
+        ..policy
+    };
+    for _ in 0..100 {
+        let backoff = policy.backoff(1);
+        assert!(backoff >= Duration::from_millis(50) && backoff <= Duration::from_millis(150));
+    }
+}
+
+#[tokio::test]
+async fn test_retry() {
+    let policy = RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::ZERO,
+        ..Default::default()
+    };
+
+    let mut attempts = 0;
+    let result = policy
+        .retry("succeed eventually", || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt < 3 {
+                    anyhow::bail!("attempt {} failed", attempt);
+                }
+                Ok(attempt)
+            }
+        })
+        .await;
+    assert_eq!(result.unwrap(), 3);
+
+    let mut attempts = 0;
+    let result: anyhow::Result<()> = policy
+        .retry("fail", || {
+            attempts += 1;
+            async { anyhow::bail!("failed") }
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 3);
+}
@@ -0,0 +1,353 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Scheduled invocations of the loaded Wasm module with fixed payloads, e.g. for periodic
+//! maintenance or cache warming. Jobs run at a fixed interval, or at the times that a cron
+//! expression matches.
+//!
+//! Scheduled invocations are encrypted to the current public key of the enclave and sent through
+//! the same `Invoke` path as client requests, so they are subject to exactly the same policy as
+//! client traffic. They are logged separately, with the name of the scheduled job.
+
+use crate::{
+    metrics,
+    proto::oak::functions::{InvokeRequest, OakFunctionsAsyncClient, PublicKeyInfo},
+    server::DEFAULT_ROUTE,
+};
+use anyhow::Context;
+use oak_crypto::{encryptor::ClientEncryptor, proto::oak::crypto::v1::EncryptedResponse};
+use oak_launcher_utils::channel::ConnectorHandle;
+use prost::Message;
+use serde::Deserialize;
+use std::{
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::watch;
+
+const EMPTY_ASSOCIATED_DATA: &[u8] = b"";
+
+/// A single job that periodically invokes the Wasm module.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ScheduledInvocation {
+    /// Name of the job, used to identify its invocations in the logs.
+    pub name: String,
+
+    /// Interval between consecutive invocations. The first invocation happens one interval after
+    /// the launcher has started. Exactly one of `intervalSeconds` and `cron` must be set.
+    #[serde(rename = "intervalSeconds", default)]
+    pub interval_seconds: Option<u64>,
+
+    /// Cron expression that matches the times of the invocations.
+    #[serde(default)]
+    pub cron: Option<CronExpression>,
+
+    /// Request body that is passed to the Wasm module.
+    #[serde(default)]
+    pub payload: String,
+}
+
+/// Set of scheduled invocations, as read from the schedule file.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Schedule {
+    pub invocations: Vec<ScheduledInvocation>,
+}
+
+impl Schedule {
+    /// Reads a schedule from a JSON file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("couldn't read schedule file {}", path.display()))?;
+        let schedule: Self =
+            serde_json::from_slice(&bytes).context("couldn't parse schedule file")?;
+        for invocation in &schedule.invocations {
+            match (invocation.interval_seconds, &invocation.cron) {
+                (Some(0), None) => anyhow::bail!(
+                    "scheduled invocation {:?} has an interval of 0 seconds",
+                    invocation.name
+                ),
+                (Some(_), None) => {}
+                (None, Some(cron)) => {
+                    if cron.next_after(SystemTime::now()).is_none() {
+                        anyhow::bail!(
+                            "cron expression of scheduled invocation {:?} never matches",
+                            invocation.name
+                        );
+                    }
+                }
+                _ => anyhow::bail!(
+                    "scheduled invocation {:?} must have either intervalSeconds or cron",
+                    invocation.name
+                ),
+            }
+        }
+        Ok(schedule)
+    }
+}
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// Number of days within which a cron expression has to match, which covers leap days.
+const MAX_CRON_DAYS: u64 = 5 * 366;
+
+/// A cron expression with five fields: minute, hour, day of the month, month and day of the week,
+/// e.g. `*/15 * * * *`, which is evaluated in UTC.
+///
+/// Each field is a comma-separated list of values, ranges such as `1-5`, and `*`, each with an
+/// optional step such as `/15`. Days of the week start with 0 for Sunday, and 7 is Sunday as well.
+/// As in cron, if neither the day of the month nor the day of the week starts with `*`, a day
+/// matches if either of them matches.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct CronExpression {
+    // Bit sets of the values that match each field.
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Whether days only match if both the day of the month and the day of the week match.
+    match_both_days: bool,
+}
+
+impl CronExpression {
+    /// Returns the first whole minute after `time` that matches the expression, or `None` if it
+    /// doesn't match within the next five years, e.g. `0 0 30 2 *`.
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let mut minute = seconds / 60 + 1;
+        let limit = minute + MAX_CRON_DAYS * MINUTES_PER_DAY;
+        while minute < limit {
+            let day = minute / MINUTES_PER_DAY;
+            if !self.matches_day(day) {
+                minute = (day + 1) * MINUTES_PER_DAY;
+            } else if !contains(self.hours, minute % MINUTES_PER_DAY / 60) {
+                minute = (minute / 60 + 1) * 60;
+            } else if !contains(self.minutes, minute % 60) {
+                minute += 1;
+            } else {
+                return Some(UNIX_EPOCH + Duration::from_secs(minute * 60));
+            }
+        }
+        None
+    }
+
+    // Returns whether the day `day` after the Unix epoch matches.
+    fn matches_day(&self, day: u64) -> bool {
+        let (month, day_of_month) = month_and_day(day);
+        // The Unix epoch was a Thursday.
+        let day_of_week = (day + 4) % 7;
+        let day_of_month = contains(self.days_of_month, day_of_month);
+        let day_of_week = contains(self.days_of_week, day_of_week);
+        let day = if self.match_both_days {
+            day_of_month && day_of_week
+        } else {
+            day_of_month || day_of_week
+        };
+        contains(self.months, month) && day
+    }
+}
+
+impl FromStr for CronExpression {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            anyhow::bail!("cron expression {:?} doesn't have five fields", expression);
+        };
+        let mut days_of_week_values =
+            parse_cron_field(days_of_week, 0, 7).context("invalid day of the week")?;
+        // Both 0 and 7 are Sunday.
+        if contains(days_of_week_values, 7) {
+            days_of_week_values |= 1;
+        }
+        Ok(Self {
+            minutes: parse_cron_field(minutes, 0, 59).context("invalid minute")?,
+            hours: parse_cron_field(hours, 0, 23).context("invalid hour")?,
+            days_of_month: parse_cron_field(days_of_month, 1, 31)
+                .context("invalid day of the month")?,
+            months: parse_cron_field(months, 1, 12).context("invalid month")?,
+            days_of_week: days_of_week_values,
+            match_both_days: days_of_month.starts_with('*') || days_of_week.starts_with('*'),
+        })
+    }
+}
+
+impl TryFrom<String> for CronExpression {
+    type Error = anyhow::Error;
+
+    fn try_from(expression: String) -> anyhow::Result<Self> {
+        expression.parse()
+    }
+}
+
+// Returns the bit set of the values from `min` to `max` that `field` matches.
+fn parse_cron_field(field: &str, min: u64, max: u64) -> anyhow::Result<u64> {
+    let parse_value = |value: &str| -> anyhow::Result<u64> {
+        let value: u64 = value
+            .parse()
+            .with_context(|| format!("invalid value {:?}", value))?;
+        anyhow::ensure!(
+            (min..=max).contains(&value),
+            "value {} is not between {} and {}",
+            value,
+            min,
+            max
+        );
+        Ok(value)
+    };
+    let mut values = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u64 = step
+                    .parse()
+                    .with_context(|| format!("invalid step {:?}", step))?;
+                anyhow::ensure!(step > 0, "step must be positive");
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start)?, parse_value(end)?)
+        } else {
+            let start = parse_value(range)?;
+            // A single value with a step, e.g. `5/15`, starts a range that ends at the maximum.
+            (start, if step.is_some() { max } else { start })
+        };
+        anyhow::ensure!(start <= end, "range {:?} is empty", range);
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            values |= 1 << value;
+        }
+    }
+    Ok(values)
+}
+
+fn contains(values: u64, value: u64) -> bool {
+    values & (1 << value) != 0
+}
+
+// Returns the month and the day of the month of the day `day` after the Unix epoch, using the
+// algorithm of http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+fn month_and_day(day: u64) -> (u64, u64) {
+    // Days since 0000-03-01, so that leap days are at the end of a year.
+    let day = day + 719_468;
+    let day_of_era = day % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months start with March.
+    let month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    (month, day_of_month)
+}
+
+/// Runs all scheduled invocations. The returned future never completes, so it runs until it is
+/// dropped.
+///
+/// Failures of individual invocations are logged and do not stop the schedule.
+pub async fn run(
+    schedule: Schedule,
+    connector_handle: ConnectorHandle,
+    public_key_info: watch::Receiver<PublicKeyInfo>,
+) {
+    let jobs = schedule.invocations.into_iter().map(|invocation| {
+        let connector_handle = connector_handle.clone();
+        let public_key_info = public_key_info.clone();
+        async move {
+            let mut interval = invocation.interval_seconds.map(|interval_seconds| {
+                let period = Duration::from_secs(interval_seconds);
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period)
+            });
+            let mut previous = SystemTime::now();
+            loop {
+                match (&mut interval, &invocation.cron) {
+                    (Some(interval), _) => {
+                        interval.tick().await;
+                    }
+                    // The previous time is never matched again, even if the timer fired early.
+                    (None, Some(cron)) => match cron.next_after(previous.max(SystemTime::now())) {
+                        Some(next) => {
+                            let delay = next.duration_since(SystemTime::now()).unwrap_or_default();
+                            tokio::time::sleep(delay).await;
+                            previous = next;
+                        }
+                        None => {
+                            log::warn!("scheduled invocation {:?} is never due", invocation.name);
+                            return;
+                        }
+                    },
+                    (None, None) => return,
+                }
+                let start = Instant::now();
+                // The key changes when the Wasm module is replaced.
+                let encryption_public_key = public_key_info.borrow().public_key.clone();
+                let result = invoke(
+                    connector_handle.clone(),
+                    &encryption_public_key,
+                    invocation.payload.as_bytes(),
+                )
+                .await;
+                metrics::observe_invocation("scheduled", DEFAULT_ROUTE, start, &result);
+                match result {
+                    Ok(response) => log::info!(
+                        "scheduled invocation {:?} succeeded ({} response bytes)",
+                        invocation.name,
+                        response.len()
+                    ),
+                    Err(err) => log::warn!(
+                        "scheduled invocation {:?} failed: {:?}",
+                        invocation.name,
+                        err
+                    ),
+                }
+            }
+        }
+    });
+    futures::future::join_all(jobs).await;
+    // With no scheduled invocations there is nothing to do, but the schedule must not terminate.
+    std::future::pending::<()>().await
+}
+
+async fn invoke(
+    connector_handle: ConnectorHandle,
+    encryption_public_key: &[u8],
+    payload: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let mut client_encryptor = ClientEncryptor::create(encryption_public_key)?;
+    let encrypted_request = client_encryptor.encrypt(payload, EMPTY_ASSOCIATED_DATA)?;
+    let invoke_request = InvokeRequest {
+        body: encrypted_request.encode_to_vec(),
+    };
+    let mut client = OakFunctionsAsyncClient::new(connector_handle);
+    let invoke_response = client
+        .invoke(&invoke_request)
+        .await
+        .flatten()
+        .map_err(|err| anyhow::anyhow!("error invoking the enclave: {:?}", err))?;
+    let encrypted_response = EncryptedResponse::decode(invoke_response.body.as_slice())
+        .context("couldn't decode encrypted response")?;
+    let (response, _) = client_encryptor.decrypt(&encrypted_response)?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests;
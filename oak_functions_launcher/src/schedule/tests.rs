@@ -0,0 +1,78 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_parse_cron_expression() {
+    let expression: CronExpression = "*/15 9-17 * 1,7 1-5".parse().unwrap();
+    assert_eq!(expression.minutes, 1 | 1 << 15 | 1 << 30 | 1 << 45);
+    assert_eq!(expression.hours, 0b11_1111_1110_0000_0000);
+    assert_eq!(expression.months, 1 << 1 | 1 << 7);
+    assert_eq!(expression.days_of_week, 0b11_1110);
+    assert!(expression.match_both_days);
+    let expression: CronExpression = "5/20 0 1 * 7".parse().unwrap();
+    assert_eq!(expression.minutes, 1 << 5 | 1 << 25 | 1 << 45);
+    assert_eq!(expression.days_of_week, 1 | 1 << 7);
+    assert!(!expression.match_both_days);
+
+    for invalid in [
+        "* * * *",
+        "* * * * * *",
+        "60 * * * *",
+        "* * 0 * *",
+        "*/0 * * * *",
+        "5-1 * * * *",
+        "a * * * *",
+    ] {
+        assert!(invalid.parse::<CronExpression>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_cron_expression_next_after() {
+    // Wednesday, 2023-03-01 00:00:00 UTC.
+    let start = UNIX_EPOCH + Duration::from_secs(1_677_628_800);
+    let next_after = |expression: &str, time: SystemTime| {
+        expression
+            .parse::<CronExpression>()
+            .unwrap()
+            .next_after(time)
+            .map(|next| next.duration_since(start).unwrap().as_secs())
+    };
+    assert_eq!(next_after("*/15 * * * *", start), Some(15 * 60));
+    assert_eq!(
+        next_after("*/15 * * * *", start + Duration::from_secs(7 * 60 + 30)),
+        Some(15 * 60)
+    );
+    // On Wednesday at 10:00, the next weekday at 09:30 is Thursday.
+    assert_eq!(
+        next_after("30 9 * * 1-5", start + Duration::from_secs(10 * 3600)),
+        Some(86_400 + 9 * 3600 + 30 * 60)
+    );
+    // The next leap day is 2024-02-29.
+    assert_eq!(
+        next_after("0 0 29 2 *", start),
+        Some(1_709_164_800 - 1_677_628_800)
+    );
+    // Either the day of the month or the day of the week has to match, so Sunday 2023-12-31
+    // matches before 2024-01-01.
+    assert_eq!(
+        next_after("0 0 1 * 0", UNIX_EPOCH + Duration::from_secs(1_703_894_400)),
+        Some(1_703_980_800 - 1_677_628_800)
+    );
+    assert_eq!(next_after("0 0 30 2 *", start), None);
+}
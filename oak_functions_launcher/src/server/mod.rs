@@ -0,0 +1,751 @@
+//
+// Copyright 2022 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::{
+    channel::ConnectorHandle,
+    metrics,
+    proto::oak::{
+        functions,
+        session::v1::{
+            request_wrapper, response_wrapper,
+            streaming_session_server::{StreamingSession, StreamingSessionServer},
+            AttestationBundle, AttestationEndorsement, AttestationEvidence, GetPublicKeyResponse,
+            InvokeResponse, RequestWrapper, ResponseWrapper,
+        },
+    },
+    trace::{Span, SpanKind, TraceContext, TRACEPARENT},
+};
+use anyhow::Context;
+use futures::{Future, SinkExt, Stream, StreamExt};
+use lru::LruCache;
+use prost::Message as _;
+use std::{
+    net::{IpAddr, SocketAddr},
+    num::NonZeroUsize,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{watch, OwnedSemaphorePermit, Semaphore},
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{
+        ErrorResponse, Request as HandshakeRequest, Response as HandshakeResponse,
+    },
+    http,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+use tonic::{
+    metadata::MetadataMap,
+    transport::{Server, ServerTlsConfig},
+    Code, Request, Response, Status, Streaming,
+};
+
+/// The route of the Wasm module passed with `--wasm`, which handles all requests that don't match
+/// any other route.
+pub const DEFAULT_ROUTE: &str = "/";
+
+/// Metadata key with which gRPC clients select the route of their session, since all sessions use
+/// the same gRPC method. Sessions without it are handled by the [`DEFAULT_ROUTE`].
+pub const ROUTE_METADATA_KEY: &str = "x-oak-route";
+
+/// A Wasm module, loaded into its own enclave, and the requests that are dispatched to it.
+#[derive(Clone)]
+pub struct Route {
+    /// Path prefix of the requests that are handled by the module, e.g. `/weather`. Also used as
+    /// the route label of metrics.
+    pub prefix: String,
+    pub connector_handle: ConnectorHandle,
+    /// Public key and attestation evidence of the enclave, which change when the Wasm module is
+    /// replaced.
+    pub public_key_info: watch::Receiver<functions::PublicKeyInfo>,
+    /// Time after the start of an invocation at which its response is returned, so that the
+    /// processing time does not reveal anything about the request. Not enforced if `None`.
+    pub constant_processing_time: Option<Duration>,
+}
+
+/// Header, or gRPC metadata key, with which rate limited clients are told how many seconds to wait
+/// before retrying.
+const RETRY_AFTER: &str = "retry-after";
+
+/// Number of clients whose token buckets are kept. The bucket of the client that invoked the
+/// enclaves least recently is dropped to make room for a new client, which is O(1), so rotating
+/// addresses or API keys can't slow down the limiter.
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// Limits on the invocations of clients, so that a single client can't monopolize the enclaves.
+/// Requests that exceed a limit are rejected with `RESOURCE_EXHAUSTED`.
+#[derive(Clone, Debug, Default)]
+pub struct RequestLimits {
+    /// Maximum size of the encrypted body of an invocation. Not limited if `None`.
+    pub max_request_body_bytes: Option<usize>,
+    /// Maximum number of invocations that are handled at a time across all transports and
+    /// routes. Not limited if `None`.
+    pub max_concurrent_requests: Option<usize>,
+    /// Rate at which each client may invoke the enclaves. Not limited if `None`.
+    pub rate_limit: Option<RateLimit>,
+    /// Lowercase name of the header, or gRPC metadata key, whose value identifies the client for
+    /// rate limiting, e.g. an API key. Clients without it are identified by their IP address.
+    ///
+    /// The launcher doesn't authenticate the value, so it must only be set if a proxy in front of
+    /// the launcher authenticates it; otherwise a client can evade its limit by sending a
+    /// different value with every request.
+    pub api_key_header: Option<String>,
+}
+
+/// Rate limit of every client, enforced with a token bucket per client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// Number of invocations per second that the bucket of a client is refilled with.
+    pub requests_per_second: f64,
+    /// Size of the bucket, i.e. the number of invocations that a client that has been idle may
+    /// make at once.
+    pub burst: u32,
+}
+
+/// Identifies the client whose invocations are rate limited together.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ClientKey {
+    /// Value of the [`RequestLimits::api_key_header`], which must have been authenticated
+    /// upstream.
+    ApiKey(String),
+    Address(IpAddr),
+    /// Clients whose address isn't known share a single bucket.
+    Unknown,
+}
+
+impl ClientKey {
+    /// Identifies the client by its API key if it sent one, and otherwise by its IP address.
+    pub fn new(api_key: Option<&str>, peer_addr: Option<SocketAddr>) -> Self {
+        match (api_key, peer_addr) {
+            (Some(api_key), _) => ClientKey::ApiKey(api_key.to_string()),
+            (None, Some(peer_addr)) => ClientKey::Address(peer_addr.ip()),
+            (None, None) => ClientKey::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// Token buckets of the clients that invoked the enclaves recently.
+struct RateLimiter {
+    rate_limit: RateLimit,
+    buckets: Mutex<LruCache<ClientKey, TokenBucket>>,
+}
+
+impl RateLimiter {
+    fn new(rate_limit: RateLimit, max_clients: NonZeroUsize) -> Self {
+        Self {
+            rate_limit,
+            buckets: Mutex::new(LruCache::new(max_clients)),
+        }
+    }
+
+    // Takes a token from the bucket of `client`, or returns how long the client has to wait until
+    // the next token is available.
+    fn acquire(&self, client: &ClientKey, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().expect("rate limiter mutex poisoned");
+        let bucket = buckets.get_or_insert_mut(client.clone(), || self.full_bucket(now));
+        let tokens = self.refill(bucket, now);
+        if tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(self.wait_time(tokens))
+        }
+    }
+
+    // Returns how long `client` has to wait until the next token is available, without taking
+    // one.
+    fn retry_after(&self, client: &ClientKey, now: Instant) -> Option<Duration> {
+        let mut buckets = self.buckets.lock().expect("rate limiter mutex poisoned");
+        let tokens = self.refill(buckets.peek_mut(client)?, now);
+        (tokens < 1.0).then(|| self.wait_time(tokens))
+    }
+
+    fn full_bucket(&self, now: Instant) -> TokenBucket {
+        TokenBucket {
+            tokens: f64::from(self.rate_limit.burst),
+            updated: now,
+        }
+    }
+
+    // Adds the tokens that accrued since the last update of `bucket`, and returns the tokens it
+    // holds.
+    fn refill(&self, bucket: &mut TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate_limit.requests_per_second)
+            .min(f64::from(self.rate_limit.burst));
+        bucket.updated = now;
+        bucket.tokens
+    }
+
+    fn wait_time(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64((1.0 - tokens) / self.rate_limit.requests_per_second)
+    }
+}
+
+// Returns the whole number of seconds to send as the `Retry-After` header, rounded up.
+fn retry_after_seconds(retry_after: Duration) -> u64 {
+    (retry_after.as_secs_f64().ceil() as u64).max(1)
+}
+
+// Returns the status of an invocation that was rejected by the rate limit, which tells the client
+// when to retry in its metadata.
+fn rate_limited(retry_after: Duration) -> Status {
+    let seconds = retry_after_seconds(retry_after);
+    let mut metadata = MetadataMap::new();
+    metadata.insert(RETRY_AFTER, seconds.into());
+    Status::with_metadata(
+        Code::ResourceExhausted,
+        format!("rate limit exceeded, retry after {seconds} seconds"),
+        metadata,
+    )
+}
+
+/// Enforces [`RequestLimits`]. Clones share the count of invocations in flight and the token
+/// buckets of the clients, so a single limiter has to be passed to all servers.
+#[derive(Clone, Default)]
+pub struct RequestLimiter {
+    max_request_body_bytes: Option<usize>,
+    in_flight: Option<Arc<Semaphore>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    api_key_header: Option<String>,
+}
+
+impl RequestLimiter {
+    pub fn new(limits: RequestLimits) -> Self {
+        Self {
+            max_request_body_bytes: limits.max_request_body_bytes,
+            in_flight: limits
+                .max_concurrent_requests
+                .map(|max| Arc::new(Semaphore::new(max))),
+            rate_limiter: limits.rate_limit.map(|rate_limit| {
+                Arc::new(RateLimiter::new(
+                    rate_limit,
+                    NonZeroUsize::new(MAX_TRACKED_CLIENTS).expect("no clients tracked"),
+                ))
+            }),
+            api_key_header: limits.api_key_header,
+        }
+    }
+
+    // Returns the name of the header that identifies clients for rate limiting, if any.
+    fn api_key_header(&self) -> Option<&str> {
+        self.api_key_header.as_deref()
+    }
+
+    // Returns how long `client` has to wait before its next invocation is admitted, if it has
+    // exhausted its rate limit.
+    fn retry_after(&self, client: &ClientKey) -> Option<Duration> {
+        self.rate_limiter
+            .as_ref()?
+            .retry_after(client, Instant::now())
+    }
+
+    // Admits an invocation of `client` with a body of `body_len` bytes. Returns a permit that must
+    // be held until the invocation completed, or the reason for the rejection, which is used as
+    // the label of metrics.
+    fn admit(
+        &self,
+        client: &ClientKey,
+        body_len: usize,
+    ) -> Result<Option<OwnedSemaphorePermit>, (&'static str, Status)> {
+        if let Some(max_request_body_bytes) = self.max_request_body_bytes {
+            if body_len > max_request_body_bytes {
+                return Err((
+                    "body_too_large",
+                    Status::resource_exhausted(format!(
+                        "request body of {body_len} bytes exceeds the limit of \
+                         {max_request_body_bytes} bytes"
+                    )),
+                ));
+            }
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .acquire(client, Instant::now())
+                .map_err(|retry_after| ("rate_limited", rate_limited(retry_after)))?;
+        }
+        match &self.in_flight {
+            Some(in_flight) => in_flight
+                .clone()
+                .try_acquire_owned()
+                .map(Some)
+                .map_err(|_| {
+                    (
+                        "too_many_requests",
+                        Status::resource_exhausted("too many concurrent requests"),
+                    )
+                }),
+            None => Ok(None),
+        }
+    }
+}
+
+// Returns the route with the longest prefix that matches `path`, if any.
+fn select_route<'a>(routes: &'a [Route], path: &str) -> Option<&'a Route> {
+    routes
+        .iter()
+        .filter(|route| matches_prefix(&route.prefix, path))
+        .max_by_key(|route| route.prefix.trim_end_matches('/').len())
+}
+
+// Prefixes only match whole path segments, so that `/foo` matches `/foo/bar` but not `/foobar`.
+fn matches_prefix(prefix: &str, path: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Handles the requests of a client session, independently of the transport they arrive on.
+#[derive(Clone)]
+struct SessionHandler {
+    /// Name of the transport that requests arrive on, used for metrics.
+    transport: &'static str,
+    route: Route,
+    /// The client whose requests are handled, for rate limiting.
+    client: ClientKey,
+    limiter: RequestLimiter,
+    /// The trace that the client propagated, which the spans of its requests are part of.
+    trace_context: Option<TraceContext>,
+}
+
+impl SessionHandler {
+    fn cached_attestation_bundle(&self) -> AttestationBundle {
+        let public_key_info = self.route.public_key_info.borrow().clone();
+        // TODO(#3641): Initialize all evidence fields.
+        let attestation_evidence = AttestationEvidence {
+            encryption_public_key: public_key_info.public_key,
+            signing_public_key: vec![],
+            attestation: public_key_info.attestation,
+            signed_application_data: vec![],
+            nonce: vec![],
+            configuration: public_key_info.configuration,
+        };
+        let attestation_endorsement = AttestationEndorsement {
+            tee_certificates: vec![],
+            binary_attestation: None,
+            application_data: None,
+        };
+        AttestationBundle {
+            attestation_evidence: Some(attestation_evidence),
+            attestation_endorsement: Some(attestation_endorsement),
+        }
+    }
+
+    // Handles a request of the session within a span, which is part of the trace of the client.
+    async fn handle(
+        &self,
+        request: request_wrapper::Request,
+    ) -> Result<response_wrapper::Response, Status> {
+        let name = match &request {
+            request_wrapper::Request::GetPublicKeyRequest(_) => "GetPublicKey",
+            request_wrapper::Request::InvokeRequest(_) => "Invoke",
+        };
+        let mut span = Span::start(name, SpanKind::Server, self.trace_context);
+        span.set_attribute("oak.transport", self.transport);
+        span.set_attribute("oak.route", &self.route.prefix);
+        let response = self.handle_request(request).await;
+        if let Err(status) = &response {
+            span.set_error(status.message());
+        }
+        response
+    }
+
+    async fn handle_request(
+        &self,
+        request: request_wrapper::Request,
+    ) -> Result<response_wrapper::Response, Status> {
+        let response = match request {
+            request_wrapper::Request::GetPublicKeyRequest(get_public_key_request) => {
+                let attestation_bundle = self.cached_attestation_bundle();
+                let attestation_bundle = if get_public_key_request.nonce.is_empty() {
+                    attestation_bundle
+                } else {
+                    // The client asked for fresh evidence, so it has to be generated by the
+                    // enclave rather than served from the cached bundle. This costs as much as an
+                    // invocation, so it is subject to the same limits.
+                    let _permit = self.admit(0)?;
+                    let enclave_request = functions::GetAttestationEvidenceRequest {
+                        nonce: get_public_key_request.nonce.clone(),
+                    };
+                    let mut enclave_client = functions::OakFunctionsAsyncClient::new(
+                        self.route.connector_handle.clone(),
+                    );
+                    let public_key_info = enclave_client
+                        .get_attestation_evidence(&enclave_request)
+                        .await
+                        .flatten()
+                        .map_err(|err| {
+                            Status::internal(format!(
+                                "error getting attestation evidence: {:?}",
+                                err
+                            ))
+                        })?
+                        .public_key_info
+                        .ok_or_else(|| Status::internal("no public key info returned"))?;
+                    AttestationBundle {
+                        attestation_evidence: Some(AttestationEvidence {
+                            encryption_public_key: public_key_info.public_key,
+                            signing_public_key: vec![],
+                            attestation: public_key_info.attestation,
+                            signed_application_data: vec![],
+                            nonce: get_public_key_request.nonce,
+                            configuration: public_key_info.configuration,
+                        }),
+                        attestation_endorsement: attestation_bundle.attestation_endorsement,
+                    }
+                };
+                response_wrapper::Response::GetPublicKeyResponse(GetPublicKeyResponse {
+                    attestation_bundle: Some(attestation_bundle),
+                })
+            }
+            request_wrapper::Request::InvokeRequest(invoke_request) => {
+                let _permit = self.admit(invoke_request.encrypted_body.len())?;
+                let enclave_invoke_request = functions::InvokeRequest {
+                    body: invoke_request.encrypted_body,
+                };
+                let start = Instant::now();
+                let enclave_invoke_response = self.invoke(&enclave_invoke_request, start).await;
+                metrics::observe_invocation(
+                    self.transport,
+                    &self.route.prefix,
+                    start,
+                    &enclave_invoke_response,
+                );
+                let enclave_invoke_response = enclave_invoke_response?;
+                response_wrapper::Response::InvokeResponse(InvokeResponse {
+                    encrypted_body: enclave_invoke_response.body,
+                })
+            }
+        };
+        Ok(response)
+    }
+
+    // Admits a request of the client that has to be handled by the enclave. Returns a permit that
+    // must be held until the enclave responded.
+    fn admit(&self, body_len: usize) -> Result<Option<OwnedSemaphorePermit>, Status> {
+        self.limiter
+            .admit(&self.client, body_len)
+            .map_err(|(reason, status)| {
+                metrics::observe_rejection(self.transport, &self.route.prefix, reason);
+                status
+            })
+    }
+
+    // Invokes the enclave. If the route has a constant processing time, the response is only
+    // returned once that time has elapsed since `start`, and invocations that take longer fail
+    // when it has elapsed.
+    async fn invoke(
+        &self,
+        request: &functions::InvokeRequest,
+        start: Instant,
+    ) -> Result<functions::InvokeResponse, Status> {
+        let mut enclave_client =
+            functions::OakFunctionsAsyncClient::new(self.route.connector_handle.clone());
+        let invoke = async {
+            enclave_client
+                .invoke(request)
+                .await
+                .flatten()
+                .map_err(|err| {
+                    Status::internal(format!("error handling client request: {:?}", err))
+                })
+        };
+        match self.route.constant_processing_time {
+            Some(constant_processing_time) => {
+                let deadline = tokio::time::Instant::from_std(start + constant_processing_time);
+                let response = tokio::time::timeout_at(deadline, invoke).await;
+                tokio::time::sleep_until(deadline).await;
+                response.unwrap_or_else(|_| {
+                    Err(Status::deadline_exceeded(
+                        "constant processing time exceeded",
+                    ))
+                })
+            }
+            None => invoke.await,
+        }
+    }
+}
+
+pub struct SessionProxy {
+    routes: Arc<[Route]>,
+    limiter: RequestLimiter,
+}
+
+#[tonic::async_trait]
+impl StreamingSession for SessionProxy {
+    type StreamStream =
+        Pin<Box<dyn Stream<Item = Result<ResponseWrapper, Status>> + Send + 'static>>;
+
+    async fn stream(
+        &self,
+        request: Request<Streaming<RequestWrapper>>,
+    ) -> Result<Response<Self::StreamStream>, tonic::Status> {
+        let path = match request.metadata().get(ROUTE_METADATA_KEY) {
+            Some(value) => value
+                .to_str()
+                .map_err(|_| tonic::Status::invalid_argument("invalid route"))?
+                .to_string(),
+            None => DEFAULT_ROUTE.to_string(),
+        };
+        let route = select_route(&self.routes, &path)
+            .ok_or_else(|| tonic::Status::not_found(format!("no route matches {path}")))?;
+        log::info!("handling client request for route {}", route.prefix);
+        let api_key = self
+            .limiter
+            .api_key_header()
+            .and_then(|name| request.metadata().get(name))
+            .and_then(|value| value.to_str().ok());
+        let trace_context = request
+            .metadata()
+            .get(TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(TraceContext::parse);
+        let handler = SessionHandler {
+            transport: "grpc",
+            route: route.clone(),
+            client: ClientKey::new(api_key, request.remote_addr()),
+            limiter: self.limiter.clone(),
+            trace_context,
+        };
+        let mut request_stream = request.into_inner();
+
+        let response_stream = async_stream::try_stream! {
+            while let Some(request) = request_stream.next().await {
+                let request = request
+                    .map_err(|err| {
+                        tonic::Status::internal(format!("error reading message from request stream: {err}"))
+                    })?
+                    .request
+                    .ok_or_else(|| tonic::Status::invalid_argument("empty request message"))?;
+
+                let response = handler.handle(request).await?;
+                yield ResponseWrapper {
+                    response: Some(response),
+                };
+            }
+        };
+
+        Ok(Response::new(
+            Box::pin(response_stream) as Self::StreamStream
+        ))
+    }
+}
+
+/// Creates the gRPC server, which dispatches each session to one of the `routes` according to its
+/// [`ROUTE_METADATA_KEY`]. If `tls_config` is set, the server only accepts TLS connections.
+/// Invocations are subject to the limits of `limiter`.
+///
+/// Once `shutdown` completes, the server stops accepting new connections, and the returned future
+/// completes after all in-flight requests have been handled.
+pub fn new<F: Future<Output = ()>>(
+    addr: SocketAddr,
+    routes: Vec<Route>,
+    tls_config: Option<ServerTlsConfig>,
+    limiter: RequestLimiter,
+    shutdown: F,
+) -> Result<impl Future<Output = Result<(), tonic::transport::Error>>, tonic::transport::Error> {
+    let server_impl = SessionProxy {
+        routes: routes.into(),
+        limiter,
+    };
+
+    let mut server = Server::builder();
+    if let Some(tls_config) = tls_config {
+        server = server.tls_config(tls_config)?;
+    }
+    Ok(server
+        .add_service(StreamingSessionServer::new(server_impl))
+        .serve_with_shutdown(addr, shutdown))
+}
+
+/// Creates a server that accepts WebSocket connections, so that browser clients can keep a session
+/// open without gRPC support.
+///
+/// Every binary message sent by the client is a serialized `RequestWrapper`, and is answered with a
+/// binary message containing the serialized `ResponseWrapper`, exactly like the messages of the
+/// gRPC stream. If a request fails, the connection is closed with the error as the reason. Each
+/// connection is dispatched to one of the `routes` according to the path of its URL. Invocations
+/// are subject to the limits of `limiter`.
+///
+/// Unlike the gRPC server, the WebSocket server only accepts plaintext connections, so TLS has to
+/// be terminated by a proxy in front of it. It is not drained on shutdown either: its connections
+/// are closed when the launcher exits, failing any request that is still in flight.
+pub fn new_websocket(
+    addr: SocketAddr,
+    routes: Vec<Route>,
+    limiter: RequestLimiter,
+) -> impl Future<Output = anyhow::Result<()>> {
+    serve_websocket(addr, routes.into(), limiter)
+}
+
+async fn serve_websocket(
+    addr: SocketAddr,
+    routes: Arc<[Route]>,
+    limiter: RequestLimiter,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .context("couldn't bind WebSocket listener")?;
+    loop {
+        let (stream, peer_addr) = listener
+            .accept()
+            .await
+            .context("couldn't accept WebSocket connection")?;
+        let routes = routes.clone();
+        let limiter = limiter.clone();
+        tokio::spawn(async move {
+            let result = handle_websocket_connection(stream, peer_addr, routes, limiter).await;
+            if let Err(err) = result {
+                log::warn!("WebSocket connection from {} failed: {:?}", peer_addr, err);
+            }
+        });
+    }
+}
+
+// Maximum size of the reason of a WebSocket close frame, as control frames carry at most 125 bytes,
+// two of which are taken by the close code.
+const MAX_CLOSE_REASON_BYTES: usize = 123;
+
+// Returns the longest prefix of `text` that has at most `max_bytes` bytes and ends at a character
+// boundary.
+fn truncate_utf8(text: &str, max_bytes: usize) -> &str {
+    let mut end = max_bytes.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+async fn handle_websocket_connection(
+    stream: TcpStream,
+    peer_addr: SocketAddr,
+    routes: Arc<[Route]>,
+    limiter: RequestLimiter,
+) -> anyhow::Result<()> {
+    // The route is selected during the handshake, so that clients of unknown routes are rejected
+    // with an HTTP error. Likewise, clients that exhausted their rate limit are rejected with
+    // status 429 right away.
+    let mut route = None;
+    let mut client = None;
+    let mut trace_context = None;
+    let select_websocket_route = |request: &HandshakeRequest, response: HandshakeResponse| {
+        let selected_route = match select_route(&routes, request.uri().path()) {
+            Some(selected_route) => selected_route,
+            None => {
+                let mut error_response = ErrorResponse::new(None);
+                *error_response.status_mut() = http::StatusCode::NOT_FOUND;
+                return Err(error_response);
+            }
+        };
+        let api_key = limiter
+            .api_key_header()
+            .and_then(|name| request.headers().get(name))
+            .and_then(|value| value.to_str().ok());
+        let selected_client = ClientKey::new(api_key, Some(peer_addr));
+        if let Some(retry_after) = limiter.retry_after(&selected_client) {
+            metrics::observe_rejection("websocket", &selected_route.prefix, "rate_limited");
+            let mut error_response = ErrorResponse::new(None);
+            *error_response.status_mut() = http::StatusCode::TOO_MANY_REQUESTS;
+            error_response.headers_mut().insert(
+                http::header::RETRY_AFTER,
+                retry_after_seconds(retry_after).into(),
+            );
+            return Err(error_response);
+        }
+        route = Some(selected_route.clone());
+        client = Some(selected_client);
+        trace_context = request
+            .headers()
+            .get(TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(TraceContext::parse);
+        Ok(response)
+    };
+    let mut websocket = tokio_tungstenite::accept_hdr_async(stream, select_websocket_route)
+        .await
+        .context("couldn't complete WebSocket handshake")?;
+    let route = route.context("no route selected")?;
+    log::info!("handling WebSocket client for route {}", route.prefix);
+    let handler = SessionHandler {
+        transport: "websocket",
+        route,
+        client: client.context("no client identified")?,
+        limiter,
+        trace_context,
+    };
+
+    while let Some(message) = websocket.next().await {
+        let request_bytes = match message.context("couldn't read WebSocket message")? {
+            Message::Binary(bytes) => bytes,
+            Message::Close(_) => break,
+            Message::Text(_) => anyhow::bail!("text messages are not supported"),
+            // Pings are answered by the WebSocket implementation.
+            _ => continue,
+        };
+        let response = match RequestWrapper::decode(request_bytes.as_slice()) {
+            Ok(RequestWrapper {
+                request: Some(request),
+            }) => handler.handle(request).await,
+            Ok(RequestWrapper { request: None }) => {
+                Err(Status::invalid_argument("empty request message"))
+            }
+            Err(err) => Err(Status::invalid_argument(format!(
+                "couldn't decode request message: {err}"
+            ))),
+        };
+        match response {
+            Ok(response) => {
+                let response_bytes = ResponseWrapper {
+                    response: Some(response),
+                }
+                .encode_to_vec();
+                websocket
+                    .send(Message::Binary(response_bytes))
+                    .await
+                    .context("couldn't send WebSocket message")?;
+            }
+            Err(status) => {
+                let close_frame = CloseFrame {
+                    code: CloseCode::Error,
+                    reason: truncate_utf8(status.message(), MAX_CLOSE_REASON_BYTES)
+                        .to_string()
+                        .into(),
+                };
+                websocket
+                    .close(Some(close_frame))
+                    .await
+                    .context("couldn't close WebSocket")?;
+                anyhow::bail!("couldn't handle request: {:?}", status);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,287 @@
+//
+// Copyright 2022 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_matches_prefix() {
+    assert!(matches_prefix("/", "/"));
+    assert!(matches_prefix("/", "/weather"));
+    assert!(matches_prefix("/weather", "/weather"));
+    assert!(matches_prefix("/weather", "/weather/today"));
+    assert!(matches_prefix("/weather/", "/weather/today"));
+    assert!(!matches_prefix("/weather", "/weathervane"));
+    assert!(!matches_prefix("/weather", "/"));
+}
+
+#[test]
+fn test_request_limiter_body_size() {
+    let limiter = RequestLimiter::new(RequestLimits {
+        max_request_body_bytes: Some(4),
+        ..Default::default()
+    });
+    assert!(limiter.admit(&ClientKey::Unknown, 4).is_ok());
+    let (reason, status) = limiter.admit(&ClientKey::Unknown, 5).unwrap_err();
+    assert_eq!(reason, "body_too_large");
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+}
+
+#[test]
+fn test_request_limiter_concurrency() {
+    let limiter = RequestLimiter::new(RequestLimits {
+        max_concurrent_requests: Some(1),
+        ..Default::default()
+    });
+    let permit = limiter.admit(&ClientKey::Unknown, 0).unwrap();
+    let (reason, status) = limiter.admit(&ClientKey::Unknown, 0).unwrap_err();
+    assert_eq!(reason, "too_many_requests");
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    drop(permit);
+    assert!(limiter.admit(&ClientKey::Unknown, 0).is_ok());
+}
+
+#[test]
+fn test_request_limiter_rate_limit() {
+    let limiter = RequestLimiter::new(RequestLimits {
+        rate_limit: Some(RateLimit {
+            requests_per_second: 0.01,
+            burst: 2,
+        }),
+        ..Default::default()
+    });
+    let client = ClientKey::new(None, Some("192.0.2.1:1234".parse().unwrap()));
+    assert!(limiter.retry_after(&client).is_none());
+    assert!(limiter.admit(&client, 0).is_ok());
+    assert!(limiter.admit(&client, 0).is_ok());
+    let (reason, status) = limiter.admit(&client, 0).unwrap_err();
+    assert_eq!(reason, "rate_limited");
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(status.metadata().get(RETRY_AFTER).unwrap(), "100");
+    assert!(limiter.retry_after(&client).is_some());
+
+    // Other ports of the same address share the bucket, API keys and other addresses don't.
+    let same_address = ClientKey::new(None, Some("192.0.2.1:5678".parse().unwrap()));
+    assert!(limiter.admit(&same_address, 0).is_err());
+    let api_key = ClientKey::new(Some("key"), Some("192.0.2.1:1234".parse().unwrap()));
+    assert!(limiter.admit(&api_key, 0).is_ok());
+    let other_address = ClientKey::new(None, Some("192.0.2.2:1234".parse().unwrap()));
+    assert!(limiter.admit(&other_address, 0).is_ok());
+}
+
+#[test]
+fn test_rate_limiter_refill() {
+    let rate_limiter = RateLimiter::new(
+        RateLimit {
+            requests_per_second: 2.0,
+            burst: 1,
+        },
+        NonZeroUsize::new(1).unwrap(),
+    );
+    let start = Instant::now();
+    assert!(rate_limiter.acquire(&ClientKey::Unknown, start).is_ok());
+    assert_eq!(
+        rate_limiter.acquire(&ClientKey::Unknown, start),
+        Err(Duration::from_millis(500))
+    );
+    let later = start + Duration::from_millis(250);
+    assert_eq!(
+        rate_limiter.retry_after(&ClientKey::Unknown, later),
+        Some(Duration::from_millis(250))
+    );
+    // Tokens don't accumulate beyond the burst.
+    let much_later = start + Duration::from_secs(10);
+    assert!(rate_limiter
+        .acquire(&ClientKey::Unknown, much_later)
+        .is_ok());
+    assert!(rate_limiter
+        .acquire(&ClientKey::Unknown, much_later)
+        .is_err());
+}
+
+#[test]
+fn test_rate_limiter_drops_least_recent_client() {
+    let rate_limiter = RateLimiter::new(
+        RateLimit {
+            requests_per_second: 1.0,
+            burst: 1,
+        },
+        NonZeroUsize::new(2).unwrap(),
+    );
+    let now = Instant::now();
+    let client = |index: u8| ClientKey::Address(IpAddr::from([192, 0, 2, index]));
+    assert!(rate_limiter.acquire(&client(1), now).is_ok());
+    assert!(rate_limiter.acquire(&client(2), now).is_ok());
+    assert!(rate_limiter.acquire(&client(1), now).is_err());
+    // Client 2 invoked the enclaves least recently, so its bucket is dropped for client 3.
+    assert!(rate_limiter.acquire(&client(3), now).is_ok());
+    assert!(rate_limiter.retry_after(&client(1), now).is_some());
+    assert!(rate_limiter.retry_after(&client(2), now).is_none());
+}
+
+#[test]
+fn test_request_limiter_unlimited() {
+    let limiter = RequestLimiter::default();
+    let permits: Vec<_> = (0..100)
+        .map(|_| limiter.admit(&ClientKey::Unknown, usize::MAX).unwrap())
+        .collect();
+    assert!(permits.iter().all(Option::is_none));
+}
+
+#[test]
+fn test_truncate_utf8() {
+    assert_eq!(truncate_utf8("error", 123), "error");
+    assert_eq!(truncate_utf8("error", 3), "err");
+    // Characters are not split.
+    assert_eq!(truncate_utf8("añb", 2), "a");
+    assert_eq!(truncate_utf8("añb", 3), "añ");
+    let reason = "é".repeat(100);
+    assert_eq!(truncate_utf8(&reason, MAX_CLOSE_REASON_BYTES).len(), 122);
+}
+
+// A Wasm module that responds to every request with `pong`.
+const PONG_MODULE: &str = r#"
+    (module
+        (import "oak_functions" "write_response" (func $write_response (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "pong")
+        (func (export "alloc") (param i32) (result i32)
+            i32.const 1024)
+        (func (export "main")
+            (drop (call $write_response (i32.const 0) (i32.const 4)))))
+"#;
+
+// Runs the Oak Functions service with `PONG_MODULE` in a thread instead of an enclave, and returns
+// a route with the given prefix to it.
+async fn start_pong_route(prefix: &str) -> Route {
+    use oak_functions_service::{proto::oak::functions::OakFunctionsServer, OakFunctionsService};
+    use oak_remote_attestation::attester::EmptyAttestationReportGenerator;
+
+    let (launcher_channel, enclave_channel) = std::os::unix::net::UnixStream::pair().unwrap();
+    std::thread::spawn(move || {
+        let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+        let mut stats = oak_core::samplestore::StaticSampleStore::<1>::new().unwrap();
+        let _ = oak_channel::server::start_blocking_server(
+            Box::new(enclave_channel),
+            OakFunctionsServer::new(service),
+            &mut stats,
+        );
+    });
+    let connector_handle =
+        oak_launcher_utils::channel::Connector::spawn(Box::new(launcher_channel));
+    let initialize_response = functions::OakFunctionsAsyncClient::new(connector_handle.clone())
+        .initialize(&functions::InitializeRequest {
+            wasm_module: wat::parse_str(PONG_MODULE).unwrap(),
+            constant_response_size: 1024,
+            ..Default::default()
+        })
+        .await
+        .flatten()
+        .expect("couldn't initialize service");
+    let (_, public_key_info) = watch::channel(initialize_response.public_key_info.unwrap());
+    Route {
+        prefix: prefix.to_string(),
+        connector_handle,
+        public_key_info,
+        constant_processing_time: None,
+    }
+}
+
+// Starts a WebSocket server for `routes`, and returns its address.
+async fn start_websocket_server(routes: Vec<Route>) -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    tokio::spawn(new_websocket(addr, routes, RequestLimiter::default()));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    addr
+}
+
+// Sends a request over the WebSocket, and returns the response to it.
+async fn round_trip(
+    websocket: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<TcpStream>,
+    >,
+    request: request_wrapper::Request,
+) -> response_wrapper::Response {
+    let request = RequestWrapper {
+        request: Some(request),
+    };
+    websocket
+        .send(Message::Binary(request.encode_to_vec()))
+        .await
+        .unwrap();
+    let response = match websocket.next().await.unwrap().unwrap() {
+        Message::Binary(bytes) => ResponseWrapper::decode(bytes.as_slice()).unwrap(),
+        message => panic!("unexpected message: {:?}", message),
+    };
+    response.response.unwrap()
+}
+
+#[tokio::test]
+async fn test_websocket_unknown_route() {
+    let addr = start_websocket_server(vec![start_pong_route("/weather").await]).await;
+
+    match tokio_tungstenite::connect_async(format!("ws://{addr}/other")).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+        }
+        result => panic!("unexpected handshake result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_invoke() {
+    use crate::proto::oak::session::v1::{GetPublicKeyRequest, InvokeRequest};
+    use oak_crypto::{encryptor::ClientEncryptor, proto::oak::crypto::v1::EncryptedResponse};
+
+    let addr = start_websocket_server(vec![start_pong_route("/weather").await]).await;
+    let (mut websocket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/weather"))
+        .await
+        .expect("couldn't complete handshake");
+    let public_key = match round_trip(
+        &mut websocket,
+        request_wrapper::Request::GetPublicKeyRequest(GetPublicKeyRequest::default()),
+    )
+    .await
+    {
+        response_wrapper::Response::GetPublicKeyResponse(response) => {
+            response
+                .attestation_bundle
+                .unwrap()
+                .attestation_evidence
+                .unwrap()
+                .encryption_public_key
+        }
+        response => panic!("unexpected response: {:?}", response),
+    };
+
+    let mut client_encryptor = ClientEncryptor::create(&public_key).unwrap();
+    let encrypted_request = client_encryptor.encrypt(b"ping", b"").unwrap();
+    let encrypted_body = match round_trip(
+        &mut websocket,
+        request_wrapper::Request::InvokeRequest(InvokeRequest {
+            encrypted_body: encrypted_request.encode_to_vec(),
+        }),
+    )
+    .await
+    {
+        response_wrapper::Response::InvokeResponse(response) => response.encrypted_body,
+        response => panic!("unexpected response: {:?}", response),
+    };
+    let encrypted_response = EncryptedResponse::decode(encrypted_body.as_slice()).unwrap();
+    let (response, _) = client_encryptor.decrypt(&encrypted_response).unwrap();
+    assert_eq!(response, b"pong");
+}
@@ -0,0 +1,339 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Export of traces of the work of the launcher to an OpenTelemetry collector, using OTLP over
+//! HTTP with JSON encoding.
+//!
+//! Spans only cover what the launcher observes on the host: every request of a client session,
+//! including the invocation of the enclave, and every update of the lookup data in the enclave. The
+//! execution of the Wasm module and its lookups happen inside the enclave and are part of the span
+//! of the invocation, since separate spans would reveal which lookups a request made. Spans of
+//! invocations end when their response is returned, so with a constant processing time they don't
+//! reveal how long the enclave took either.
+//!
+//! Clients continue their own traces by sending a W3C `traceparent` header with the WebSocket
+//! handshake, or as metadata of the gRPC session.
+
+use anyhow::Context;
+use hyper::{client::HttpConnector, header::CONTENT_TYPE, Body, Client, Method, Request, Uri};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use once_cell::sync::OnceCell;
+use rand::RngCore;
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// Header, or gRPC metadata key, with which clients propagate the context of their trace.
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Maximum number of spans that are exported in a single request.
+const MAX_BATCH_SIZE: usize = 512;
+
+/// Maximum number of finished spans that wait to be exported. Spans that finish while the queue is
+/// full are dropped, so that a slow collector doesn't hold up requests.
+const MAX_QUEUED_SPANS: usize = 2048;
+
+/// Interval at which spans are exported, unless a batch fills up earlier.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Queue of the spans that wait to be exported, set once export is initialized.
+static EXPORTER: OnceCell<mpsc::Sender<Value>> = OnceCell::new();
+
+/// Configuration of the export of traces.
+#[derive(Clone, Debug)]
+pub struct TraceConfig {
+    /// URL to which spans are exported, i.e. the `/v1/traces` path of the collector.
+    traces_url: Uri,
+    /// Name of the service to which spans are attributed.
+    service_name: String,
+}
+
+impl TraceConfig {
+    /// Exports spans to the OTLP/HTTP endpoint of the collector at `endpoint`, e.g.
+    /// `http://localhost:4318`, attributed to `service_name`.
+    pub fn new(endpoint: &str, service_name: &str) -> anyhow::Result<Self> {
+        let traces_url = format!("{}/v1/traces", endpoint.trim_end_matches('/'))
+            .parse()
+            .with_context(|| format!("invalid OTLP endpoint {:?}", endpoint))?;
+        Ok(Self {
+            traces_url,
+            service_name: service_name.to_string(),
+        })
+    }
+}
+
+/// Starts exporting spans in the background according to `config`. Spans are discarded until this
+/// is called. Must be called from within a Tokio runtime.
+pub fn init(config: TraceConfig) -> anyhow::Result<()> {
+    let (sender, receiver) = mpsc::channel(MAX_QUEUED_SPANS);
+    EXPORTER
+        .set(sender)
+        .map_err(|_| anyhow::anyhow!("trace export is already initialized"))?;
+    tokio::spawn(export(config, receiver));
+    Ok(())
+}
+
+/// Context of the trace of a client, as propagated in its `traceparent` header according to the
+/// W3C Trace Context specification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub parent_span_id: [u8; 8],
+    /// Whether the client records its trace, in which case the spans of the launcher are recorded
+    /// too.
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Parses the value of a `traceparent` header, e.g.
+    /// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`. Returns `None` if it is invalid,
+    /// in which case the spans of the launcher start a new trace.
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut fields = traceparent.trim().split('-');
+        let [version] = decode_hex::<1>(fields.next()?)?;
+        let trace_id = decode_hex::<16>(fields.next()?)?;
+        let parent_span_id = decode_hex::<8>(fields.next()?)?;
+        let [flags] = decode_hex::<1>(fields.next()?)?;
+        // Version 0 has exactly four fields, later versions may append more.
+        if version == 0xff || (version == 0 && fields.next().is_some()) {
+            return None;
+        }
+        if trace_id == [0; 16] || parent_span_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            parent_span_id,
+            sampled: flags & 1 == 1,
+        })
+    }
+}
+
+// Decodes exactly `N` bytes of lowercase hex, as used by trace contexts.
+fn decode_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        return None;
+    }
+    let mut bytes = [0; N];
+    hex::decode_to_slice(hex, &mut bytes).ok()?;
+    Some(bytes)
+}
+
+// Returns a random trace or span ID, which must not be all zeros.
+fn random_id<const N: usize>() -> [u8; N] {
+    let mut id = [0; N];
+    loop {
+        rand::thread_rng().fill_bytes(&mut id);
+        if id != [0; N] {
+            return id;
+        }
+    }
+}
+
+/// Kind of a span, with the values defined by OTLP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpanKind {
+    /// Work that the launcher does on its own, e.g. updating the lookup data.
+    Internal = 1,
+    /// Handling a request of a client.
+    Server = 2,
+}
+
+/// A span that is exported when it is dropped. Spans are not recorded if export isn't initialized,
+/// or if the client doesn't record its trace.
+pub struct Span {
+    data: Option<SpanData>,
+}
+
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    name: &'static str,
+    kind: SpanKind,
+    start: SystemTime,
+    attributes: Vec<(&'static str, String)>,
+    error: Option<String>,
+}
+
+impl Span {
+    /// Starts a span named `name`, as a child of the span of the client if `parent` is set, and
+    /// as the root of a new trace otherwise.
+    pub fn start(name: &'static str, kind: SpanKind, parent: Option<TraceContext>) -> Self {
+        if EXPORTER.get().is_none() || parent.map_or(false, |parent| !parent.sampled) {
+            return Self { data: None };
+        }
+        Self {
+            data: Some(SpanData::new(name, kind, parent)),
+        }
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: impl ToString) {
+        if let Some(data) = &mut self.data {
+            data.attributes.push((key, value.to_string()));
+        }
+    }
+
+    /// Marks the span as failed with `message`.
+    pub fn set_error(&mut self, message: impl ToString) {
+        if let Some(data) = &mut self.data {
+            data.error = Some(message.to_string());
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let (Some(data), Some(exporter)) = (self.data.take(), EXPORTER.get()) {
+            if exporter.try_send(data.to_json(SystemTime::now())).is_err() {
+                log::debug!("trace export queue is full, dropping span {}", data.name);
+            }
+        }
+    }
+}
+
+impl SpanData {
+    fn new(name: &'static str, kind: SpanKind, parent: Option<TraceContext>) -> Self {
+        Self {
+            trace_id: parent.map_or_else(random_id, |parent| parent.trace_id),
+            span_id: random_id(),
+            parent_span_id: parent.map(|parent| parent.parent_span_id),
+            name,
+            kind,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            error: None,
+        }
+    }
+
+    // Returns the span in the OTLP JSON encoding, which uses hex for IDs, unlike the standard
+    // protobuf JSON mapping.
+    fn to_json(&self, end: SystemTime) -> Value {
+        let attributes = self
+            .attributes
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()));
+        let status = match &self.error {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({}),
+        };
+        let mut span = json!({
+            "traceId": hex::encode(self.trace_id),
+            "spanId": hex::encode(self.span_id),
+            "name": self.name,
+            "kind": self.kind as u8,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(end),
+            "attributes": to_json_attributes(attributes),
+            "status": status,
+        });
+        if let Some(parent_span_id) = self.parent_span_id {
+            span["parentSpanId"] = hex::encode(parent_span_id).into();
+        }
+        span
+    }
+}
+
+// 64-bit integers are encoded as strings in JSON.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn to_json_attributes<'a>(attributes: impl IntoIterator<Item = (&'a str, &'a str)>) -> Value {
+    attributes
+        .into_iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+// Returns the body of an OTLP export request with `spans`.
+fn export_request(service_name: &str, spans: Vec<Value>) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": to_json_attributes([("service.name", service_name)]),
+            },
+            "scopeSpans": [{
+                "scope": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+// Exports the spans from `receiver` in batches, until all senders are dropped. Batches that can't
+// be exported are dropped rather than retried.
+async fn export(config: TraceConfig, mut receiver: mpsc::Receiver<Value>) {
+    let connector = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = Client::builder().build(connector);
+    let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+    let mut batch = Vec::new();
+    loop {
+        tokio::select! {
+            span = receiver.recv() => match span {
+                Some(span) => {
+                    batch.push(span);
+                    if batch.len() < MAX_BATCH_SIZE {
+                        continue;
+                    }
+                }
+                None => return,
+            },
+            _ = interval.tick() => {}
+        }
+        if batch.is_empty() {
+            continue;
+        }
+        let body = export_request(&config.service_name, std::mem::take(&mut batch));
+        if let Err(err) = send(&client, &config.traces_url, body).await {
+            log::warn!("couldn't export spans: {:?}", err);
+        }
+    }
+}
+
+async fn send(
+    client: &Client<HttpsConnector<HttpConnector>>,
+    traces_url: &Uri,
+    body: Value,
+) -> anyhow::Result<()> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(traces_url.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .context("couldn't build export request")?;
+    let response = client
+        .request(request)
+        .await
+        .context("couldn't send spans to the collector")?;
+    if !response.status().is_success() {
+        anyhow::bail!("collector responded with status {}", response.status());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,82 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+#[test]
+fn test_parse_traceparent() {
+    let context =
+        TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+    assert_eq!(
+        hex::encode(context.trace_id),
+        "4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+    assert_eq!(hex::encode(context.parent_span_id), "00f067aa0ba902b7");
+    assert!(context.sampled);
+    let context =
+        TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap();
+    assert!(!context.sampled);
+
+    // Later versions may append fields.
+    assert!(
+        TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-ab").is_some()
+    );
+    for invalid in [
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-ab",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+    ] {
+        assert_eq!(TraceContext::parse(invalid), None, "{invalid}");
+    }
+}
+
+#[test]
+fn test_span_to_json() {
+    let parent =
+        TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+    let mut data = SpanData::new("Invoke", SpanKind::Server, Some(parent));
+    data.start = UNIX_EPOCH + Duration::from_secs(1);
+    data.attributes.push(("oak.route", "/".to_string()));
+    data.error = Some("failed".to_string());
+    let span = data.to_json(UNIX_EPOCH + Duration::from_secs(2));
+    assert_eq!(span["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(span["parentSpanId"], "00f067aa0ba902b7");
+    assert_eq!(span["spanId"].as_str().unwrap().len(), 16);
+    assert_eq!(span["kind"], 2);
+    assert_eq!(span["startTimeUnixNano"], "1000000000");
+    assert_eq!(span["endTimeUnixNano"], "2000000000");
+    assert_eq!(
+        span["attributes"],
+        json!([{ "key": "oak.route", "value": { "stringValue": "/" } }])
+    );
+    assert_eq!(span["status"], json!({ "code": 2, "message": "failed" }));
+
+    let root = SpanData::new("UpdateLookupData", SpanKind::Internal, None);
+    let span = root.to_json(SystemTime::now());
+    assert_eq!(span["traceId"].as_str().unwrap().len(), 32);
+    assert!(span.get("parentSpanId").is_none());
+}
+
+#[test]
+fn test_span_not_recorded_without_export() {
+    let mut span = Span::start("Invoke", SpanKind::Server, None);
+    span.set_attribute("oak.route", "/");
+    assert!(span.data.is_none());
+}
@@ -0,0 +1,73 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Detection of changes to the files that the launcher loads into the enclave.
+
+use anyhow::Context;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::{fs, path::Path, time::SystemTime};
+use tokio::sync::mpsc;
+
+/// Identifies a version of a file, so that unchanged files are not reloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileVersion {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl FileVersion {
+    pub fn of(path: &Path) -> anyhow::Result<Self> {
+        let metadata = fs::metadata(path)
+            .with_context(|| format!("couldn't read metadata of {}", path.display()))?;
+        Ok(Self {
+            modified: metadata.modified()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+/// Watches the file at `path` for changes, and notifies `changes` whenever it is modified.
+///
+/// The parent directory is watched rather than the file itself, so that files that are replaced
+/// atomically by renaming are detected too. Notifications stop when the returned watcher is
+/// dropped.
+pub fn watch_file(path: &Path, changes: mpsc::Sender<()>) -> anyhow::Result<RecommendedWatcher> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{} has no file name", path.display()))?
+        .to_os_string();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if event
+                .paths
+                .iter()
+                .any(|path| path.file_name() == Some(file_name.as_os_str()))
+            {
+                // If the channel is full, an update is already pending.
+                let _ = changes.try_send(());
+            }
+        }
+    })
+    .context("couldn't create file watcher")?;
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .with_context(|| format!("couldn't watch directory {}", directory.display()))?;
+    Ok(watcher)
+}
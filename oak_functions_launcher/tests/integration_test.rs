@@ -18,8 +18,10 @@
 use oak_crypto::{encryptor::ClientEncryptor, proto::oak::crypto::v1::EncryptedResponse};
 use oak_functions_client::OakFunctionsClient;
 use oak_functions_launcher::{
-    proto::oak::functions::{InvokeRequest, OakFunctionsAsyncClient},
-    update_lookup_data, LookupDataConfig,
+    compression::Compression,
+    proto::oak::functions::{InvokeRequest, LookupDataBackend, OakFunctionsAsyncClient},
+    retry::RetryPolicy,
+    update_lookup_data, LookupDataConfig, LookupDataSource, ResponsePolicy, WasmConfig,
 };
 use oak_launcher_utils::launcher;
 use prost::Message;
@@ -156,20 +158,31 @@ async fn test_launcher_looks_up_key() {
     let constant_response_size: u32 = 1024;
 
     let lookup_data_config = LookupDataConfig {
-        lookup_data_path: xtask::launcher::MOCK_LOOKUP_DATA_PATH.to_path_buf(),
+        lookup_data_source: LookupDataSource::File(
+            xtask::launcher::MOCK_LOOKUP_DATA_PATH.to_path_buf(),
+        ),
         update_interval: None,
         max_chunk_size: ByteUnit::Gibibyte(2),
+        compression: Compression::None,
+        initial_load_retry: RetryPolicy::default(),
+        start_without_lookup_data: false,
+        backend: LookupDataBackend::HashMap,
+        expiry_interval: None,
     };
 
     let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("key_value_lookup")
         .expect("Failed to build Wasm module");
 
-    let (launched_instance, connector_handle, initialize_response) =
+    let (launched_instance, connector_handle, initialize_response, _) =
         oak_functions_launcher::create(
             launcher::GuestMode::Native(params),
             lookup_data_config,
-            wasm_path.into(),
-            constant_response_size,
+            std::fs::read(wasm_path).expect("couldn't read Wasm module"),
+            ResponsePolicy {
+                constant_response_size,
+                ..Default::default()
+            },
+            WasmConfig::default(),
         )
         .await
         .expect("Failed to create launcher");
@@ -240,28 +253,42 @@ async fn test_load_large_lookup_data() {
         &oak_functions_test_utils::serialize_entries(entries_one_chunk),
     );
     let lookup_data_config = LookupDataConfig {
-        lookup_data_path: lookup_data_file.path().to_path_buf(),
+        lookup_data_source: LookupDataSource::File(lookup_data_file.path().to_path_buf()),
         update_interval: None,
         max_chunk_size,
+        compression: Compression::None,
+        initial_load_retry: RetryPolicy::default(),
+        start_without_lookup_data: false,
+        backend: LookupDataBackend::HashMap,
+        expiry_interval: None,
     };
     let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("key_value_lookup")
         .expect("Failed to build Wasm module");
     let status_one_chunk = oak_functions_launcher::create(
         launcher::GuestMode::Native(params),
         lookup_data_config,
-        wasm_path.into(),
-        1024,
+        std::fs::read(wasm_path).expect("couldn't read Wasm module"),
+        ResponsePolicy {
+            constant_response_size: 1024,
+            ..Default::default()
+        },
+        WasmConfig::default(),
     )
     .await;
     assert!(status_one_chunk.is_ok());
 
-    let (launched_instance, connector_handle, _) = status_one_chunk.unwrap();
+    let (launched_instance, connector_handle, _, _) = status_one_chunk.unwrap();
     let mut client = OakFunctionsAsyncClient::new(connector_handle);
 
     let lookup_data_config = LookupDataConfig {
-        lookup_data_path: lookup_data_file.path().to_path_buf(),
+        lookup_data_source: LookupDataSource::File(lookup_data_file.path().to_path_buf()),
         update_interval: None,
         max_chunk_size,
+        compression: Compression::None,
+        initial_load_retry: RetryPolicy::default(),
+        start_without_lookup_data: false,
+        backend: LookupDataBackend::HashMap,
+        expiry_interval: None,
     };
 
     // Write 2 chunks in lookup data.
@@ -308,17 +335,26 @@ async fn test_load_two_gib_lookup_data() {
     );
     // This takes >5 min but will get there eventually.
     let lookup_data_config = LookupDataConfig {
-        lookup_data_path: lookup_data_file.path().to_path_buf(),
+        lookup_data_source: LookupDataSource::File(lookup_data_file.path().to_path_buf()),
         update_interval: None,
         max_chunk_size,
+        compression: Compression::None,
+        initial_load_retry: RetryPolicy::default(),
+        start_without_lookup_data: false,
+        backend: LookupDataBackend::HashMap,
+        expiry_interval: None,
     };
     let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("key_value_lookup")
         .expect("Failed to build Wasm module");
     let status = oak_functions_launcher::create(
         launcher::GuestMode::Native(params),
         lookup_data_config,
-        wasm_path.into(),
-        1024,
+        std::fs::read(wasm_path).expect("couldn't read Wasm module"),
+        ResponsePolicy {
+            constant_response_size: 1024,
+            ..Default::default()
+        },
+        WasmConfig::default(),
     )
     .await;
     assert!(status.is_ok());
@@ -0,0 +1,90 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Procedural macros for the Oak Functions SDK. Use them via the re-exports in
+//! `oak_functions_sdk` rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, ItemFn, Signature};
+
+/// Marks a function as the request handler of an Oak Functions Wasm module.
+///
+/// The handler takes the request as its only argument and returns the response. The macro
+/// generates the `main` function that is exported to the Oak Functions runtime, which reads the
+/// request, converts it with `oak_functions_sdk::handler::FromRequest`, calls the handler and
+/// writes the response converted with `oak_functions_sdk::handler::IntoResponse`.
+///
+/// ```ignore
+/// #[oak_functions_sdk::main]
+/// fn handle(request: Vec<u8>) -> Vec<u8> {
+///     request
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::TokenStream::from(attr).span(),
+            "`#[oak_functions_sdk::main]` does not take any arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let handler = parse_macro_input!(item as ItemFn);
+    if let Err(err) = check_signature(&handler.sig) {
+        return err.to_compile_error().into();
+    }
+    let handler_name = &handler.sig.ident;
+
+    quote! {
+        #handler
+
+        #[cfg_attr(not(test), no_mangle)]
+        pub extern "C" fn main() {
+            ::oak_functions_sdk::handler::run(#handler_name)
+        }
+    }
+    .into()
+}
+
+fn check_signature(signature: &Signature) -> syn::Result<()> {
+    if signature.ident == "main" {
+        return Err(syn::Error::new(
+            signature.ident.span(),
+            "the handler must not be called `main`, since `main` is the generated export",
+        ));
+    }
+    if let Some(asyncness) = signature.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "the handler must not be async",
+        ));
+    }
+    if !signature.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            signature.generics.span(),
+            "the handler must not be generic",
+        ));
+    }
+    if signature.inputs.len() != 1 {
+        return Err(syn::Error::new(
+            signature.inputs.span(),
+            "the handler must take exactly one argument, the request",
+        ));
+    }
+    Ok(())
+}
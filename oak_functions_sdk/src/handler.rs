@@ -0,0 +1,106 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Conversions between the raw request and response bodies and the argument and return types of
+//! handlers annotated with [`main`](crate::main).
+
+use crate::{log, read_request, write_response};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Display;
+
+/// A type that a handler can take as its request argument.
+pub trait FromRequest: Sized {
+    fn from_request(body: Vec<u8>) -> Result<Self, String>;
+}
+
+/// A type that a handler can return as its response.
+pub trait IntoResponse {
+    fn into_response(self) -> Result<Vec<u8>, String>;
+}
+
+impl FromRequest for Vec<u8> {
+    fn from_request(body: Vec<u8>) -> Result<Self, String> {
+        Ok(body)
+    }
+}
+
+impl FromRequest for String {
+    fn from_request(body: Vec<u8>) -> Result<Self, String> {
+        String::from_utf8(body).map_err(|err| format!("request is not valid UTF-8: {err}"))
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Result<Vec<u8>, String> {
+        Ok(self)
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Result<Vec<u8>, String> {
+        Ok(self.into_bytes())
+    }
+}
+
+/// Returning an error from a handler aborts the invocation, after logging the error.
+impl<T: IntoResponse, E: Display> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Result<Vec<u8>, String> {
+        self.map_err(|err| err.to_string())?.into_response()
+    }
+}
+
+/// A request or response that is serialized as JSON.
+///
+/// ```ignore
+/// #[oak_functions_sdk::main]
+/// fn handle(Json(request): Json<WeatherRequest>) -> Json<WeatherResponse> {
+///     ...
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Json<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(body: Vec<u8>) -> Result<Self, String> {
+        serde_json::from_slice(&body)
+            .map(Json)
+            .map_err(|err| format!("couldn't parse JSON request: {err}"))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(&self.0)
+            .map_err(|err| format!("couldn't serialize JSON response: {err}"))
+    }
+}
+
+/// Runs a single invocation of `handler`. This is called by the `main` function generated by the
+/// [`main`](crate::main) attribute, and is not meant to be called directly.
+///
+/// If the request cannot be converted, or the handler fails, the error is logged and the invocation
+/// is aborted without writing a response.
+#[doc(hidden)]
+pub fn run<Req: FromRequest, Res: IntoResponse>(handler: impl FnOnce(Req) -> Res) {
+    let body = read_request().expect("couldn't read request body");
+    match Req::from_request(body).and_then(|request| handler(request).into_response()) {
+        Ok(response) => write_response(&response).expect("couldn't write response body"),
+        Err(err) => {
+            log!("handler failed: {}", err);
+            panic!("handler failed: {err}");
+        }
+    }
+}
@@ -16,9 +16,17 @@
 
 #![doc = include_str!("../README.md")]
 
-use oak_functions_abi::{proto::OakStatus, StorageGetItemResponse};
+pub mod handler;
+
+use oak_functions_abi::{
+    proto::OakStatus, StorageGetItemResponse, StorageGetRangeRequest, StorageGetRangeResponse,
+};
 use std::convert::AsRef;
 
+pub use handler::Json;
+pub use oak_functions_abi::ResponseMetadata;
+pub use oak_functions_sdk_macros::main;
+
 /// See [`read_request`](https://github.com/project-oak/oak/blob/main/docs/oak_functions_abi.md#oak_functions_abi.md#read_request).
 pub fn read_request() -> Result<Vec<u8>, OakStatus> {
     let mut buf_ptr: *mut u8 = std::ptr::null_mut();
@@ -40,6 +48,18 @@ pub fn write_response(buf: &[u8]) -> Result<(), OakStatus> {
     result_from_status(status as i32, ())
 }
 
+/// Sets the HTTP status code and headers of the response. Only headers in
+/// [`oak_functions_abi::ALLOWED_RESPONSE_HEADERS`] may be set, otherwise
+/// [`OakStatus::ErrInvalidArgs`] is returned. If this is not called, the response has the status
+/// `200 OK` and no headers.
+///
+/// The metadata only reaches the client if the runtime is configured to include it in responses.
+pub fn write_response_metadata(metadata: &ResponseMetadata) -> Result<(), OakStatus> {
+    let buf = Vec::from(metadata);
+    let status = unsafe { oak_functions_abi::write_response_metadata(buf.as_ptr(), buf.len()) };
+    result_from_status(status as i32, ())
+}
+
 /// Looks up an item from the in-memory lookup store.
 pub fn storage_get_item(key: &[u8]) -> Result<Option<Vec<u8>>, OakStatus> {
     let response = invoke(oak_functions_abi::ExtensionHandle::LookupHandle, key)?;
@@ -50,16 +70,62 @@ pub fn storage_get_item(key: &[u8]) -> Result<Option<Vec<u8>>, OakStatus> {
     Ok(result.value)
 }
 
+/// Looks up the items whose keys are at least `start` and, if given, less than `end` from the
+/// in-memory lookup store, ordered by key.
+///
+/// The runtime returns at most the number of items it is configured with, and sets
+/// [`StorageGetRangeResponse::truncated`] if further items were left out.
+pub fn storage_get_range(
+    start: &[u8],
+    end: Option<&[u8]>,
+) -> Result<StorageGetRangeResponse, OakStatus> {
+    get_range(StorageGetRangeRequest {
+        start: start.to_vec(),
+        end: end.map(<[u8]>::to_vec),
+    })
+}
+
+/// Looks up the items whose keys start with `prefix` from the in-memory lookup store, ordered by
+/// key.
+///
+/// The number of items is limited as in [`storage_get_range`].
+pub fn storage_get_prefix(prefix: &[u8]) -> Result<StorageGetRangeResponse, OakStatus> {
+    get_range(StorageGetRangeRequest::prefix(prefix))
+}
+
+fn get_range(request: StorageGetRangeRequest) -> Result<StorageGetRangeResponse, OakStatus> {
+    let response = invoke(
+        oak_functions_abi::ExtensionHandle::LookupRangeHandle,
+        &Vec::from(request),
+    )?;
+    (&response[..]).try_into().map_err(|err| {
+        log!("Failed to deserialize response: {}", err);
+        OakStatus::ErrSerializing
+    })
+}
+
 /// Writes a debug log message.
 ///
-/// These log messages are considered sensitive, so will only be logged by the runtime if the
-/// `oak_unsafe` feature is enabled.
+/// These log messages are considered sensitive, so will only be logged by the runtime if sensitive
+/// logging is enabled for debugging. Otherwise they are dropped.
 pub fn write_log_message<T: AsRef<str>>(message: T) -> Result<(), OakStatus> {
     let buf = message.as_ref().as_bytes();
     invoke(oak_functions_abi::ExtensionHandle::LoggingHandle, buf)?;
     Ok(())
 }
 
+/// Counts the current invocation towards the given bucket of the differentially private metrics.
+///
+/// Reporting the same bucket more than once per invocation has no further effect. Only noised
+/// counts that are aggregated over many invocations are released by the runtime.
+pub fn report_metric(bucket: &str) -> Result<(), OakStatus> {
+    invoke(
+        oak_functions_abi::ExtensionHandle::MetricsHandle,
+        bucket.as_bytes(),
+    )?;
+    Ok(())
+}
+
 /// Calls the testing extension with the given request. The response is directly passed on, as it is
 /// decoded by the caller.
 pub fn testing(request: &[u8]) -> Result<Vec<u8>, OakStatus> {
@@ -94,8 +160,8 @@ fn invoke(
 
 /// Logs a debug message.
 ///
-/// These log messages are considered sensitive, so will only be logged by the runtime if the
-/// `oak_unsafe` feature is enabled.
+/// These log messages are considered sensitive, so will only be logged by the runtime if sensitive
+/// logging is enabled for debugging. Otherwise they are dropped.
 #[macro_export]
 macro_rules! log {
     ($($arg:tt)+) => {
@@ -20,6 +20,7 @@ use oak_functions_abi::{Request, Response};
 use oak_functions_lookup::{LookupDataManager, LookupFactory};
 use oak_functions_wasm::WasmHandler;
 use oak_functions_workload_logging::WorkloadLoggingFactory;
+use prost::Message;
 use std::{path::PathBuf, sync::Arc};
 
 lazy_static! {
@@ -40,6 +41,22 @@ lazy_static! {
         manifest_path.push("testing_module");
         manifest_path.push("Cargo.toml");
 
+        oak_functions_test_utils::compile_rust_wasm(manifest_path.to_str().unwrap(), false)
+            .expect("couldn't read Wasm module")
+    };
+    static ref MACRO_WASM_MODULE_BYTES: Vec<u8> = {
+        let mut manifest_path = PATH_TO_MODULES.clone();
+        manifest_path.push("macro_module");
+        manifest_path.push("Cargo.toml");
+
+        oak_functions_test_utils::compile_rust_wasm(manifest_path.to_str().unwrap(), false)
+            .expect("couldn't read Wasm module")
+    };
+    static ref PROTO_WASM_MODULE_BYTES: Vec<u8> = {
+        let mut manifest_path = PATH_TO_MODULES.clone();
+        manifest_path.push("proto_module");
+        manifest_path.push("Cargo.toml");
+
         oak_functions_test_utils::compile_rust_wasm(manifest_path.to_str().unwrap(), false)
             .expect("couldn't read Wasm module")
     };
@@ -47,7 +64,7 @@ lazy_static! {
 
 #[tokio::test]
 async fn test_read_write() {
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let lookup_data_manager = Arc::new(LookupDataManager::for_test(HashMap::new(), logger.clone()));
     let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager)
         .expect("couldn't create LookupFactory");
@@ -64,7 +81,7 @@ async fn test_read_write() {
 
 #[tokio::test]
 async fn test_double_read() {
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let lookup_data_manager = Arc::new(LookupDataManager::for_test(HashMap::new(), logger.clone()));
     let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager)
         .expect("couldn't create LookupFactory");
@@ -81,7 +98,7 @@ async fn test_double_read() {
 
 #[tokio::test]
 async fn test_double_write() {
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let lookup_data_manager = Arc::new(LookupDataManager::for_test(HashMap::new(), logger.clone()));
     let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager)
         .expect("couldn't create LookupFactory");
@@ -98,7 +115,7 @@ async fn test_double_write() {
 
 #[tokio::test]
 async fn test_write_log() {
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let lookup_data_manager = Arc::new(LookupDataManager::for_test(HashMap::new(), logger.clone()));
     let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager)
         .expect("couldn't create LookupFactory");
@@ -125,7 +142,7 @@ async fn test_storage_get_item() {
     let entries =
         HashMap::from_iter([(b"StorageGet".to_vec(), b"StorageGetResponse".to_vec())].into_iter());
 
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let lookup_data_manager = Arc::new(LookupDataManager::for_test(entries, logger.clone()));
     let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager)
         .expect("couldn't create LookupFactory");
@@ -145,7 +162,7 @@ async fn test_storage_get_item_not_found() {
     // empty lookup data, no key will be found
     let entries = HashMap::new();
 
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let lookup_data_manager = Arc::new(LookupDataManager::for_test(entries, logger.clone()));
     let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager)
         .expect("couldn't create LookupFactory");
@@ -166,7 +183,7 @@ async fn test_storage_get_item_huge_key() {
     let bytes: Vec<u8> = vec![42u8; 1 << 20];
     let entries = HashMap::from_iter([(bytes.clone(), bytes.clone())].into_iter());
 
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let lookup_data_manager = Arc::new(LookupDataManager::for_test(entries, logger.clone()));
     let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager)
         .expect("couldn't create LookupFactory");
@@ -184,7 +201,7 @@ async fn test_storage_get_item_huge_key() {
 
 #[tokio::test]
 async fn test_echo() {
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let message_to_echo = "ECHO";
 
     let testing_factory =
@@ -210,7 +227,7 @@ async fn test_blackhole() {
     // Keep in sync with
     // `workspace/oak_functions/sdk/oak_functions/tests/testing_module/src/lib.rs`.
 
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
     let message_to_blackhole = "BLACKHOLE";
 
     let testing_factory =
@@ -237,7 +254,7 @@ async fn test_huge_response() {
     // Keep in sync with
     // `workspace/oak_functions/sdk/oak_functions/tests/testing_module/src/lib.rs`.
 
-    let logger = oak_functions_service::StandaloneLogger {};
+    let logger = oak_functions_service::StandaloneLogger::default();
 
     let testing_factory =
         oak_functions_testing_extension::TestingFactory::new_boxed_extension_factory(
@@ -256,3 +273,60 @@ async fn test_huge_response() {
     let response: Response = wasm_handler.handle_invoke(request).unwrap();
     assert_eq!(response.body, vec![42u8; 1 << 20])
 }
+
+#[tokio::test]
+async fn test_main_macro_json() {
+    let logger = oak_functions_service::StandaloneLogger::default();
+    let wasm_handler = WasmHandler::create(&MACRO_WASM_MODULE_BYTES, vec![], logger)
+        .expect("couldn't instantiate WasmHandler");
+
+    let request = Request {
+        body: br#"{"name":"Oak"}"#.to_vec(),
+    };
+    let response: Response = wasm_handler.handle_invoke(request).unwrap();
+    oak_functions_test_utils::assert_response_body(response, r#"{"greeting":"Hello, Oak!"}"#);
+}
+
+#[tokio::test]
+async fn test_main_macro_invalid_request() {
+    let logger = oak_functions_service::StandaloneLogger::default();
+    let wasm_handler = WasmHandler::create(&MACRO_WASM_MODULE_BYTES, vec![], logger)
+        .expect("couldn't instantiate WasmHandler");
+
+    // Neither a request that cannot be parsed nor a handler error produce a response.
+    for body in [b"not JSON".to_vec(), br#"{"name":""}"#.to_vec()] {
+        let response: Response = wasm_handler.handle_invoke(Request { body }).unwrap();
+        assert!(response.body.is_empty());
+    }
+}
+
+// Keep in sync with `oak_functions_sdk/tests/proto_module/proto/greeting.proto`.
+#[derive(Clone, PartialEq, Message)]
+struct GreetingRequest {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct GreetingResponse {
+    #[prost(string, tag = "1")]
+    greeting: String,
+}
+
+#[tokio::test]
+async fn test_generated_proto_conversions() {
+    let logger = oak_functions_service::StandaloneLogger::default();
+    let wasm_handler = WasmHandler::create(&PROTO_WASM_MODULE_BYTES, vec![], logger)
+        .expect("couldn't instantiate WasmHandler");
+
+    let request = GreetingRequest {
+        name: "Oak".to_string(),
+    };
+    let response: Response = wasm_handler
+        .handle_invoke(Request {
+            body: request.encode_to_vec(),
+        })
+        .unwrap();
+    let response = GreetingResponse::decode(response.body().unwrap()).unwrap();
+    assert_eq!(response.greeting, "Hello, Oak!");
+}
@@ -0,0 +1,40 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Oak Functions SDK `main` attribute test.
+
+use oak_functions_sdk::Json;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+struct GreetingRequest {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct GreetingResponse {
+    greeting: String,
+}
+
+#[oak_functions_sdk::main]
+fn handle(Json(request): Json<GreetingRequest>) -> Result<Json<GreetingResponse>, String> {
+    if request.name.is_empty() {
+        return Err("empty name".to_string());
+    }
+    Ok(Json(GreetingResponse {
+        greeting: format!("Hello, {}!", request.name),
+    }))
+}
@@ -0,0 +1,97 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+#![feature(result_flattening)]
+
+use oak_functions_client::OakFunctionsClient;
+use oak_functions_launcher::{
+    proto::oak::functions::{InitializeRequest, OakFunctionsAsyncClient},
+    server::{RequestLimiter, Route, DEFAULT_ROUTE},
+};
+use oak_functions_sdk_proto_client_test::proto::{GreetingClient, GreetingRequest};
+use oak_functions_service::{proto::oak::functions::OakFunctionsServer, OakFunctionsService};
+use oak_launcher_utils::channel::Connector;
+use oak_remote_attestation::attester::EmptyAttestationReportGenerator;
+use std::{os::unix::net::UnixStream, sync::Arc, time::Duration};
+
+// Serves `proto_module` through the server of the launcher, with the Oak Functions service running
+// in a thread instead of an enclave, and returns the address of the server.
+async fn serve_proto_module() -> std::net::SocketAddr {
+    let manifest_path = format!("{}/../proto_module/Cargo.toml", env!("CARGO_MANIFEST_DIR"));
+    let wasm_module = oak_functions_test_utils::compile_rust_wasm(&manifest_path, false)
+        .expect("couldn't compile proto_module");
+
+    let (launcher_channel, enclave_channel) = UnixStream::pair().unwrap();
+    std::thread::spawn(move || {
+        let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+        let mut stats = oak_core::samplestore::StaticSampleStore::<1>::new().unwrap();
+        let _ = oak_channel::server::start_blocking_server(
+            Box::new(enclave_channel),
+            OakFunctionsServer::new(service),
+            &mut stats,
+        );
+    });
+    let connector_handle = Connector::spawn(Box::new(launcher_channel));
+    let initialize_response = OakFunctionsAsyncClient::new(connector_handle.clone())
+        .initialize(&InitializeRequest {
+            wasm_module,
+            constant_response_size: 1024,
+            ..Default::default()
+        })
+        .await
+        .flatten()
+        .expect("couldn't initialize service");
+    let (_, public_key_info) =
+        tokio::sync::watch::channel(initialize_response.public_key_info.unwrap());
+    let route = Route {
+        prefix: DEFAULT_ROUTE.to_string(),
+        connector_handle,
+        public_key_info,
+        constant_processing_time: None,
+    };
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    let server = oak_functions_launcher::server::new(
+        addr,
+        vec![route],
+        None,
+        RequestLimiter::default(),
+        std::future::pending(),
+    )
+    .expect("couldn't create server");
+    tokio::spawn(server);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    addr
+}
+
+#[tokio::test]
+async fn test_generated_client() {
+    let addr = serve_proto_module().await;
+    let oak_functions_client = OakFunctionsClient::new(&format!("http://{addr}"))
+        .await
+        .expect("couldn't connect to server");
+    let mut client = GreetingClient::new(oak_functions_client);
+
+    let response = client
+        .greet(&GreetingRequest {
+            name: "Oak".to_string(),
+        })
+        .await
+        .expect("couldn't invoke proto_module");
+    assert_eq!(response.greeting, "Hello, Oak!");
+}
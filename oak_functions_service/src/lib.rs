@@ -29,42 +29,133 @@ pub mod proto {
     }
 }
 mod logger;
-mod wasm;
+mod policy;
+pub mod wasm;
 
+use crate::policy::PaddingHandler;
 use alloc::{boxed::Box, format, sync::Arc};
-use oak_functions_lookup::LookupDataManager;
+use core::sync::atomic::{AtomicU64, Ordering};
+use oak_functions_abi::proto::{ConfigurationReport, ServerPolicy};
+use oak_functions_lookup::{Backend, LookupDataManager};
+use oak_functions_private_metrics::PrivateMetricsConfig;
+use oak_functions_wasm::{FuelExhausted, MemoryLimitExceeded, WasmLimits};
 use oak_remote_attestation::{
     attester::AttestationReportGenerator,
     handler::{AttestationHandler, AttestationSessionHandler},
 };
+use prost::Message;
 use proto::oak::functions::{
-    AbortNextLookupDataResponse, Empty, ExtendNextLookupDataRequest, ExtendNextLookupDataResponse,
-    FinishNextLookupDataRequest, FinishNextLookupDataResponse, InitializeRequest,
-    InitializeResponse, InvokeRequest, InvokeResponse, LookupDataChunk, OakFunctions,
-    PublicKeyInfo,
+    AbortNextLookupDataRequest, AbortNextLookupDataResponse, Empty, ExpireLookupDataRequest,
+    ExtendNextLookupDataRequest, ExtendNextLookupDataResponse, FinishNextLookupDataRequest,
+    FinishNextLookupDataResponse, GetAttestationEvidenceRequest, GetAttestationEvidenceResponse,
+    InitializeRequest, InitializeResponse, InvokeRequest, InvokeResponse, LookupDataBackend,
+    LookupDataChunk, OakFunctions, PublicKeyInfo, ReplaceWasmModuleRequest,
+    SetLookupDataShardCountRequest, WasmBackend,
 };
+use sha2::{Digest, Sha256};
 
 pub use crate::logger::StandaloneLogger;
 
 enum InitializationState {
     Uninitialized,
-    Initialized(Box<dyn AttestationHandler>),
+    // Also keeps the initialization request, so that its settings can be reused when the Wasm
+    // module is replaced.
+    Initialized(Box<dyn AttestationHandler>, InitializeRequest),
 }
 
 pub struct OakFunctionsService {
     attestation_report_generator: Arc<dyn AttestationReportGenerator>,
     initialization_state: InitializationState,
     lookup_data_manager: Arc<LookupDataManager<logger::StandaloneLogger>>,
+    // Shared by the lookup data and all Wasm handlers.
+    logger: StandaloneLogger,
+    // Fuel consumed by the latest invocation, as metered by the Wasm handler.
+    fuel_meter: Arc<AtomicU64>,
 }
 
 impl OakFunctionsService {
     pub fn new(attestation_report_generator: Arc<dyn AttestationReportGenerator>) -> Self {
+        let logger = StandaloneLogger::default();
         Self {
             attestation_report_generator,
             initialization_state: InitializationState::Uninitialized,
-            lookup_data_manager: Arc::new(
-                LookupDataManager::new_empty(StandaloneLogger::default()),
+            lookup_data_manager: Arc::new(LookupDataManager::new_empty(logger.clone())),
+            logger,
+            fuel_meter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Creates the handler for invocations, which executes the Wasm module as configured by
+    // `initialization`.
+    fn create_attestation_handler(
+        &self,
+        initialization: &InitializeRequest,
+    ) -> Result<Box<dyn AttestationHandler>, micro_rpc::Status> {
+        if initialization.pad_responses && initialization.constant_response_size == 0 {
+            return Err(micro_rpc::Status::new_with_message(
+                micro_rpc::StatusCode::InvalidArgument,
+                "padding responses requires a constant response size",
+            ));
+        }
+        let private_metrics = private_metrics_config(initialization);
+        if let Some(config) = &private_metrics {
+            config.validate().map_err(|err| {
+                micro_rpc::Status::new_with_message(
+                    micro_rpc::StatusCode::InvalidArgument,
+                    format!("invalid private metrics config: {:?}", err),
+                )
+            })?;
+        }
+        // Zero means that the respective resource is not limited.
+        let limits = WasmLimits {
+            max_fuel: Some(initialization.max_fuel).filter(|fuel| *fuel > 0),
+            max_memory_pages: Some(initialization.max_memory_pages).filter(|pages| *pages > 0),
+        };
+        let wasm_backend = WasmBackend::from_i32(initialization.wasm_backend).ok_or_else(|| {
+            micro_rpc::Status::new_with_message(
+                micro_rpc::StatusCode::InvalidArgument,
+                format!("unknown Wasm backend: {}", initialization.wasm_backend),
+            )
+        })?;
+        self.logger
+            .set_sensitive_logging(initialization.sensitive_logging);
+        match wasm_backend {
+            WasmBackend::Wasmi => new_attestation_handler(
+                self.attestation_report_generator.clone(),
+                initialization,
+                wasm::new_wasm_handler(
+                    &initialization.wasm_module,
+                    self.logger.clone(),
+                    self.lookup_data_manager.clone(),
+                    initialization.max_range_lookup_entries as usize,
+                    private_metrics,
+                    limits,
+                    initialization.instance_pool_size as usize,
+                    self.fuel_meter.clone(),
+                    initialization.wasi,
+                    initialization.response_metadata,
+                ),
+            ),
+            #[cfg(feature = "wasmtime")]
+            WasmBackend::Wasmtime => new_attestation_handler(
+                self.attestation_report_generator.clone(),
+                initialization,
+                wasm::new_wasmtime_handler(
+                    &initialization.wasm_module,
+                    self.logger.clone(),
+                    self.lookup_data_manager.clone(),
+                    initialization.max_range_lookup_entries as usize,
+                    private_metrics,
+                    limits,
+                    initialization.wasi,
+                    initialization.response_metadata,
+                ),
             ),
+            #[cfg(not(feature = "wasmtime"))]
+            WasmBackend::Wasmtime => Err(micro_rpc::Status::new_with_message(
+                micro_rpc::StatusCode::Unimplemented,
+                "the Wasmtime backend is not available in this build",
+            )),
         }
     }
 }
@@ -75,51 +166,17 @@ impl OakFunctions for OakFunctionsService {
         initialization: &InitializeRequest,
     ) -> Result<InitializeResponse, micro_rpc::Status> {
         match &mut self.initialization_state {
-            InitializationState::Initialized(_attestation_handler) => {
-                Err(micro_rpc::Status::new_with_message(
-                    micro_rpc::StatusCode::FailedPrecondition,
-                    "already initialized",
-                ))
-            }
+            InitializationState::Initialized(..) => Err(micro_rpc::Status::new_with_message(
+                micro_rpc::StatusCode::FailedPrecondition,
+                "already initialized",
+            )),
             InitializationState::Uninitialized => {
-                // TODO(#3442): Implement constant response size policy.
-                let wasm_handler = wasm::new_wasm_handler(
-                    &initialization.wasm_module,
-                    self.lookup_data_manager.clone(),
-                )
-                .map_err(|err| {
-                    micro_rpc::Status::new_with_message(
-                        micro_rpc::StatusCode::Internal,
-                        format!("couldn't initialize Wasm handler: {:?}", err),
-                    )
-                })?;
-                let attestation_handler = Box::new(
-                    AttestationSessionHandler::create(
-                        self.attestation_report_generator.clone(),
-                        wasm_handler,
-                    )
-                    .map_err(|err| {
-                        micro_rpc::Status::new_with_message(
-                            micro_rpc::StatusCode::Internal,
-                            format!("couldn't create attestation handler: {:?}", err),
-                        )
-                    })?,
-                );
-                let attestation_evidence =
-                    attestation_handler
-                        .get_attestation_evidence()
-                        .map_err(|err| {
-                            micro_rpc::Status::new_with_message(
-                                micro_rpc::StatusCode::Internal,
-                                format!("couldn't get attestation evidence: {:?}", err),
-                            )
-                        })?;
-                self.initialization_state = InitializationState::Initialized(attestation_handler);
+                let attestation_handler = self.create_attestation_handler(initialization)?;
+                let public_key_info = public_key_info(attestation_handler.as_ref())?;
+                self.initialization_state =
+                    InitializationState::Initialized(attestation_handler, initialization.clone());
                 Ok(InitializeResponse {
-                    public_key_info: Some(PublicKeyInfo {
-                        public_key: attestation_evidence.encryption_public_key,
-                        attestation: attestation_evidence.attestation,
-                    }),
+                    public_key_info: Some(public_key_info),
                 })
             }
         }
@@ -134,17 +191,34 @@ impl OakFunctions for OakFunctionsService {
                 micro_rpc::StatusCode::FailedPrecondition,
                 "not initialized",
             )),
-            InitializationState::Initialized(attestation_handler) => {
+            InitializationState::Initialized(attestation_handler, initialization) => {
+                self.fuel_meter.store(0, Ordering::Relaxed);
                 let response =
                     attestation_handler
                         .invoke(&request_message.body)
                         .map_err(|err| {
-                            micro_rpc::Status::new_with_message(
-                                micro_rpc::StatusCode::Internal,
-                                format!("{:?}", err),
-                            )
+                            // Invocations that exceeded their limits are reported separately so
+                            // that clients can tell them apart from internal failures.
+                            let code = if err.downcast_ref::<FuelExhausted>().is_some()
+                                || err.downcast_ref::<MemoryLimitExceeded>().is_some()
+                            {
+                                micro_rpc::StatusCode::ResourceExhausted
+                            } else {
+                                micro_rpc::StatusCode::Internal
+                            };
+                            micro_rpc::Status::new_with_message(code, format!("{:?}", err))
                         })?;
-                Ok(InvokeResponse { body: response })
+                // Fuel consumption depends on the request, so it is only reported together with
+                // sensitive logs.
+                let fuel_consumed = if initialization.sensitive_logging {
+                    self.fuel_meter.load(Ordering::Relaxed)
+                } else {
+                    0
+                };
+                Ok(InvokeResponse {
+                    body: response,
+                    fuel_consumed,
+                })
             }
         }
     }
@@ -153,36 +227,242 @@ impl OakFunctions for OakFunctionsService {
         &mut self,
         request: &ExtendNextLookupDataRequest,
     ) -> Result<ExtendNextLookupDataResponse, micro_rpc::Status> {
+        let (data, expiries) = to_data(&request.chunk);
         self.lookup_data_manager
-            .extend_next_lookup_data(to_data(&request.chunk));
+            .extend_next_lookup_data_shard_with_expiries(request.shard as usize, data, expiries)
+            .map_err(invalid_argument)?;
         Ok(ExtendNextLookupDataResponse {})
     }
 
     fn finish_next_lookup_data(
         &mut self,
-        _request: &FinishNextLookupDataRequest,
+        request: &FinishNextLookupDataRequest,
     ) -> Result<FinishNextLookupDataResponse, micro_rpc::Status> {
-        self.lookup_data_manager.finish_next_lookup_data();
+        self.lookup_data_manager
+            .finish_next_lookup_data_shard(request.shard as usize)
+            .map_err(invalid_argument)?;
         Ok(FinishNextLookupDataResponse {})
     }
 
     fn abort_next_lookup_data(
         &mut self,
-        _request: &Empty,
+        request: &AbortNextLookupDataRequest,
     ) -> Result<AbortNextLookupDataResponse, micro_rpc::Status> {
-        self.lookup_data_manager.abort_next_lookup_data();
+        self.lookup_data_manager
+            .abort_next_lookup_data_shard(request.shard as usize)
+            .map_err(invalid_argument)?;
         Ok(AbortNextLookupDataResponse {})
     }
+
+    fn set_lookup_data_shard_count(
+        &mut self,
+        request: &SetLookupDataShardCountRequest,
+    ) -> Result<Empty, micro_rpc::Status> {
+        // Changing the shard count discards the lookup data, which must not happen while
+        // requests are served.
+        if let InitializationState::Initialized(..) = self.initialization_state {
+            return Err(micro_rpc::Status::new_with_message(
+                micro_rpc::StatusCode::FailedPrecondition,
+                "already initialized",
+            ));
+        }
+        let backend = match LookupDataBackend::from_i32(request.backend) {
+            Some(LookupDataBackend::HashMap) => Backend::HashMap,
+            Some(LookupDataBackend::SortedTable) => Backend::SortedTable,
+            None => {
+                return Err(micro_rpc::Status::new_with_message(
+                    micro_rpc::StatusCode::InvalidArgument,
+                    format!("unknown lookup data backend: {}", request.backend),
+                ))
+            }
+        };
+        self.lookup_data_manager
+            .set_shard_count(request.shard_count as usize)
+            .map_err(invalid_argument)?;
+        self.lookup_data_manager.set_backend(backend);
+        Ok(Empty {})
+    }
+
+    fn expire_lookup_data(
+        &mut self,
+        request: &ExpireLookupDataRequest,
+    ) -> Result<Empty, micro_rpc::Status> {
+        self.lookup_data_manager
+            .expire_lookup_data(request.now_unix_seconds);
+        Ok(Empty {})
+    }
+
+    fn get_attestation_evidence(
+        &mut self,
+        request: &GetAttestationEvidenceRequest,
+    ) -> Result<GetAttestationEvidenceResponse, micro_rpc::Status> {
+        match &self.initialization_state {
+            InitializationState::Uninitialized => Err(micro_rpc::Status::new_with_message(
+                micro_rpc::StatusCode::FailedPrecondition,
+                "not initialized",
+            )),
+            InitializationState::Initialized(attestation_handler, _) => {
+                let attestation_evidence = attestation_handler
+                    .get_attestation_evidence_with_nonce(&request.nonce)
+                    .map_err(|err| {
+                        micro_rpc::Status::new_with_message(
+                            micro_rpc::StatusCode::Internal,
+                            format!("couldn't get attestation evidence: {:?}", err),
+                        )
+                    })?;
+                Ok(GetAttestationEvidenceResponse {
+                    public_key_info: Some(PublicKeyInfo {
+                        public_key: attestation_evidence.encryption_public_key,
+                        attestation: attestation_evidence.attestation,
+                        configuration: attestation_evidence.configuration,
+                    }),
+                })
+            }
+        }
+    }
+
+    fn replace_wasm_module(
+        &mut self,
+        request: &ReplaceWasmModuleRequest,
+    ) -> Result<InitializeResponse, micro_rpc::Status> {
+        let initialization = match &self.initialization_state {
+            InitializationState::Uninitialized => {
+                return Err(micro_rpc::Status::new_with_message(
+                    micro_rpc::StatusCode::FailedPrecondition,
+                    "not initialized",
+                ))
+            }
+            InitializationState::Initialized(_, initialization) => InitializeRequest {
+                wasm_module: request.wasm_module.clone(),
+                ..initialization.clone()
+            },
+        };
+        // The previous handler keeps serving until the new module has been loaded successfully.
+        // Invocations are handled one at a time, so none of them is in flight during the swap.
+        let attestation_handler = self.create_attestation_handler(&initialization)?;
+        let public_key_info = public_key_info(attestation_handler.as_ref())?;
+        self.initialization_state =
+            InitializationState::Initialized(attestation_handler, initialization);
+        Ok(InitializeResponse {
+            public_key_info: Some(public_key_info),
+        })
+    }
 }
 
-// Helper function to convert LookupDataChunk to Data.
-// TODO(#3791): Check if we really have to copy here.
-fn to_data(chunk: &Option<LookupDataChunk>) -> oak_functions_lookup::Data {
-    chunk
+// Wraps the given Wasm handler, so that requests to it are encrypted and bound to the attestation
+// evidence of the service.
+fn new_attestation_handler<H>(
+    attestation_report_generator: Arc<dyn AttestationReportGenerator>,
+    initialization: &InitializeRequest,
+    wasm_handler: anyhow::Result<H>,
+) -> Result<Box<dyn AttestationHandler>, micro_rpc::Status>
+where
+    H: micro_rpc::Transport<Error = anyhow::Error> + 'static,
+{
+    let wasm_handler = wasm_handler.map_err(|err| {
+        micro_rpc::Status::new_with_message(
+            micro_rpc::StatusCode::Internal,
+            format!("couldn't initialize Wasm handler: {:?}", err),
+        )
+    })?;
+    // Responses are padded before they are encrypted, since the size of the ciphertext reveals the
+    // size of the plaintext.
+    let constant_response_size = Some(initialization.constant_response_size as usize)
+        .filter(|_| initialization.pad_responses);
+    let mut attestation_handler = AttestationSessionHandler::create_with_configuration(
+        attestation_report_generator,
+        configuration_report(initialization).encode_to_vec(),
+        PaddingHandler::new(wasm_handler, constant_response_size),
+    )
+    .map_err(|err| {
+        micro_rpc::Status::new_with_message(
+            micro_rpc::StatusCode::Internal,
+            format!("couldn't create attestation handler: {:?}", err),
+        )
+    })?;
+    if initialization.reject_plaintext_sessions {
+        attestation_handler = attestation_handler.reject_plaintext_sessions();
+    }
+    Ok(Box::new(attestation_handler))
+}
+
+// Returns the public key of the handler together with its attestation evidence.
+fn public_key_info(
+    attestation_handler: &dyn AttestationHandler,
+) -> Result<PublicKeyInfo, micro_rpc::Status> {
+    let attestation_evidence = attestation_handler
+        .get_attestation_evidence()
+        .map_err(|err| {
+            micro_rpc::Status::new_with_message(
+                micro_rpc::StatusCode::Internal,
+                format!("couldn't get attestation evidence: {:?}", err),
+            )
+        })?;
+    Ok(PublicKeyInfo {
+        public_key: attestation_evidence.encryption_public_key,
+        attestation: attestation_evidence.attestation,
+        configuration: attestation_evidence.configuration,
+    })
+}
+
+// Measures the effective configuration of the service, so that it can be bound to the attestation
+// evidence and checked by the client.
+fn configuration_report(initialization: &InitializeRequest) -> ConfigurationReport {
+    ConfigurationReport {
+        wasm_hash: Sha256::digest(&initialization.wasm_module).to_vec(),
+        policy: Some(ServerPolicy {
+            constant_response_size_bytes: initialization.constant_response_size,
+            // The processing time is only enforced by the untrusted launcher, so the enclave can't
+            // vouch for it.
+            constant_processing_time_ms: 0,
+        }),
+        pad_responses: initialization.pad_responses,
+        extensions: wasm::extensions(
+            initialization.max_range_lookup_entries as usize,
+            initialization.private_metrics.is_some(),
+        )
+        .into_iter()
+        .map(|extension| extension as i32)
+        .collect(),
+        sensitive_logging: initialization.sensitive_logging,
+        response_metadata: initialization.response_metadata,
+        reject_plaintext_sessions: initialization.reject_plaintext_sessions,
+    }
+}
+
+fn private_metrics_config(initialization: &InitializeRequest) -> Option<PrivateMetricsConfig> {
+    initialization
+        .private_metrics
         .as_ref()
-        .unwrap()
-        .items
+        .map(|config| PrivateMetricsConfig {
+            epsilon: config.epsilon,
+            batch_size: config.batch_size as usize,
+            buckets: config.buckets.clone(),
+        })
+}
+
+// Reports invalid lookup data updates to the launcher.
+fn invalid_argument(err: anyhow::Error) -> micro_rpc::Status {
+    micro_rpc::Status::new_with_message(
+        micro_rpc::StatusCode::InvalidArgument,
+        format!("{:?}", err),
+    )
+}
+
+// Helper function to convert LookupDataChunk to Data, and the expiries of its entries.
+// TODO(#3791): Check if we really have to copy here.
+fn to_data(
+    chunk: &Option<LookupDataChunk>,
+) -> (oak_functions_lookup::Data, oak_functions_lookup::Expiries) {
+    let items = &chunk.as_ref().unwrap().items;
+    let data = items
         .iter()
         .map(|entry| (entry.key.clone(), entry.value.clone()))
-        .collect()
+        .collect();
+    let expiries = items
+        .iter()
+        .filter(|entry| entry.expires_at_unix_seconds != 0)
+        .map(|entry| (entry.key.clone(), entry.expires_at_unix_seconds))
+        .collect();
+    (data, expiries)
 }
@@ -14,19 +14,63 @@
 // limitations under the License.
 //
 
+use alloc::{format, sync::Arc};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use log::log;
 use oak_logger::{Level, OakLogger};
 
 /// Temporary OakLogger implementation using the `log` crate.
 ///
+/// Sensitive messages, e.g. the logs of the Wasm module, may contain data of individual requests,
+/// so they are only logged if sensitive logging is enabled for debugging. Otherwise they are
+/// dropped, and only the total number of dropped messages is logged whenever it reaches a power of
+/// two, which reveals little about individual requests. Clones share these settings and counts.
+///
 /// TODO(#2783): Replace with redesigned logger implementation.
 #[derive(Clone, Default)]
-pub struct StandaloneLogger {}
+pub struct StandaloneLogger {
+    state: Arc<LoggerState>,
+}
+
+#[derive(Default)]
+struct LoggerState {
+    sensitive_logging: AtomicBool,
+    dropped_sensitive_messages: AtomicU64,
+}
+
+impl StandaloneLogger {
+    /// Enables or disables logging of sensitive messages, for this logger and all its clones.
+    pub fn set_sensitive_logging(&self, enabled: bool) {
+        self.state
+            .sensitive_logging
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns the number of sensitive messages that were dropped.
+    pub fn dropped_sensitive_messages(&self) -> u64 {
+        self.state
+            .dropped_sensitive_messages
+            .load(Ordering::Relaxed)
+    }
+}
 
-// TODO(#2783): Implement a logger that differentiates between public and sensitive loges.
 impl OakLogger for StandaloneLogger {
     fn log_sensitive(&self, level: Level, message: &str) {
-        log!(level, "{}", message,);
+        if self.state.sensitive_logging.load(Ordering::Relaxed) {
+            log!(level, "{}", message,);
+            return;
+        }
+        let dropped = self
+            .state
+            .dropped_sensitive_messages
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        if dropped.is_power_of_two() {
+            self.log_public(
+                Level::Info,
+                &format!("dropped {} sensitive log messages in total", dropped),
+            );
+        }
     }
 
     fn log_public(&self, level: Level, message: &str) {
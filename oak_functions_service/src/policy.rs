@@ -0,0 +1,57 @@
+//
+// Copyright 2023 The Project Oak Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Enforcement of the constant response size policy.
+
+use alloc::vec::Vec;
+use oak_functions_abi::{Response, StatusCode};
+
+/// Wraps a handler of invocations, and pads its responses to a constant size, so that the size of
+/// the encrypted responses does not reveal anything about their content.
+pub struct PaddingHandler<H> {
+    inner: H,
+    /// Size to which response bodies are padded. Responses are passed through unchanged if `None`.
+    constant_response_size: Option<usize>,
+}
+
+impl<H> PaddingHandler<H> {
+    pub fn new(inner: H, constant_response_size: Option<usize>) -> Self {
+        Self {
+            inner,
+            constant_response_size,
+        }
+    }
+}
+
+impl<H: micro_rpc::Transport<Error = anyhow::Error>> micro_rpc::Transport for PaddingHandler<H> {
+    type Error = anyhow::Error;
+    fn invoke(&mut self, request: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let body = self.inner.invoke(request)?;
+        let constant_response_size = match self.constant_response_size {
+            Some(constant_response_size) => constant_response_size,
+            None => return Ok(body),
+        };
+        // Responses that are too large are replaced by an error of the same size, so that they
+        // can't be told apart from other responses either.
+        let response = Response::create(StatusCode::Success, body)
+            .pad(constant_response_size)
+            .or_else(|_| {
+                Response::create(StatusCode::PolicySizeViolation, Vec::new())
+                    .pad(constant_response_size)
+            })?;
+        Ok(response.encode_to_vec())
+    }
+}
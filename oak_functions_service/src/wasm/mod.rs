@@ -14,23 +14,122 @@
 // limitations under the License.
 //
 
+//! Wasm handlers of the service, with the extensions that the service provides to the module.
+
 use crate::logger::StandaloneLogger;
-use alloc::{sync::Arc, vec};
-use oak_functions_lookup::{LookupDataManager, LookupFactory};
-use oak_functions_wasm::WasmHandler;
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+use core::sync::atomic::AtomicU64;
+use oak_functions_abi::ExtensionHandle;
+use oak_functions_extension::ExtensionFactory;
+use oak_functions_lookup::{LookupDataManager, LookupFactory, RangeLookupFactory};
+use oak_functions_private_metrics::{PrivateMetricsConfig, PrivateMetricsFactory};
+use oak_functions_wasm::{WasmHandler, WasmLimits};
 use oak_functions_workload_logging::WorkloadLoggingFactory;
 
-/// Creates a new `WasmHandler` instance.
+/// Returns the handles of the extensions that [`new_wasm_handler`] makes available to the Wasm
+/// module. Range lookups are only available if the number of entries they return is limited, and
+/// metrics only if they are differentially private.
+pub fn extensions(max_range_lookup_entries: usize, private_metrics: bool) -> Vec<ExtensionHandle> {
+    let mut extensions = vec![
+        ExtensionHandle::LoggingHandle,
+        ExtensionHandle::LookupHandle,
+    ];
+    if max_range_lookup_entries > 0 {
+        extensions.push(ExtensionHandle::LookupRangeHandle);
+    }
+    if private_metrics {
+        extensions.push(ExtensionHandle::MetricsHandle);
+    }
+    extensions
+}
+
+/// Creates a new `WasmHandler` instance that enforces the given `limits` on every invocation,
+/// reuses up to `instance_pool_size` instances of the module and stores the fuel consumed by each
+/// invocation in `fuel_meter`. If `wasi` is set, the module may import the WASI shim. If
+/// `response_metadata` is set, the status code and headers of the module are prepended to its
+/// responses.
+#[allow(clippy::too_many_arguments)]
 pub fn new_wasm_handler(
     wasm_module_bytes: &[u8],
+    logger: StandaloneLogger,
     lookup_data_manager: Arc<LookupDataManager<StandaloneLogger>>,
+    max_range_lookup_entries: usize,
+    private_metrics: Option<PrivateMetricsConfig>,
+    limits: WasmLimits,
+    instance_pool_size: usize,
+    fuel_meter: Arc<AtomicU64>,
+    wasi: bool,
+    response_metadata: bool,
 ) -> anyhow::Result<WasmHandler<StandaloneLogger>> {
-    let logger = StandaloneLogger::default();
-    let logging_factory = WorkloadLoggingFactory::new_boxed_extension_factory(logger.clone())?;
-    let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager)?;
-    WasmHandler::create(
+    WasmHandler::create_with_limits(
+        wasm_module_bytes,
+        extension_factories(
+            logger.clone(),
+            lookup_data_manager,
+            max_range_lookup_entries,
+            private_metrics,
+        )?,
+        logger,
+        limits,
+    )?
+    .with_fuel_meter(fuel_meter)
+    .with_wasi(wasi)
+    .with_response_metadata(response_metadata)
+    .with_instance_pool(instance_pool_size)
+}
+
+/// Creates a new `WasmtimeHandler` instance that enforces the given `limits` on every invocation.
+#[cfg(feature = "wasmtime")]
+#[allow(clippy::too_many_arguments)]
+pub fn new_wasmtime_handler(
+    wasm_module_bytes: &[u8],
+    logger: StandaloneLogger,
+    lookup_data_manager: Arc<LookupDataManager<StandaloneLogger>>,
+    max_range_lookup_entries: usize,
+    private_metrics: Option<PrivateMetricsConfig>,
+    limits: WasmLimits,
+    wasi: bool,
+    response_metadata: bool,
+) -> anyhow::Result<oak_functions_wasm::WasmtimeHandler<StandaloneLogger>> {
+    oak_functions_wasm::WasmtimeHandler::create_with_limits(
         wasm_module_bytes,
-        vec![logging_factory, lookup_factory],
+        extension_factories(
+            logger.clone(),
+            lookup_data_manager,
+            max_range_lookup_entries,
+            private_metrics,
+        )?,
         logger,
+        limits,
     )
+    .map(|handler| {
+        handler
+            .with_wasi(wasi)
+            .with_response_metadata(response_metadata)
+    })
+}
+
+// Creates the factories of the extensions returned by [`extensions`].
+fn extension_factories(
+    logger: StandaloneLogger,
+    lookup_data_manager: Arc<LookupDataManager<StandaloneLogger>>,
+    max_range_lookup_entries: usize,
+    private_metrics: Option<PrivateMetricsConfig>,
+) -> anyhow::Result<Vec<Box<dyn ExtensionFactory<StandaloneLogger>>>> {
+    let logging_factory = WorkloadLoggingFactory::new_boxed_extension_factory(logger.clone())?;
+    let lookup_factory = LookupFactory::new_boxed_extension_factory(lookup_data_manager.clone())?;
+    let mut factories = vec![logging_factory, lookup_factory];
+    if max_range_lookup_entries > 0 {
+        factories.push(RangeLookupFactory::new_boxed_extension_factory(
+            lookup_data_manager,
+            max_range_lookup_entries,
+        )?);
+    }
+    if let Some(private_metrics) = private_metrics {
+        factories.push(PrivateMetricsFactory::new_boxed_extension_factory(
+            private_metrics,
+            logger,
+        )?);
+    }
+    Ok(factories)
 }
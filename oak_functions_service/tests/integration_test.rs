@@ -20,14 +20,23 @@
 extern crate alloc;
 
 use core::assert_matches::assert_matches;
-use oak_crypto::{encryptor::ClientEncryptor, proto::oak::crypto::v1::EncryptedResponse};
+use oak_crypto::{
+    encryptor::{ClientEncryptor, TEST_ENCAPSULATED_PUBLIC_KEY},
+    proto::oak::crypto::v1::{AeadEncryptedMessage, EncryptedRequest, EncryptedResponse},
+};
+use oak_functions_abi::{
+    proto::ConfigurationReport, ExtensionHandle, Response, StatusCode, RESPONSE_BODY_OFFSET,
+};
 use oak_functions_service::{
     proto::oak::functions::{
         ExtendNextLookupDataRequest, FinishNextLookupDataRequest, InitializeRequest, InvokeRequest,
-        LookupDataChunk, LookupDataEntry, OakFunctionsClient, OakFunctionsServer,
+        LookupDataBackend, LookupDataChunk, LookupDataEntry, OakFunctionsClient,
+        OakFunctionsServer, PrivateMetricsConfig, ReplaceWasmModuleRequest,
+        SetLookupDataShardCountRequest,
     },
-    OakFunctionsService,
+    OakFunctionsService, StandaloneLogger,
 };
+use oak_logger::{Level, OakLogger};
 use oak_remote_attestation::attester::EmptyAttestationReportGenerator;
 use prost::Message;
 use std::sync::Arc;
@@ -66,6 +75,7 @@ fn it_should_handle_user_requests_after_initialization() {
     let request = InitializeRequest {
         wasm_module: wasm_bytes,
         constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        ..Default::default()
     };
 
     let initialize_response = client.initialize(&request).into_ok().unwrap();
@@ -95,6 +105,128 @@ fn it_should_handle_user_requests_after_initialization() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn it_should_abort_user_requests_exceeding_fuel_limit() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        max_fuel: 1,
+        ..Default::default()
+    };
+
+    let initialize_response = client.initialize(&request).into_ok().unwrap();
+    let server_encryption_public_key = initialize_response
+        .public_key_info
+        .expect("no public key info returned")
+        .public_key;
+
+    // Encrypt request.
+    let mut client_encryptor =
+        ClientEncryptor::create(&server_encryption_public_key).expect("couldn't create encryptor");
+    let encrypted_request = client_encryptor
+        .encrypt(&[1, 2, 3], EMPTY_ASSOCIATED_DATA)
+        .expect("couldn't encrypt request");
+
+    // Serialize request.
+    let mut serialized_request = vec![];
+    encrypted_request
+        .encode(&mut serialized_request)
+        .expect("couldn't serialize request");
+
+    // Send invoke request.
+    let invoke_request = InvokeRequest {
+        body: serialized_request,
+    };
+    let result = client.invoke(&invoke_request).into_ok();
+    assert_matches!(
+        result,
+        Err(micro_rpc::Status {
+            code: micro_rpc::StatusCode::ResourceExhausted,
+            ..
+        })
+    );
+}
+
+#[test]
+fn it_should_replace_wasm_module() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes.clone(),
+        constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        ..Default::default()
+    };
+    let initialize_response = client.initialize(&request).into_ok().unwrap();
+
+    let replace_response = client
+        .replace_wasm_module(&ReplaceWasmModuleRequest {
+            wasm_module: wasm_bytes,
+        })
+        .into_ok()
+        .unwrap();
+    let previous_public_key_info = initialize_response
+        .public_key_info
+        .expect("no public key info returned");
+    let public_key_info = replace_response
+        .public_key_info
+        .expect("no public key info returned");
+    // The module is bound to a new key.
+    assert_ne!(
+        previous_public_key_info.public_key,
+        public_key_info.public_key
+    );
+
+    // Encrypt request.
+    let mut client_encryptor =
+        ClientEncryptor::create(&public_key_info.public_key).expect("couldn't create encryptor");
+    let encrypted_request = client_encryptor
+        .encrypt(&[1, 2, 3], EMPTY_ASSOCIATED_DATA)
+        .expect("couldn't encrypt request");
+
+    // Serialize request.
+    let mut serialized_request = vec![];
+    encrypted_request
+        .encode(&mut serialized_request)
+        .expect("couldn't serialize request");
+
+    // Send invoke request.
+    let invoke_request = InvokeRequest {
+        body: serialized_request,
+    };
+    let result = client.invoke(&invoke_request).into_ok();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn it_should_not_replace_wasm_module_before_initialization() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let result = client
+        .replace_wasm_module(&ReplaceWasmModuleRequest {
+            wasm_module: wasm_bytes,
+        })
+        .into_ok();
+
+    assert_matches!(
+        result,
+        Err(micro_rpc::Status {
+            code: micro_rpc::StatusCode::FailedPrecondition,
+            ..
+        })
+    );
+}
+
 #[test]
 fn it_should_only_initialize_once() {
     let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
@@ -105,6 +237,7 @@ fn it_should_only_initialize_once() {
     let request = InitializeRequest {
         wasm_module: wasm_bytes,
         constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        ..Default::default()
     };
     client.initialize(&request).into_ok().unwrap();
 
@@ -119,6 +252,157 @@ fn it_should_only_initialize_once() {
     );
 }
 
+#[test]
+fn it_should_report_configuration() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        ..Default::default()
+    };
+
+    let initialize_response = client.initialize(&request).into_ok().unwrap();
+    let configuration = initialize_response
+        .public_key_info
+        .expect("no public key info returned")
+        .configuration;
+    let configuration_report = ConfigurationReport::decode(configuration.as_ref())
+        .expect("couldn't decode configuration report");
+
+    assert!(!configuration_report.wasm_hash.is_empty());
+    assert_eq!(
+        configuration_report
+            .policy
+            .expect("no policy in configuration report")
+            .constant_response_size_bytes,
+        MOCK_CONSTANT_RESPONSE_SIZE
+    );
+    assert!(!configuration_report.pad_responses);
+    assert!(configuration_report
+        .extensions
+        .contains(&(ExtensionHandle::LoggingHandle as i32)));
+}
+
+#[test]
+fn it_should_report_range_lookup_extension_if_enabled() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        max_range_lookup_entries: 100,
+        ..Default::default()
+    };
+
+    let initialize_response = client.initialize(&request).into_ok().unwrap();
+    let configuration = initialize_response
+        .public_key_info
+        .expect("no public key info returned")
+        .configuration;
+    let configuration_report = ConfigurationReport::decode(configuration.as_ref())
+        .expect("couldn't decode configuration report");
+
+    assert!(configuration_report
+        .extensions
+        .contains(&(ExtensionHandle::LookupRangeHandle as i32)));
+}
+
+#[test]
+fn it_should_report_metrics_extension_if_enabled() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let mut request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        private_metrics: Some(PrivateMetricsConfig {
+            epsilon: 1.0,
+            batch_size: 0,
+            buckets: vec!["miss".to_string()],
+        }),
+        ..Default::default()
+    };
+
+    // Batches must not be empty.
+    let result = client.initialize(&request).into_ok();
+    assert_matches!(
+        result,
+        Err(micro_rpc::Status {
+            code: micro_rpc::StatusCode::InvalidArgument,
+            ..
+        })
+    );
+
+    request.private_metrics.as_mut().unwrap().batch_size = 10;
+    let initialize_response = client.initialize(&request).into_ok().unwrap();
+    let configuration = initialize_response
+        .public_key_info
+        .expect("no public key info returned")
+        .configuration;
+    let configuration_report = ConfigurationReport::decode(configuration.as_ref())
+        .expect("couldn't decode configuration report");
+
+    assert!(configuration_report
+        .extensions
+        .contains(&(ExtensionHandle::MetricsHandle as i32)));
+}
+
+#[test]
+fn it_should_reject_plaintext_sessions_if_enabled() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        reject_plaintext_sessions: true,
+        ..Default::default()
+    };
+
+    let initialize_response = client.initialize(&request).into_ok().unwrap();
+    let public_key_info = initialize_response
+        .public_key_info
+        .expect("no public key info returned");
+    let configuration_report = ConfigurationReport::decode(public_key_info.configuration.as_ref())
+        .expect("couldn't decode configuration report");
+    assert!(configuration_report.reject_plaintext_sessions);
+
+    // Requests of plaintext sessions are not encrypted.
+    let plaintext_request = EncryptedRequest {
+        encrypted_message: Some(AeadEncryptedMessage {
+            ciphertext: vec![1, 2, 3],
+            associated_data: EMPTY_ASSOCIATED_DATA.to_vec(),
+        }),
+        serialized_encapsulated_public_key: Some(TEST_ENCAPSULATED_PUBLIC_KEY.to_vec()),
+    };
+    let invoke_request = InvokeRequest {
+        body: plaintext_request.encode_to_vec(),
+    };
+    assert!(client.invoke(&invoke_request).into_ok().is_err());
+
+    // Encrypted requests are still handled.
+    let mut client_encryptor =
+        ClientEncryptor::create(&public_key_info.public_key).expect("couldn't create encryptor");
+    let encrypted_request = client_encryptor
+        .encrypt(&[1, 2, 3], EMPTY_ASSOCIATED_DATA)
+        .expect("couldn't encrypt request");
+    let invoke_request = InvokeRequest {
+        body: encrypted_request.encode_to_vec(),
+    };
+    assert!(client.invoke(&invoke_request).into_ok().is_ok());
+}
+
 #[tokio::test]
 async fn it_should_support_lookup_data() {
     let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
@@ -129,6 +413,7 @@ async fn it_should_support_lookup_data() {
     let request = InitializeRequest {
         wasm_module: wasm_bytes,
         constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        ..Default::default()
     };
 
     let initialize_response = client.initialize(&request).into_ok().unwrap();
@@ -141,14 +426,18 @@ async fn it_should_support_lookup_data() {
         items: vec![LookupDataEntry {
             key: LOOKUP_TEST_KEY.to_vec(),
             value: LOOKUP_TEST_VALUE.to_vec(),
+            expires_at_unix_seconds: 0,
         }],
     };
 
-    let request = ExtendNextLookupDataRequest { chunk: Some(chunk) };
+    let request = ExtendNextLookupDataRequest {
+        chunk: Some(chunk),
+        shard: 0,
+    };
 
     client.extend_next_lookup_data(&request).into_ok().unwrap();
     client
-        .finish_next_lookup_data(&FinishNextLookupDataRequest {})
+        .finish_next_lookup_data(&FinishNextLookupDataRequest { shard: 0 })
         .into_ok()
         .unwrap();
 
@@ -185,3 +474,162 @@ async fn it_should_support_lookup_data() {
 
     assert_eq!(LOOKUP_TEST_VALUE, response);
 }
+
+#[test]
+fn it_should_pad_responses_to_constant_size() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        pad_responses: true,
+        ..Default::default()
+    };
+
+    let initialize_response = client.initialize(&request).into_ok().unwrap();
+    let server_encryption_public_key = initialize_response
+        .public_key_info
+        .expect("no public key info returned")
+        .public_key;
+
+    let mut client_encryptor =
+        ClientEncryptor::create(&server_encryption_public_key).expect("couldn't create encryptor");
+    let encrypted_request = client_encryptor
+        .encrypt(&[1, 2, 3], EMPTY_ASSOCIATED_DATA)
+        .expect("couldn't encrypt request");
+    let invoke_request = InvokeRequest {
+        body: encrypted_request.encode_to_vec(),
+    };
+    let serialized_response = client.invoke(&invoke_request).into_ok().unwrap().body;
+    let encrypted_response = EncryptedResponse::decode(serialized_response.as_ref())
+        .expect("couldn't deserialize response");
+    let (response, _) = client_encryptor
+        .decrypt(&encrypted_response)
+        .expect("client couldn't decrypt response");
+
+    assert_eq!(
+        response.len(),
+        RESPONSE_BODY_OFFSET + MOCK_CONSTANT_RESPONSE_SIZE as usize
+    );
+    let response = Response::decode(&response).expect("couldn't decode response");
+    assert_eq!(response.status, StatusCode::Success);
+    assert_eq!(response.body().unwrap(), &[1, 2, 3]);
+}
+
+#[test]
+fn it_should_replace_responses_exceeding_constant_size() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: 2,
+        pad_responses: true,
+        ..Default::default()
+    };
+
+    let initialize_response = client.initialize(&request).into_ok().unwrap();
+    let server_encryption_public_key = initialize_response
+        .public_key_info
+        .expect("no public key info returned")
+        .public_key;
+
+    let mut client_encryptor =
+        ClientEncryptor::create(&server_encryption_public_key).expect("couldn't create encryptor");
+    let encrypted_request = client_encryptor
+        .encrypt(&[1, 2, 3], EMPTY_ASSOCIATED_DATA)
+        .expect("couldn't encrypt request");
+    let invoke_request = InvokeRequest {
+        body: encrypted_request.encode_to_vec(),
+    };
+    let serialized_response = client.invoke(&invoke_request).into_ok().unwrap().body;
+    let encrypted_response = EncryptedResponse::decode(serialized_response.as_ref())
+        .expect("couldn't deserialize response");
+    let (response, _) = client_encryptor
+        .decrypt(&encrypted_response)
+        .expect("client couldn't decrypt response");
+
+    assert_eq!(response.len(), RESPONSE_BODY_OFFSET + 2);
+    let response = Response::decode(&response).expect("couldn't decode response");
+    assert_eq!(response.status, StatusCode::PolicySizeViolation);
+}
+
+#[test]
+fn it_should_not_pad_responses_without_constant_size() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: 0,
+        pad_responses: true,
+        ..Default::default()
+    };
+
+    let result = client.initialize(&request).into_ok();
+
+    assert_matches!(
+        result,
+        Err(micro_rpc::Status {
+            code: micro_rpc::StatusCode::InvalidArgument,
+            ..
+        })
+    );
+}
+
+#[test]
+fn it_should_not_set_lookup_data_shard_count_after_initialization() {
+    let service = OakFunctionsService::new(Arc::new(EmptyAttestationReportGenerator));
+    let mut client = OakFunctionsClient::new(OakFunctionsServer::new(service));
+
+    let shard_count_request = SetLookupDataShardCountRequest {
+        shard_count: 2,
+        backend: LookupDataBackend::SortedTable as i32,
+    };
+    client
+        .set_lookup_data_shard_count(&shard_count_request)
+        .into_ok()
+        .unwrap();
+
+    let wasm_path = oak_functions_test_utils::build_rust_crate_wasm("echo").unwrap();
+    let wasm_bytes = std::fs::read(wasm_path).unwrap();
+    let request = InitializeRequest {
+        wasm_module: wasm_bytes,
+        constant_response_size: MOCK_CONSTANT_RESPONSE_SIZE,
+        ..Default::default()
+    };
+    client.initialize(&request).into_ok().unwrap();
+
+    let result = client
+        .set_lookup_data_shard_count(&shard_count_request)
+        .into_ok();
+
+    assert_matches!(
+        result,
+        Err(micro_rpc::Status {
+            code: micro_rpc::StatusCode::FailedPrecondition,
+            ..
+        })
+    );
+}
+
+#[test]
+fn it_should_drop_sensitive_logs_unless_enabled() {
+    let logger = StandaloneLogger::default();
+    let clone = logger.clone();
+    logger.log_sensitive(Level::Debug, "sensitive");
+    clone.log_sensitive(Level::Debug, "sensitive");
+    logger.log_public(Level::Info, "public");
+    assert_eq!(logger.dropped_sensitive_messages(), 2);
+
+    clone.set_sensitive_logging(true);
+    logger.log_sensitive(Level::Debug, "sensitive");
+    assert_eq!(logger.dropped_sensitive_messages(), 2);
+}
@@ -18,6 +18,7 @@ use crate::proto::oak::session::v1::AttestationEvidence;
 use alloc::{sync::Arc, vec::Vec};
 use anyhow::Context;
 use oak_crypto::encryptor::EncryptionKeyProvider;
+use sha2::{Digest, Sha256};
 
 /// A trait implementing the functionality of generating a remote attestation report.
 ///
@@ -46,6 +47,8 @@ impl AttestationReportGenerator for EmptyAttestationReportGenerator {
 pub struct Attester {
     attestation_report_generator: Arc<dyn AttestationReportGenerator>,
     encryption_key_provider: Arc<EncryptionKeyProvider>,
+    /// Serialized application configuration that is measured together with the public key.
+    configuration: Vec<u8>,
 }
 
 impl Attester {
@@ -56,16 +59,42 @@ impl Attester {
         Self {
             attestation_report_generator,
             encryption_key_provider,
+            configuration: Vec::new(),
         }
     }
 
+    /// Sets the serialized application configuration that is bound to all the attestation
+    /// evidence generated by this attester, so that it can be checked by the verifier.
+    pub fn with_configuration(mut self, configuration: Vec<u8>) -> Self {
+        self.configuration = configuration;
+        self
+    }
+
     /// Generate an attestation evidence containing a remote attestation report and ensuring that
     /// `attested_data` is cryptographically bound to the result (e.g. via a signature).
     pub fn generate_attestation_evidence(&self) -> anyhow::Result<AttestationEvidence> {
+        self.generate_attestation_evidence_with_nonce(&[])
+    }
+
+    /// Generate an attestation evidence that is bound to a client-supplied `nonce`.
+    ///
+    /// If there is neither an application configuration nor a nonce, the attested data is the
+    /// serialized encryption public key. Otherwise it is the digest computed by
+    /// [`attested_data_digest`], so that a verifier that supplied a fresh nonce can check that the
+    /// evidence was not replayed.
+    pub fn generate_attestation_evidence_with_nonce(
+        &self,
+        nonce: &[u8],
+    ) -> anyhow::Result<AttestationEvidence> {
         let encryption_public_key = self.encryption_key_provider.get_serialized_public_key();
+        let attested_data = if self.configuration.is_empty() && nonce.is_empty() {
+            encryption_public_key.clone()
+        } else {
+            attested_data_digest(&encryption_public_key, &self.configuration, nonce).to_vec()
+        };
         let attestation_report = self
             .attestation_report_generator
-            .generate_attestation_report(&encryption_public_key)
+            .generate_attestation_report(&attested_data)
             .context("couldn't generate attestation report")?;
         Ok(AttestationEvidence {
             attestation: attestation_report,
@@ -74,6 +103,27 @@ impl Attester {
             signing_public_key: Vec::new(),
             // TODO(#3640): Sign application data.
             signed_application_data: Vec::new(),
+            nonce: nonce.to_vec(),
+            configuration: self.configuration.clone(),
         })
     }
 }
+
+/// Returns the SHA-256 digest of the serialized encryption public key, the application
+/// configuration and the nonce that is bound to attestation reports, which fits into the report
+/// data of any TEE.
+///
+/// Each field is prefixed with its length as a little-endian `u64`, so that the boundaries between
+/// fields are unambiguous.
+pub fn attested_data_digest(
+    encryption_public_key: &[u8],
+    configuration: &[u8],
+    nonce: &[u8],
+) -> [u8; 32] {
+    let mut digest = Sha256::new();
+    for field in [encryption_public_key, configuration, nonce] {
+        digest.update((field.len() as u64).to_le_bytes());
+        digest.update(field);
+    }
+    digest.finalize().into()
+}
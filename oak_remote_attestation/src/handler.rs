@@ -40,6 +40,12 @@ pub struct PublicKeyInfo {
 
 pub trait AttestationHandler: micro_rpc::Transport<Error = anyhow::Error> {
     fn get_attestation_evidence(&self) -> anyhow::Result<AttestationEvidence>;
+
+    /// Returns fresh attestation evidence that is bound to the client-supplied `nonce`.
+    fn get_attestation_evidence_with_nonce(
+        &self,
+        nonce: &[u8],
+    ) -> anyhow::Result<AttestationEvidence>;
 }
 
 pub struct AttestationSessionHandler<H: micro_rpc::Transport<Error = anyhow::Error>> {
@@ -47,23 +53,45 @@ pub struct AttestationSessionHandler<H: micro_rpc::Transport<Error = anyhow::Err
     attester: Arc<Attester>,
     encryption_key_provider: Arc<EncryptionKeyProvider>,
     request_handler: H,
+    allow_plaintext_sessions: bool,
 }
 
 impl<H: micro_rpc::Transport<Error = anyhow::Error>> AttestationSessionHandler<H> {
     pub fn create(
         attestation_report_generator: Arc<dyn AttestationReportGenerator>,
         request_handler: H,
+    ) -> anyhow::Result<Self> {
+        Self::create_with_configuration(attestation_report_generator, Vec::new(), request_handler)
+    }
+
+    /// Creates a handler whose attestation evidence also binds the serialized application
+    /// `configuration`.
+    pub fn create_with_configuration(
+        attestation_report_generator: Arc<dyn AttestationReportGenerator>,
+        configuration: Vec<u8>,
+        request_handler: H,
     ) -> anyhow::Result<Self> {
         let encryption_key_provider = Arc::new(EncryptionKeyProvider::new());
         Ok(Self {
-            attester: Arc::new(Attester::new(
-                attestation_report_generator,
-                encryption_key_provider.clone(),
-            )),
+            attester: Arc::new(
+                Attester::new(
+                    attestation_report_generator,
+                    encryption_key_provider.clone(),
+                )
+                .with_configuration(configuration),
+            ),
             encryption_key_provider,
             request_handler,
+            allow_plaintext_sessions: true,
         })
     }
+
+    /// Rejects requests of plaintext sessions, so that only encrypted requests are handled. See
+    /// [`ServerEncryptor::reject_plaintext_sessions`].
+    pub fn reject_plaintext_sessions(mut self) -> Self {
+        self.allow_plaintext_sessions = false;
+        self
+    }
 }
 
 impl<H: micro_rpc::Transport<Error = anyhow::Error>> micro_rpc::Transport
@@ -72,6 +100,9 @@ impl<H: micro_rpc::Transport<Error = anyhow::Error>> micro_rpc::Transport
     type Error = anyhow::Error;
     fn invoke(&mut self, request_body: &[u8]) -> anyhow::Result<Vec<u8>> {
         let mut server_encryptor = ServerEncryptor::new(self.encryption_key_provider.clone());
+        if !self.allow_plaintext_sessions {
+            server_encryptor = server_encryptor.reject_plaintext_sessions();
+        }
 
         // Deserialize and decrypt request.
         let encrypted_request = EncryptedRequest::decode(request_body)
@@ -109,4 +140,13 @@ impl<H: micro_rpc::Transport<Error = anyhow::Error>> AttestationHandler
             .generate_attestation_evidence()
             .context("couldn't generate attestation evidence")
     }
+
+    fn get_attestation_evidence_with_nonce(
+        &self,
+        nonce: &[u8],
+    ) -> anyhow::Result<AttestationEvidence> {
+        self.attester
+            .generate_attestation_evidence_with_nonce(nonce)
+            .context("couldn't generate attestation evidence")
+    }
 }
@@ -15,12 +15,20 @@
 //
 
 use crate::{
-    attester::{Attester, EmptyAttestationReportGenerator},
+    attester::{
+        attested_data_digest, AttestationReportGenerator, Attester, EmptyAttestationReportGenerator,
+    },
+    handler::AttestationSessionHandler,
     proto::oak::session::v1::AttestationEndorsement,
     verifier::{AttestationVerifier, InsecureAttestationVerifier, ReferenceValue},
 };
-use alloc::{sync::Arc, vec};
-use oak_crypto::encryptor::EncryptionKeyProvider;
+use alloc::{sync::Arc, vec, vec::Vec};
+use micro_rpc::Transport;
+use oak_crypto::{
+    encryptor::{EncryptionKeyProvider, TEST_ENCAPSULATED_PUBLIC_KEY},
+    proto::oak::crypto::v1::{AeadEncryptedMessage, EncryptedRequest},
+};
+use prost::Message;
 
 const TEST_ATTESTATION_ENDORSEMENT: AttestationEndorsement = AttestationEndorsement {
     tee_certificates: vec![],
@@ -31,6 +39,25 @@ const TEST_REFERENCE_VALUE: ReferenceValue = ReferenceValue {
     binary_hash: vec![],
 };
 
+/// An instance of [`AttestationReportGenerator`] that returns the attested data as the report.
+struct EchoAttestationReportGenerator;
+
+impl AttestationReportGenerator for EchoAttestationReportGenerator {
+    fn generate_attestation_report(&self, attested_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        Ok(attested_data.to_vec())
+    }
+}
+
+/// A request handler that responds with the request.
+struct EchoHandler;
+
+impl Transport for EchoHandler {
+    type Error = anyhow::Error;
+    fn invoke(&mut self, request_body: &[u8]) -> anyhow::Result<Vec<u8>> {
+        Ok(request_body.to_vec())
+    }
+}
+
 #[test]
 fn test_empty_attestation() {
     let attestation_report_generator = Arc::new(EmptyAttestationReportGenerator);
@@ -50,3 +77,103 @@ fn test_empty_attestation() {
     );
     assert!(verify_result.is_ok());
 }
+
+#[test]
+fn test_attestation_evidence_with_nonce() {
+    let attestation_report_generator = Arc::new(EmptyAttestationReportGenerator);
+    let encryption_key_provider = Arc::new(EncryptionKeyProvider::new());
+    let attester = Arc::new(Attester::new(
+        attestation_report_generator,
+        encryption_key_provider.clone(),
+    ));
+    let nonce = b"test nonce";
+    let attestation_evidence = attester
+        .generate_attestation_evidence_with_nonce(nonce)
+        .expect("couldn't generate attestation evidence");
+
+    assert_eq!(attestation_evidence.nonce, nonce.to_vec());
+    assert_eq!(
+        attestation_evidence.encryption_public_key,
+        encryption_key_provider.get_serialized_public_key()
+    );
+}
+
+#[test]
+fn test_attestation_evidence_with_configuration() {
+    let attestation_report_generator = Arc::new(EmptyAttestationReportGenerator);
+    let encryption_key_provider = Arc::new(EncryptionKeyProvider::new());
+    let configuration = b"test configuration".to_vec();
+    let attester = Attester::new(attestation_report_generator, encryption_key_provider)
+        .with_configuration(configuration.clone());
+    let attestation_evidence = attester
+        .generate_attestation_evidence()
+        .expect("couldn't generate attestation evidence");
+
+    assert_eq!(attestation_evidence.configuration, configuration);
+}
+
+#[test]
+fn test_attested_data() {
+    let encryption_key_provider = Arc::new(EncryptionKeyProvider::new());
+    let encryption_public_key = encryption_key_provider.get_serialized_public_key();
+    let configuration = b"test configuration".to_vec();
+    let nonce = b"test nonce";
+    let attester = Attester::new(
+        Arc::new(EchoAttestationReportGenerator),
+        encryption_key_provider,
+    );
+    let configured_attester = Attester::new(
+        Arc::new(EchoAttestationReportGenerator),
+        Arc::new(EncryptionKeyProvider::new()),
+    )
+    .with_configuration(configuration.clone());
+
+    // Without a configuration or a nonce, only the public key is attested.
+    let attestation_evidence = attester
+        .generate_attestation_evidence()
+        .expect("couldn't generate attestation evidence");
+    assert_eq!(attestation_evidence.attestation, encryption_public_key);
+
+    let attestation_evidence = attester
+        .generate_attestation_evidence_with_nonce(nonce)
+        .expect("couldn't generate attestation evidence");
+    assert_eq!(
+        attestation_evidence.attestation,
+        attested_data_digest(&encryption_public_key, &[], nonce)
+    );
+
+    let attestation_evidence = configured_attester
+        .generate_attestation_evidence()
+        .expect("couldn't generate attestation evidence");
+    assert_eq!(
+        attestation_evidence.attestation,
+        attested_data_digest(
+            &attestation_evidence.encryption_public_key,
+            &configuration,
+            &[]
+        )
+    );
+}
+
+#[test]
+fn test_handler_rejects_plaintext_sessions() {
+    let plaintext_request = EncryptedRequest {
+        encrypted_message: Some(AeadEncryptedMessage {
+            ciphertext: b"test request".to_vec(),
+            associated_data: vec![],
+        }),
+        serialized_encapsulated_public_key: Some(TEST_ENCAPSULATED_PUBLIC_KEY.to_vec()),
+    }
+    .encode_to_vec();
+
+    let mut handler =
+        AttestationSessionHandler::create(Arc::new(EmptyAttestationReportGenerator), EchoHandler)
+            .unwrap();
+    assert!(handler.invoke(&plaintext_request).is_ok());
+
+    let mut handler =
+        AttestationSessionHandler::create(Arc::new(EmptyAttestationReportGenerator), EchoHandler)
+            .unwrap()
+            .reject_plaintext_sessions();
+    assert!(handler.invoke(&plaintext_request).is_err());
+}
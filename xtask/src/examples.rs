@@ -20,7 +20,30 @@ use crate::{
         build_binary, build_stage0, run_oak_functions_launcher_example_with_lookup_data,
         LauncherMode, MOCK_LOOKUP_DATA_PATH, OAK_RESTRICTED_KERNEL_BIN_DIR,
     },
+    workspace_path,
 };
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+static OAK_FUNCTIONS_CLIENT_BIN: Lazy<PathBuf> = Lazy::new(|| {
+    workspace_path(&[
+        "target",
+        "x86_64-unknown-linux-gnu",
+        "debug",
+        "oak_functions_client",
+    ])
+});
+static END_TO_END_LOOKUP_DATA_PATH: Lazy<PathBuf> =
+    Lazy::new(|| workspace_path(&["target", "oak_functions_end_to_end_lookup_data.bin"]));
+
+/// How long to wait for the server to start listening, which includes booting the VM.
+const SERVER_STARTUP_TIMEOUT: Duration = Duration::from_secs(300);
+
+const END_TO_END_EXAMPLE_NAME: &str = "key_value_lookup";
 
 /// Build the Rust crate that will be used as the Wasm module for the Oak Functions server.
 pub fn build_rust_crate_wasm(crate_name: &str) -> Step {
@@ -70,3 +93,185 @@ pub fn run_oak_functions_example(opt: &RunOakExampleOpt) -> Step {
         ],
     }
 }
+
+/// Builds all the components of the key / value lookup example, generates lookup data for it,
+/// starts the server, and checks the response of the client for every lookup data entry. The
+/// server is stopped once the client is done.
+pub fn run_oak_functions_end_to_end(opt: &RunOakEndToEndOpt) -> Step {
+    let variant = LauncherMode::Virtual("oak_functions_enclave_app".to_string());
+    let wasm_path = oak_functions_test_utils::rust_crate_wasm_out_path(END_TO_END_EXAMPLE_NAME);
+    let lookup_data_path = END_TO_END_LOOKUP_DATA_PATH.to_str().unwrap();
+
+    let clients = (0..opt.entries)
+        .map(|i| {
+            Cmd::new(
+                OAK_FUNCTIONS_CLIENT_BIN.to_str().unwrap(),
+                vec![
+                    format!("--uri=http://localhost:{}", opt.port),
+                    format!("--request=key_{i}"),
+                    format!("--expected-response-pattern=^value_{i}$"),
+                ],
+            ) as Box<dyn Runnable>
+        })
+        .collect();
+
+    Step::Multiple {
+        name: "run Oak Functions example end to end".to_string(),
+        steps: vec![
+            build_stage0(),
+            build_binary(
+                "build Oak Restricted Kernel binary",
+                OAK_RESTRICTED_KERNEL_BIN_DIR.to_str().unwrap(),
+            ),
+            build_binary(
+                "build Oak Functions enclave app",
+                &variant.enclave_crate_path(),
+            ),
+            build_rust_crate_wasm(END_TO_END_EXAMPLE_NAME),
+            Step::Single {
+                name: "build launcher and client".to_string(),
+                command: Cmd::new(
+                    "cargo",
+                    vec![
+                        "build",
+                        "--package=oak_functions_launcher",
+                        "--package=oak_functions_client",
+                    ],
+                ),
+            },
+            Step::Single {
+                name: "generate lookup data".to_string(),
+                command: Cmd::new(
+                    "cargo",
+                    vec![
+                        "run".to_string(),
+                        "--package=lookup_data_generator".to_string(),
+                        "--".to_string(),
+                        format!("--out-file-path={lookup_data_path}"),
+                        "sequential".to_string(),
+                        format!("--entries={}", opt.entries),
+                    ],
+                ),
+            },
+            Step::Single {
+                name: "run client against server".to_string(),
+                command: Box::new(WithServer {
+                    server: run_oak_functions_launcher_example_with_lookup_data(
+                        &variant,
+                        &wasm_path,
+                        opt.port,
+                        lookup_data_path,
+                    ),
+                    port: opt.port,
+                    clients,
+                }),
+            },
+        ],
+    }
+}
+
+/// Runs client commands one after the other against a server command. The server is started first,
+/// and killed once all the clients have finished, or one of them has failed.
+struct WithServer {
+    server: Box<dyn Runnable>,
+    port: u16,
+    clients: Vec<Box<dyn Runnable>>,
+}
+
+impl Runnable for WithServer {
+    fn description(&self) -> String {
+        let clients: Vec<String> = self
+            .clients
+            .iter()
+            .map(|client| client.description())
+            .collect();
+        format!(
+            "{} with clients [{}]",
+            self.server.description(),
+            clients.join(", ")
+        )
+    }
+
+    fn run(self: Box<Self>, opt: &Opt) -> Box<dyn Running> {
+        if opt.dry_run {
+            return Box::new(SingleStatusResult {
+                value: StatusResultValue::Skipped,
+                logs: String::new(),
+            });
+        }
+        let mut server = self.server.run(opt);
+        // Drain the output of the server, so that it never blocks on a full pipe.
+        tokio::spawn(read_to_end(server.stdout()));
+        tokio::spawn(read_to_end(server.stderr()));
+        Box::new(RunningWithServer {
+            server,
+            port: self.port,
+            clients: self.clients,
+            opt: opt.clone(),
+        })
+    }
+}
+
+struct RunningWithServer {
+    server: Box<dyn Running>,
+    port: u16,
+    clients: Vec<Box<dyn Runnable>>,
+    opt: Opt,
+}
+
+impl RunningWithServer {
+    async fn run_clients(&mut self) -> SingleStatusResult {
+        if !wait_for_port(self.port, SERVER_STARTUP_TIMEOUT).await {
+            return SingleStatusResult {
+                value: StatusResultValue::Error,
+                logs: format!(
+                    "server didn't listen on port {} within {:?}",
+                    self.port, SERVER_STARTUP_TIMEOUT
+                ),
+            };
+        }
+        let mut logs = String::new();
+        for client in self.clients.drain(..) {
+            let result = client.run(&self.opt).result().await;
+            logs += &result.logs;
+            if result.value == StatusResultValue::Error {
+                return SingleStatusResult {
+                    value: StatusResultValue::Error,
+                    logs,
+                };
+            }
+        }
+        SingleStatusResult {
+            value: StatusResultValue::Ok,
+            logs,
+        }
+    }
+}
+
+#[async_trait]
+impl Running for RunningWithServer {
+    fn kill(&mut self) {
+        self.server.kill();
+    }
+
+    async fn result(mut self: Box<Self>) -> SingleStatusResult {
+        let result = self.run_clients().await;
+        self.server.kill();
+        result
+    }
+}
+
+/// Waits until a TCP connection to the given local port succeeds, or the timeout expires.
+async fn wait_for_port(port: u16, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if tokio::net::TcpStream::connect(("localhost", port))
+            .await
+            .is_ok()
+        {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    false
+}
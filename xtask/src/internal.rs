@@ -57,6 +57,8 @@ pub struct Opt {
 pub enum Command {
     BuildEnclaveBinaryVariants(BuildEnclaveBinaryVariantsOpt),
     RunOakFunctionsExample(RunOakExampleOpt),
+    #[command(about = "build and run the key / value lookup example with a scripted client")]
+    RunOakFunctionsEndToEnd(RunOakEndToEndOpt),
     Format,
     CheckFormat,
     RunTests,
@@ -94,6 +96,23 @@ pub struct RunOakExampleOpt {
     pub lookup_data_path: Option<String>,
 }
 
+/// Holds the options for running the key / value lookup example end to end.
+#[derive(Parser, Clone, Debug)]
+pub struct RunOakEndToEndOpt {
+    #[arg(
+        long,
+        help = "number of lookup data entries to generate and to request",
+        default_value = "10"
+    )]
+    pub entries: usize,
+    #[arg(
+        long,
+        help = "port on which the server listens",
+        default_value = "8080"
+    )]
+    pub port: u16,
+}
+
 #[derive(Clone, Debug, PartialEq, Default)]
 pub enum Scope {
     // The entire code base.
@@ -98,6 +98,7 @@ fn match_cmd(opt: &Opt) -> Step {
             launcher::build_enclave_binary_variants(opts)
         }
         Command::RunOakFunctionsExample(ref run_opt) => run_oak_functions_example(run_opt),
+        Command::RunOakFunctionsEndToEnd(ref run_opt) => run_oak_functions_end_to_end(run_opt),
         Command::RunTests => run_tests(),
         Command::RunCargoClippy => run_cargo_clippy(&opt.scope),
         Command::RunCargoTests(ref run_opt) => run_cargo_tests(run_opt, &opt.scope),